@@ -0,0 +1,17 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut builder = tonic_build::configure();
+    // With the raw-recordsets feature, recordsets cross the server as
+    // verbatim bytes instead of parsed messages.
+    if std::env::var_os("CARGO_FEATURE_RAW_RECORDSETS").is_some() {
+        builder = builder.extern_path(".flwr.proto.RecordSet", "crate::model::raw::RawRecordSet");
+    }
+    builder.compile(
+        &[
+            "proto/flwr/proto/fleet.proto",
+            "proto/flwr/proto/driver.proto",
+            "proto/flwr/proto/admin.proto",
+        ],
+        &["proto"],
+    )?;
+    Ok(())
+}
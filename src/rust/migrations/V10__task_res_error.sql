@@ -0,0 +1,3 @@
+ALTER TABLE task_res
+    ADD COLUMN error_code BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN error_reason TEXT NOT NULL DEFAULT '';
@@ -0,0 +1,7 @@
+ALTER TABLE task_ins
+    ALTER COLUMN ancestry TYPE TEXT[]
+    USING CASE WHEN ancestry = '' THEN '{}'::TEXT[] ELSE string_to_array(ancestry, ', ') END;
+
+ALTER TABLE task_res
+    ALTER COLUMN ancestry TYPE TEXT[]
+    USING CASE WHEN ancestry = '' THEN '{}'::TEXT[] ELSE string_to_array(ancestry, ', ') END;
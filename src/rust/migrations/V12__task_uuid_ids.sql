@@ -0,0 +1,10 @@
+ALTER TABLE task_ins
+    ALTER COLUMN id TYPE UUID USING id::uuid,
+    ALTER COLUMN ancestry TYPE UUID[] USING ancestry::uuid[];
+
+ALTER TABLE task_res
+    ALTER COLUMN id TYPE UUID USING id::uuid,
+    ALTER COLUMN ancestry TYPE UUID[] USING ancestry::uuid[];
+
+ALTER TABLE task_dead_letter
+    ALTER COLUMN id TYPE UUID USING id::uuid;
@@ -0,0 +1,11 @@
+ALTER TABLE task_ins
+    ALTER COLUMN delivered_at DROP NOT NULL,
+    ALTER COLUMN created_at TYPE TIMESTAMPTZ USING to_timestamp(created_at),
+    ALTER COLUMN pushed_at TYPE TIMESTAMPTZ USING to_timestamp(pushed_at),
+    ALTER COLUMN delivered_at TYPE TIMESTAMPTZ USING NULLIF(delivered_at, '')::timestamptz;
+
+ALTER TABLE task_res
+    ALTER COLUMN delivered_at DROP NOT NULL,
+    ALTER COLUMN created_at TYPE TIMESTAMPTZ USING to_timestamp(created_at),
+    ALTER COLUMN pushed_at TYPE TIMESTAMPTZ USING to_timestamp(pushed_at),
+    ALTER COLUMN delivered_at TYPE TIMESTAMPTZ USING NULLIF(delivered_at, '')::timestamptz;
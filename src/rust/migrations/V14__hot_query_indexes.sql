@@ -0,0 +1,9 @@
+-- The pull path scans undelivered TaskIns for one consumer.
+CREATE INDEX task_ins_consumer_idx
+    ON task_ins (consumer_node_id, consumer_anonymous, delivered_at);
+
+-- Ancestry matching uses the array overlap operator, which a b-tree
+-- cannot serve; delivered_at is rechecked from the heap.
+CREATE INDEX task_res_ancestry_idx ON task_res USING GIN (ancestry);
+
+CREATE INDEX node_online_until_idx ON node (online_until);
@@ -0,0 +1,3 @@
+-- The client version (or gRPC user-agent) last reported by the node,
+-- captured on CreateNode/Ping; empty when never reported.
+ALTER TABLE node ADD COLUMN client_version TEXT NOT NULL DEFAULT '';
@@ -0,0 +1,4 @@
+-- The authentication identity (public key or certificate
+-- fingerprint) the node presented via the x-flwr-node-key header;
+-- empty when the node never presented one.
+ALTER TABLE node ADD COLUMN identity TEXT NOT NULL DEFAULT '';
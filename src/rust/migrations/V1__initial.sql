@@ -0,0 +1,43 @@
+CREATE TABLE node (
+    id BIGINT PRIMARY KEY,
+    online_until DOUBLE PRECISION NOT NULL DEFAULT 0,
+    ping_interval DOUBLE PRECISION NOT NULL DEFAULT 0
+);
+
+CREATE TABLE run (
+    id BIGINT PRIMARY KEY
+);
+
+CREATE TABLE task_ins (
+    id TEXT PRIMARY KEY,
+    group_id TEXT NOT NULL,
+    run_id BIGINT NOT NULL,
+    producer_anonymous BOOLEAN NOT NULL,
+    producer_node_id BIGINT NOT NULL,
+    consumer_anonymous BOOLEAN NOT NULL,
+    consumer_node_id BIGINT NOT NULL,
+    created_at DOUBLE PRECISION NOT NULL,
+    delivered_at TEXT NOT NULL,
+    pushed_at DOUBLE PRECISION NOT NULL,
+    ttl TEXT NOT NULL,
+    ancestry TEXT NOT NULL,
+    task_type TEXT NOT NULL,
+    recordset BYTEA NOT NULL
+);
+
+CREATE TABLE task_res (
+    id TEXT PRIMARY KEY,
+    group_id TEXT NOT NULL,
+    run_id BIGINT NOT NULL,
+    producer_anonymous BOOLEAN NOT NULL,
+    producer_node_id BIGINT NOT NULL,
+    consumer_anonymous BOOLEAN NOT NULL,
+    consumer_node_id BIGINT NOT NULL,
+    created_at DOUBLE PRECISION NOT NULL,
+    delivered_at TEXT NOT NULL,
+    pushed_at DOUBLE PRECISION NOT NULL,
+    ttl TEXT NOT NULL,
+    ancestry TEXT NOT NULL,
+    task_type TEXT NOT NULL,
+    recordset BYTEA NOT NULL
+);
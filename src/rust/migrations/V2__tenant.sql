@@ -0,0 +1,4 @@
+ALTER TABLE node ADD COLUMN tenant TEXT NOT NULL DEFAULT '';
+ALTER TABLE run ADD COLUMN tenant TEXT NOT NULL DEFAULT '';
+ALTER TABLE task_ins ADD COLUMN tenant TEXT NOT NULL DEFAULT '';
+ALTER TABLE task_res ADD COLUMN tenant TEXT NOT NULL DEFAULT '';
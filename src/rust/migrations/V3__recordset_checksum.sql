@@ -0,0 +1,2 @@
+ALTER TABLE task_ins ADD COLUMN recordset_checksum TEXT NOT NULL DEFAULT '';
+ALTER TABLE task_res ADD COLUMN recordset_checksum TEXT NOT NULL DEFAULT '';
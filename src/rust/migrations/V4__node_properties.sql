@@ -0,0 +1 @@
+ALTER TABLE node ADD COLUMN properties TEXT NOT NULL DEFAULT '{}';
@@ -0,0 +1 @@
+ALTER TABLE node ADD COLUMN task_types TEXT NOT NULL DEFAULT '[]';
@@ -0,0 +1,6 @@
+CREATE TABLE banned_node (
+    id BIGINT NOT NULL,
+    tenant TEXT NOT NULL DEFAULT '',
+    reason TEXT NOT NULL DEFAULT '',
+    PRIMARY KEY (id, tenant)
+);
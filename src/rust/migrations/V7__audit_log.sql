@@ -0,0 +1,9 @@
+CREATE TABLE audit_log (
+    id TEXT PRIMARY KEY,
+    tenant TEXT NOT NULL DEFAULT '',
+    created_at DOUBLE PRECISION NOT NULL,
+    action TEXT NOT NULL,
+    node_id BIGINT NOT NULL DEFAULT 0,
+    run_id BIGINT NOT NULL DEFAULT 0,
+    detail TEXT NOT NULL DEFAULT ''
+);
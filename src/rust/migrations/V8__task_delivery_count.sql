@@ -0,0 +1,2 @@
+ALTER TABLE task_ins
+    ADD COLUMN delivery_count INTEGER NOT NULL DEFAULT 0;
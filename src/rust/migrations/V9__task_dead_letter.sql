@@ -0,0 +1,13 @@
+CREATE TABLE task_dead_letter (
+    id TEXT PRIMARY KEY,
+    tenant TEXT NOT NULL DEFAULT '',
+    group_id TEXT NOT NULL DEFAULT '',
+    run_id BIGINT NOT NULL,
+    consumer_anonymous BOOLEAN NOT NULL,
+    consumer_node_id BIGINT NOT NULL,
+    created_at DOUBLE PRECISION NOT NULL,
+    dead_at DOUBLE PRECISION NOT NULL,
+    delivery_count INTEGER NOT NULL DEFAULT 0,
+    task_type TEXT NOT NULL DEFAULT '',
+    reason TEXT NOT NULL DEFAULT ''
+);
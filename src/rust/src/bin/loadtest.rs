@@ -0,0 +1,209 @@
+//! Fleet API load generator: K simulated nodes ping and pull at a
+//! configurable rate and answer every instruction with a payload of a
+//! configurable size. Per-operation latencies are collected and
+//! reported as percentiles, so state-layer regressions show up as
+//! numbers instead of hunches.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use clap::Parser;
+
+use flwr_superlink::client::FleetNode;
+#[cfg(not(feature = "raw-recordsets"))]
+use flwr_superlink::pb::{Array, ParametersRecord};
+use flwr_superlink::pb::{RecordSet, Task, TaskRes};
+
+#[derive(Debug, Parser)]
+#[command(name = "loadtest", about = "Flower SuperLink Fleet API load generator")]
+struct Args {
+    /// SuperLink endpoint, e.g. `http://localhost:9092`.
+    #[arg(long, default_value = "http://localhost:9092")]
+    endpoint: String,
+
+    /// Tenant the simulated nodes register under.
+    #[arg(long, default_value = "")]
+    tenant: String,
+
+    /// Number of simulated nodes.
+    #[arg(long, default_value_t = 10)]
+    nodes: u32,
+
+    /// Seconds between pulls per node.
+    #[arg(long, default_value_t = 1.0)]
+    pull_interval: f64,
+
+    /// Ping interval in seconds reported to the server.
+    #[arg(long, default_value_t = 30.0)]
+    ping_interval: f64,
+
+    /// Size in bytes of the recordset pushed with every result.
+    #[arg(long, default_value_t = 1024)]
+    payload_size: usize,
+
+    /// How long to run before reporting, in seconds.
+    #[arg(long, default_value_t = 60)]
+    duration: u64,
+}
+
+/// Latency samples for one operation, reported as percentiles.
+#[derive(Default)]
+struct Samples {
+    latencies: Mutex<Vec<Duration>>,
+}
+
+impl Samples {
+    fn record(&self, latency: Duration) {
+        self.latencies.lock().unwrap().push(latency);
+    }
+
+    fn report(&self, operation: &str) {
+        let mut latencies = self.latencies.lock().unwrap();
+        if latencies.is_empty() {
+            println!("{operation:>12}: no samples");
+            return;
+        }
+        latencies.sort();
+        let percentile = |p: f64| {
+            let index = ((latencies.len() - 1) as f64 * p / 100.0).round() as usize;
+            latencies[index]
+        };
+        println!(
+            "{operation:>12}: n={} p50={:?} p90={:?} p99={:?} max={:?}",
+            latencies.len(),
+            percentile(50.0),
+            percentile(90.0),
+            percentile(99.0),
+            percentile(100.0),
+        );
+    }
+}
+
+/// Per-operation latency registry shared by all simulated nodes.
+#[derive(Default)]
+struct Metrics {
+    ping: Samples,
+    pull: Samples,
+    push: Samples,
+}
+
+/// A recordset whose serialized size is roughly `size` bytes.
+#[cfg(not(feature = "raw-recordsets"))]
+fn payload_recordset(size: usize) -> RecordSet {
+    let array = Array {
+        dtype: "uint8".to_owned(),
+        shape: vec![size as i32],
+        stype: "ndarray".to_owned(),
+        data: vec![0u8; size],
+    };
+    RecordSet {
+        parameters: [(
+            "payload".to_owned(),
+            ParametersRecord {
+                data_keys: vec!["weights".to_owned()],
+                data_values: vec![array],
+            },
+        )]
+        .into_iter()
+        .collect(),
+        metrics: HashMap::new(),
+        configs: HashMap::new(),
+    }
+}
+
+/// A recordset whose serialized size is roughly `size` bytes: one
+/// length-delimited field a structured decoder would skip as unknown.
+#[cfg(feature = "raw-recordsets")]
+fn payload_recordset(size: usize) -> RecordSet {
+    let mut bytes = Vec::with_capacity(size + 8);
+    prost::encoding::encode_key(15, prost::encoding::WireType::LengthDelimited, &mut bytes);
+    prost::encoding::encode_varint(size as u64, &mut bytes);
+    bytes.resize(bytes.len() + size, 0);
+    RecordSet::from_bytes(bytes.into())
+}
+
+async fn simulate_node(
+    args: &Args,
+    metrics: &Metrics,
+    deadline: Instant,
+) -> flwr_superlink::client::Result<()> {
+    let mut node = FleetNode::register(
+        &args.endpoint,
+        &args.tenant,
+        args.ping_interval,
+        &HashMap::new(),
+        &[],
+    )
+    .await?;
+    let payload = payload_recordset(args.payload_size);
+    let mut interval = tokio::time::interval(Duration::from_secs_f64(args.pull_interval));
+    while Instant::now() < deadline {
+        interval.tick().await;
+
+        let started = Instant::now();
+        node.ping().await?;
+        metrics.ping.record(started.elapsed());
+
+        let started = Instant::now();
+        let instructions = node.pull_task_ins().await?;
+        metrics.pull.record(started.elapsed());
+
+        for task_ins in instructions {
+            let task = task_ins.task.unwrap_or_default();
+            let now = chrono::Utc::now().timestamp_micros() as f64 / 1_000_000.0;
+            let task_res = TaskRes {
+                task_id: String::new(),
+                group_id: task_ins.group_id,
+                run_id: task_ins.run_id,
+                task: Some(Task {
+                    producer: task.consumer,
+                    consumer: task.producer,
+                    created_at: now,
+                    delivered_at: String::new(),
+                    // Stamped by the server; a client-set value is rejected.
+                    pushed_at: 0.0,
+                    ttl: task.ttl,
+                    ancestry: vec![task_ins.task_id],
+                    task_type: task.task_type,
+                    recordset: Some(payload.clone()),
+                    recordset_checksum: String::new(),
+                    error: None,
+                }),
+            };
+            let started = Instant::now();
+            node.push_task_res(task_res).await?;
+            metrics.push.record(started.elapsed());
+        }
+    }
+    node.deregister().await
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    tracing_subscriber::fmt::init();
+
+    let args = std::sync::Arc::new(args);
+    let metrics = std::sync::Arc::new(Metrics::default());
+    let deadline = Instant::now() + Duration::from_secs(args.duration);
+
+    let mut workers = Vec::with_capacity(args.nodes as usize);
+    for worker in 0..args.nodes {
+        let args = args.clone();
+        let metrics = metrics.clone();
+        workers.push(tokio::spawn(async move {
+            if let Err(err) = simulate_node(&args, &metrics, deadline).await {
+                tracing::warn!(worker, error = %err, "simulated node failed");
+            }
+        }));
+    }
+    for worker in workers {
+        worker.await?;
+    }
+
+    metrics.ping.report("ping");
+    metrics.pull.report("pull_task_ins");
+    metrics.push.report("push_task_res");
+    Ok(())
+}
@@ -0,0 +1,115 @@
+//! Manages the refinery migrations from `migrations/` against the
+//! configured Postgres database.
+
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+use refinery::{Migration, Target};
+
+use flwr_superlink::config::Config;
+use flwr_superlink::embedded;
+
+#[derive(Debug, Parser)]
+#[command(name = "migration", about = "Manage SuperLink database migrations")]
+struct Args {
+    /// Path to a YAML configuration file.
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Apply pending migrations.
+    Apply {
+        /// List the migrations that would run without applying them.
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Stop after this migration version instead of migrating to
+        /// the latest.
+        #[arg(long)]
+        target_version: Option<u32>,
+    },
+    /// List applied and pending migrations.
+    Status,
+}
+
+async fn connect(
+    config: &Config,
+) -> Result<tokio_postgres::Client, Box<dyn std::error::Error>> {
+    let (client, connection) =
+        tokio_postgres::connect(&config.database.uri, tokio_postgres::NoTls).await?;
+    tokio::spawn(async move {
+        if let Err(err) = connection.await {
+            eprintln!("connection error: {err}");
+        }
+    });
+    Ok(client)
+}
+
+/// Embedded migrations that have not been applied yet, in order.
+fn pending(applied: &[Migration]) -> Vec<Migration> {
+    let applied_versions: Vec<u32> = applied.iter().map(Migration::version).collect();
+    let mut pending: Vec<Migration> = embedded::migrations::runner()
+        .get_migrations()
+        .iter()
+        .filter(|migration| !applied_versions.contains(&migration.version()))
+        .cloned()
+        .collect();
+    pending.sort_by_key(Migration::version);
+    pending
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let config = Config::load(args.config.as_deref())?;
+    let mut client = connect(&config).await?;
+
+    match args.command {
+        Command::Apply {
+            dry_run,
+            target_version,
+        } => {
+            let mut runner = embedded::migrations::runner();
+            if let Some(version) = target_version {
+                runner = runner.set_target(Target::Version(version));
+            }
+            if dry_run {
+                let applied = runner.get_applied_migrations_async(&mut client).await?;
+                let mut to_apply = pending(&applied);
+                if let Some(version) = target_version {
+                    to_apply.retain(|migration| migration.version() <= version);
+                }
+                if to_apply.is_empty() {
+                    println!("nothing to apply");
+                }
+                for migration in to_apply {
+                    println!("would apply {migration}");
+                }
+                return Ok(());
+            }
+            let report = runner.run_async(&mut client).await?;
+            for migration in report.applied_migrations() {
+                println!("applied {migration}");
+            }
+            if report.applied_migrations().is_empty() {
+                println!("database is up to date");
+            }
+        }
+        Command::Status => {
+            let runner = embedded::migrations::runner();
+            let applied = runner.get_applied_migrations_async(&mut client).await?;
+            for migration in &applied {
+                println!("applied {migration}");
+            }
+            for migration in pending(&applied) {
+                println!("pending {migration}");
+            }
+        }
+    }
+    Ok(())
+}
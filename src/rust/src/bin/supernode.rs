@@ -0,0 +1,109 @@
+//! Reference SuperNode: registers a node, keeps it pinged, pulls task
+//! instructions, answers them through a task callback, and pushes the
+//! results back. The built-in `echo` callback answers every task with
+//! its own recordset, which makes the binary double as a load
+//! generator; embedded Rust clients replace [`handle_task`] with real
+//! work.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use clap::Parser;
+
+use flwr_superlink::client::{FleetNode, Result};
+use flwr_superlink::pb::{Task, TaskIns, TaskRes};
+
+#[derive(Debug, Parser)]
+#[command(name = "supernode", about = "Flower SuperNode reference client")]
+struct Args {
+    /// SuperLink endpoint, e.g. `http://localhost:9092`.
+    #[arg(long, default_value = "http://localhost:9092")]
+    endpoint: String,
+
+    /// Tenant to register under; empty means the default tenant.
+    #[arg(long, default_value = "")]
+    tenant: String,
+
+    /// Ping interval in seconds reported to the server.
+    #[arg(long, default_value_t = 30.0)]
+    ping_interval: f64,
+
+    /// Seconds between pull attempts.
+    #[arg(long, default_value_t = 1.0)]
+    poll_interval: f64,
+
+    /// Task types this node accepts; empty accepts any.
+    #[arg(long)]
+    task_type: Vec<String>,
+
+    /// Node properties as `key=value` pairs, matched by selectors.
+    #[arg(long, value_parser = parse_property)]
+    property: Vec<(String, String)>,
+}
+
+fn parse_property(raw: &str) -> std::result::Result<(String, String), String> {
+    raw.split_once('=')
+        .map(|(key, value)| (key.to_owned(), value.to_owned()))
+        .ok_or_else(|| format!("expected key=value, got {raw}"))
+}
+
+/// The task callback: turn one received TaskIns into the TaskRes to
+/// push back. The reference implementation echoes the recordset.
+async fn handle_task(task_ins: TaskIns) -> Result<TaskRes> {
+    let task = task_ins.task.unwrap_or_default();
+    let now = chrono::Utc::now().timestamp_micros() as f64 / 1_000_000.0;
+    Ok(TaskRes {
+        // The server assigns the definitive id.
+        task_id: String::new(),
+        group_id: task_ins.group_id,
+        run_id: task_ins.run_id,
+        task: Some(Task {
+            producer: task.consumer,
+            consumer: task.producer,
+            created_at: now,
+            delivered_at: String::new(),
+            // Stamped by the server; a client-set value is rejected.
+            pushed_at: 0.0,
+            ttl: task.ttl,
+            ancestry: vec![task_ins.task_id],
+            task_type: task.task_type,
+            recordset: task.recordset,
+            recordset_checksum: String::new(),
+            error: None,
+        }),
+    })
+}
+
+#[tokio::main]
+async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    tracing_subscriber::fmt::init();
+
+    let properties: HashMap<String, String> = args.property.into_iter().collect();
+    let mut node = FleetNode::register(
+        &args.endpoint,
+        &args.tenant,
+        args.ping_interval,
+        &properties,
+        &args.task_type,
+    )
+    .await?;
+    tracing::info!(node_id = node.node().node_id, "node registered");
+
+    let poll_interval = Duration::from_secs_f64(args.poll_interval);
+    let outcome = node
+        .poll(poll_interval, |task_ins| async move {
+            tracing::info!(
+                task_id = %task_ins.task_id,
+                run_id = task_ins.run_id,
+                "handling task"
+            );
+            handle_task(task_ins).await
+        })
+        .await;
+    // The poll loop only returns on error; deregister before bailing.
+    tracing::warn!(error = %outcome.as_ref().unwrap_err(), "poll loop failed, deregistering");
+    node.deregister().await?;
+    outcome?;
+    Ok(())
+}
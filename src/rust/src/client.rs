@@ -0,0 +1,289 @@
+//! Typed async clients for the Fleet and Driver APIs.
+//!
+//! Thin wrappers over the generated tonic stubs so Rust-based edge
+//! nodes and drivers can talk to a SuperLink without hand-rolling
+//! request plumbing: tenant metadata, node bookkeeping, and the
+//! ping/poll loop are handled here. Enabled with the `client` feature.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use tonic::metadata::MetadataValue;
+use tonic::transport::Channel;
+use tonic::{Request, Status};
+
+use crate::pb::driver_client::DriverClient;
+use crate::pb::fleet_client::FleetClient;
+use crate::pb::{
+    AcknowledgeTaskResRequest, CreateNodeRequest, CreateRunRequest, DeleteNodeRequest,
+    DeleteRunRequest, GetNodesRequest, NackTaskInsRequest, Node, PingRequest, PullTaskInsRequest,
+    PullTaskResRequest, PushTaskInsRequest, PushTaskResRequest, TaskIns, TaskRes,
+};
+use crate::service::{API_VERSION, API_VERSION_METADATA_KEY, TENANT_METADATA_KEY};
+
+/// Client-side errors: transport trouble or a gRPC status.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("transport error: {0}")]
+    Transport(#[from] tonic::transport::Error),
+    #[error("{0}")]
+    Status(#[from] Status),
+    #[error("server response missing {0}")]
+    MissingField(&'static str),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+fn with_tenant<T>(message: T, tenant: &str) -> Result<Request<T>> {
+    let mut request = Request::new(message);
+    if !tenant.is_empty() {
+        let value = MetadataValue::try_from(tenant)
+            .map_err(|_| Status::invalid_argument("tenant must be valid ASCII"))?;
+        request.metadata_mut().insert(TENANT_METADATA_KEY, value);
+    }
+    request.metadata_mut().insert(
+        API_VERSION_METADATA_KEY,
+        MetadataValue::from(API_VERSION),
+    );
+    Ok(request)
+}
+
+/// A Fleet API client bound to one registered node.
+pub struct FleetNode {
+    client: FleetClient<Channel>,
+    tenant: String,
+    node: Node,
+    ping_interval: f64,
+}
+
+impl FleetNode {
+    /// Connect to `endpoint` and register a node.
+    pub async fn register(
+        endpoint: &str,
+        tenant: &str,
+        ping_interval: f64,
+        properties: &HashMap<String, String>,
+        task_types: &[String],
+    ) -> Result<Self> {
+        let mut client = FleetClient::connect(endpoint.to_owned()).await?;
+        let response = client
+            .create_node(with_tenant(
+                CreateNodeRequest {
+                    ping_interval,
+                    properties: properties.clone(),
+                    task_types: task_types.to_vec(),
+                    prior_node_id: 0,
+                },
+                tenant,
+            )?)
+            .await?
+            .into_inner();
+        let node = response.node.ok_or(Error::MissingField("node"))?;
+        Ok(Self {
+            client,
+            tenant: tenant.to_owned(),
+            node,
+            ping_interval,
+        })
+    }
+
+    /// The node this client registered.
+    pub fn node(&self) -> &Node {
+        &self.node
+    }
+
+    /// Send one ping; returns whether the server still knows the node.
+    pub async fn ping(&mut self) -> Result<bool> {
+        let response = self
+            .client
+            .ping(with_tenant(
+                PingRequest {
+                    node: Some(self.node.clone()),
+                    ping_interval: self.ping_interval,
+                    task_types: Vec::new(),
+                },
+                &self.tenant,
+            )?)
+            .await?
+            .into_inner();
+        Ok(response.success)
+    }
+
+    /// Pull the next batch of task instructions.
+    pub async fn pull_task_ins(&mut self) -> Result<Vec<TaskIns>> {
+        let response = self
+            .client
+            .pull_task_ins(with_tenant(
+                PullTaskInsRequest {
+                    node: Some(self.node.clone()),
+                    task_ids: Vec::new(),
+                },
+                &self.tenant,
+            )?)
+            .await?
+            .into_inner();
+        Ok(response.task_ins_list)
+    }
+
+    /// Push one task result.
+    pub async fn push_task_res(&mut self, task_res: TaskRes) -> Result<()> {
+        self.client
+            .push_task_res(with_tenant(
+                PushTaskResRequest {
+                    task_res_list: vec![task_res],
+                },
+                &self.tenant,
+            )?)
+            .await?;
+        Ok(())
+    }
+
+    /// Hand received instructions back for immediate redelivery.
+    pub async fn nack_task_ins(&mut self, task_ids: Vec<String>) -> Result<()> {
+        self.client
+            .nack_task_ins(with_tenant(
+                NackTaskInsRequest {
+                    node: Some(self.node.clone()),
+                    task_ids,
+                },
+                &self.tenant,
+            )?)
+            .await?;
+        Ok(())
+    }
+
+    /// Poll for instructions at `poll_interval`, pinging alongside,
+    /// and answer each with `handler`; runs until `handler` errors.
+    pub async fn poll<F, Fut>(&mut self, poll_interval: Duration, mut handler: F) -> Result<()>
+    where
+        F: FnMut(TaskIns) -> Fut,
+        Fut: std::future::Future<Output = Result<TaskRes>>,
+    {
+        let mut interval = tokio::time::interval(poll_interval);
+        loop {
+            interval.tick().await;
+            self.ping().await?;
+            for task_ins in self.pull_task_ins().await? {
+                let task_res = handler(task_ins).await?;
+                self.push_task_res(task_res).await?;
+            }
+        }
+    }
+
+    /// Deregister the node.
+    pub async fn deregister(mut self) -> Result<()> {
+        self.client
+            .delete_node(with_tenant(
+                DeleteNodeRequest {
+                    node: Some(self.node.clone()),
+                },
+                &self.tenant,
+            )?)
+            .await?;
+        Ok(())
+    }
+}
+
+/// A Driver API client.
+pub struct Driver {
+    client: DriverClient<Channel>,
+    tenant: String,
+}
+
+impl Driver {
+    /// Connect to `endpoint`.
+    pub async fn connect(endpoint: &str, tenant: &str) -> Result<Self> {
+        let client = DriverClient::connect(endpoint.to_owned()).await?;
+        Ok(Self {
+            client,
+            tenant: tenant.to_owned(),
+        })
+    }
+
+    /// Create a run and return its id.
+    pub async fn create_run(&mut self) -> Result<i64> {
+        let response = self
+            .client
+            .create_run(with_tenant(CreateRunRequest {}, &self.tenant)?)
+            .await?
+            .into_inner();
+        Ok(response.run_id)
+    }
+
+    /// Delete a finished run and every task stored for it.
+    pub async fn delete_run(&mut self, run_id: i64) -> Result<()> {
+        self.client
+            .delete_run(with_tenant(DeleteRunRequest { run_id }, &self.tenant)?)
+            .await?;
+        Ok(())
+    }
+
+    /// List the nodes currently online for `run_id`.
+    pub async fn get_nodes(&mut self, run_id: i64) -> Result<Vec<Node>> {
+        let response = self
+            .client
+            .get_nodes(with_tenant(GetNodesRequest { run_id }, &self.tenant)?)
+            .await?
+            .into_inner();
+        Ok(response.nodes)
+    }
+
+    /// Push task instructions and return their assigned ids.
+    pub async fn push_task_ins(&mut self, task_ins_list: Vec<TaskIns>) -> Result<Vec<String>> {
+        let response = self
+            .client
+            .push_task_ins(with_tenant(PushTaskInsRequest { task_ins_list }, &self.tenant)?)
+            .await?
+            .into_inner();
+        Ok(response.task_ids)
+    }
+
+    /// Pull whatever results for `task_ids` have arrived so far.
+    pub async fn pull_task_res(&mut self, task_ids: Vec<String>) -> Result<Vec<TaskRes>> {
+        let response = self
+            .client
+            .pull_task_res(with_tenant(
+                PullTaskResRequest {
+                    node: None,
+                    task_ids,
+                    keep: false,
+                },
+                &self.tenant,
+            )?)
+            .await?
+            .into_inner();
+        Ok(response.task_res_list)
+    }
+
+    /// Acknowledge results pulled in peek mode.
+    pub async fn acknowledge_task_res(&mut self, task_ids: Vec<String>) -> Result<()> {
+        self.client
+            .acknowledge_task_res(with_tenant(
+                AcknowledgeTaskResRequest { task_ids },
+                &self.tenant,
+            )?)
+            .await?;
+        Ok(())
+    }
+
+    /// Poll at `poll_interval` until every task id has a result.
+    pub async fn await_task_res(
+        &mut self,
+        task_ids: Vec<String>,
+        poll_interval: Duration,
+    ) -> Result<Vec<TaskRes>> {
+        let mut pending = task_ids;
+        let mut results = Vec::with_capacity(pending.len());
+        let mut interval = tokio::time::interval(poll_interval);
+        while !pending.is_empty() {
+            interval.tick().await;
+            for task_res in self.pull_task_res(pending.clone()).await? {
+                if let Some(task) = &task_res.task {
+                    pending.retain(|id| !task.ancestry.contains(id));
+                }
+                results.push(task_res);
+            }
+        }
+        Ok(results)
+    }
+}
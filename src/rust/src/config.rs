@@ -0,0 +1,695 @@
+//! Configuration loaded through figment: defaults, an optional YAML
+//! file, and `FLWR_`-prefixed environment variables (nested fields
+//! separated by `__`, e.g. `FLWR_DATABASE__URI`).
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use figment::providers::{Env, Format, Serialized, Yaml};
+use figment::Figment;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub server: Server,
+    pub dashboard: Dashboard,
+    pub rest: Rest,
+    pub database: Database,
+    pub blob: Blob,
+    pub fleet: Fleet,
+    pub tasks: Tasks,
+    pub simulation: Simulation,
+    pub logging: Logging,
+    pub tracer: Tracer,
+    pub validation: Validation,
+    pub notifier: Notifier,
+    pub events: Events,
+    /// Named federations, each an isolated tenant with its own policy.
+    /// With none configured every tenant is accepted, as before.
+    pub federations: std::collections::HashMap<String, Federation>,
+}
+
+/// Policy of one named federation. The federation name doubles as the
+/// tenant id, so state isolation comes from the existing per-tenant
+/// keying; requests select their federation through the
+/// `x-flwr-tenant` metadata. Once any federation is configured,
+/// requests naming an unknown one are rejected.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Federation {
+    /// Bearer token requests of this federation must present in the
+    /// `authorization` metadata; empty requires none.
+    pub auth_token: String,
+    /// Task types this federation accepts on push; empty falls back
+    /// to `validation.allowed_task_types`.
+    pub allowed_task_types: Vec<String>,
+    /// Override of `tasks.max_pending_per_node` for this federation;
+    /// absent inherits the global value.
+    pub max_pending_per_node: Option<u32>,
+    /// Override of `tasks.max_pending_per_run`.
+    pub max_pending_per_run: Option<u32>,
+    /// Override of `tasks.max_run_tasks`.
+    pub max_run_tasks: Option<u64>,
+    /// Override of `tasks.max_run_recordset_bytes`.
+    pub max_run_recordset_bytes: Option<u64>,
+    /// Override of `tasks.max_run_consumers`.
+    pub max_run_consumers: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Server {
+    /// Address the gRPC server binds to.
+    pub address: SocketAddr,
+    /// Maximum size in bytes of a decoded request message.
+    pub max_message_size: usize,
+    /// HTTP/2 initial per-stream flow-control window in bytes; unset
+    /// keeps tonic's default. Raise it for high-latency fleets moving
+    /// large recordsets.
+    pub initial_stream_window_size: Option<u32>,
+    /// HTTP/2 initial connection flow-control window in bytes; unset
+    /// keeps tonic's default.
+    pub initial_connection_window_size: Option<u32>,
+    /// Maximum concurrent HTTP/2 streams per connection; unset keeps
+    /// tonic's default.
+    pub max_concurrent_streams: Option<u32>,
+    /// Set TCP_NODELAY on accepted connections; small ping/pull RPCs
+    /// are latency sensitive, so this defaults to on.
+    pub tcp_nodelay: bool,
+    /// Length of the kernel accept queue for pending connections.
+    pub accept_backlog: u32,
+    /// Also accept gRPC-Web over HTTP/1.1, so browser-based dashboards
+    /// and JS drivers can call the APIs directly.
+    pub enable_grpc_web: bool,
+    /// Origins allowed by the CORS policy when gRPC-Web is enabled;
+    /// empty allows any origin.
+    pub cors_allowed_origins: Vec<String>,
+}
+
+/// The embedded web dashboard: a single bundled page plus the JSON
+/// endpoints it polls, served over plain HTTP on its own address so it
+/// never competes with gRPC traffic.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Dashboard {
+    /// Serve the dashboard; off by default.
+    pub enabled: bool,
+    /// Address the dashboard's HTTP server binds to.
+    pub address: SocketAddr,
+    /// Bearer token requests must present in the `Authorization`
+    /// header, mirroring the federation tokens; empty requires none.
+    pub auth_token: String,
+}
+
+/// JSON transcoding of the unary Fleet RPCs (`/v1/fleet/*`), served
+/// over plain HTTP on its own address together with the OpenAPI
+/// document describing it at `/openapi.json`. The federation policy
+/// applies unchanged: the metadata headers ride along.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rest {
+    /// Serve the REST transport; off by default.
+    pub enabled: bool,
+    /// Address the REST HTTP server binds to.
+    pub address: SocketAddr,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Database {
+    /// Postgres connection URI.
+    pub uri: String,
+    /// Read the connection URI from this file instead (mounted
+    /// Kubernetes/Docker secrets); takes precedence over `uri`.
+    pub uri_file: Option<PathBuf>,
+    /// Maximum number of pooled connections.
+    pub pool_size: u32,
+    /// Run pending migrations before serving.
+    pub migrate_on_startup: bool,
+    /// Partition the task tables by run id, one partition per run, so
+    /// deleting a run drops its partitions. Converts the schema on the
+    /// next startup; once enabled it must stay enabled.
+    pub partition_tasks_by_run: bool,
+    /// Log state operations slower than this many milliseconds at
+    /// WARN; 0 disables slow-query logging.
+    pub slow_query_ms: u64,
+    /// Abort state operations after this many milliseconds with
+    /// DEADLINE_EXCEEDED; 0 disables the deadline.
+    pub operation_timeout_ms: u64,
+    /// Per-operation overrides of `operation_timeout_ms`, keyed by the
+    /// `State` method name, e.g. `task_instructions`.
+    pub operation_timeouts_ms: std::collections::HashMap<String, u64>,
+    /// Consecutive failures before the circuit breaker opens and state
+    /// calls fail fast; 0 disables the breaker.
+    pub breaker_failure_threshold: u32,
+    /// Cool-down in milliseconds before an open circuit lets a probe
+    /// through.
+    pub breaker_reset_ms: u64,
+    /// Retries after a connection acquisition failure; 0 disables
+    /// retrying.
+    pub retry_attempts: u32,
+    /// Delay in milliseconds before the first retry, doubling on each
+    /// further one.
+    pub retry_backoff_ms: u64,
+    /// How long node list queries are served from an in-process cache,
+    /// in milliseconds; 0 disables the cache.
+    pub node_cache_ttl_ms: u64,
+    /// How long run existence checks are served from an in-process
+    /// cache, in milliseconds; 0 disables the cache.
+    pub run_cache_ttl_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Blob {
+    /// Offload recordsets to this backend: `filesystem` or `s3`; unset
+    /// keeps them inline in Postgres.
+    pub backend: Option<String>,
+    /// Root directory of the `filesystem` backend.
+    pub path: PathBuf,
+    /// Bucket of the `s3` backend.
+    pub bucket: String,
+    /// Recordsets at or above this many bytes are offloaded.
+    pub inline_threshold: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fleet {
+    /// Number of task instructions handed out per pull.
+    pub pull_task_ins_limit: u32,
+    /// Policy deciding which instructions a pull hands out: `fifo`
+    /// serves strictly oldest-first, `fair` round-robins across runs
+    /// so a busy run cannot starve the others, and `priority` serves
+    /// the task types listed in `priority_task_types` first.
+    pub scheduler: SchedulerKind,
+    /// Task types in descending precedence for the `priority`
+    /// scheduler; unlisted types come last.
+    pub priority_task_types: Vec<String>,
+    /// Let registered nodes claim instructions addressed to the
+    /// anonymous consumer, first-come-first-served, turning the
+    /// anonymous pool into a shared job queue.
+    pub anonymous_pool: bool,
+    /// Oldest client protocol version still accepted; clients below it
+    /// (including ones predating version negotiation) are rejected
+    /// with FAILED_PRECONDITION. 0 accepts everything.
+    pub min_api_version: u32,
+    /// Log an INFO line whenever every instruction of a task group has
+    /// a stored result, for log-driven round orchestration.
+    pub log_completed_groups: bool,
+    /// Fraction of its own `ping_interval` a node may overshoot
+    /// `online_until` and still count as online, absorbing network
+    /// jitter: 0.5 keeps a node online for half an extra interval
+    /// after a missed ping. 0 means a late ping takes the node
+    /// offline immediately.
+    pub ping_grace: f64,
+}
+
+/// Built-in pull schedulers selectable via `fleet.scheduler`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SchedulerKind {
+    Fifo,
+    Fair,
+    Priority,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tasks {
+    /// Mint task ids as UUIDv5 of (run_id, group_id, consumer,
+    /// sequence) instead of random UUIDv4, making retried pushes
+    /// idempotent by construction.
+    pub deterministic_ids: bool,
+    /// Maximum number of undelivered TaskIns per consumer node; 0
+    /// disables the cap.
+    pub max_pending_per_node: u32,
+    /// Maximum number of undelivered TaskIns per run; pushes beyond it
+    /// are rejected with RESOURCE_EXHAUSTED. 0 disables the cap.
+    pub max_pending_per_run: u32,
+    /// Redeliver a delivered TaskIns whose result has not arrived
+    /// within this many milliseconds; 0 disables redelivery.
+    pub redelivery_after_ms: u64,
+    /// Deliveries per TaskIns before it is no longer retried; 0 means
+    /// no cap.
+    pub max_redeliveries: u32,
+    /// Total TaskIns a run may have stored, delivered or not; pushes
+    /// beyond it are rejected with RESOURCE_EXHAUSTED. 0 disables the
+    /// quota.
+    pub max_run_tasks: u64,
+    /// Total bytes of recordsets (instructions and results) a run may
+    /// have stored; enforced like `max_run_tasks`. 0 disables the
+    /// quota.
+    pub max_run_recordset_bytes: u64,
+    /// Distinct registered consumer nodes a run may address over its
+    /// lifetime of stored tasks; enforced like `max_run_tasks`. 0
+    /// disables the quota.
+    pub max_run_consumers: u32,
+    /// Log a WARN when a task group goes this many milliseconds
+    /// without a new instruction or result while incomplete; 0
+    /// disables stall detection.
+    pub stall_after_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Simulation {
+    /// Number of virtual nodes to pre-provision at startup; 0 disables
+    /// simulation mode.
+    pub virtual_nodes: u32,
+    /// Tenant the virtual nodes are provisioned under.
+    pub tenant: String,
+    /// Ping interval in seconds reported for the virtual nodes; the
+    /// keepalive refreshes them at half this interval.
+    pub ping_interval: f64,
+    /// Task types declared by the virtual nodes; empty accepts any.
+    pub task_types: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Events {
+    /// Mirror task state transitions into this event bus: `kafka` or
+    /// `nats`; unset disables mirroring.
+    pub backend: Option<String>,
+    /// Kafka bootstrap servers or the NATS server URL.
+    pub servers: String,
+    /// Kafka topic or NATS subject the events are published to.
+    pub topic: String,
+    /// Events queued for publication before new ones are dropped.
+    pub queue_size: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notifier {
+    /// URLs every lifecycle event is POSTed to as JSON; empty
+    /// disables webhooks.
+    pub webhook_urls: Vec<String>,
+    /// Secret signing each delivery with HMAC-SHA256 in the
+    /// `x-flwr-signature` header; empty sends deliveries unsigned.
+    pub secret: String,
+    /// Delivery attempts per URL before an event is given up on.
+    pub attempts: u32,
+    /// Delay in milliseconds before the first retry, doubling on each
+    /// further one.
+    pub backoff_ms: u64,
+    /// Events queued for delivery before new ones are dropped, so a
+    /// slow endpoint cannot stall the serving path.
+    pub queue_size: usize,
+    /// Deliver through this proxy (`http://`, `https://` or
+    /// `socks5://`); unset connects directly.
+    pub proxy_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Logging {
+    /// `EnvFilter` directive, e.g. `info` or `flwr_superlink=debug`.
+    pub level: String,
+    /// What the debug-level SQL statement logs may contain: `off`
+    /// disables them, `redacted` strips the bind values (recordset
+    /// bytes among them), `full` includes everything.
+    pub log_sql: LogSql,
+    /// Include verbose span fields (payload sizes, headers) on every
+    /// request.
+    pub verbose: bool,
+    /// Include verbose span fields only for these services or methods,
+    /// e.g. `flwr.proto.Fleet` or `flwr.proto.Fleet/PushTaskRes`.
+    pub verbose_methods: Vec<String>,
+}
+
+/// Payload policy for debug-level SQL statement logs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogSql {
+    Off,
+    Redacted,
+    Full,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tracer {
+    /// Export traces and metrics when set.
+    pub enabled: bool,
+    /// Trace exporter: `otlp`, `jaeger` or `zipkin` (the latter two
+    /// behind the matching cargo features), or `stdout` for local
+    /// debugging. Metrics always export via OTLP (or stdout), since
+    /// Jaeger and Zipkin are trace-only protocols.
+    pub exporter: TraceExporter,
+    /// OTLP collector endpoint.
+    pub otlp_endpoint: String,
+    /// Jaeger agent `host:port` for the `jaeger` exporter.
+    pub jaeger_endpoint: String,
+    /// Zipkin collector URL for the `zipkin` exporter.
+    pub zipkin_endpoint: String,
+    /// Maximum number of distinct `run_id` metric labels; further runs
+    /// are aggregated under the label `other`.
+    pub max_run_labels: usize,
+    /// `service.name` resource attribute, distinguishing multiple
+    /// superlinks in the tracing backend.
+    pub service_name: String,
+    /// `deployment.environment` resource attribute, e.g. `staging`;
+    /// empty omits it.
+    pub deployment_environment: String,
+    /// Further resource attributes, e.g. `region: eu-west-1`.
+    pub resource_attributes: std::collections::HashMap<String, String>,
+    /// Export through this proxy (`http://`, `https://` or
+    /// `socks5://`) for egress-restricted networks. Proxied exports go
+    /// as protobuf-over-HTTP, so point `otlp_endpoint` at the
+    /// collector's HTTP port (4318); unset exports gRPC directly.
+    pub proxy_url: Option<String>,
+}
+
+/// Trace exporters selectable via `tracer.exporter`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TraceExporter {
+    Otlp,
+    Jaeger,
+    Zipkin,
+    Stdout,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Validation {
+    /// Maximum number of seconds `created_at` may lie in the past.
+    pub message_expires_after: f64,
+    /// Maximum serialized recordset size in bytes; 0 disables the
+    /// limit.
+    pub max_recordset_size: usize,
+    /// Per-task-type overrides of `max_recordset_size`.
+    pub max_recordset_sizes: std::collections::HashMap<String, usize>,
+    /// Task types accepted on push across all federations; empty
+    /// accepts all.
+    pub allowed_task_types: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            server: Server {
+                address: "[::]:9092".parse().expect("valid default address"),
+                max_message_size: 4 * 1024 * 1024,
+                initial_stream_window_size: None,
+                initial_connection_window_size: None,
+                max_concurrent_streams: None,
+                tcp_nodelay: true,
+                accept_backlog: 1024,
+                enable_grpc_web: false,
+                cors_allowed_origins: Vec::new(),
+            },
+            dashboard: Dashboard {
+                enabled: false,
+                address: "[::]:9095".parse().expect("valid default address"),
+                auth_token: String::new(),
+            },
+            rest: Rest {
+                enabled: false,
+                address: "[::]:9096".parse().expect("valid default address"),
+            },
+            database: Database {
+                uri: "postgres://postgres@localhost:5432/flwr".to_owned(),
+                uri_file: None,
+                pool_size: 10,
+                migrate_on_startup: false,
+                partition_tasks_by_run: false,
+                slow_query_ms: 500,
+                operation_timeout_ms: 0,
+                operation_timeouts_ms: std::collections::HashMap::new(),
+                breaker_failure_threshold: 0,
+                breaker_reset_ms: 5000,
+                retry_attempts: 0,
+                retry_backoff_ms: 100,
+                node_cache_ttl_ms: 0,
+                run_cache_ttl_ms: 0,
+            },
+            blob: Blob {
+                backend: None,
+                path: PathBuf::from("blobs"),
+                bucket: String::new(),
+                inline_threshold: 1024 * 1024,
+            },
+            fleet: Fleet {
+                pull_task_ins_limit: 1,
+                scheduler: SchedulerKind::Fifo,
+                priority_task_types: Vec::new(),
+                anonymous_pool: false,
+                min_api_version: 0,
+                log_completed_groups: false,
+                ping_grace: 0.0,
+            },
+            tasks: Tasks {
+                deterministic_ids: false,
+                max_pending_per_node: 0,
+                max_pending_per_run: 0,
+                redelivery_after_ms: 0,
+                max_redeliveries: 5,
+                max_run_tasks: 0,
+                max_run_recordset_bytes: 0,
+                max_run_consumers: 0,
+                stall_after_ms: 0,
+            },
+            simulation: Simulation {
+                virtual_nodes: 0,
+                tenant: String::new(),
+                ping_interval: 30.0,
+                task_types: Vec::new(),
+            },
+            logging: Logging {
+                level: "info".to_owned(),
+                log_sql: LogSql::Redacted,
+                verbose: false,
+                verbose_methods: Vec::new(),
+            },
+            tracer: Tracer {
+                enabled: false,
+                exporter: TraceExporter::Otlp,
+                otlp_endpoint: "http://localhost:4317".to_owned(),
+                jaeger_endpoint: "127.0.0.1:6831".to_owned(),
+                zipkin_endpoint: "http://127.0.0.1:9411/api/v2/spans".to_owned(),
+                max_run_labels: 100,
+                service_name: env!("CARGO_PKG_NAME").to_owned(),
+                deployment_environment: String::new(),
+                resource_attributes: std::collections::HashMap::new(),
+                proxy_url: None,
+            },
+            validation: Validation {
+                message_expires_after: 3600.0,
+                max_recordset_size: 0,
+                max_recordset_sizes: std::collections::HashMap::new(),
+                allowed_task_types: Vec::new(),
+            },
+            events: Events {
+                backend: None,
+                servers: String::new(),
+                topic: "flwr.tasks".to_owned(),
+                queue_size: 1024,
+            },
+            notifier: Notifier {
+                webhook_urls: Vec::new(),
+                secret: String::new(),
+                attempts: 3,
+                backoff_ms: 1000,
+                queue_size: 1024,
+                proxy_url: None,
+            },
+            federations: std::collections::HashMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// The figment pipeline: defaults < YAML file < environment.
+    ///
+    /// `${VAR}` placeholders in the YAML file are expanded from the
+    /// environment before the merge, so one config template can be
+    /// reused across environments.
+    pub fn figment(path: Option<&Path>) -> Figment {
+        let mut figment = Figment::from(Serialized::defaults(Config::default()));
+        if let Some(path) = path {
+            match std::fs::read_to_string(path) {
+                Ok(contents) => {
+                    figment = figment.merge(Yaml::string(&expand_env_vars(&contents)));
+                }
+                // Match Yaml::file's behaviour for missing files.
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => {
+                    tracing::warn!(path = %path.display(), error = %err, "failed to read config file");
+                }
+            }
+        }
+        figment.merge(Env::prefixed("FLWR_").split("__"))
+    }
+
+    /// Load and deserialize the configuration, resolving `*_file`
+    /// secret indirections.
+    pub fn load(path: Option<&Path>) -> Result<Self, figment::Error> {
+        let mut config: Self = Self::figment(path).extract()?;
+        config.resolve_secret_files()?;
+        Ok(config)
+    }
+
+    /// Replace values that have a `*_file` variant set with the
+    /// trimmed contents of the referenced file.
+    fn resolve_secret_files(&mut self) -> Result<(), figment::Error> {
+        if let Some(path) = &self.database.uri_file {
+            self.database.uri = read_secret_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Expand `${VAR}` placeholders from the environment; unset variables
+/// expand to the empty string.
+fn expand_env_vars(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        match rest[start + 2..].find('}') {
+            Some(end) => {
+                let name = &rest[start + 2..start + 2 + end];
+                out.push_str(&std::env::var(name).unwrap_or_default());
+                rest = &rest[start + 2 + end + 1..];
+            }
+            None => {
+                out.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn read_secret_file(path: &Path) -> Result<String, figment::Error> {
+    std::fs::read_to_string(path)
+        .map(|value| value.trim_end().to_owned())
+        .map_err(|err| figment::Error::from(format!("failed to read {}: {err}", path.display())))
+}
+
+impl From<&Config> for crate::state::breaker::BreakerConfig {
+    fn from(config: &Config) -> Self {
+        Self {
+            failure_threshold: config.database.breaker_failure_threshold,
+            reset_after: std::time::Duration::from_millis(config.database.breaker_reset_ms),
+        }
+    }
+}
+
+impl From<&Config> for crate::state::cache::CacheConfig {
+    fn from(config: &Config) -> Self {
+        Self {
+            node_ttl_ms: config.database.node_cache_ttl_ms,
+        }
+    }
+}
+
+impl From<&Config> for crate::state::retry::RetryConfig {
+    fn from(config: &Config) -> Self {
+        Self {
+            attempts: config.database.retry_attempts,
+            backoff_ms: config.database.retry_backoff_ms,
+        }
+    }
+}
+
+impl From<&Config> for crate::state::timeout::TimeoutConfig {
+    fn from(config: &Config) -> Self {
+        Self {
+            default_ms: config.database.operation_timeout_ms,
+            per_operation_ms: config.database.operation_timeouts_ms.clone(),
+        }
+    }
+}
+
+impl From<&Config> for crate::tracer::ResourceConfig {
+    fn from(config: &Config) -> Self {
+        Self {
+            service_name: config.tracer.service_name.clone(),
+            deployment_environment: config.tracer.deployment_environment.clone(),
+            attributes: config.tracer.resource_attributes.clone(),
+        }
+    }
+}
+
+impl From<&Config> for crate::service::validate::ValidationConfig {
+    fn from(config: &Config) -> Self {
+        Self {
+            message_expires_after: config.validation.message_expires_after,
+            max_recordset_size: config.validation.max_recordset_size,
+            max_recordset_sizes: config.validation.max_recordset_sizes.clone(),
+            allowed_task_types: config.validation.allowed_task_types.clone(),
+        }
+    }
+}
+
+/// The subset of settings that can change at runtime; distributed to
+/// the services through a watch channel on config reload (SIGHUP).
+#[derive(Debug, Clone, PartialEq)]
+pub struct DynamicConfig {
+    pub logging_level: String,
+    pub validation: crate::service::validate::ValidationConfig,
+    pub pull_task_ins_limit: u32,
+    pub max_pending_per_node: u32,
+    pub max_pending_per_run: u32,
+    pub max_run_tasks: u64,
+    pub max_run_recordset_bytes: u64,
+    pub max_run_consumers: u32,
+    pub min_api_version: u32,
+    pub federations: std::collections::HashMap<String, Federation>,
+}
+
+impl From<&Config> for DynamicConfig {
+    fn from(config: &Config) -> Self {
+        Self {
+            logging_level: config.logging.level.clone(),
+            validation: config.into(),
+            pull_task_ins_limit: config.fleet.pull_task_ins_limit,
+            max_pending_per_node: config.tasks.max_pending_per_node,
+            max_pending_per_run: config.tasks.max_pending_per_run,
+            max_run_tasks: config.tasks.max_run_tasks,
+            max_run_recordset_bytes: config.tasks.max_run_recordset_bytes,
+            max_run_consumers: config.tasks.max_run_consumers,
+            min_api_version: config.fleet.min_api_version,
+            federations: config.federations.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_load_without_file() {
+        let config = Config::load(None).unwrap();
+        assert_eq!(config.database.pool_size, 10);
+        assert!(!config.tracer.enabled);
+    }
+
+    #[test]
+    fn env_overrides_defaults() {
+        figment::Jail::expect_with(|jail| {
+            jail.set_env("FLWR_DATABASE__POOL_SIZE", "3");
+            let config = Config::load(None).unwrap();
+            assert_eq!(config.database.pool_size, 3);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn yaml_placeholders_expand_from_env() {
+        figment::Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.yaml",
+                "database:\n  uri: postgres://user@${DB_HOST}:5432/flwr\n",
+            )?;
+            jail.set_env("DB_HOST", "db.internal");
+            let config = Config::load(Some(Path::new("config.yaml"))).unwrap();
+            assert_eq!(config.database.uri, "postgres://user@db.internal:5432/flwr");
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn uri_file_overrides_uri() {
+        figment::Jail::expect_with(|jail| {
+            jail.create_file("db-uri", "postgres://secret@db:5432/flwr\n")?;
+            jail.set_env("FLWR_DATABASE__URI_FILE", "db-uri");
+            let config = Config::load(None).unwrap();
+            assert_eq!(config.database.uri, "postgres://secret@db:5432/flwr");
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,247 @@
+//! Embedded operations dashboard.
+//!
+//! A single bundled page plus the JSON endpoints it polls, served over
+//! plain HTTP on `dashboard.address`: online nodes, runs with their
+//! queue depth and stored footprint, and the most recently
+//! dead-lettered tasks. The page is compiled into the binary, so
+//! nothing needs to be deployed next to it. When
+//! `dashboard.auth_token` is set the JSON endpoints require it as an
+//! `Authorization: Bearer` header — the page itself stays open (it
+//! holds no data) and asks for the token in the browser.
+
+use std::sync::Arc;
+
+use axum::extract::{Query, State as Extract};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::Html;
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+
+use crate::state::{State, TaskCursor};
+
+/// Dead letters fetched per page while finding the most recent ones.
+const PAGE_SIZE: u32 = 500;
+
+/// Most recent dead letters returned by `/api/errors`.
+const ERRORS_SHOWN: usize = 50;
+
+/// Shared handler context.
+#[derive(Clone)]
+struct Context {
+    state: Arc<dyn State>,
+    auth_token: String,
+}
+
+/// Serve the dashboard until the process exits, logging rather than
+/// surfacing failures: the dashboard is an observability concern and
+/// must not take the server down.
+pub async fn serve(state: Arc<dyn State>, config: crate::config::Dashboard) {
+    let context = Context {
+        state,
+        auth_token: config.auth_token,
+    };
+    let router = Router::new()
+        .route("/", get(index))
+        .route("/api/overview", get(overview))
+        .route("/api/errors", get(errors))
+        .with_state(context);
+    tracing::info!(address = %config.address, "dashboard listening");
+    if let Err(err) = axum::Server::bind(&config.address)
+        .serve(router.into_make_service())
+        .await
+    {
+        tracing::error!(error = %err, "dashboard server failed");
+    }
+}
+
+/// Check the `Authorization: Bearer` header against the configured
+/// token, mirroring the federation token check.
+fn authorize(context: &Context, headers: &HeaderMap) -> Result<(), StatusCode> {
+    if context.auth_token.is_empty() {
+        return Ok(());
+    }
+    let presented = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if presented == Some(context.auth_token.as_str()) {
+        Ok(())
+    } else {
+        Err(StatusCode::UNAUTHORIZED)
+    }
+}
+
+/// Log a failed state query and answer 500; the body stays empty so
+/// no backend detail leaks to the browser.
+fn internal(err: crate::state::Error) -> StatusCode {
+    tracing::warn!(error = %err, "dashboard query failed");
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+/// Query parameters shared by the JSON endpoints.
+#[derive(Debug, Default, Deserialize)]
+struct Params {
+    /// Tenant (federation) shown; defaults to the unnamed tenant.
+    #[serde(default)]
+    tenant: String,
+}
+
+/// The `/api/overview` payload.
+#[derive(Debug, Serialize)]
+struct Overview {
+    online_nodes: u64,
+    runs: Vec<RunOverview>,
+}
+
+#[derive(Debug, Serialize)]
+struct RunOverview {
+    run_id: i64,
+    /// Undelivered instructions — the run's queue depth.
+    pending_task_ins: u64,
+    /// Stored instructions, delivered or not.
+    tasks: u64,
+    recordset_bytes: u64,
+    consumers: u64,
+}
+
+/// One dead-lettered task, the dashboard's notion of a recent error.
+#[derive(Debug, Serialize)]
+struct ErrorEntry {
+    task_id: String,
+    run_id: i64,
+    group_id: String,
+    consumer_id: i64,
+    task_type: String,
+    reason: String,
+    /// Seconds since the Unix epoch.
+    dead_at: f64,
+    delivery_count: u32,
+}
+
+async fn index() -> Html<&'static str> {
+    Html(include_str!("dashboard/index.html"))
+}
+
+async fn overview(
+    Extract(context): Extract<Context>,
+    headers: HeaderMap,
+    Query(params): Query<Params>,
+) -> Result<Json<Overview>, StatusCode> {
+    authorize(&context, &headers)?;
+    let state = context.state.as_ref();
+    let online_nodes = state.online_nodes(&params.tenant).await.map_err(internal)?;
+    let mut runs = Vec::new();
+    for run_id in state.runs(&params.tenant).await.map_err(internal)? {
+        let pending = state
+            .pending_run_task_ins(&params.tenant, run_id)
+            .await
+            .map_err(internal)?;
+        let usage = state.run_usage(&params.tenant, run_id).await.map_err(internal)?;
+        runs.push(RunOverview {
+            run_id: run_id.0,
+            pending_task_ins: pending,
+            tasks: usage.tasks,
+            recordset_bytes: usage.recordset_bytes,
+            consumers: usage.consumers.len() as u64,
+        });
+    }
+    Ok(Json(Overview { online_nodes, runs }))
+}
+
+async fn errors(
+    Extract(context): Extract<Context>,
+    headers: HeaderMap,
+    Query(params): Query<Params>,
+) -> Result<Json<Vec<ErrorEntry>>, StatusCode> {
+    authorize(&context, &headers)?;
+    // The queue is ordered oldest-first, so drain it and keep a
+    // rolling tail of the newest entries.
+    let mut recent = Vec::new();
+    let mut after: Option<TaskCursor> = None;
+    loop {
+        let page = context
+            .state
+            .list_dead_letters(&params.tenant, after.as_ref(), PAGE_SIZE)
+            .await
+            .map_err(internal)?;
+        let full = page.len() == PAGE_SIZE as usize;
+        if let Some(dead_letter) = page.last() {
+            after = Some(TaskCursor {
+                created_at: dead_letter.dead_at,
+                id: dead_letter.id.0.clone(),
+            });
+        }
+        recent.extend(page.into_iter().map(|dead_letter| ErrorEntry {
+            task_id: dead_letter.id.0,
+            run_id: dead_letter.run_id.0,
+            group_id: dead_letter.group_id.0,
+            consumer_id: dead_letter.consumer.id.0,
+            task_type: dead_letter.task_type,
+            reason: dead_letter.reason,
+            dead_at: dead_letter.dead_at,
+            delivery_count: dead_letter.delivery_count,
+        }));
+        if recent.len() > ERRORS_SHOWN {
+            recent.drain(..recent.len() - ERRORS_SHOWN);
+        }
+        if !full {
+            break;
+        }
+    }
+    recent.reverse();
+    Ok(Json(recent))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::state::memory::Memory;
+
+    fn context(auth_token: &str) -> Context {
+        Context {
+            state: Arc::new(Memory::new()),
+            auth_token: auth_token.to_owned(),
+        }
+    }
+
+    #[test]
+    fn requests_need_the_configured_token() {
+        let context = context("secret");
+        assert_eq!(authorize(&context, &HeaderMap::new()), Err(StatusCode::UNAUTHORIZED));
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, "Bearer wrong".parse().unwrap());
+        assert_eq!(authorize(&context, &headers), Err(StatusCode::UNAUTHORIZED));
+        headers.insert(header::AUTHORIZATION, "Bearer secret".parse().unwrap());
+        assert_eq!(authorize(&context, &headers), Ok(()));
+    }
+
+    #[test]
+    fn an_empty_token_leaves_the_dashboard_open() {
+        assert_eq!(authorize(&context(""), &HeaderMap::new()), Ok(()));
+    }
+
+    #[tokio::test]
+    async fn overview_reports_runs_and_online_nodes() {
+        let context = context("");
+        let run_id = context.state.create_run("").await.unwrap();
+        context
+            .state
+            .create_nodes("", 2, 3600.0, &HashMap::new(), &[])
+            .await
+            .unwrap();
+        let Json(overview) = overview(
+            Extract(context),
+            HeaderMap::new(),
+            Query(Params::default()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(overview.online_nodes, 2);
+        assert_eq!(overview.runs.len(), 1);
+        assert_eq!(overview.runs[0].run_id, run_id.0);
+        assert_eq!(overview.runs[0].pending_task_ins, 0);
+    }
+}
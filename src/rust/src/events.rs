@@ -0,0 +1,188 @@
+//! Mirror task state transitions into an external event bus.
+//!
+//! Transitions are queued onto a bounded channel and forwarded to the
+//! configured [`EventSink`] by a background worker, so the serving
+//! path never waits on the bus; when the queue is full new events are
+//! dropped with a WARN. Analytics pipelines consume the JSON-encoded
+//! events from the configured Kafka topic or NATS subject.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+/// One observable state transition of a task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transition {
+    /// A TaskIns was stored.
+    InsPushed,
+    /// A TaskIns was handed to its consumer.
+    InsDelivered,
+    /// A TaskRes was stored.
+    ResPushed,
+    /// A TaskRes was handed to its driver.
+    ResDelivered,
+    /// A TaskIns was moved to the dead-letter queue.
+    DeadLettered,
+}
+
+/// A task state transition, serialized as JSON for the bus.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TaskEvent {
+    pub tenant: String,
+    pub run_id: i64,
+    pub task_id: String,
+    pub transition: Transition,
+    /// Seconds since the Unix epoch.
+    pub occurred_at: f64,
+}
+
+impl TaskEvent {
+    pub fn new(tenant: &str, run_id: i64, task_id: &str, transition: Transition) -> Self {
+        Self {
+            tenant: tenant.to_owned(),
+            run_id,
+            task_id: task_id.to_owned(),
+            transition,
+            occurred_at: chrono::Utc::now().timestamp_micros() as f64 / 1e6,
+        }
+    }
+}
+
+/// Destination for task events; implementations publish one event and
+/// log failures rather than surfacing them, since the bus is an
+/// observability concern and must not fail task processing.
+#[async_trait]
+pub trait EventSink: Send + Sync {
+    async fn publish(&self, event: &TaskEvent);
+}
+
+/// Handle queueing events for publication to a sink.
+#[derive(Clone)]
+pub struct EventBus {
+    queue: mpsc::Sender<TaskEvent>,
+}
+
+impl EventBus {
+    /// Spawn the forwarding worker and return its handle.
+    pub fn spawn(sink: Arc<dyn EventSink>, queue_size: usize) -> Self {
+        let (queue, mut receiver) = mpsc::channel::<TaskEvent>(queue_size.max(1));
+        tokio::spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                sink.publish(&event).await;
+            }
+        });
+        Self { queue }
+    }
+
+    /// Queue `event` for publication.
+    pub fn publish(&self, event: TaskEvent) {
+        if self.queue.try_send(event).is_err() {
+            tracing::warn!("event bus queue full, task event dropped");
+        }
+    }
+}
+
+/// Kafka sink publishing JSON events to a topic, keyed by task id so
+/// a task's transitions land in one partition, in order.
+#[cfg(feature = "kafka")]
+pub struct Kafka {
+    producer: rdkafka::producer::FutureProducer,
+    topic: String,
+}
+
+#[cfg(feature = "kafka")]
+impl Kafka {
+    pub fn new(brokers: &str, topic: String) -> Result<Self, rdkafka::error::KafkaError> {
+        let producer = rdkafka::ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .create()?;
+        Ok(Self { producer, topic })
+    }
+}
+
+#[cfg(feature = "kafka")]
+#[async_trait]
+impl EventSink for Kafka {
+    async fn publish(&self, event: &TaskEvent) {
+        let payload = serde_json::to_vec(event).expect("task events serialize");
+        let record = rdkafka::producer::FutureRecord::to(&self.topic)
+            .key(&event.task_id)
+            .payload(&payload);
+        if let Err((err, _)) = self
+            .producer
+            .send(record, rdkafka::util::Timeout::Never)
+            .await
+        {
+            tracing::warn!(topic = %self.topic, error = %err, "kafka publish failed");
+        }
+    }
+}
+
+/// NATS sink publishing JSON events to a subject.
+#[cfg(feature = "nats")]
+pub struct Nats {
+    client: async_nats::Client,
+    subject: String,
+}
+
+#[cfg(feature = "nats")]
+impl Nats {
+    pub async fn connect(url: &str, subject: String) -> Result<Self, async_nats::ConnectError> {
+        let client = async_nats::connect(url).await?;
+        Ok(Self { client, subject })
+    }
+}
+
+#[cfg(feature = "nats")]
+#[async_trait]
+impl EventSink for Nats {
+    async fn publish(&self, event: &TaskEvent) {
+        let payload = serde_json::to_vec(event).expect("task events serialize");
+        if let Err(err) = self.client.publish(self.subject.clone(), payload.into()).await {
+            tracing::warn!(subject = %self.subject, error = %err, "nats publish failed");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    struct Recorder(Mutex<Vec<TaskEvent>>);
+
+    #[async_trait]
+    impl EventSink for Recorder {
+        async fn publish(&self, event: &TaskEvent) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    #[test]
+    fn transitions_serialize_in_snake_case() {
+        let event = TaskEvent::new("t", 7, "task-1", Transition::InsPushed);
+        let value = serde_json::to_value(&event).unwrap();
+        assert_eq!(value["transition"], "ins_pushed");
+        assert_eq!(value["run_id"], 7);
+    }
+
+    #[tokio::test]
+    async fn bus_forwards_events_to_the_sink() {
+        let sink = Arc::new(Recorder(Mutex::new(Vec::new())));
+        let bus = EventBus::spawn(sink.clone(), 8);
+        let event = TaskEvent::new("t", 7, "task-1", Transition::ResPushed);
+        bus.publish(event.clone());
+        tokio::time::timeout(std::time::Duration::from_secs(1), async {
+            while sink.0.lock().unwrap().is_empty() {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .expect("event forwarded");
+        assert_eq!(sink.0.lock().unwrap().as_slice(), [event]);
+    }
+}
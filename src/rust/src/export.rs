@@ -0,0 +1,287 @@
+//! Offline export of stored task metadata.
+//!
+//! Backs the `superlink export` subcommand: the instructions and
+//! results of one run are flattened into rows and written as CSV or,
+//! behind the `parquet` feature, Parquet. Payloads (recordsets) are
+//! never exported — only their sizes — so dumps stay small and free
+//! of model data.
+
+use crate::model::handler::{secs_from_datetime, RunId, TaskIns, TaskRes};
+use crate::state::{Result, State, TaskCursor};
+
+/// Rows fetched per page while draining the task listings.
+const PAGE_SIZE: u32 = 500;
+
+/// Metadata of one stored task, flattened for analysis; `res` rows
+/// carry their instruction's id in `parent_id`, so participation and
+/// latency can be joined offline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    /// `ins` or `res`.
+    pub kind: &'static str,
+    pub task_id: String,
+    pub parent_id: String,
+    pub group_id: String,
+    pub run_id: i64,
+    pub producer_id: i64,
+    pub consumer_id: i64,
+    pub task_type: String,
+    /// Seconds since the Unix epoch.
+    pub created_at: f64,
+    pub delivered_at: Option<f64>,
+    pub pushed_at: f64,
+    pub recordset_bytes: u64,
+    pub error_code: Option<i64>,
+}
+
+impl Row {
+    fn from_ins(task_ins: &TaskIns) -> Self {
+        Self {
+            kind: "ins",
+            task_id: task_ins.id.0.clone(),
+            parent_id: String::new(),
+            group_id: task_ins.group_id.0.clone(),
+            run_id: task_ins.run_id.0,
+            producer_id: task_ins.task.producer.id.0,
+            consumer_id: task_ins.task.consumer.id.0,
+            task_type: task_ins.task.task_type.clone(),
+            created_at: secs_from_datetime(task_ins.task.created_at),
+            delivered_at: task_ins.task.delivered_at.map(secs_from_datetime),
+            pushed_at: secs_from_datetime(task_ins.task.pushed_at),
+            recordset_bytes: task_ins.task.recordset.len() as u64,
+            error_code: task_ins.task.error.as_ref().map(|error| error.code),
+        }
+    }
+
+    fn from_res(task_res: &TaskRes) -> Self {
+        Self {
+            kind: "res",
+            task_id: task_res.id.0.clone(),
+            parent_id: task_res.task.ancestry.first().map(|id| id.0.clone()).unwrap_or_default(),
+            group_id: task_res.group_id.0.clone(),
+            run_id: task_res.run_id.0,
+            producer_id: task_res.task.producer.id.0,
+            consumer_id: task_res.task.consumer.id.0,
+            task_type: task_res.task.task_type.clone(),
+            created_at: secs_from_datetime(task_res.task.created_at),
+            delivered_at: task_res.task.delivered_at.map(secs_from_datetime),
+            pushed_at: secs_from_datetime(task_res.task.pushed_at),
+            recordset_bytes: task_res.task.recordset.len() as u64,
+            error_code: task_res.task.error.as_ref().map(|error| error.code),
+        }
+    }
+}
+
+/// Drain every instruction and result of `run_id` into rows,
+/// instructions first.
+pub async fn collect(state: &dyn State, tenant: &str, run_id: RunId) -> Result<Vec<Row>> {
+    let mut rows = Vec::new();
+    let mut after: Option<TaskCursor> = None;
+    loop {
+        let page = state.list_task_ins(tenant, run_id, &[], after.as_ref(), PAGE_SIZE).await?;
+        rows.extend(page.iter().map(Row::from_ins));
+        match page.last() {
+            Some(task_ins) if page.len() == PAGE_SIZE as usize => {
+                after = Some(TaskCursor {
+                    created_at: secs_from_datetime(task_ins.task.created_at),
+                    id: task_ins.id.0.clone(),
+                });
+            }
+            _ => break,
+        }
+    }
+    let mut after: Option<TaskCursor> = None;
+    loop {
+        let page = state.list_task_res(tenant, run_id, &[], after.as_ref(), PAGE_SIZE).await?;
+        rows.extend(page.iter().map(Row::from_res));
+        match page.last() {
+            Some(task_res) if page.len() == PAGE_SIZE as usize => {
+                after = Some(TaskCursor {
+                    created_at: secs_from_datetime(task_res.task.created_at),
+                    id: task_res.id.0.clone(),
+                });
+            }
+            _ => break,
+        }
+    }
+    Ok(rows)
+}
+
+/// The exported column names, in order.
+const COLUMNS: [&str; 13] = [
+    "kind",
+    "task_id",
+    "parent_id",
+    "group_id",
+    "run_id",
+    "producer_id",
+    "consumer_id",
+    "task_type",
+    "created_at",
+    "delivered_at",
+    "pushed_at",
+    "recordset_bytes",
+    "error_code",
+];
+
+/// Quote a CSV field when it contains a delimiter, quote or newline.
+fn csv_field(value: &str) -> String {
+    if value.contains(['"', ',', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+/// Write the rows as RFC 4180 CSV with a header line; optional fields
+/// are left empty when absent.
+pub fn write_csv<W: std::io::Write>(rows: &[Row], mut writer: W) -> std::io::Result<()> {
+    writeln!(writer, "{}", COLUMNS.join(","))?;
+    for row in rows {
+        let fields = [
+            row.kind.to_owned(),
+            csv_field(&row.task_id),
+            csv_field(&row.parent_id),
+            csv_field(&row.group_id),
+            row.run_id.to_string(),
+            row.producer_id.to_string(),
+            row.consumer_id.to_string(),
+            csv_field(&row.task_type),
+            row.created_at.to_string(),
+            row.delivered_at.map(|at| at.to_string()).unwrap_or_default(),
+            row.pushed_at.to_string(),
+            row.recordset_bytes.to_string(),
+            row.error_code.map(|code| code.to_string()).unwrap_or_default(),
+        ];
+        writeln!(writer, "{}", fields.join(","))?;
+    }
+    writer.flush()
+}
+
+/// Write the rows as a single-row-group Parquet file.
+#[cfg(feature = "parquet")]
+pub fn write_parquet<W: std::io::Write + Send>(
+    rows: &[Row],
+    writer: W,
+) -> std::result::Result<(), parquet::errors::ParquetError> {
+    use parquet::data_type::ByteArray;
+    use parquet::file::writer::SerializedFileWriter;
+
+    let schema = parquet::schema::parser::parse_message_type(
+        "message task {
+            required byte_array kind (utf8);
+            required byte_array task_id (utf8);
+            required byte_array parent_id (utf8);
+            required byte_array group_id (utf8);
+            required int64 run_id;
+            required int64 producer_id;
+            required int64 consumer_id;
+            required byte_array task_type (utf8);
+            required double created_at;
+            optional double delivered_at;
+            required double pushed_at;
+            required int64 recordset_bytes;
+            optional int64 error_code;
+        }",
+    )?;
+    let mut writer = SerializedFileWriter::new(
+        writer,
+        std::sync::Arc::new(schema),
+        std::sync::Arc::new(parquet::file::properties::WriterProperties::new()),
+    )?;
+    let mut group = writer.next_row_group()?;
+
+    type Group<'a> = parquet::file::writer::SerializedRowGroupWriter<'a, W>;
+    type ParquetResult = std::result::Result<(), parquet::errors::ParquetError>;
+
+    fn strings(group: &mut Group<'_>, values: Vec<ByteArray>) -> ParquetResult {
+        let mut column = group.next_column()?.expect("schema has the column");
+        column
+            .typed::<parquet::data_type::ByteArrayType>()
+            .write_batch(&values, None, None)?;
+        column.close()
+    }
+    fn required<T: parquet::data_type::DataType>(
+        group: &mut Group<'_>,
+        values: Vec<T::T>,
+    ) -> ParquetResult {
+        let mut column = group.next_column()?.expect("schema has the column");
+        column.typed::<T>().write_batch(&values, None, None)?;
+        column.close()
+    }
+    fn optional<T: parquet::data_type::DataType>(
+        group: &mut Group<'_>,
+        values: Vec<Option<T::T>>,
+    ) -> ParquetResult {
+        let def_levels: Vec<i16> = values.iter().map(|value| value.is_some() as i16).collect();
+        let present: Vec<T::T> = values.into_iter().flatten().collect();
+        let mut column = group.next_column()?.expect("schema has the column");
+        column.typed::<T>().write_batch(&present, Some(&def_levels), None)?;
+        column.close()
+    }
+    fn byte_arrays<'a>(values: impl Iterator<Item = &'a str>) -> Vec<ByteArray> {
+        values.map(|value| ByteArray::from(value.as_bytes().to_vec())).collect()
+    }
+
+    use parquet::data_type::{DoubleType, Int64Type};
+    // Columns must be written in schema order.
+    strings(&mut group, byte_arrays(rows.iter().map(|row| row.kind)))?;
+    strings(&mut group, byte_arrays(rows.iter().map(|row| row.task_id.as_str())))?;
+    strings(&mut group, byte_arrays(rows.iter().map(|row| row.parent_id.as_str())))?;
+    strings(&mut group, byte_arrays(rows.iter().map(|row| row.group_id.as_str())))?;
+    required::<Int64Type>(&mut group, rows.iter().map(|row| row.run_id).collect())?;
+    required::<Int64Type>(&mut group, rows.iter().map(|row| row.producer_id).collect())?;
+    required::<Int64Type>(&mut group, rows.iter().map(|row| row.consumer_id).collect())?;
+    strings(&mut group, byte_arrays(rows.iter().map(|row| row.task_type.as_str())))?;
+    required::<DoubleType>(&mut group, rows.iter().map(|row| row.created_at).collect())?;
+    optional::<DoubleType>(&mut group, rows.iter().map(|row| row.delivered_at).collect())?;
+    required::<DoubleType>(&mut group, rows.iter().map(|row| row.pushed_at).collect())?;
+    required::<Int64Type>(
+        &mut group,
+        rows.iter().map(|row| row.recordset_bytes as i64).collect(),
+    )?;
+    optional::<Int64Type>(&mut group, rows.iter().map(|row| row.error_code).collect())?;
+    group.close()?;
+    writer.close()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_quotes_only_what_needs_quoting() {
+        assert_eq!(csv_field("train"), "train");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn csv_rows_line_up_with_the_header() {
+        let row = Row {
+            kind: "ins",
+            task_id: "task-1".to_owned(),
+            parent_id: String::new(),
+            group_id: "round-1".to_owned(),
+            run_id: 7,
+            producer_id: 0,
+            consumer_id: 3,
+            task_type: "train".to_owned(),
+            created_at: 1.5,
+            delivered_at: None,
+            pushed_at: 1.5,
+            recordset_bytes: 42,
+            error_code: None,
+        };
+        let mut out = Vec::new();
+        write_csv(&[row], &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let mut lines = text.lines();
+        let header = lines.next().unwrap();
+        let data = lines.next().unwrap();
+        assert_eq!(header.split(',').count(), COLUMNS.len());
+        assert_eq!(data.split(',').count(), COLUMNS.len());
+        assert_eq!(data, "ins,task-1,,round-1,7,0,3,train,1.5,,1.5,42,");
+    }
+}
@@ -0,0 +1,122 @@
+//! Admin-facing business logic.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::model::handler::{
+    AuditEvent, DeadLetter, GroupId, NodeId, NodeInfo, RunId, TaskIns, TaskRes,
+};
+use crate::state::{Result, State, TaskCursor, TaskFilter};
+
+use super::audit;
+
+/// Handles Admin API requests against the configured state backend.
+#[derive(Clone)]
+pub struct AdminHandler {
+    state: Arc<dyn State>,
+}
+
+/// Default and maximum page size for list queries.
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 1000;
+
+fn clamp_page_size(page_size: u32) -> u32 {
+    if page_size == 0 {
+        DEFAULT_PAGE_SIZE
+    } else {
+        page_size.min(MAX_PAGE_SIZE)
+    }
+}
+
+impl AdminHandler {
+    pub fn new(state: Arc<dyn State>) -> Self {
+        Self { state }
+    }
+
+    /// One page of task instructions for a run, narrowed to the rows
+    /// matching every filter.
+    pub async fn list_task_ins(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        filters: &[TaskFilter],
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<TaskIns>> {
+        self.state
+            .list_task_ins(tenant, run_id, filters, after, clamp_page_size(page_size))
+            .await
+    }
+
+    /// Per-group instruction and result counts of a run, sorted by
+    /// group id.
+    pub async fn run_progress(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+    ) -> Result<Vec<(GroupId, u64, u64)>> {
+        self.state.run_progress(tenant, run_id).await
+    }
+
+    /// Node counts per reported client version.
+    pub async fn client_versions(&self, tenant: &str) -> Result<HashMap<String, u64>> {
+        self.state.client_versions(tenant).await
+    }
+
+    /// Every node of the tenant with its identity and liveness
+    /// metadata, sorted by id.
+    pub async fn list_nodes(&self, tenant: &str) -> Result<Vec<NodeInfo>> {
+        self.state.list_nodes(tenant).await
+    }
+
+    /// Ban a node, removing it and rejecting it until unbanned.
+    pub async fn ban_node(&self, tenant: &str, node_id: NodeId, reason: &str) -> Result<()> {
+        self.state.ban_node(tenant, node_id, reason).await?;
+        audit(self.state.as_ref(), tenant, "node.ban", node_id, RunId(0), reason).await
+    }
+
+    /// Lift a ban issued via [`AdminHandler::ban_node`].
+    pub async fn unban_node(&self, tenant: &str, node_id: NodeId) -> Result<()> {
+        self.state.unban_node(tenant, node_id).await?;
+        audit(self.state.as_ref(), tenant, "node.unban", node_id, RunId(0), "").await
+    }
+
+    /// One page of the audit log.
+    pub async fn list_audit_events(
+        &self,
+        tenant: &str,
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<AuditEvent>> {
+        self.state
+            .list_audit_events(tenant, after, clamp_page_size(page_size))
+            .await
+    }
+
+    /// One page of the dead-letter queue.
+    pub async fn list_dead_letters(
+        &self,
+        tenant: &str,
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<DeadLetter>> {
+        self.state
+            .list_dead_letters(tenant, after, clamp_page_size(page_size))
+            .await
+    }
+
+    /// One page of task results for a run, filtered like
+    /// [`AdminHandler::list_task_ins`].
+    pub async fn list_task_res(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        filters: &[TaskFilter],
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<TaskRes>> {
+        self.state
+            .list_task_res(tenant, run_id, filters, after, clamp_page_size(page_size))
+            .await
+    }
+}
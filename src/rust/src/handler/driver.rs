@@ -0,0 +1,387 @@
+//! Driver-facing business logic.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::events::{EventBus, TaskEvent, Transition};
+use crate::middleware::metrics::TaskMetrics;
+use crate::notifier::{Event, Notifier};
+use crate::model::handler::{Node, NodeId, RunId, TaskId, TaskIns, TaskRes};
+use crate::state::blob::BlobBackend;
+use crate::state::{Error, Result, State};
+
+use super::watchdog::RoundWatchdog;
+use super::{audit, mint_task_id, TaskIdMode};
+
+/// Limits applied to a push, resolved from the dynamic configuration
+/// at request time; every field uses 0 for "disabled".
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PushLimits {
+    /// Undelivered instructions a registered consumer may accumulate.
+    pub max_pending_per_node: u32,
+    /// Undelivered instructions a run may accumulate.
+    pub max_pending_per_run: u32,
+    /// Total instructions a run may have stored, delivered or not.
+    pub max_run_tasks: u64,
+    /// Total bytes of recordsets a run may have stored.
+    pub max_run_recordset_bytes: u64,
+    /// Distinct registered consumer nodes a run may address.
+    pub max_run_consumers: u32,
+}
+
+/// Handles Driver API requests against the configured state backend.
+#[derive(Clone)]
+pub struct DriverHandler {
+    state: Arc<dyn State>,
+    blob: Option<BlobBackend>,
+    task_id_mode: TaskIdMode,
+    metrics: Option<Arc<TaskMetrics>>,
+    watchdog: Option<Arc<RoundWatchdog>>,
+    notifier: Option<Arc<Notifier>>,
+    events: Option<EventBus>,
+}
+
+impl DriverHandler {
+    pub fn new(
+        state: Arc<dyn State>,
+        blob: Option<BlobBackend>,
+        task_id_mode: TaskIdMode,
+        metrics: Option<Arc<TaskMetrics>>,
+    ) -> Self {
+        Self {
+            state,
+            blob,
+            task_id_mode,
+            metrics,
+            watchdog: None,
+            notifier: None,
+            events: None,
+        }
+    }
+
+    /// Install a watchdog logging task groups that stall.
+    pub fn set_watchdog(&mut self, watchdog: Arc<RoundWatchdog>) {
+        self.watchdog = Some(watchdog);
+    }
+
+    /// Install a notifier emitting lifecycle webhooks.
+    pub fn set_notifier(&mut self, notifier: Arc<Notifier>) {
+        self.notifier = Some(notifier);
+    }
+
+    /// Install a bus mirroring task state transitions.
+    pub fn set_event_bus(&mut self, events: EventBus) {
+        self.events = Some(events);
+    }
+
+    fn notify(&self, event: Event) {
+        if let Some(notifier) = &self.notifier {
+            notifier.notify(event);
+        }
+    }
+
+    /// The task metrics instruments, when metrics are enabled.
+    pub fn metrics(&self) -> Option<&TaskMetrics> {
+        self.metrics.as_deref()
+    }
+
+    /// Create a new run.
+    pub async fn create_run(&self, tenant: &str) -> Result<RunId> {
+        let run_id = self.state.create_run(tenant).await?;
+        audit(self.state.as_ref(), tenant, "run.create", NodeId(0), run_id, "").await?;
+        self.notify(Event::RunCreated {
+            tenant: tenant.to_owned(),
+            run_id: run_id.0,
+        });
+        Ok(run_id)
+    }
+
+    /// Delete a finished run and every task stored for it.
+    pub async fn delete_run(&self, tenant: &str, run_id: RunId) -> Result<()> {
+        self.state.delete_run(tenant, run_id).await?;
+        audit(self.state.as_ref(), tenant, "run.delete", NodeId(0), run_id, "").await?;
+        self.notify(Event::RunFinished {
+            tenant: tenant.to_owned(),
+            run_id: run_id.0,
+        });
+        Ok(())
+    }
+
+    /// All nodes currently online for `run_id`.
+    pub async fn nodes(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        selector: &HashMap<String, String>,
+    ) -> Result<Vec<Node>> {
+        let ids = self.state.nodes(tenant, run_id, selector).await?;
+        Ok(ids
+            .into_iter()
+            .map(|id| Node {
+                id,
+                anonymous: false,
+            })
+            .collect())
+    }
+
+    /// A server-side random sample of online nodes for `run_id`.
+    pub async fn sample_nodes(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        count: u32,
+        seed: Option<u64>,
+        selector: &HashMap<String, String>,
+    ) -> Result<Vec<Node>> {
+        let ids = self
+            .state
+            .sample_nodes(tenant, run_id, count, seed, selector)
+            .await?;
+        Ok(ids
+            .into_iter()
+            .map(|id| Node {
+                id,
+                anonymous: false,
+            })
+            .collect())
+    }
+
+    /// Reject the batch when storing it would take any of its runs
+    /// over an enabled run quota.
+    async fn check_run_quotas(
+        &self,
+        tenant: &str,
+        instructions: &[TaskIns],
+        limits: &PushLimits,
+    ) -> Result<()> {
+        if limits.max_run_tasks == 0
+            && limits.max_run_recordset_bytes == 0
+            && limits.max_run_consumers == 0
+        {
+            return Ok(());
+        }
+        let mut batches: HashMap<RunId, (u64, u64, HashSet<NodeId>)> = HashMap::new();
+        for instruction in instructions {
+            let batch = batches.entry(instruction.run_id).or_default();
+            batch.0 += 1;
+            batch.1 += instruction.task.recordset.len() as u64;
+            if !instruction.task.consumer.anonymous {
+                batch.2.insert(instruction.task.consumer.id);
+            }
+        }
+        for (&run_id, (pushed, bytes, consumers)) in &batches {
+            let usage = self.state.run_usage(tenant, run_id).await?;
+            if limits.max_run_tasks > 0 && usage.tasks + pushed > limits.max_run_tasks {
+                return Err(Error::RunQuota {
+                    run_id,
+                    resource: "task",
+                    used: usage.tasks,
+                    limit: limits.max_run_tasks,
+                });
+            }
+            if limits.max_run_recordset_bytes > 0
+                && usage.recordset_bytes + bytes > limits.max_run_recordset_bytes
+            {
+                return Err(Error::RunQuota {
+                    run_id,
+                    resource: "recordset byte",
+                    used: usage.recordset_bytes,
+                    limit: limits.max_run_recordset_bytes,
+                });
+            }
+            let combined = usage.consumers.union(consumers).count() as u64;
+            if limits.max_run_consumers > 0 && combined > u64::from(limits.max_run_consumers) {
+                return Err(Error::RunQuota {
+                    run_id,
+                    resource: "consumer node",
+                    used: usage.consumers.len() as u64,
+                    limit: u64::from(limits.max_run_consumers),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Store task instructions, returning their assigned ids.
+    ///
+    /// Pushes that would exceed any enabled limit are rejected as a
+    /// whole: the per-node and per-run pending limits bound the
+    /// undelivered queue depth, and the run quotas bound what a run
+    /// may have stored in total.
+    pub async fn push_task_instructions(
+        &self,
+        tenant: &str,
+        mut instructions: Vec<TaskIns>,
+        limits: &PushLimits,
+    ) -> Result<Vec<TaskId>> {
+        if limits.max_pending_per_run > 0 {
+            let mut batch: HashMap<RunId, u64> = HashMap::new();
+            for instruction in &instructions {
+                *batch.entry(instruction.run_id).or_default() += 1;
+            }
+            for (&run_id, &pushed) in &batch {
+                let pending = self.state.pending_run_task_ins(tenant, run_id).await?;
+                if pending + pushed > u64::from(limits.max_pending_per_run) {
+                    return Err(Error::RunTaskLimit {
+                        run_id,
+                        pending,
+                        limit: limits.max_pending_per_run,
+                    });
+                }
+            }
+        }
+        if limits.max_pending_per_node > 0 {
+            let mut batch: HashMap<NodeId, u64> = HashMap::new();
+            for instruction in &instructions {
+                if !instruction.task.consumer.anonymous {
+                    *batch.entry(instruction.task.consumer.id).or_default() += 1;
+                }
+            }
+            for (&node_id, &pushed) in &batch {
+                let consumer = Node {
+                    id: node_id,
+                    anonymous: false,
+                };
+                let pending = self.state.pending_task_ins(tenant, &consumer).await?;
+                if pending + pushed > u64::from(limits.max_pending_per_node) {
+                    return Err(Error::PendingTaskLimit {
+                        node_id,
+                        pending,
+                        limit: limits.max_pending_per_node,
+                    });
+                }
+            }
+        }
+        self.check_run_quotas(tenant, &instructions, limits).await?;
+        for (sequence, instruction) in instructions.iter_mut().enumerate() {
+            instruction.id = mint_task_id(
+                self.task_id_mode,
+                instruction.run_id,
+                &instruction.group_id,
+                &instruction.task.consumer,
+                sequence,
+            );
+            if let Some(blob) = &self.blob {
+                blob.offload(&mut instruction.task.recordset).await?;
+            }
+        }
+        let ids = self
+            .state
+            .insert_task_instructions(tenant, &instructions)
+            .await?;
+        if let Some(metrics) = &self.metrics {
+            for instruction in &instructions {
+                metrics.task_ins_pushed(instruction.run_id.0, 1);
+            }
+        }
+        if let Some(events) = &self.events {
+            for instruction in &instructions {
+                events.publish(TaskEvent::new(
+                    tenant,
+                    instruction.run_id.0,
+                    instruction.id.as_ref(),
+                    Transition::InsPushed,
+                ));
+            }
+        }
+        if let Some(watchdog) = &self.watchdog {
+            for instruction in &instructions {
+                if !instruction.group_id.0.is_empty() {
+                    watchdog.touch(tenant, instruction.run_id, &instruction.group_id);
+                }
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Expand one instruction into a copy per online node of its run,
+    /// matching `selector`, and store them in a single push; the
+    /// template's consumer is ignored. Returns the assigned ids.
+    pub async fn broadcast_task_instructions(
+        &self,
+        tenant: &str,
+        template: TaskIns,
+        selector: &HashMap<String, String>,
+        limits: &PushLimits,
+    ) -> Result<Vec<TaskId>> {
+        let mut node_ids: Vec<NodeId> = self
+            .state
+            .nodes(tenant, template.run_id, selector)
+            .await?
+            .into_iter()
+            .collect();
+        node_ids.sort_unstable();
+        let instructions: Vec<TaskIns> = node_ids
+            .into_iter()
+            .map(|node_id| {
+                let mut instruction = template.clone();
+                instruction.task.consumer = Node {
+                    id: node_id,
+                    anonymous: false,
+                };
+                instruction
+            })
+            .collect();
+        if instructions.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.push_task_instructions(tenant, instructions, limits).await
+    }
+
+    /// Retrieve results for previously pushed instructions.
+    ///
+    /// Returned tasks are deleted from the state right away; a second
+    /// pull for the same ids yields nothing.
+    pub async fn pull_task_results(
+        &self,
+        tenant: &str,
+        task_ids: &[TaskId],
+        keep: bool,
+    ) -> Result<Vec<TaskRes>> {
+        let mut results = self.state.task_results(tenant, task_ids, None, !keep).await?;
+        if let Some(blob) = &self.blob {
+            for task_res in &mut results {
+                blob.resolve(&mut task_res.task.recordset).await?;
+            }
+        }
+        if keep {
+            // Peek mode: the results stay stored and pullable until
+            // the driver acknowledges receipt.
+            return Ok(results);
+        }
+        let delivered: Vec<TaskId> = results
+            .iter()
+            .flat_map(|task_res| task_res.task.ancestry.clone())
+            .collect();
+        self.state.delete_tasks(tenant, &delivered).await?;
+        if let Some(metrics) = &self.metrics {
+            for task_res in &results {
+                metrics.task_res_delivered(task_res.run_id.0, 1);
+            }
+        }
+        if let Some(events) = &self.events {
+            for task_res in &results {
+                events.publish(TaskEvent::new(
+                    tenant,
+                    task_res.run_id.0,
+                    task_res.id.as_ref(),
+                    Transition::ResDelivered,
+                ));
+            }
+        }
+        Ok(results)
+    }
+
+    /// Confirm receipt of results pulled in peek mode: the answered
+    /// tasks are marked delivered and deleted.
+    pub async fn acknowledge_task_results(&self, tenant: &str, task_ids: &[TaskId]) -> Result<()> {
+        let results = self.state.task_results(tenant, task_ids, None, true).await?;
+        self.state.delete_tasks(tenant, task_ids).await?;
+        if let Some(metrics) = &self.metrics {
+            for task_res in &results {
+                metrics.task_res_delivered(task_res.run_id.0, 1);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,521 @@
+//! Fleet-facing business logic.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::events::{EventBus, TaskEvent, Transition};
+use crate::middleware::metrics::TaskMetrics;
+use crate::notifier::{Event, Notifier};
+use crate::model::handler::{Node, NodeId, RunId, TaskId, TaskIns, TaskRes};
+use crate::state::blob::BlobBackend;
+use crate::state::{Error, PingOutcome, Result, State};
+
+use super::hooks::AggregationHook;
+use super::scheduler::{Fifo, Scheduler};
+use super::watchdog::RoundWatchdog;
+use super::{audit, mint_task_id, mint_task_res_id, TaskIdMode};
+
+/// Handles Fleet API requests against the configured state backend.
+#[derive(Clone)]
+pub struct FleetHandler {
+    state: Arc<dyn State>,
+    blob: Option<BlobBackend>,
+    task_id_mode: TaskIdMode,
+    metrics: Option<Arc<TaskMetrics>>,
+    scheduler: Arc<dyn Scheduler>,
+    pool_mode: bool,
+    hook: Option<Arc<dyn AggregationHook>>,
+    watchdog: Option<Arc<RoundWatchdog>>,
+    notifier: Option<Arc<Notifier>>,
+    events: Option<EventBus>,
+}
+
+impl FleetHandler {
+    pub fn new(
+        state: Arc<dyn State>,
+        blob: Option<BlobBackend>,
+        task_id_mode: TaskIdMode,
+        metrics: Option<Arc<TaskMetrics>>,
+    ) -> Self {
+        Self {
+            state,
+            blob,
+            task_id_mode,
+            metrics,
+            scheduler: Arc::new(Fifo),
+            pool_mode: false,
+            hook: None,
+            watchdog: None,
+            notifier: None,
+            events: None,
+        }
+    }
+
+    /// Replace the policy deciding which instructions a pull hands
+    /// out; the default is strictly oldest-first.
+    pub fn set_scheduler(&mut self, scheduler: Arc<dyn Scheduler>) {
+        self.scheduler = scheduler;
+    }
+
+    /// The task metrics instruments, when metrics are enabled.
+    pub fn metrics(&self) -> Option<&TaskMetrics> {
+        self.metrics.as_deref()
+    }
+
+    /// Let registered nodes top up their pull from the anonymous pool,
+    /// first-come-first-served, for job-queue style federations.
+    pub fn set_pool_mode(&mut self, pool_mode: bool) {
+        self.pool_mode = pool_mode;
+    }
+
+    /// Install a hook observing task-group completion; the default is
+    /// none.
+    pub fn set_aggregation_hook(&mut self, hook: Arc<dyn AggregationHook>) {
+        self.hook = Some(hook);
+    }
+
+    /// Install a watchdog logging task groups that stall.
+    pub fn set_watchdog(&mut self, watchdog: Arc<RoundWatchdog>) {
+        self.watchdog = Some(watchdog);
+    }
+
+    /// Install a notifier emitting lifecycle webhooks.
+    pub fn set_notifier(&mut self, notifier: Arc<Notifier>) {
+        self.notifier = Some(notifier);
+    }
+
+    /// Install a bus mirroring task state transitions.
+    pub fn set_event_bus(&mut self, events: EventBus) {
+        self.events = Some(events);
+    }
+
+    fn notify(&self, event: Event) {
+        if let Some(notifier) = &self.notifier {
+            notifier.notify(event);
+        }
+    }
+
+    /// Register a new node.
+    pub async fn create_node(
+        &self,
+        tenant: &str,
+        ping_interval: f64,
+        properties: &HashMap<String, String>,
+        task_types: &[String],
+        client_version: &str,
+        identity: &str,
+    ) -> Result<Node> {
+        let node_id = self
+            .state
+            .create_node(tenant, ping_interval, properties, task_types)
+            .await?;
+        if !client_version.is_empty() {
+            self.state
+                .record_client_version(tenant, node_id, client_version)
+                .await?;
+        }
+        if !identity.is_empty() {
+            self.state
+                .record_node_identity(tenant, node_id, identity)
+                .await?;
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.client_request(client_version);
+            metrics.nodes_registered(1);
+            metrics.ping_interval(ping_interval);
+        }
+        audit(self.state.as_ref(), tenant, "node.create", node_id, RunId(0), "").await?;
+        self.notify(Event::NodeJoined {
+            tenant: tenant.to_owned(),
+            node_id: node_id.0,
+        });
+        Ok(Node {
+            id: node_id,
+            anonymous: false,
+        })
+    }
+
+    /// Reconnect a node under its previous id, refreshing the
+    /// existing record in place; `None` means the id is unknown (or
+    /// was deleted) and the caller should register afresh.
+    pub async fn reconnect_node(
+        &self,
+        tenant: &str,
+        node_id: NodeId,
+        ping_interval: f64,
+        properties: &HashMap<String, String>,
+        task_types: &[String],
+        client_version: &str,
+        identity: &str,
+    ) -> Result<Option<Node>> {
+        let node = Node {
+            id: node_id,
+            anonymous: false,
+        };
+        self.ensure_not_banned(tenant, &node).await?;
+        if !self
+            .state
+            .reconnect_node(tenant, node_id, ping_interval, properties, task_types)
+            .await?
+        {
+            return Ok(None);
+        }
+        if !client_version.is_empty() {
+            self.state
+                .record_client_version(tenant, node_id, client_version)
+                .await?;
+        }
+        if !identity.is_empty() {
+            self.state
+                .record_node_identity(tenant, node_id, identity)
+                .await?;
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.client_request(client_version);
+            metrics.ping_interval(ping_interval);
+        }
+        audit(self.state.as_ref(), tenant, "node.reconnect", node_id, RunId(0), "").await?;
+        Ok(Some(node))
+    }
+
+    /// Register `count` nodes in one bulk insert, for simulation
+    /// drivers that stand up large federations.
+    pub async fn create_nodes(
+        &self,
+        tenant: &str,
+        count: u32,
+        ping_interval: f64,
+        properties: &HashMap<String, String>,
+        task_types: &[String],
+    ) -> Result<Vec<Node>> {
+        let node_ids = self
+            .state
+            .create_nodes(tenant, count, ping_interval, properties, task_types)
+            .await?;
+        if let Some(metrics) = &self.metrics {
+            metrics.nodes_registered(node_ids.len() as u64);
+        }
+        let detail = format!("batch of {}", node_ids.len());
+        audit(self.state.as_ref(), tenant, "node.create_batch", NodeId(0), RunId(0), &detail)
+            .await?;
+        for &node_id in &node_ids {
+            self.notify(Event::NodeJoined {
+                tenant: tenant.to_owned(),
+                node_id: node_id.0,
+            });
+        }
+        Ok(node_ids
+            .into_iter()
+            .map(|node_id| Node {
+                id: node_id,
+                anonymous: false,
+            })
+            .collect())
+    }
+
+    /// Remove a node from the federation.
+    pub async fn delete_node(&self, tenant: &str, node: &Node, identity: &str) -> Result<()> {
+        if node.anonymous {
+            return Ok(());
+        }
+        self.ensure_owns_node(tenant, node, identity).await?;
+        self.state.delete_node(tenant, node.id).await?;
+        if let Some(metrics) = &self.metrics {
+            metrics.nodes_deregistered(1);
+        }
+        self.notify(Event::NodeLeft {
+            tenant: tenant.to_owned(),
+            node_id: node.id.0,
+        });
+        audit(self.state.as_ref(), tenant, "node.delete", node.id, RunId(0), "").await
+    }
+
+    /// Remove several nodes in one bulk delete.
+    pub async fn delete_nodes(&self, tenant: &str, node_ids: &[NodeId]) -> Result<()> {
+        self.state.delete_nodes(tenant, node_ids).await?;
+        if let Some(metrics) = &self.metrics {
+            metrics.nodes_deregistered(node_ids.len() as u64);
+        }
+        for &node_id in node_ids {
+            self.notify(Event::NodeLeft {
+                tenant: tenant.to_owned(),
+                node_id: node_id.0,
+            });
+        }
+        let detail = format!("batch of {}", node_ids.len());
+        audit(self.state.as_ref(), tenant, "node.delete_batch", NodeId(0), RunId(0), &detail)
+            .await
+    }
+
+    /// Acknowledge a ping from `node`.
+    pub async fn ping(
+        &self,
+        tenant: &str,
+        node: &Node,
+        ping_interval: f64,
+        task_types: &[String],
+        client_version: &str,
+        identity: &str,
+    ) -> Result<bool> {
+        self.ensure_not_banned(tenant, node).await?;
+        self.ensure_owns_node(tenant, node, identity).await?;
+        let outcome = self.state.update_ping(tenant, node, ping_interval, task_types).await?;
+        let known = outcome != PingOutcome::Unknown;
+        if known && !node.anonymous && !client_version.is_empty() {
+            self.state
+                .record_client_version(tenant, node.id, client_version)
+                .await?;
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.client_request(client_version);
+            metrics.ping_interval(ping_interval);
+            if outcome == PingOutcome::Lapsed {
+                metrics.node_expired();
+            }
+        }
+        Ok(known)
+    }
+
+    /// Acknowledge pings for many nodes in one bulk update, for
+    /// gateways proxying fleets of devices; each entry pairs a node id
+    /// with its ping interval. Returns how many of the nodes were
+    /// known and refreshed.
+    pub async fn ping_batch(&self, tenant: &str, pings: &[(NodeId, f64)]) -> Result<u64> {
+        self.state.update_pings(tenant, pings).await
+    }
+
+    /// Pull undelivered task instructions for `node`.
+    pub async fn pull_task_instructions(
+        &self,
+        tenant: &str,
+        node: &Node,
+        limit: Option<u32>,
+        identity: &str,
+    ) -> Result<Vec<TaskIns>> {
+        self.ensure_not_banned(tenant, node).await?;
+        self.ensure_owns_node(tenant, node, identity).await?;
+        let mut instructions = match limit {
+            Some(limit) if limit > 0 => {
+                // Let the scheduler over-fetch and reorder, keep the
+                // first `limit` and hand the surplus back undelivered.
+                let fetch = self.scheduler.fetch_limit(limit).max(limit);
+                let fetched = self.state.task_instructions(tenant, node, Some(fetch)).await?;
+                let mut instructions = self.scheduler.select(fetched);
+                let surplus = instructions.split_off((limit as usize).min(instructions.len()));
+                if !surplus.is_empty() {
+                    let ids = surplus.iter().map(|ins| ins.id.clone()).collect::<Vec<_>>();
+                    self.state.release_tasks(tenant, node, &ids).await?;
+                }
+                instructions
+            }
+            _ => {
+                let fetched = self.state.task_instructions(tenant, node, limit).await?;
+                self.scheduler.select(fetched)
+            }
+        };
+        if self.pool_mode && !node.anonymous {
+            let remaining = limit.map(|limit| limit.saturating_sub(instructions.len() as u32));
+            if remaining != Some(0) {
+                let claimed = self
+                    .state
+                    .claim_pool_task_instructions(tenant, node, remaining)
+                    .await?;
+                instructions.extend(claimed);
+            }
+        }
+        if let Some(blob) = &self.blob {
+            for instruction in &mut instructions {
+                blob.resolve(&mut instruction.task.recordset).await?;
+            }
+        }
+        if let Some(metrics) = &self.metrics {
+            for instruction in &instructions {
+                metrics.task_ins_delivered(instruction.run_id.0, 1);
+            }
+        }
+        if let Some(events) = &self.events {
+            for instruction in &instructions {
+                events.publish(TaskEvent::new(
+                    tenant,
+                    instruction.run_id.0,
+                    instruction.id.as_ref(),
+                    Transition::InsDelivered,
+                ));
+            }
+        }
+        Ok(instructions)
+    }
+
+    /// Hand back instructions the node cannot process; they become
+    /// immediately eligible for redelivery.
+    pub async fn nack_task_instructions(
+        &self,
+        tenant: &str,
+        node: &Node,
+        task_ids: &[TaskId],
+        identity: &str,
+    ) -> Result<u64> {
+        self.ensure_not_banned(tenant, node).await?;
+        self.ensure_owns_node(tenant, node, identity).await?;
+        self.state.release_tasks(tenant, node, task_ids).await
+    }
+
+    /// Store one task result, returning its assigned id.
+    pub async fn push_task_result(
+        &self,
+        tenant: &str,
+        mut task_res: TaskRes,
+        identity: &str,
+    ) -> Result<TaskId> {
+        self.ensure_owns_node(tenant, &task_res.task.producer, identity).await?;
+        // A result must come from the node its ancestor instruction
+        // was addressed to and stay in the same run; anything else
+        // could poison aggregation with spoofed results. Instructions
+        // addressed to the anonymous pool carry no id to compare.
+        let ancestors = self
+            .state
+            .task_ins_consumers(tenant, &task_res.task.ancestry)
+            .await?;
+        for (ancestor_id, (run_id, consumer)) in &ancestors {
+            if *run_id != task_res.run_id {
+                return Err(Error::AncestorMismatch {
+                    task_id: ancestor_id.clone(),
+                    reason: "run id differs",
+                });
+            }
+            if !consumer.anonymous && *consumer != task_res.task.producer {
+                return Err(Error::AncestorMismatch {
+                    task_id: ancestor_id.clone(),
+                    reason: "produced by a node other than the addressed consumer",
+                });
+            }
+        }
+        task_res.id = mint_task_res_id(self.task_id_mode, &task_res);
+        if let Some(blob) = &self.blob {
+            blob.offload(&mut task_res.task.recordset).await?;
+        }
+        let run_id = task_res.run_id;
+        let group_id = task_res.group_id.clone();
+        let mut ids = self.state.insert_task_results(tenant, &[task_res]).await?;
+        if let Some(metrics) = &self.metrics {
+            metrics.task_res_pushed(run_id.0, 1);
+        }
+        if let (Some(events), Some(id)) = (&self.events, ids.last()) {
+            events.publish(TaskEvent::new(tenant, run_id.0, id.as_ref(), Transition::ResPushed));
+        }
+        if !group_id.0.is_empty()
+            && (self.hook.is_some() || self.watchdog.is_some() || self.notifier.is_some())
+        {
+            if let Some(watchdog) = &self.watchdog {
+                watchdog.touch(tenant, run_id, &group_id);
+            }
+            let (instructions, results) =
+                self.state.group_progress(tenant, run_id, &group_id).await?;
+            if instructions > 0 && results >= instructions {
+                if let Some(hook) = &self.hook {
+                    hook.on_group_complete(tenant, run_id, &group_id, results).await;
+                }
+                if let Some(watchdog) = &self.watchdog {
+                    watchdog.complete(tenant, run_id, &group_id);
+                }
+                if let Some(metrics) = &self.metrics {
+                    metrics.group_completed(run_id.0);
+                }
+                self.notify(Event::RoundFinished {
+                    tenant: tenant.to_owned(),
+                    run_id: run_id.0,
+                    group_id: group_id.0.clone(),
+                    results,
+                });
+            }
+        }
+        Ok(ids.pop().expect("one result stored"))
+    }
+
+    /// Enforce that a caller acting on `node` presented the identity
+    /// recorded for it at registration. Nodes that never presented
+    /// one stay open, keeping enforcement opt-in per node; anonymous
+    /// nodes carry no id to protect.
+    async fn ensure_owns_node(&self, tenant: &str, node: &Node, identity: &str) -> Result<()> {
+        if node.anonymous {
+            return Ok(());
+        }
+        let recorded = self
+            .state
+            .node_identity(tenant, node.id)
+            .await?
+            .unwrap_or_default();
+        if recorded.is_empty() || recorded == identity {
+            return Ok(());
+        }
+        let detail = "identity mismatch";
+        audit(self.state.as_ref(), tenant, "auth.rejected", node.id, RunId(0), detail).await?;
+        Err(Error::NotNodeOwner(node.id))
+    }
+
+    /// Reject requests from banned nodes; anonymous nodes carry no id
+    /// to check.
+    async fn ensure_not_banned(&self, tenant: &str, node: &Node) -> Result<()> {
+        if !node.anonymous && self.state.is_node_banned(tenant, node.id).await? {
+            audit(self.state.as_ref(), tenant, "auth.rejected", node.id, RunId(0), "banned")
+                .await?;
+            return Err(Error::NodeBanned(node.id));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::handler::{GroupId, Task};
+    use crate::state::memory::Memory;
+
+    fn task_res(run_id: RunId, producer: Node) -> TaskRes {
+        let now = chrono::Utc::now();
+        TaskRes {
+            id: TaskId::default(),
+            group_id: GroupId("round-1".to_owned()),
+            run_id,
+            task: Task {
+                producer,
+                consumer: Node {
+                    id: NodeId(0),
+                    anonymous: true,
+                },
+                created_at: now,
+                delivered_at: None,
+                pushed_at: now,
+                ttl: String::new(),
+                ancestry: Vec::new(),
+                task_type: "train".to_owned(),
+                recordset: bytes::Bytes::new(),
+                recordset_checksum: String::new(),
+                error: None,
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn deterministic_results_from_distinct_producers_are_both_stored() {
+        let state: Arc<dyn State> = Arc::new(Memory::default());
+        let run_id = state.create_run("t").await.unwrap();
+        let handler = FleetHandler::new(state.clone(), None, TaskIdMode::Deterministic, None);
+        let mut ids = Vec::new();
+        for node_id in [NodeId(1), NodeId(2)] {
+            let producer = Node {
+                id: node_id,
+                anonymous: false,
+            };
+            let id = handler
+                .push_task_result("t", task_res(run_id, producer), "")
+                .await
+                .unwrap();
+            ids.push(id);
+        }
+        assert_ne!(ids[0], ids[1]);
+        let group = GroupId("round-1".to_owned());
+        let (_, results) = state.group_progress("t", run_id, &group).await.unwrap();
+        assert_eq!(results, 2);
+    }
+}
@@ -0,0 +1,43 @@
+//! Server-side hooks observing task-group progress.
+
+use async_trait::async_trait;
+
+use crate::model::handler::{GroupId, RunId};
+
+/// Plugin point invoked when every instruction of a task group has a
+/// stored result.
+///
+/// One hook is configured at startup and shared by all tenants; the
+/// default installs none. Hooks run on the push path after the
+/// completing result was stored, so they must be cheap — anything
+/// heavy belongs in a spawned task.
+#[async_trait]
+pub trait AggregationHook: Send + Sync {
+    /// Called after the result completing `group_id` of `run_id` was
+    /// stored; `results` is the number of results now present.
+    async fn on_group_complete(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        group_id: &GroupId,
+        results: u64,
+    );
+}
+
+/// Built-in hook announcing completed groups on the log, for
+/// deployments that tail logs to drive round orchestration.
+pub struct LogHook;
+
+#[async_trait]
+impl AggregationHook for LogHook {
+    async fn on_group_complete(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        group_id: &GroupId,
+        results: u64,
+    ) {
+        let group_id = group_id.as_ref();
+        tracing::info!(tenant, run_id = run_id.0, group_id, results, "task group complete");
+    }
+}
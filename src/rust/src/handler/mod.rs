@@ -0,0 +1,176 @@
+//! Business logic shared by the transport layers.
+
+use uuid::Uuid;
+
+use crate::model::handler::{AuditEvent, GroupId, Node, NodeId, RunId, TaskId, TaskRes};
+use crate::state::{Result, State};
+
+pub mod admin;
+pub mod driver;
+pub mod fleet;
+pub mod hooks;
+pub mod scheduler;
+pub mod watchdog;
+
+pub use admin::AdminHandler;
+pub use driver::DriverHandler;
+pub use fleet::FleetHandler;
+
+/// Append a security-relevant event to the audit log.
+///
+/// Events are also emitted on the `audit` tracing target, so operators
+/// can tee them into a JSON log stream with a directive like
+/// `audit=info`.
+pub(crate) async fn audit(
+    state: &dyn State,
+    tenant: &str,
+    action: &str,
+    node_id: NodeId,
+    run_id: RunId,
+    detail: &str,
+) -> Result<()> {
+    let event = AuditEvent {
+        id: Uuid::new_v4().to_string(),
+        created_at: chrono::Utc::now().timestamp_micros() as f64 / 1e6,
+        action: action.to_owned(),
+        node_id,
+        run_id,
+        detail: detail.to_owned(),
+    };
+    tracing::info!(
+        target: "audit",
+        tenant,
+        action = %event.action,
+        node_id = event.node_id.0,
+        run_id = event.run_id.0,
+        detail = %event.detail,
+        "audit event"
+    );
+    state.record_audit_event(tenant, &event).await
+}
+
+/// Namespace for deterministic (UUIDv5) task ids.
+const TASK_ID_NAMESPACE: Uuid = Uuid::from_u128(0x666c_7772_0000_0000_0000_0000_0000_0001);
+
+/// How new task ids are minted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskIdMode {
+    /// Random UUIDv4 (the default).
+    Random,
+    /// UUIDv5 of (run_id, group_id, consumer, sequence) for
+    /// instructions and of (run_id, group_id, producer, ancestry) for
+    /// results, so retries of the same push produce the same ids and
+    /// cross-system correlation does not depend on server state.
+    Deterministic,
+}
+
+pub(crate) fn mint_task_id(
+    mode: TaskIdMode,
+    run_id: RunId,
+    group_id: &GroupId,
+    consumer: &Node,
+    sequence: usize,
+) -> TaskId {
+    let id = match mode {
+        TaskIdMode::Random => Uuid::new_v4(),
+        TaskIdMode::Deterministic => {
+            let name = format!(
+                "{run_id}/{group_id}/{}/{}/{sequence}",
+                consumer.id, consumer.anonymous
+            );
+            Uuid::new_v5(&TASK_ID_NAMESPACE, name.as_bytes())
+        }
+    };
+    TaskId(id.to_string())
+}
+
+/// Mint the id of a pushed result. The deterministic name covers the
+/// producing node and the instruction ancestry — not the consumer,
+/// which is the same driver-side node for every result of a round —
+/// so a retried push mints the same id while results from distinct
+/// nodes stay distinct.
+pub(crate) fn mint_task_res_id(mode: TaskIdMode, task_res: &TaskRes) -> TaskId {
+    let id = match mode {
+        TaskIdMode::Random => Uuid::new_v4(),
+        TaskIdMode::Deterministic => {
+            let producer = &task_res.task.producer;
+            let ancestry: Vec<&str> = task_res.task.ancestry.iter().map(|id| id.as_ref()).collect();
+            let name = format!(
+                "{}/{}/res/{}/{}/{}",
+                task_res.run_id,
+                task_res.group_id,
+                producer.id,
+                producer.anonymous,
+                ancestry.join(",")
+            );
+            Uuid::new_v5(&TASK_ID_NAMESPACE, name.as_bytes())
+        }
+    };
+    TaskId(id.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deterministic_ids_are_stable() {
+        let consumer = Node {
+            id: NodeId(7),
+            anonymous: false,
+        };
+        let group = GroupId("round-1".to_owned());
+        let a = mint_task_id(TaskIdMode::Deterministic, RunId(1), &group, &consumer, 0);
+        let b = mint_task_id(TaskIdMode::Deterministic, RunId(1), &group, &consumer, 0);
+        assert_eq!(a, b);
+        let c = mint_task_id(TaskIdMode::Deterministic, RunId(1), &group, &consumer, 1);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn deterministic_result_ids_distinguish_producers() {
+        let result = |producer_id, ancestry| TaskRes {
+            id: TaskId::default(),
+            group_id: GroupId("round-1".to_owned()),
+            run_id: RunId(1),
+            task: crate::model::handler::Task {
+                producer: Node {
+                    id: producer_id,
+                    anonymous: false,
+                },
+                consumer: Node {
+                    id: NodeId(0),
+                    anonymous: true,
+                },
+                created_at: chrono::Utc::now(),
+                delivered_at: None,
+                pushed_at: chrono::Utc::now(),
+                ttl: String::new(),
+                ancestry,
+                task_type: "train".to_owned(),
+                recordset: bytes::Bytes::new(),
+                recordset_checksum: String::new(),
+                error: None,
+            },
+        };
+        let parent = TaskId("parent".to_owned());
+        let mode = TaskIdMode::Deterministic;
+        let a = mint_task_res_id(mode, &result(NodeId(1), vec![parent.clone()]));
+        let b = mint_task_res_id(mode, &result(NodeId(2), vec![parent.clone()]));
+        assert_ne!(a, b);
+        let again = mint_task_res_id(mode, &result(NodeId(1), vec![parent]));
+        assert_eq!(a, again);
+    }
+
+    #[test]
+    fn random_ids_differ() {
+        let consumer = Node {
+            id: NodeId(7),
+            anonymous: false,
+        };
+        let group = GroupId("round-1".to_owned());
+        let a = mint_task_id(TaskIdMode::Random, RunId(1), &group, &consumer, 0);
+        let b = mint_task_id(TaskIdMode::Random, RunId(1), &group, &consumer, 0);
+        assert_ne!(a, b);
+    }
+}
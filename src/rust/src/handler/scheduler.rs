@@ -0,0 +1,182 @@
+//! Pluggable selection of which task instructions a node pulls next.
+
+use std::collections::VecDeque;
+
+use crate::model::handler::{RunId, TaskIns};
+
+/// How many times the pull limit reordering schedulers fetch; the
+/// surplus is released back undelivered.
+const FETCH_FACTOR: u32 = 4;
+
+/// Decides which of a node's eligible instructions a pull hands out.
+///
+/// The state backend returns candidates oldest-first. A scheduler may
+/// ask for more candidates than the pull limit via [`fetch_limit`] and
+/// reorder them via [`select`]; the pull keeps the first `limit`
+/// selected instructions and releases the surplus back undelivered.
+///
+/// [`fetch_limit`]: Scheduler::fetch_limit
+/// [`select`]: Scheduler::select
+pub trait Scheduler: Send + Sync {
+    /// How many candidates to fetch for a pull capped at `limit`.
+    fn fetch_limit(&self, limit: u32) -> u32 {
+        limit
+    }
+
+    /// Order `candidates` by delivery preference, most urgent first.
+    fn select(&self, candidates: Vec<TaskIns>) -> Vec<TaskIns>;
+}
+
+/// Strictly oldest-first, the default.
+pub struct Fifo;
+
+impl Scheduler for Fifo {
+    fn select(&self, candidates: Vec<TaskIns>) -> Vec<TaskIns> {
+        candidates
+    }
+}
+
+/// Round-robin across runs, so a busy run cannot starve the others.
+pub struct FairShare;
+
+impl Scheduler for FairShare {
+    fn fetch_limit(&self, limit: u32) -> u32 {
+        limit.saturating_mul(FETCH_FACTOR)
+    }
+
+    fn select(&self, candidates: Vec<TaskIns>) -> Vec<TaskIns> {
+        interleave_by_run(candidates)
+    }
+}
+
+/// Configured task types first: instructions whose task type appears
+/// earlier in the precedence list are handed out before later ones,
+/// unlisted types come last, and ties stay oldest-first.
+pub struct Priority {
+    precedence: Vec<String>,
+}
+
+impl Priority {
+    pub fn new(precedence: Vec<String>) -> Self {
+        Self { precedence }
+    }
+
+    fn rank(&self, task_type: &str) -> usize {
+        self.precedence
+            .iter()
+            .position(|preferred| preferred == task_type)
+            .unwrap_or(self.precedence.len())
+    }
+}
+
+impl Scheduler for Priority {
+    fn fetch_limit(&self, limit: u32) -> u32 {
+        limit.saturating_mul(FETCH_FACTOR)
+    }
+
+    fn select(&self, mut candidates: Vec<TaskIns>) -> Vec<TaskIns> {
+        // Stable sort: candidates arrive oldest-first and keep that
+        // order within each rank.
+        candidates.sort_by_key(|instruction| self.rank(&instruction.task.task_type));
+        candidates
+    }
+}
+
+/// Interleave `instructions` round-robin across run ids. Runs rotate
+/// in order of their oldest instruction and the oldest-first order
+/// within each run is preserved.
+fn interleave_by_run(instructions: Vec<TaskIns>) -> Vec<TaskIns> {
+    let mut queues: Vec<(RunId, VecDeque<TaskIns>)> = Vec::new();
+    for instruction in instructions {
+        match queues.iter_mut().find(|(run_id, _)| *run_id == instruction.run_id) {
+            Some((_, queue)) => queue.push_back(instruction),
+            None => queues.push((instruction.run_id, VecDeque::from([instruction]))),
+        }
+    }
+    let mut interleaved = Vec::new();
+    while !queues.is_empty() {
+        for (_, queue) in &mut queues {
+            interleaved.extend(queue.pop_front());
+        }
+        queues.retain(|(_, queue)| !queue.is_empty());
+    }
+    interleaved
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::model::handler::{GroupId, Node, NodeId, Task, TaskId};
+
+    use super::*;
+
+    fn task_ins(id: &str, run_id: i64, task_type: &str) -> TaskIns {
+        TaskIns {
+            id: TaskId(id.to_owned()),
+            group_id: GroupId::default(),
+            run_id: RunId(run_id),
+            task: Task {
+                producer: Node {
+                    id: NodeId(0),
+                    anonymous: true,
+                },
+                consumer: Node {
+                    id: NodeId(7),
+                    anonymous: false,
+                },
+                created_at: Utc::now(),
+                delivered_at: None,
+                pushed_at: Utc::now(),
+                ttl: String::new(),
+                ancestry: Vec::new(),
+                task_type: task_type.to_owned(),
+                recordset: bytes::Bytes::new(),
+                recordset_checksum: String::new(),
+                error: None,
+            },
+        }
+    }
+
+    #[test]
+    fn fair_share_alternates_runs() {
+        let selected = FairShare.select(vec![
+            task_ins("a1", 1, ""),
+            task_ins("a2", 1, ""),
+            task_ins("a3", 1, ""),
+            task_ins("b1", 2, ""),
+            task_ins("b2", 2, ""),
+            task_ins("c1", 3, ""),
+        ]);
+        let ids = selected.iter().map(|ins| ins.id.as_ref()).collect::<Vec<_>>();
+        assert_eq!(ids, ["a1", "b1", "c1", "a2", "b2", "a3"]);
+    }
+
+    #[test]
+    fn fair_share_keeps_single_run_order() {
+        let selected = FairShare.select(vec![task_ins("a1", 1, ""), task_ins("a2", 1, "")]);
+        let ids = selected.iter().map(|ins| ins.id.as_ref()).collect::<Vec<_>>();
+        assert_eq!(ids, ["a1", "a2"]);
+    }
+
+    #[test]
+    fn priority_orders_by_precedence_then_age() {
+        let scheduler = Priority::new(vec!["evaluate".to_owned(), "fit".to_owned()]);
+        let selected = scheduler.select(vec![
+            task_ins("f1", 1, "fit"),
+            task_ins("q1", 1, "query"),
+            task_ins("e1", 1, "evaluate"),
+            task_ins("f2", 1, "fit"),
+        ]);
+        let ids = selected.iter().map(|ins| ins.id.as_ref()).collect::<Vec<_>>();
+        assert_eq!(ids, ["e1", "f1", "f2", "q1"]);
+    }
+
+    #[test]
+    fn fifo_is_a_passthrough() {
+        assert_eq!(Fifo.fetch_limit(3), 3);
+        let selected = Fifo.select(vec![task_ins("a1", 1, ""), task_ins("b1", 2, "")]);
+        let ids = selected.iter().map(|ins| ins.id.as_ref()).collect::<Vec<_>>();
+        assert_eq!(ids, ["a1", "b1"]);
+    }
+}
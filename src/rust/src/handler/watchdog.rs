@@ -0,0 +1,110 @@
+//! In-process watchdog logging task groups that stopped making
+//! progress.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::model::handler::{GroupId, RunId};
+
+/// Watches every task group seen on the push paths and logs a WARN
+/// once a group goes longer than the stall threshold without a new
+/// instruction or result. Purely observational and per replica: it
+/// never touches the state backend, and a restart simply starts
+/// watching afresh.
+pub struct RoundWatchdog {
+    stall_after: Duration,
+    groups: Mutex<HashMap<(String, RunId, GroupId), GroupWatch>>,
+}
+
+struct GroupWatch {
+    last_progress: Instant,
+    warned: bool,
+}
+
+impl RoundWatchdog {
+    pub fn new(stall_after: Duration) -> Self {
+        Self {
+            stall_after,
+            groups: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record progress (a pushed instruction or result) for a group,
+    /// rearming its stall timer.
+    pub fn touch(&self, tenant: &str, run_id: RunId, group_id: &GroupId) {
+        let mut groups = self.groups.lock().unwrap();
+        let watch = groups
+            .entry((tenant.to_owned(), run_id, group_id.clone()))
+            .or_insert(GroupWatch {
+                last_progress: Instant::now(),
+                warned: false,
+            });
+        watch.last_progress = Instant::now();
+        watch.warned = false;
+    }
+
+    /// Stop watching a completed group.
+    pub fn complete(&self, tenant: &str, run_id: RunId, group_id: &GroupId) {
+        self.groups
+            .lock()
+            .unwrap()
+            .remove(&(tenant.to_owned(), run_id, group_id.clone()));
+    }
+
+    /// Log every group without progress for longer than the stall
+    /// threshold, once per stall.
+    fn sweep(&self) {
+        let mut groups = self.groups.lock().unwrap();
+        for ((tenant, run_id, group_id), watch) in groups.iter_mut() {
+            if !watch.warned && watch.last_progress.elapsed() >= self.stall_after {
+                watch.warned = true;
+                tracing::warn!(
+                    tenant,
+                    run_id = run_id.0,
+                    group_id = group_id.as_ref(),
+                    stalled_for = ?watch.last_progress.elapsed(),
+                    "task group stalled"
+                );
+            }
+        }
+    }
+
+    /// Spawn a background task sweeping for stalled groups at half the
+    /// stall threshold.
+    pub fn spawn(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        let period = Duration::from_secs_f64((self.stall_after.as_secs_f64() / 2.0).max(1.0));
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(period);
+            loop {
+                ticker.tick().await;
+                self.sweep();
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stalled_groups_are_warned_once_until_touched() {
+        let watchdog = RoundWatchdog::new(Duration::ZERO);
+        let group = GroupId("round-1".to_owned());
+        watchdog.touch("t", RunId(1), &group);
+        watchdog.sweep();
+        {
+            let groups = watchdog.groups.lock().unwrap();
+            assert!(groups.values().all(|watch| watch.warned));
+        }
+        // New progress rearms the stall timer.
+        watchdog.touch("t", RunId(1), &group);
+        {
+            let groups = watchdog.groups.lock().unwrap();
+            assert!(groups.values().all(|watch| !watch.warned));
+        }
+        watchdog.complete("t", RunId(1), &group);
+        assert!(watchdog.groups.lock().unwrap().is_empty());
+    }
+}
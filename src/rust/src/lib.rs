@@ -0,0 +1,36 @@
+//! Flower SuperLink: long-running server exposing the Fleet API towards
+//! client nodes and the Driver API towards server-side drivers.
+
+#[cfg(feature = "client")]
+pub mod client;
+pub mod config;
+pub mod dashboard;
+pub mod events;
+pub mod export;
+pub mod handler;
+pub mod logging;
+pub mod migrate;
+pub mod middleware;
+pub mod model;
+pub mod notifier;
+pub mod rest;
+pub mod service;
+pub mod simulation;
+pub mod state;
+pub mod tracer;
+
+/// Generated protobuf/gRPC types for the `flwr.proto` package.
+pub mod pb {
+    tonic::include_proto!("flwr.proto");
+
+    // With the raw-recordsets feature the code generator maps
+    // `flwr.proto.RecordSet` onto the raw type; re-export it under the
+    // generated name so call sites stay feature-agnostic.
+    #[cfg(feature = "raw-recordsets")]
+    pub use crate::model::raw::RawRecordSet as RecordSet;
+}
+
+/// Embedded refinery migrations, shared by the binaries.
+pub mod embedded {
+    refinery::embed_migrations!("migrations");
+}
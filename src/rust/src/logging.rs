@@ -0,0 +1,27 @@
+//! Runtime control over the tracing log filter.
+
+use tracing_subscriber::reload;
+use tracing_subscriber::EnvFilter;
+
+/// Type-erased handle to the reloadable `EnvFilter`, so the services
+/// do not have to be generic over the subscriber stack.
+pub struct LogFilterHandle {
+    reload: Box<dyn Fn(EnvFilter) -> Result<(), reload::Error> + Send + Sync>,
+}
+
+impl LogFilterHandle {
+    pub fn new<S>(handle: reload::Handle<EnvFilter, S>) -> Self
+    where
+        S: Send + Sync + 'static,
+    {
+        Self {
+            reload: Box::new(move |filter| handle.reload(filter)),
+        }
+    }
+
+    /// Replace the active filter with the given directives.
+    pub fn set(&self, directives: &str) -> Result<(), String> {
+        let filter = EnvFilter::try_new(directives).map_err(|err| err.to_string())?;
+        (self.reload)(filter).map_err(|err| err.to_string())
+    }
+}
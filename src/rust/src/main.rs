@@ -0,0 +1,512 @@
+//! SuperLink entry point: serves the Fleet and Driver APIs.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use clap::{Parser, Subcommand};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+use flwr_superlink::config::{Config, DynamicConfig, SchedulerKind};
+use flwr_superlink::events::{EventBus, TaskEvent, Transition};
+use flwr_superlink::handler::{hooks, watchdog};
+use flwr_superlink::handler::scheduler::{self, Scheduler};
+use flwr_superlink::handler::{AdminHandler, DriverHandler, FleetHandler, TaskIdMode};
+use flwr_superlink::logging::LogFilterHandle;
+use flwr_superlink::middleware::deadline::DeadlineLayer;
+use flwr_superlink::middleware::message_size::MessageSizeLayer;
+use flwr_superlink::middleware::metrics::{ServerMetrics, ServerMetricsLayer, TaskMetrics};
+use flwr_superlink::middleware::trace;
+use flwr_superlink::model::handler::RunId;
+use flwr_superlink::notifier::{Event, Notifier};
+use flwr_superlink::pb::admin_server::AdminServer;
+use flwr_superlink::pb::driver_server::DriverServer;
+use flwr_superlink::pb::fleet_server::FleetServer;
+use flwr_superlink::service::{AdminService, DriverService, FleetService};
+use flwr_superlink::simulation::VirtualPool;
+use flwr_superlink::state::blob::{BlobBackend, Filesystem};
+use flwr_superlink::state::breaker::Breaker;
+use flwr_superlink::state::cache::Cache;
+use flwr_superlink::state::postgres::Postgres;
+use flwr_superlink::state::retry::Retry;
+use flwr_superlink::state::timeout::Timeout;
+use flwr_superlink::state::State;
+use flwr_superlink::tracer;
+
+#[derive(Debug, Parser)]
+#[command(name = "superlink", about = "Flower SuperLink")]
+struct Args {
+    /// Path to a YAML configuration file.
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Run the server.
+    Serve,
+    /// Apply pending migrations and exit.
+    Migrate,
+    /// Validate the configuration and print the effective values.
+    CheckConfig,
+    /// Dump the task metadata of a run (never the payloads) for
+    /// offline analysis of participation and latency.
+    Export {
+        /// Run to export.
+        #[arg(long)]
+        run_id: i64,
+        /// Tenant the run belongs to.
+        #[arg(long, default_value = "")]
+        tenant: String,
+        /// Output format; `parquet` requires building with the
+        /// parquet feature.
+        #[arg(long, value_enum, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+        /// File the export is written to.
+        #[arg(long)]
+        output: PathBuf,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum ExportFormat {
+    Csv,
+    Parquet,
+}
+
+type Error = Box<dyn std::error::Error>;
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    let args = Args::parse();
+    let config = Config::load(args.config.as_deref())?;
+
+    match args.command {
+        Command::Serve => serve(config, args.config).await,
+        Command::Migrate => {
+            init_logging(&config)?;
+            flwr_superlink::migrate::run(&config.database.uri).await?;
+            Ok(())
+        }
+        Command::CheckConfig => {
+            println!("{config:#?}");
+            Ok(())
+        }
+        Command::Export {
+            run_id,
+            tenant,
+            format,
+            output,
+        } => export(&config, run_id, &tenant, format, &output).await,
+    }
+}
+
+/// Dump the task metadata of one run to `output`.
+async fn export(
+    config: &Config,
+    run_id: i64,
+    tenant: &str,
+    format: ExportFormat,
+    output: &std::path::Path,
+) -> Result<(), Error> {
+    init_logging(config)?;
+    let state = Postgres::new(&config.database.uri, config.database.pool_size).await?;
+    let rows = flwr_superlink::export::collect(&state, tenant, RunId(run_id)).await?;
+    let file = std::fs::File::create(output)?;
+    match format {
+        ExportFormat::Csv => flwr_superlink::export::write_csv(&rows, file)?,
+        #[cfg(feature = "parquet")]
+        ExportFormat::Parquet => flwr_superlink::export::write_parquet(&rows, file)?,
+        #[cfg(not(feature = "parquet"))]
+        ExportFormat::Parquet => {
+            return Err("format \"parquet\" requires building with the parquet feature".into())
+        }
+    }
+    tracing::info!(run_id, rows = rows.len(), path = %output.display(), "run exported");
+    Ok(())
+}
+
+fn init_logging(config: &Config) -> Result<(), Error> {
+    tracing_subscriber::registry()
+        .with(EnvFilter::try_new(&config.logging.level)?)
+        .with(tracing_subscriber::fmt::layer())
+        .init();
+    Ok(())
+}
+
+async fn serve(config: Config, config_path: Option<PathBuf>) -> Result<(), Error> {
+    let (filter, filter_handle) =
+        tracing_subscriber::reload::Layer::new(EnvFilter::try_new(&config.logging.level)?);
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer());
+    if config.tracer.enabled {
+        let otel_tracer = tracer::install(&config.tracer, &(&config).into())?;
+        registry
+            .with(tracing_opentelemetry::layer().with_tracer(otel_tracer))
+            .init();
+    } else {
+        registry.init();
+    }
+
+    if config.database.migrate_on_startup {
+        flwr_superlink::migrate::run(&config.database.uri).await?;
+    }
+    if config.database.partition_tasks_by_run {
+        flwr_superlink::state::postgres::partition::convert(&config.database.uri).await?;
+    }
+
+    let meter = if config.tracer.enabled {
+        Some(tracer::install_metrics(&config.tracer, &(&config).into())?)
+    } else {
+        None
+    };
+    let metrics_layer = meter
+        .as_ref()
+        .map(|meter| ServerMetricsLayer::new(ServerMetrics::new(meter)));
+    let task_metrics = meter
+        .as_ref()
+        .map(|meter| Arc::new(TaskMetrics::new(meter, config.tracer.max_run_labels)));
+
+    let mut postgres = Postgres::new(&config.database.uri, config.database.pool_size).await?;
+    postgres.set_slow_query_threshold(std::time::Duration::from_millis(
+        config.database.slow_query_ms,
+    ));
+    postgres.set_partition_by_run(config.database.partition_tasks_by_run);
+    postgres.set_run_cache_ttl(std::time::Duration::from_millis(
+        config.database.run_cache_ttl_ms,
+    ));
+    postgres.set_log_sql(config.logging.log_sql);
+    postgres.set_ping_grace(config.fleet.ping_grace);
+    if let Some(meter) = &meter {
+        postgres.register_metrics(meter);
+    }
+    let retry = Retry::new(postgres, (&config).into());
+    let breaker = Breaker::new(Timeout::new(retry, (&config).into()), (&config).into());
+    let mut breaker_open = breaker.subscribe();
+    let state: Arc<dyn State> = Arc::new(Cache::new(breaker, (&config).into()));
+    let notifier = Arc::new(Notifier::spawn(config.notifier.clone()));
+    let events = event_bus(&config).await?;
+    if config.tasks.redelivery_after_ms > 0 {
+        let lease = std::time::Duration::from_millis(config.tasks.redelivery_after_ms);
+        let max_redeliveries = config.tasks.max_redeliveries;
+        let sweeper = state.clone();
+        let sweep_notifier = notifier.clone();
+        let sweep_events = events.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(lease);
+            loop {
+                interval.tick().await;
+                match sweeper.release_expired_tasks(lease, max_redeliveries).await {
+                    Ok((released, dead)) => {
+                        if released > 0 {
+                            tracing::info!(released, "expired task leases released");
+                        }
+                        for (tenant, parked) in dead {
+                            if let Some(events) = &sweep_events {
+                                events.publish(TaskEvent::new(
+                                    &tenant,
+                                    parked.run_id.0,
+                                    parked.id.as_ref(),
+                                    Transition::DeadLettered,
+                                ));
+                            }
+                            sweep_notifier.notify(Event::TaskDeadLettered {
+                                tenant,
+                                run_id: parked.run_id.0,
+                                task_id: parked.id.0,
+                                reason: parked.reason,
+                            });
+                        }
+                    }
+                    Err(err) => tracing::warn!(error = %err, "lease sweep failed"),
+                }
+            }
+        });
+    }
+    if config.dashboard.enabled {
+        tokio::spawn(flwr_superlink::dashboard::serve(
+            state.clone(),
+            config.dashboard.clone(),
+        ));
+    }
+    if config.simulation.virtual_nodes > 0 {
+        let pool = VirtualPool::provision(
+            state.clone(),
+            &config.simulation.tenant,
+            config.simulation.virtual_nodes,
+            config.simulation.ping_interval,
+            &std::collections::HashMap::new(),
+            &config.simulation.task_types,
+        )
+        .await?;
+        tracing::info!(nodes = pool.node_ids().len(), "virtual node pool provisioned");
+        Arc::new(pool).spawn_keepalive();
+    }
+    let blob = blob_backend(&config).await?;
+    let task_id_mode = if config.tasks.deterministic_ids {
+        TaskIdMode::Deterministic
+    } else {
+        TaskIdMode::Random
+    };
+
+    let mut fleet_handler =
+        FleetHandler::new(state.clone(), blob.clone(), task_id_mode, task_metrics.clone());
+    let pull_scheduler: Arc<dyn Scheduler> = match config.fleet.scheduler {
+        SchedulerKind::Fifo => Arc::new(scheduler::Fifo),
+        SchedulerKind::Fair => Arc::new(scheduler::FairShare),
+        SchedulerKind::Priority => {
+            Arc::new(scheduler::Priority::new(config.fleet.priority_task_types.clone()))
+        }
+    };
+    fleet_handler.set_scheduler(pull_scheduler);
+    fleet_handler.set_pool_mode(config.fleet.anonymous_pool);
+    if config.fleet.log_completed_groups {
+        fleet_handler.set_aggregation_hook(Arc::new(hooks::LogHook));
+    }
+    fleet_handler.set_notifier(notifier.clone());
+    if let Some(events) = &events {
+        fleet_handler.set_event_bus(events.clone());
+    }
+    let mut driver_handler = DriverHandler::new(state.clone(), blob, task_id_mode, task_metrics);
+    driver_handler.set_notifier(notifier.clone());
+    if let Some(events) = events {
+        driver_handler.set_event_bus(events);
+    }
+    if config.tasks.stall_after_ms > 0 {
+        let watchdog = Arc::new(watchdog::RoundWatchdog::new(std::time::Duration::from_millis(
+            config.tasks.stall_after_ms,
+        )));
+        watchdog.clone().spawn();
+        fleet_handler.set_watchdog(watchdog.clone());
+        driver_handler.set_watchdog(watchdog);
+    }
+    let admin_handler = AdminHandler::new(state.clone());
+
+    trace::init_verbose(trace::VerboseConfig::new(
+        config.logging.verbose,
+        config.logging.verbose_methods.clone(),
+    ));
+
+    let (dynamic_tx, dynamic_rx) = tokio::sync::watch::channel(DynamicConfig::from(&config));
+    spawn_reload_handler(config_path, dynamic_tx, filter_handle);
+
+    if config.rest.enabled {
+        tokio::spawn(flwr_superlink::rest::serve(
+            FleetService::new(fleet_handler.clone(), dynamic_rx.clone()),
+            config.rest.clone(),
+        ));
+    }
+    let fleet = FleetServer::new(FleetService::new(fleet_handler, dynamic_rx.clone()))
+        .max_decoding_message_size(config.server.max_message_size);
+    let driver = DriverServer::new(DriverService::new(driver_handler, dynamic_rx))
+        .max_decoding_message_size(config.server.max_message_size);
+    let admin = AdminServer::new(AdminService::new(
+        admin_handler,
+        LogFilterHandle::new(filter_handle.clone()),
+        notifier,
+    ));
+
+    let (mut health_reporter, health_service) = tonic_health::server::health_reporter();
+    health_reporter.set_serving::<FleetServer<FleetService>>().await;
+    health_reporter.set_serving::<DriverServer<DriverService>>().await;
+
+    // Mirror the circuit breaker into the health service so load
+    // balancers stop routing to this replica during a database outage.
+    let mut breaker_reporter = health_reporter.clone();
+    tokio::spawn(async move {
+        while breaker_open.changed().await.is_ok() {
+            let open = *breaker_open.borrow();
+            if open {
+                breaker_reporter.set_not_serving::<FleetServer<FleetService>>().await;
+                breaker_reporter.set_not_serving::<DriverServer<DriverService>>().await;
+            } else {
+                breaker_reporter.set_serving::<FleetServer<FleetService>>().await;
+                breaker_reporter.set_serving::<DriverServer<DriverService>>().await;
+            }
+        }
+    });
+
+    let cors = if config.server.enable_grpc_web {
+        Some(cors_layer(&config.server.cors_allowed_origins)?)
+    } else {
+        None
+    };
+    let grpc_web = config
+        .server
+        .enable_grpc_web
+        .then(tonic_web::GrpcWebLayer::new);
+    let router = tonic::transport::Server::builder()
+        .trace_fn(trace::make_span)
+        .accept_http1(config.server.enable_grpc_web)
+        .initial_stream_window_size(config.server.initial_stream_window_size)
+        .initial_connection_window_size(config.server.initial_connection_window_size)
+        .max_concurrent_streams(config.server.max_concurrent_streams)
+        .layer(tower::util::option_layer(cors))
+        .layer(tower::util::option_layer(grpc_web))
+        .layer(tower::util::option_layer(metrics_layer))
+        .layer(DeadlineLayer)
+        .layer(MessageSizeLayer::new(config.server.max_message_size, meter.as_ref()))
+        .add_service(health_service)
+        .add_service(fleet)
+        .add_service(driver)
+        .add_service(admin);
+
+    let socket = match config.server.address {
+        std::net::SocketAddr::V4(_) => tokio::net::TcpSocket::new_v4()?,
+        std::net::SocketAddr::V6(_) => tokio::net::TcpSocket::new_v6()?,
+    };
+    socket.bind(config.server.address)?;
+    let listener = socket.listen(config.server.accept_backlog)?;
+    let incoming = tonic::transport::server::TcpIncoming::from_listener(
+        listener,
+        config.server.tcp_nodelay,
+        None,
+    )?;
+
+    tracing::info!(address = %config.server.address, "starting SuperLink");
+    router
+        .serve_with_incoming_shutdown(incoming, async {
+            let _ = tokio::signal::ctrl_c().await;
+            tracing::info!("shutting down");
+        })
+        .await?;
+
+    opentelemetry::global::shutdown_tracer_provider();
+    Ok(())
+}
+
+/// The CORS policy for browser clients: the configured origins, or
+/// any origin when none are listed.
+fn cors_layer(allowed_origins: &[String]) -> Result<tower_http::cors::CorsLayer, Error> {
+    use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+    let origin = if allowed_origins.is_empty() {
+        AllowOrigin::any()
+    } else {
+        let origins = allowed_origins
+            .iter()
+            .map(|origin| origin.parse())
+            .collect::<Result<Vec<_>, _>>()?;
+        AllowOrigin::list(origins)
+    };
+    Ok(CorsLayer::new()
+        .allow_origin(origin)
+        .allow_headers(Any)
+        .allow_methods(Any)
+        .expose_headers(Any))
+}
+
+/// The event bus selected in the configuration, if any.
+async fn event_bus(config: &Config) -> Result<Option<EventBus>, Error> {
+    match config.events.backend.as_deref() {
+        None => Ok(None),
+        #[cfg(feature = "kafka")]
+        Some("kafka") => {
+            let sink = Arc::new(flwr_superlink::events::Kafka::new(
+                &config.events.servers,
+                config.events.topic.clone(),
+            )?);
+            Ok(Some(EventBus::spawn(sink, config.events.queue_size)))
+        }
+        #[cfg(not(feature = "kafka"))]
+        Some("kafka") => {
+            Err("event backend \"kafka\" requires building with the kafka feature".into())
+        }
+        #[cfg(feature = "nats")]
+        Some("nats") => {
+            let sink = Arc::new(
+                flwr_superlink::events::Nats::connect(
+                    &config.events.servers,
+                    config.events.topic.clone(),
+                )
+                .await?,
+            );
+            Ok(Some(EventBus::spawn(sink, config.events.queue_size)))
+        }
+        #[cfg(not(feature = "nats"))]
+        Some("nats") => {
+            Err("event backend \"nats\" requires building with the nats feature".into())
+        }
+        Some(other) => Err(format!("unknown event backend: {other}").into()),
+    }
+}
+
+/// The blob backend selected in the configuration, if any.
+async fn blob_backend(config: &Config) -> Result<Option<BlobBackend>, Error> {
+    let store: Arc<dyn flwr_superlink::state::blob::BlobStore> =
+        match config.blob.backend.as_deref() {
+            None => return Ok(None),
+            Some("filesystem") => Arc::new(Filesystem::new(config.blob.path.clone())),
+            #[cfg(feature = "s3")]
+            Some("s3") => {
+                Arc::new(flwr_superlink::state::blob::S3::new(config.blob.bucket.clone()).await)
+            }
+            #[cfg(not(feature = "s3"))]
+            Some("s3") => {
+                return Err("blob backend \"s3\" requires building with the s3 feature".into())
+            }
+            Some(other) => return Err(format!("unknown blob backend: {other}").into()),
+        };
+    Ok(Some(BlobBackend {
+        store,
+        inline_threshold: config.blob.inline_threshold,
+    }))
+}
+
+/// Re-read the configuration on SIGHUP and publish the dynamic
+/// settings; the static settings (bind address, pool size, ...) keep
+/// their startup values until the next restart.
+fn spawn_reload_handler(
+    config_path: Option<PathBuf>,
+    dynamic_tx: tokio::sync::watch::Sender<DynamicConfig>,
+    filter_handle: tracing_subscriber::reload::Handle<EnvFilter, tracing_subscriber::Registry>,
+) {
+    tokio::spawn(async move {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => hangup,
+            Err(err) => {
+                tracing::error!(error = %err, "failed to install SIGHUP handler");
+                return;
+            }
+        };
+        while hangup.recv().await.is_some() {
+            let config = match Config::load(config_path.as_deref()) {
+                Ok(config) => config,
+                Err(err) => {
+                    tracing::error!(error = %err, "config reload failed, keeping current config");
+                    continue;
+                }
+            };
+            match EnvFilter::try_new(&config.logging.level) {
+                Ok(filter) => {
+                    if let Err(err) = filter_handle.reload(filter) {
+                        tracing::error!(error = %err, "failed to update log filter");
+                    }
+                }
+                Err(err) => {
+                    tracing::error!(
+                        level = %config.logging.level,
+                        error = %err,
+                        "invalid logging level in reloaded config",
+                    );
+                }
+            }
+            let dynamic = DynamicConfig::from(&config);
+            dynamic_tx.send_if_modified(|current| {
+                if *current == dynamic {
+                    false
+                } else {
+                    *current = dynamic;
+                    true
+                }
+            });
+            tracing::info!("configuration reloaded");
+        }
+    });
+}
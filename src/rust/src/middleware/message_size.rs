@@ -0,0 +1,214 @@
+//! Clear errors for oversized messages.
+//!
+//! When a request exceeds `max_decoding_message_size`, tonic rejects
+//! it with an opaque OUT_OF_RANGE ("Error, message length too large").
+//! This layer rewrites that trailers-only response into an
+//! INVALID_ARGUMENT naming the configured limit and the observed
+//! size, and counts the rejections per service and method.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use http::HeaderValue;
+use opentelemetry::metrics::{Counter, Meter};
+use opentelemetry::KeyValue;
+use pin_project::pin_project;
+use tonic::body::BoxBody;
+use tower::{Layer, Service};
+
+use super::metrics::split_path;
+
+/// The phrase tonic's codec uses when a message exceeds the limit.
+const TOO_LARGE_MARKER: &str = "message length too large";
+
+/// grpc-status values involved in the rewrite.
+const OUT_OF_RANGE: &str = "11";
+const INVALID_ARGUMENT: &str = "3";
+
+/// The observed size in tonic's "... found `<n>` bytes, the limit is:
+/// `<m>` bytes" phrasing.
+fn observed_size(message: &str) -> Option<u64> {
+    message.split("found ").nth(1)?.split(' ').next()?.parse().ok()
+}
+
+/// Rewrite a trailers-only oversized-message response in place;
+/// returns the observed size when the response matched.
+fn rewrite(response: &mut http::Response<BoxBody>, limit: usize) -> Option<u64> {
+    let headers = response.headers();
+    if headers.get("grpc-status").map(HeaderValue::as_bytes) != Some(OUT_OF_RANGE.as_bytes()) {
+        return None;
+    }
+    let message = headers
+        .get("grpc-message")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if !message.contains(TOO_LARGE_MARKER) {
+        return None;
+    }
+    let found = observed_size(message).unwrap_or_default();
+    let detail = format!("message of {found} bytes exceeds the {limit} byte limit");
+    let headers = response.headers_mut();
+    headers.insert("grpc-status", HeaderValue::from_static(INVALID_ARGUMENT));
+    if let Ok(value) = HeaderValue::from_str(&detail) {
+        headers.insert("grpc-message", value);
+    }
+    Some(found)
+}
+
+/// Layer adding [`MessageSize`] rewriting to a service.
+#[derive(Clone)]
+pub struct MessageSizeLayer {
+    limit: usize,
+    rejections: Option<Counter<u64>>,
+}
+
+impl MessageSizeLayer {
+    /// Build the layer for the configured limit; without a meter the
+    /// responses are still rewritten, only the counter is skipped.
+    pub fn new(limit: usize, meter: Option<&Meter>) -> Self {
+        let rejections = meter.map(|meter| {
+            meter
+                .u64_counter("rpc.server.oversized_messages")
+                .with_description("Requests rejected for exceeding the message size limit")
+                .init()
+        });
+        Self { limit, rejections }
+    }
+}
+
+impl<S> Layer<S> for MessageSizeLayer {
+    type Service = MessageSize<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        MessageSize {
+            inner,
+            limit: self.limit,
+            rejections: self.rejections.clone(),
+        }
+    }
+}
+
+/// Middleware translating oversized-message rejections.
+#[derive(Clone)]
+pub struct MessageSize<S> {
+    inner: S,
+    limit: usize,
+    rejections: Option<Counter<u64>>,
+}
+
+impl<S, B> Service<http::Request<B>> for MessageSize<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<BoxBody>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let path = request.uri().path().to_owned();
+        ResponseFuture {
+            inner: self.inner.call(request),
+            limit: self.limit,
+            rejections: self.rejections.clone(),
+            path,
+        }
+    }
+}
+
+#[pin_project]
+pub struct ResponseFuture<F> {
+    #[pin]
+    inner: F,
+    limit: usize,
+    rejections: Option<Counter<u64>>,
+    path: String,
+}
+
+impl<F, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<http::Response<BoxBody>, E>>,
+{
+    type Output = Result<http::Response<BoxBody>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        match this.inner.poll(cx) {
+            Poll::Ready(Ok(mut response)) => {
+                if let Some(found) = rewrite(&mut response, *this.limit) {
+                    tracing::warn!(
+                        path = %this.path,
+                        found,
+                        limit = *this.limit,
+                        "oversized message rejected"
+                    );
+                    if let Some(rejections) = this.rejections {
+                        let (service, method) = split_path(this.path);
+                        rejections.add(
+                            1,
+                            &[
+                                KeyValue::new("rpc.service", service),
+                                KeyValue::new("rpc.method", method),
+                            ],
+                        );
+                    }
+                }
+                Poll::Ready(Ok(response))
+            }
+            other => other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oversized_response() -> http::Response<BoxBody> {
+        let mut response = http::Response::new(tonic::body::empty_body());
+        let headers = response.headers_mut();
+        headers.insert("grpc-status", HeaderValue::from_static("11"));
+        headers.insert(
+            "grpc-message",
+            HeaderValue::from_static(
+                "Error, message length too large: found 9000000 bytes, the limit is: 4194304 bytes",
+            ),
+        );
+        response
+    }
+
+    #[test]
+    fn parses_the_observed_size() {
+        assert_eq!(
+            observed_size("Error, message length too large: found 42 bytes, the limit is: 4 bytes"),
+            Some(42)
+        );
+        assert_eq!(observed_size("something else entirely"), None);
+    }
+
+    #[test]
+    fn rewrites_oversized_rejections() {
+        let mut response = oversized_response();
+        assert_eq!(rewrite(&mut response, 4194304), Some(9000000));
+        assert_eq!(response.headers()["grpc-status"], "3");
+        let message = response.headers()["grpc-message"].to_str().unwrap();
+        assert_eq!(message, "message of 9000000 bytes exceeds the 4194304 byte limit");
+    }
+
+    #[test]
+    fn leaves_other_statuses_alone() {
+        let mut response = http::Response::new(tonic::body::empty_body());
+        response
+            .headers_mut()
+            .insert("grpc-status", HeaderValue::from_static("11"));
+        response
+            .headers_mut()
+            .insert("grpc-message", HeaderValue::from_static("value out of range"));
+        assert_eq!(rewrite(&mut response, 4194304), None);
+        assert_eq!(response.headers()["grpc-status"], "11");
+    }
+}
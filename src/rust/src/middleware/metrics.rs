@@ -0,0 +1,348 @@
+//! Request metrics middleware.
+//!
+//! Records a request counter, a duration histogram and request/response
+//! payload size histograms per gRPC service/method. Modeled loosely on
+//! tower-http's `in_flight_requests` middleware.
+
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use opentelemetry::metrics::{Counter, Histogram, Meter, UpDownCounter};
+use opentelemetry::KeyValue;
+use pin_project::pin_project;
+use tower::{Layer, Service};
+
+/// Instruments maintained by the middleware.
+#[derive(Clone)]
+pub struct ServerMetrics {
+    requests: Counter<u64>,
+    duration: Histogram<f64>,
+    request_size: Histogram<u64>,
+    response_size: Histogram<u64>,
+    in_flight: UpDownCounter<i64>,
+}
+
+impl ServerMetrics {
+    pub fn new(meter: &Meter) -> Self {
+        Self {
+            requests: meter
+                .u64_counter("rpc.server.requests")
+                .with_description("Number of RPCs received")
+                .init(),
+            duration: meter
+                .f64_histogram("rpc.server.duration")
+                .with_unit(opentelemetry::metrics::Unit::new("s"))
+                .with_description("RPC duration")
+                .init(),
+            request_size: meter
+                .u64_histogram("rpc.server.request.size")
+                .with_unit(opentelemetry::metrics::Unit::new("By"))
+                .with_description("Request body size")
+                .init(),
+            response_size: meter
+                .u64_histogram("rpc.server.response.size")
+                .with_unit(opentelemetry::metrics::Unit::new("By"))
+                .with_description("Response body size")
+                .init(),
+            in_flight: meter
+                .i64_up_down_counter("rpc.server.in_flight_requests")
+                .with_description("Number of RPCs currently being served")
+                .init(),
+        }
+    }
+}
+
+/// Per-run task flow counters, recorded by the handlers (the tower
+/// layer never sees a `run_id`).
+///
+/// The number of distinct `run_id` label values is capped; runs beyond
+/// the cap are aggregated under the label `other` so a long-lived
+/// server cannot blow up metric cardinality.
+pub struct TaskMetrics {
+    task_ins_pushed: Counter<u64>,
+    task_ins_delivered: Counter<u64>,
+    task_res_pushed: Counter<u64>,
+    task_res_delivered: Counter<u64>,
+    validation_failures: Counter<u64>,
+    client_requests: Counter<u64>,
+    group_completions: Counter<u64>,
+    node_registrations: Counter<u64>,
+    node_deregistrations: Counter<u64>,
+    node_expirations: Counter<u64>,
+    ping_intervals: Histogram<f64>,
+    seen_runs: Mutex<HashSet<i64>>,
+    max_run_labels: usize,
+}
+
+impl TaskMetrics {
+    pub fn new(meter: &Meter, max_run_labels: usize) -> Self {
+        Self {
+            task_ins_pushed: meter
+                .u64_counter("flwr.task_ins.pushed")
+                .with_description("Task instructions accepted from drivers")
+                .init(),
+            task_ins_delivered: meter
+                .u64_counter("flwr.task_ins.delivered")
+                .with_description("Task instructions delivered to nodes")
+                .init(),
+            task_res_pushed: meter
+                .u64_counter("flwr.task_res.pushed")
+                .with_description("Task results accepted from nodes")
+                .init(),
+            task_res_delivered: meter
+                .u64_counter("flwr.task_res.delivered")
+                .with_description("Task results delivered to drivers")
+                .init(),
+            validation_failures: meter
+                .u64_counter("flwr.validation.failures")
+                .with_description("Field violations in rejected requests")
+                .init(),
+            client_requests: meter
+                .u64_counter("flwr.client.requests")
+                .with_description("CreateNode/Ping requests by reported client version")
+                .init(),
+            group_completions: meter
+                .u64_counter("flwr.group.completions")
+                .with_description("Task groups whose results all arrived")
+                .init(),
+            node_registrations: meter
+                .u64_counter("flwr.node.registrations")
+                .with_description("Nodes registered via CreateNode")
+                .init(),
+            node_deregistrations: meter
+                .u64_counter("flwr.node.deregistrations")
+                .with_description("Nodes deregistered via DeleteNode")
+                .init(),
+            node_expirations: meter
+                .u64_counter("flwr.node.expirations")
+                .with_description("Pings arriving after the node's lease had lapsed")
+                .init(),
+            ping_intervals: meter
+                .f64_histogram("flwr.node.ping_interval")
+                .with_unit(opentelemetry::metrics::Unit::new("s"))
+                .with_description("Ping intervals reported by nodes")
+                .init(),
+            seen_runs: Mutex::new(HashSet::new()),
+            max_run_labels,
+        }
+    }
+
+    /// The `run_id` label for a run, or `other` once the cap is hit.
+    fn run_label(&self, run_id: i64) -> String {
+        let mut seen = self.seen_runs.lock().unwrap();
+        if seen.contains(&run_id) {
+            return run_id.to_string();
+        }
+        if seen.len() < self.max_run_labels {
+            seen.insert(run_id);
+            return run_id.to_string();
+        }
+        "other".to_owned()
+    }
+
+    fn labels(&self, run_id: i64) -> [KeyValue; 1] {
+        [KeyValue::new("run_id", self.run_label(run_id))]
+    }
+
+    pub fn task_ins_pushed(&self, run_id: i64, count: u64) {
+        self.task_ins_pushed.add(count, &self.labels(run_id));
+    }
+
+    pub fn task_ins_delivered(&self, run_id: i64, count: u64) {
+        self.task_ins_delivered.add(count, &self.labels(run_id));
+    }
+
+    pub fn task_res_pushed(&self, run_id: i64, count: u64) {
+        self.task_res_pushed.add(count, &self.labels(run_id));
+    }
+
+    pub fn task_res_delivered(&self, run_id: i64, count: u64) {
+        self.task_res_delivered.add(count, &self.labels(run_id));
+    }
+
+    /// Record one task group whose results have all arrived.
+    pub fn group_completed(&self, run_id: i64) {
+        self.group_completions.add(1, &self.labels(run_id));
+    }
+
+    /// Record one violated field of a rejected request. Field paths
+    /// come from the validator, so the label set stays bounded.
+    pub fn validation_failure(&self, field: &str) {
+        self.validation_failures
+            .add(1, &[KeyValue::new("field", field.to_owned())]);
+    }
+
+    /// Record `count` nodes joining via CreateNode.
+    pub fn nodes_registered(&self, count: u64) {
+        self.node_registrations.add(count, &[]);
+    }
+
+    /// Record `count` nodes leaving via DeleteNode.
+    pub fn nodes_deregistered(&self, count: u64) {
+        self.node_deregistrations.add(count, &[]);
+    }
+
+    /// Record one ping that arrived after the node's lease had already
+    /// lapsed — a missed-ping expiration.
+    pub fn node_expired(&self) {
+        self.node_expirations.add(1, &[]);
+    }
+
+    /// Record the ping interval a node reported.
+    pub fn ping_interval(&self, seconds: f64) {
+        self.ping_intervals.record(seconds, &[]);
+    }
+
+    /// Record one CreateNode/Ping from a client reporting `version`;
+    /// clients reporting nothing land under the empty string.
+    pub fn client_request(&self, version: &str) {
+        self.client_requests
+            .add(1, &[KeyValue::new("client_version", version.to_owned())]);
+    }
+}
+
+/// Decrements the in-flight counter when the request finishes, whether
+/// it completed or was cancelled mid-flight.
+struct InFlightGuard {
+    in_flight: UpDownCounter<i64>,
+    labels: Vec<KeyValue>,
+}
+
+impl InFlightGuard {
+    fn new(in_flight: UpDownCounter<i64>, labels: Vec<KeyValue>) -> Self {
+        in_flight.add(1, &labels);
+        Self { in_flight, labels }
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.in_flight.add(-1, &self.labels);
+    }
+}
+
+/// Layer adding [`ServerMetrics`] recording to a service.
+#[derive(Clone)]
+pub struct ServerMetricsLayer {
+    metrics: ServerMetrics,
+}
+
+impl ServerMetricsLayer {
+    pub fn new(metrics: ServerMetrics) -> Self {
+        Self { metrics }
+    }
+}
+
+impl<S> Layer<S> for ServerMetricsLayer {
+    type Service = ServerMetricsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ServerMetricsService {
+            inner,
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ServerMetricsService<S> {
+    inner: S,
+    metrics: ServerMetrics,
+}
+
+/// Split `/flwr.proto.Fleet/PullTaskIns` into service and method labels.
+pub(crate) fn split_path(path: &str) -> (String, String) {
+    let mut parts = path.trim_start_matches('/').splitn(2, '/');
+    let service = parts.next().unwrap_or_default().to_owned();
+    let method = parts.next().unwrap_or_default().to_owned();
+    (service, method)
+}
+
+/// The body size when it is known upfront; streamed bodies with an
+/// unknown size are not recorded.
+fn body_size<B: http_body::Body>(body: &B) -> Option<u64> {
+    body.size_hint().exact()
+}
+
+impl<S, B, R> Service<http::Request<B>> for ServerMetricsService<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<R>>,
+    B: http_body::Body,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let (service, method) = split_path(request.uri().path());
+        let labels = vec![
+            KeyValue::new("rpc.service", service),
+            KeyValue::new("rpc.method", method),
+        ];
+        self.metrics.requests.add(1, &labels);
+        if let Some(size) = body_size(request.body()) {
+            self.metrics.request_size.record(size, &labels);
+        }
+        let guard = InFlightGuard::new(self.metrics.in_flight.clone(), labels.clone());
+        ResponseFuture {
+            inner: self.inner.call(request),
+            metrics: self.metrics.clone(),
+            labels,
+            started_at: Instant::now(),
+            _in_flight: guard,
+        }
+    }
+}
+
+#[pin_project]
+pub struct ResponseFuture<F> {
+    #[pin]
+    inner: F,
+    metrics: ServerMetrics,
+    labels: Vec<KeyValue>,
+    started_at: Instant,
+    _in_flight: InFlightGuard,
+}
+
+impl<F, R, E> std::future::Future for ResponseFuture<F>
+where
+    F: std::future::Future<Output = Result<http::Response<R>, E>>,
+    R: http_body::Body,
+{
+    type Output = Result<http::Response<R>, E>;
+
+    fn poll(self: std::pin::Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let result = match this.inner.poll(cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => return Poll::Pending,
+        };
+        this.metrics
+            .duration
+            .record(this.started_at.elapsed().as_secs_f64(), this.labels);
+        if let Ok(response) = &result {
+            if let Some(size) = body_size(response.body()) {
+                this.metrics.response_size.record(size, this.labels);
+            }
+        }
+        Poll::Ready(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_path;
+
+    #[test]
+    fn splits_grpc_path() {
+        let (service, method) = split_path("/flwr.proto.Fleet/PullTaskIns");
+        assert_eq!(service, "flwr.proto.Fleet");
+        assert_eq!(method, "PullTaskIns");
+    }
+}
@@ -0,0 +1,7 @@
+//! Tower middleware applied to the gRPC server stack.
+
+pub mod deadline;
+pub mod message_size;
+pub mod metrics;
+pub mod scrub;
+pub mod trace;
@@ -0,0 +1,68 @@
+//! Redaction of sensitive values before they reach tracing spans.
+//!
+//! Scrubbing happens at the source — when a value is recorded on a
+//! span — because a `tracing` layer cannot rewrite fields already
+//! handed to downstream layers or the OTLP exporter. Recordset bytes
+//! never reach spans to begin with (the verbose request span records
+//! payload sizes, and the state backend strips bind values from
+//! `db.statement`), so this module covers the remaining source:
+//! request headers.
+
+use http::HeaderMap;
+
+/// Placeholder recorded instead of a sensitive or non-ASCII value.
+const REDACTED: &str = "[redacted]";
+
+/// Headers whose values never reach a span, compared case-insensitively.
+const SENSITIVE_HEADERS: &[&str] =
+    &["authorization", "cookie", "proxy-authorization", "x-flwr-node-key"];
+
+/// Whether values under this header name must be scrubbed.
+pub fn is_sensitive(name: &str) -> bool {
+    SENSITIVE_HEADERS.iter().any(|header| name.eq_ignore_ascii_case(header))
+}
+
+/// All request headers as one loggable string, with sensitive values
+/// scrubbed and binary values elided.
+pub fn headers(headers: &HeaderMap) -> String {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if is_sensitive(name.as_str()) {
+                REDACTED
+            } else {
+                value.to_str().unwrap_or(REDACTED)
+            };
+            format!("{name}={value}")
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sensitive_headers_are_scrubbed_case_insensitively() {
+        let mut map = HeaderMap::new();
+        map.insert("Authorization", "Bearer secret-token".parse().unwrap());
+        map.insert("x-flwr-node-key", "node-secret".parse().unwrap());
+        map.insert("user-agent", "flwr/1.7".parse().unwrap());
+        let scrubbed = headers(&map);
+        assert!(!scrubbed.contains("secret-token"));
+        assert!(!scrubbed.contains("node-secret"));
+        assert!(scrubbed.contains("authorization=[redacted]"));
+        assert!(scrubbed.contains("user-agent=flwr/1.7"));
+    }
+
+    #[test]
+    fn binary_values_are_elided() {
+        let mut map = HeaderMap::new();
+        map.insert(
+            "x-binary",
+            http::HeaderValue::from_bytes(&[0xff, 0xfe]).unwrap(),
+        );
+        assert_eq!(headers(&map), "x-binary=[redacted]");
+    }
+}
@@ -0,0 +1,143 @@
+//! Request tracing: one span per RPC with a request id and, where
+//! available, the caller's identity.
+
+use std::sync::OnceLock;
+
+use tracing::{info_span, Span};
+use uuid::Uuid;
+
+use crate::middleware::scrub;
+use crate::service::TENANT_METADATA_KEY;
+
+/// Header carrying a request id minted by an upstream proxy or client;
+/// honoured so one id follows a request across services.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Header carrying the caller's node id. Until an auth layer verifies
+/// it, this is self-reported and only used for log correlation.
+pub const NODE_ID_HEADER: &str = "x-flwr-node-id";
+
+/// Which requests get verbose span fields (headers, payload sizes).
+///
+/// Stored in a process-wide cell because `Server::trace_fn` only
+/// accepts a plain function pointer.
+static VERBOSE: OnceLock<VerboseConfig> = OnceLock::new();
+
+#[derive(Debug, Clone, Default)]
+pub struct VerboseConfig {
+    all: bool,
+    methods: Vec<String>,
+}
+
+impl VerboseConfig {
+    pub fn new(all: bool, methods: Vec<String>) -> Self {
+        Self { all, methods }
+    }
+
+    /// Whether the request path (`/package.Service/Method`) is covered;
+    /// allowlist entries name either a full method or a whole service.
+    fn matches(&self, path: &str) -> bool {
+        if self.all {
+            return true;
+        }
+        let path = path.trim_start_matches('/');
+        let service = path.split('/').next().unwrap_or(path);
+        self.methods
+            .iter()
+            .any(|entry| entry == path || entry == service)
+    }
+}
+
+/// Install the verbose allowlist; later calls are ignored.
+pub fn init_verbose(verbose: VerboseConfig) {
+    let _ = VERBOSE.set(verbose);
+}
+
+/// The ASCII value of `name`, if present.
+fn header_str<'a, B>(request: &'a http::Request<B>, name: &str) -> Option<&'a str> {
+    request.headers().get(name).and_then(|value| value.to_str().ok())
+}
+
+/// The propagated request id, or a fresh UUIDv4 for requests arriving
+/// without one.
+fn request_id<B>(request: &http::Request<B>) -> String {
+    header_str(request, REQUEST_ID_HEADER)
+        .map(str::to_owned)
+        .unwrap_or_else(|| Uuid::new_v4().to_string())
+}
+
+/// Build the span for an incoming request.
+///
+/// Handler logs are emitted inside this span, so `request_id`,
+/// `tenant` and `node_id` show up on every line without the handlers
+/// threading them through explicitly.
+pub fn make_span<B>(request: &http::Request<B>) -> Span {
+    let path = request.uri().path();
+    let verbose = VERBOSE
+        .get()
+        .map(|config| config.matches(path))
+        .unwrap_or(false);
+    let request_id = request_id(request);
+    let tenant = header_str(request, TENANT_METADATA_KEY).unwrap_or_default();
+    let node_id = header_str(request, NODE_ID_HEADER).unwrap_or_default();
+    if verbose {
+        info_span!(
+            "request",
+            path = %path,
+            request_id = %request_id,
+            tenant = %tenant,
+            node_id = %node_id,
+            content_length = ?request.headers().get(http::header::CONTENT_LENGTH),
+            user_agent = ?request.headers().get(http::header::USER_AGENT),
+            headers = %scrub::headers(request.headers()),
+        )
+    } else {
+        info_span!(
+            "request",
+            path = %path,
+            request_id = %request_id,
+            tenant = %tenant,
+            node_id = %node_id,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allowlist_matches_method_and_service() {
+        let config = VerboseConfig::new(
+            false,
+            vec![
+                "flwr.proto.Fleet/PushTaskRes".to_owned(),
+                "flwr.proto.Driver".to_owned(),
+            ],
+        );
+        assert!(config.matches("/flwr.proto.Fleet/PushTaskRes"));
+        assert!(!config.matches("/flwr.proto.Fleet/PullTaskIns"));
+        assert!(config.matches("/flwr.proto.Driver/GetNodes"));
+    }
+
+    #[test]
+    fn incoming_request_id_is_honoured() {
+        let request = http::Request::builder()
+            .uri("/flwr.proto.Fleet/Ping")
+            .header(REQUEST_ID_HEADER, "req-123")
+            .body(())
+            .unwrap();
+        assert_eq!(request_id(&request), "req-123");
+        let request = http::Request::builder()
+            .uri("/flwr.proto.Fleet/Ping")
+            .body(())
+            .unwrap();
+        assert_eq!(request_id(&request).len(), 36);
+    }
+
+    #[test]
+    fn verbose_all_matches_everything() {
+        let config = VerboseConfig::new(true, Vec::new());
+        assert!(config.matches("/flwr.proto.Fleet/Ping"));
+    }
+}
@@ -0,0 +1,38 @@
+//! Startup migrations: lets the superlink binary bring the schema up
+//! to date before serving.
+
+/// Advisory lock key serializing migration runs across replicas.
+const ADVISORY_LOCK_KEY: i64 = 0x666c_7772; // "flwr"
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("database connection failed")]
+    Postgres(#[from] tokio_postgres::Error),
+    #[error("migration failed")]
+    Refinery(#[from] refinery::Error),
+}
+
+/// Apply all pending migrations, holding a Postgres advisory lock so
+/// replicas starting concurrently do not race each other.
+pub async fn run(database_uri: &str) -> Result<(), Error> {
+    let (mut client, connection) = tokio_postgres::connect(database_uri, tokio_postgres::NoTls).await?;
+    let handle = tokio::spawn(connection);
+
+    client
+        .query("SELECT pg_advisory_lock($1)", &[&ADVISORY_LOCK_KEY])
+        .await?;
+    let result = crate::embedded::migrations::runner()
+        .run_async(&mut client)
+        .await;
+    client
+        .query("SELECT pg_advisory_unlock($1)", &[&ADVISORY_LOCK_KEY])
+        .await?;
+    let report = result?;
+    for migration in report.applied_migrations() {
+        tracing::info!(%migration, "applied migration");
+    }
+
+    drop(client);
+    handle.abort();
+    Ok(())
+}
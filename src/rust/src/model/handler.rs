@@ -0,0 +1,228 @@
+//! Plain data types passed between the gRPC services and the handlers.
+//!
+//! These deliberately mirror the `flwr.proto` messages but carry no
+//! protobuf machinery, so the state layer never depends on the wire
+//! format. Timestamps are `DateTime<Utc>` internally and converted to
+//! the wire's epoch seconds and RFC 3339 strings at the proto boundary.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+
+/// Convert epoch seconds from the wire into a UTC timestamp.
+pub fn datetime_from_secs(secs: f64) -> DateTime<Utc> {
+    DateTime::from_timestamp_micros((secs * 1e6).round() as i64).unwrap_or_default()
+}
+
+/// Convert a UTC timestamp into the wire's epoch seconds.
+pub fn secs_from_datetime(at: DateTime<Utc>) -> f64 {
+    at.timestamp_micros() as f64 / 1e6
+}
+
+/// Typed identifier of a run; a distinct type from [`NodeId`] so the
+/// two kinds of `i64` id cannot be swapped silently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RunId(pub i64);
+
+impl fmt::Display for RunId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<i64> for RunId {
+    fn from(id: i64) -> Self {
+        Self(id)
+    }
+}
+
+impl From<RunId> for i64 {
+    fn from(id: RunId) -> Self {
+        id.0
+    }
+}
+
+/// Typed identifier of a registered node; see [`RunId`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(pub i64);
+
+impl fmt::Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<i64> for NodeId {
+    fn from(id: i64) -> Self {
+        Self(id)
+    }
+}
+
+impl From<NodeId> for i64 {
+    fn from(id: NodeId) -> Self {
+        id.0
+    }
+}
+
+/// Typed identifier of a stored task: a UUID in string form, minted by
+/// the state layer.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TaskId(pub String);
+
+impl fmt::Display for TaskId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<String> for TaskId {
+    fn from(id: String) -> Self {
+        Self(id)
+    }
+}
+
+impl From<TaskId> for String {
+    fn from(id: TaskId) -> Self {
+        id.0
+    }
+}
+
+impl AsRef<str> for TaskId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Typed identifier of a task group within a run, chosen by the
+/// driver; empty when the driver groups nothing.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct GroupId(pub String);
+
+impl fmt::Display for GroupId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<String> for GroupId {
+    fn from(id: String) -> Self {
+        Self(id)
+    }
+}
+
+impl From<GroupId> for String {
+    fn from(id: GroupId) -> Self {
+        id.0
+    }
+}
+
+impl AsRef<str> for GroupId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// A node participating in a federation, either registered (`id != 0`)
+/// or anonymous.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Node {
+    pub id: NodeId,
+    pub anonymous: bool,
+}
+
+/// Common task payload shared by instructions and results.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Task {
+    pub producer: Node,
+    pub consumer: Node,
+    pub created_at: DateTime<Utc>,
+    /// When the task was handed to its consumer; `None` while pending.
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub pushed_at: DateTime<Utc>,
+    pub ttl: String,
+    pub ancestry: Vec<TaskId>,
+    pub task_type: String,
+    /// Encoded `flwr.proto.RecordSet` bytes; reference-counted so a
+    /// model fanned out to thousands of nodes is held once.
+    pub recordset: Bytes,
+    /// Lowercase hex SHA-256 of `recordset`; empty when unknown.
+    pub recordset_checksum: String,
+    /// Permanent failure reported in place of a result.
+    pub error: Option<TaskError>,
+}
+
+/// A permanent failure carried on a `TaskRes`, e.g. when the server
+/// dead-letters the ancestor `TaskIns`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskError {
+    pub code: i64,
+    pub reason: String,
+}
+
+/// A task instruction scheduled by a driver for one consumer node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskIns {
+    pub id: TaskId,
+    pub group_id: GroupId,
+    pub run_id: RunId,
+    pub task: Task,
+}
+
+/// A security-relevant event recorded in the audit log.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditEvent {
+    pub id: String,
+    pub created_at: f64,
+    /// Dotted action name, e.g. `node.create` or `node.ban`.
+    pub action: String,
+    /// Node the event concerns; 0 when not applicable.
+    pub node_id: NodeId,
+    /// Run the event concerns; 0 when not applicable.
+    pub run_id: RunId,
+    /// Free-form context, e.g. a ban reason.
+    pub detail: String,
+}
+
+/// One registered node as the admin API reports it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeInfo {
+    pub id: NodeId,
+    /// Auth identity (public key or certificate fingerprint) the node
+    /// presented; empty when it never presented one.
+    pub identity: String,
+    /// Client version last reported on CreateNode/Ping.
+    pub client_version: String,
+    /// Lease end as seconds since the Unix epoch.
+    pub online_until: f64,
+    pub ping_interval: f64,
+    pub properties: HashMap<String, String>,
+}
+
+/// A `TaskIns` parked in the dead-letter queue after failing
+/// permanently.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeadLetter {
+    pub id: TaskId,
+    pub group_id: GroupId,
+    pub run_id: RunId,
+    pub consumer: Node,
+    pub created_at: f64,
+    /// When the task was dead-lettered.
+    pub dead_at: f64,
+    /// Deliveries attempted before giving up.
+    pub delivery_count: u32,
+    pub task_type: String,
+    /// Why the task was parked, e.g. `redelivery limit exceeded`.
+    pub reason: String,
+}
+
+/// A task result produced by a node for one ancestor `TaskIns`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskRes {
+    pub id: TaskId,
+    pub group_id: GroupId,
+    pub run_id: RunId,
+    pub task: Task,
+}
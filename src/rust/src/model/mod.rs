@@ -0,0 +1,4 @@
+//! Domain model shared between the service, handler and state layers.
+
+pub mod handler;
+pub mod raw;
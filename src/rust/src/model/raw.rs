@@ -0,0 +1,161 @@
+//! Raw-bytes stand-in for the generated `RecordSet` message.
+//!
+//! The server treats recordsets as opaque payloads — it stores,
+//! checksums and forwards them but never looks inside — yet the
+//! generated code parses every multi-megabyte recordset into maps of
+//! records on decode and serializes them back on encode. With the
+//! `raw-recordsets` feature the code generator maps
+//! `flwr.proto.RecordSet` onto [`RawRecordSet`] instead: decoding
+//! copies the encoded fields verbatim and encoding replays them, so
+//! payloads cross the server without being parsed at all. Clients are
+//! unaffected and keep exchanging structured record sets.
+//!
+//! Decoding re-emits field keys and varints canonically, so the bytes
+//! are identical to the input for any canonically encoded message —
+//! which includes everything prost itself produces.
+
+use prost::bytes::{Buf, BufMut, Bytes};
+use prost::encoding::{self, DecodeContext, WireType};
+use prost::DecodeError;
+
+/// The encoded form of a `RecordSet`, passed through untouched.
+#[derive(Clone, Default, PartialEq)]
+pub struct RawRecordSet {
+    /// Shared bytes adopted via [`RawRecordSet::from_bytes`]; clones
+    /// reference the same payload.
+    shared: Bytes,
+    /// Fields captured while decoding from the wire.
+    captured: Vec<u8>,
+}
+
+impl RawRecordSet {
+    /// Wrap already-encoded bytes without copying them.
+    pub fn from_bytes(bytes: Bytes) -> Self {
+        Self {
+            shared: bytes,
+            captured: Vec::new(),
+        }
+    }
+
+    /// The encoded message; copies only in the unusual case of fields
+    /// having been merged into an adopted payload.
+    pub fn into_bytes(self) -> Bytes {
+        if self.captured.is_empty() {
+            self.shared
+        } else if self.shared.is_empty() {
+            self.captured.into()
+        } else {
+            let mut bytes = self.shared.to_vec();
+            bytes.extend_from_slice(&self.captured);
+            bytes.into()
+        }
+    }
+}
+
+impl std::fmt::Debug for RawRecordSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RawRecordSet")
+            .field("len", &self.encoded_len())
+            .finish()
+    }
+}
+
+impl prost::Message for RawRecordSet {
+    fn encode_raw<B: BufMut>(&self, buf: &mut B) {
+        buf.put_slice(&self.shared);
+        buf.put_slice(&self.captured);
+    }
+
+    fn merge_field<B: Buf>(
+        &mut self,
+        tag: u32,
+        wire_type: WireType,
+        buf: &mut B,
+        _ctx: DecodeContext,
+    ) -> Result<(), DecodeError> {
+        encoding::encode_key(tag, wire_type, &mut self.captured);
+        match wire_type {
+            WireType::Varint => {
+                let value = encoding::decode_varint(buf)?;
+                encoding::encode_varint(value, &mut self.captured);
+            }
+            WireType::SixtyFourBit => {
+                if buf.remaining() < 8 {
+                    return Err(DecodeError::new("buffer underflow"));
+                }
+                self.captured.put_u64_le(buf.get_u64_le());
+            }
+            WireType::ThirtyTwoBit => {
+                if buf.remaining() < 4 {
+                    return Err(DecodeError::new("buffer underflow"));
+                }
+                self.captured.put_u32_le(buf.get_u32_le());
+            }
+            WireType::LengthDelimited => {
+                let len = encoding::decode_varint(buf)? as usize;
+                if buf.remaining() < len {
+                    return Err(DecodeError::new("buffer underflow"));
+                }
+                encoding::encode_varint(len as u64, &mut self.captured);
+                let start = self.captured.len();
+                self.captured.resize(start + len, 0);
+                buf.copy_to_slice(&mut self.captured[start..]);
+            }
+            WireType::StartGroup | WireType::EndGroup => {
+                return Err(DecodeError::new("groups are not supported"));
+            }
+        }
+        Ok(())
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.shared.len() + self.captured.len()
+    }
+
+    fn clear(&mut self) {
+        self.shared = Bytes::new();
+        self.captured.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prost::Message;
+
+    use super::*;
+
+    /// A hand-assembled canonical message: field 1 varint 150, field 2
+    /// length-delimited "testing", field 3 fixed32, field 4 fixed64.
+    fn canonical_bytes() -> Vec<u8> {
+        let mut bytes = vec![0x08, 0x96, 0x01];
+        bytes.extend_from_slice(&[0x12, 0x07]);
+        bytes.extend_from_slice(b"testing");
+        bytes.extend_from_slice(&[0x1d, 1, 2, 3, 4]);
+        bytes.extend_from_slice(&[0x21, 1, 2, 3, 4, 5, 6, 7, 8]);
+        bytes
+    }
+
+    #[test]
+    fn decode_then_encode_is_byte_identical() {
+        let bytes = canonical_bytes();
+        let raw = RawRecordSet::decode(bytes.as_slice()).unwrap();
+        assert_eq!(raw.encode_to_vec(), bytes);
+        assert_eq!(raw.encoded_len(), bytes.len());
+        assert_eq!(raw.into_bytes(), canonical_bytes());
+    }
+
+    #[test]
+    fn adopted_bytes_are_shared_not_copied() {
+        let shared = Bytes::from(canonical_bytes());
+        let raw = RawRecordSet::from_bytes(shared.clone());
+        assert_eq!(raw.encode_to_vec(), canonical_bytes());
+        assert_eq!(raw.into_bytes().as_ptr(), shared.as_ptr());
+    }
+
+    #[test]
+    fn truncated_input_is_rejected() {
+        let mut bytes = canonical_bytes();
+        bytes.truncate(6);
+        assert!(RawRecordSet::decode(bytes.as_slice()).is_err());
+    }
+}
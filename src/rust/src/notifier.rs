@@ -0,0 +1,222 @@
+//! Webhook notifier POSTing lifecycle events to external
+//! orchestrators.
+//!
+//! Events are queued onto a bounded channel and delivered by a
+//! background worker, so the serving path never waits on an endpoint;
+//! when the queue is full new events are dropped with a WARN.
+//! Deliveries carry an HMAC-SHA256 signature of the body when a
+//! secret is configured, letting receivers authenticate the sender.
+//!
+//! Independently of webhooks, every event is also published on an
+//! in-process broadcast channel that the admin `StreamEvents` RPC
+//! subscribes to.
+
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use tokio::sync::{broadcast, mpsc};
+
+/// Header carrying the delivery signature, `sha256=<hex>`.
+pub const SIGNATURE_HEADER: &str = "x-flwr-signature";
+
+/// A lifecycle event, serialized as JSON with an `event` tag, e.g.
+/// `{"event":"run_created","tenant":"","run_id":7}`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    NodeJoined {
+        tenant: String,
+        node_id: i64,
+    },
+    NodeLeft {
+        tenant: String,
+        node_id: i64,
+    },
+    RunCreated {
+        tenant: String,
+        run_id: i64,
+    },
+    RunFinished {
+        tenant: String,
+        run_id: i64,
+    },
+    RoundFinished {
+        tenant: String,
+        run_id: i64,
+        group_id: String,
+        results: u64,
+    },
+    TaskDeadLettered {
+        tenant: String,
+        run_id: i64,
+        task_id: String,
+        reason: String,
+    },
+}
+
+impl Event {
+    /// The tenant the event belongs to.
+    pub fn tenant(&self) -> &str {
+        match self {
+            Event::NodeJoined { tenant, .. }
+            | Event::NodeLeft { tenant, .. }
+            | Event::RunCreated { tenant, .. }
+            | Event::RunFinished { tenant, .. }
+            | Event::RoundFinished { tenant, .. }
+            | Event::TaskDeadLettered { tenant, .. } => tenant,
+        }
+    }
+}
+
+/// Hub distributing lifecycle events to webhooks and in-process
+/// subscribers.
+pub struct Notifier {
+    queue: Option<mpsc::Sender<Event>>,
+    stream: broadcast::Sender<Event>,
+}
+
+impl Notifier {
+    /// Create the hub, spawning the webhook delivery worker when any
+    /// URL is configured; the broadcast stream is always available.
+    pub fn spawn(config: crate::config::Notifier) -> Self {
+        let (stream, _) = broadcast::channel(config.queue_size.max(1));
+        let queue = if config.webhook_urls.is_empty() {
+            None
+        } else {
+            let (queue, receiver) = mpsc::channel(config.queue_size.max(1));
+            tokio::spawn(deliver(receiver, config));
+            Some(queue)
+        };
+        Self { queue, stream }
+    }
+
+    /// Publish `event` to subscribers and queue it for webhook
+    /// delivery.
+    pub fn notify(&self, event: Event) {
+        // A send error only means there is no live subscriber.
+        let _ = self.stream.send(event.clone());
+        if let Some(queue) = &self.queue {
+            if queue.try_send(event).is_err() {
+                tracing::warn!("webhook queue full, event dropped");
+            }
+        }
+    }
+
+    /// Subscribe to the live event stream; subscribers that lag more
+    /// than the channel capacity behind miss events.
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.stream.subscribe()
+    }
+}
+
+async fn deliver(mut receiver: mpsc::Receiver<Event>, config: crate::config::Notifier) {
+    let client = webhook_client(config.proxy_url.as_deref());
+    while let Some(event) = receiver.recv().await {
+        let body = serde_json::to_vec(&event).expect("events serialize");
+        for url in &config.webhook_urls {
+            post_with_retries(&client, &config, url, &body).await;
+        }
+    }
+}
+
+/// The delivery client, routed through the configured proxy when one
+/// is set; an invalid proxy URL falls back to direct delivery with a
+/// WARN rather than silently dropping every webhook.
+fn webhook_client(proxy_url: Option<&str>) -> reqwest::Client {
+    if let Some(proxy_url) = proxy_url {
+        match reqwest::Proxy::all(proxy_url)
+            .and_then(|proxy| reqwest::Client::builder().proxy(proxy).build())
+        {
+            Ok(client) => return client,
+            Err(err) => {
+                tracing::warn!(
+                    proxy_url,
+                    error = %err,
+                    "invalid webhook proxy, delivering directly"
+                );
+            }
+        }
+    }
+    reqwest::Client::new()
+}
+
+/// POST one event to one URL, retrying failed deliveries with
+/// exponential backoff until the attempt budget is spent.
+async fn post_with_retries(
+    client: &reqwest::Client,
+    config: &crate::config::Notifier,
+    url: &str,
+    body: &[u8],
+) {
+    let attempts = config.attempts.max(1);
+    for attempt in 1..=attempts {
+        let mut request = client
+            .post(url)
+            .header("content-type", "application/json")
+            .body(body.to_vec());
+        if !config.secret.is_empty() {
+            request = request.header(SIGNATURE_HEADER, signature(config.secret.as_bytes(), body));
+        }
+        match request.send().await {
+            Ok(response) if response.status().is_success() => return,
+            Ok(response) => {
+                tracing::warn!(url, status = %response.status(), attempt, "webhook rejected");
+            }
+            Err(err) => {
+                tracing::warn!(url, error = %err, attempt, "webhook delivery failed");
+            }
+        }
+        if attempt < attempts {
+            let backoff = config.backoff_ms.saturating_mul(1 << (attempt - 1).min(16));
+            tokio::time::sleep(std::time::Duration::from_millis(backoff)).await;
+        }
+    }
+    tracing::warn!(url, "webhook delivery given up on");
+}
+
+/// The `sha256=<hex>` HMAC-SHA256 of `body` under `secret`, in the
+/// style of GitHub webhook signatures.
+fn signature(secret: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_serialize_with_an_event_tag() {
+        let event = Event::RunCreated {
+            tenant: "t".to_owned(),
+            run_id: 7,
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({"event": "run_created", "tenant": "t", "run_id": 7})
+        );
+    }
+
+    #[tokio::test]
+    async fn subscribers_receive_published_events() {
+        let notifier = Notifier::spawn(crate::config::Config::default().notifier);
+        let mut events = notifier.subscribe();
+        let event = Event::NodeJoined {
+            tenant: "t".to_owned(),
+            node_id: 7,
+        };
+        notifier.notify(event.clone());
+        assert_eq!(events.recv().await.unwrap(), event);
+        assert_eq!(event.tenant(), "t");
+    }
+
+    #[test]
+    fn signatures_match_the_reference_vector() {
+        // RFC 4231 test case 2.
+        assert_eq!(
+            signature(b"Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
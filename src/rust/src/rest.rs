@@ -0,0 +1,518 @@
+//! JSON transcoding of the Fleet API.
+//!
+//! The unary Fleet RPCs are exposed as plain HTTP endpoints under
+//! `/v1/fleet/*`, with serde mirror types of the proto messages, so
+//! non-gRPC clients and curl-based debugging are first-class. Each
+//! request is translated into the corresponding gRPC service call
+//! with its metadata headers (`x-flwr-tenant`, `authorization`,
+//! `x-flwr-api-version`, `x-flwr-client-version`) carried over, so
+//! the federation policy applies unchanged. Recordset bytes are
+//! base64-encoded, following the protobuf JSON mapping. The OpenAPI
+//! document describing the endpoints is served at `/openapi.json`;
+//! the streaming and batch RPCs stay gRPC-only.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::extract::State as Extract;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use tonic::Status;
+
+use crate::pb;
+use crate::pb::fleet_server::Fleet;
+use crate::service::fleet::FleetService;
+use crate::service::{
+    API_VERSION_METADATA_KEY, CLIENT_VERSION_METADATA_KEY, NODE_KEY_METADATA_KEY,
+    TENANT_METADATA_KEY,
+};
+
+/// Serve the REST transport until the process exits, logging rather
+/// than surfacing failures, like the dashboard.
+pub async fn serve(service: FleetService, config: crate::config::Rest) {
+    let service = Arc::new(service);
+    let router = Router::new()
+        .route("/openapi.json", get(openapi))
+        .route("/v1/fleet/create-node", post(create_node))
+        .route("/v1/fleet/delete-node", post(delete_node))
+        .route("/v1/fleet/ping", post(ping))
+        .route("/v1/fleet/pull-task-ins", post(pull_task_ins))
+        .route("/v1/fleet/push-task-res", post(push_task_res))
+        .route("/v1/fleet/server-info", get(server_info))
+        .with_state(service);
+    tracing::info!(address = %config.address, "rest transport listening");
+    if let Err(err) = axum::Server::bind(&config.address)
+        .serve(router.into_make_service())
+        .await
+    {
+        tracing::error!(error = %err, "rest server failed");
+    }
+}
+
+/// JSON error body paired with the mapped HTTP status.
+#[derive(Debug, Serialize)]
+pub struct ErrorBody {
+    /// The gRPC status code name, e.g. `InvalidArgument`.
+    pub code: String,
+    pub message: String,
+}
+
+type Rejection = (StatusCode, Json<ErrorBody>);
+type RestResult<T> = Result<Json<T>, Rejection>;
+
+/// Map a gRPC status onto the conventional HTTP status.
+fn http_status(code: tonic::Code) -> StatusCode {
+    use tonic::Code;
+    match code {
+        Code::InvalidArgument | Code::FailedPrecondition | Code::OutOfRange => {
+            StatusCode::BAD_REQUEST
+        }
+        Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+        Code::PermissionDenied => StatusCode::FORBIDDEN,
+        Code::NotFound => StatusCode::NOT_FOUND,
+        Code::AlreadyExists | Code::Aborted => StatusCode::CONFLICT,
+        Code::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+        Code::Unimplemented => StatusCode::NOT_IMPLEMENTED,
+        Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        Code::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+fn reject(status: Status) -> Rejection {
+    (
+        http_status(status.code()),
+        Json(ErrorBody {
+            code: format!("{:?}", status.code()),
+            message: status.message().to_owned(),
+        }),
+    )
+}
+
+/// Wrap a JSON body in a gRPC request, carrying over the metadata
+/// headers the services read.
+fn grpc_request<T>(headers: &HeaderMap, message: T) -> tonic::Request<T> {
+    let mut request = tonic::Request::new(message);
+    for key in [
+        TENANT_METADATA_KEY,
+        "authorization",
+        API_VERSION_METADATA_KEY,
+        CLIENT_VERSION_METADATA_KEY,
+        NODE_KEY_METADATA_KEY,
+    ] {
+        let value = headers
+            .get(key)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok());
+        if let Some(value) = value {
+            request.metadata_mut().insert(key, value);
+        }
+    }
+    request
+}
+
+// ---- serde mirrors of the proto messages ----
+//
+// Request fields carry proto3 semantics: absent means the default.
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Node {
+    pub id: i64,
+    pub anonymous: bool,
+}
+
+impl From<pb::Node> for Node {
+    fn from(node: pb::Node) -> Self {
+        Self {
+            id: node.node_id,
+            anonymous: node.anonymous,
+        }
+    }
+}
+
+impl From<Node> for pb::Node {
+    fn from(node: Node) -> Self {
+        Self {
+            node_id: node.id,
+            anonymous: node.anonymous,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TaskError {
+    pub code: i64,
+    pub reason: String,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Task {
+    pub producer: Option<Node>,
+    pub consumer: Option<Node>,
+    pub created_at: f64,
+    pub delivered_at: String,
+    pub pushed_at: f64,
+    pub ttl: String,
+    pub ancestry: Vec<String>,
+    pub task_type: String,
+    /// Base64 of the serialized `flwr.proto.RecordSet`.
+    pub recordset: Option<String>,
+    pub recordset_checksum: String,
+    pub error: Option<TaskError>,
+}
+
+impl From<pb::Task> for Task {
+    fn from(task: pb::Task) -> Self {
+        Self {
+            producer: task.producer.map(Into::into),
+            consumer: task.consumer.map(Into::into),
+            created_at: task.created_at,
+            delivered_at: task.delivered_at,
+            pushed_at: task.pushed_at,
+            ttl: task.ttl,
+            ancestry: task.ancestry,
+            task_type: task.task_type,
+            recordset: task.recordset.map(|recordset| BASE64.encode(recordset.encode_to_vec())),
+            recordset_checksum: task.recordset_checksum,
+            error: task.error.map(|error| TaskError {
+                code: error.code,
+                reason: error.reason,
+            }),
+        }
+    }
+}
+
+impl TryFrom<Task> for pb::Task {
+    type Error = Rejection;
+
+    fn try_from(task: Task) -> Result<Self, Rejection> {
+        let recordset = match task.recordset {
+            Some(encoded) => {
+                let bytes = BASE64.decode(encoded).map_err(|_| {
+                    reject(Status::invalid_argument("recordset is not valid base64"))
+                })?;
+                Some(pb::RecordSet::decode(bytes.as_slice()).map_err(|_| {
+                    reject(Status::invalid_argument(
+                        "recordset is not a serialized flwr.proto.RecordSet",
+                    ))
+                })?)
+            }
+            None => None,
+        };
+        Ok(Self {
+            producer: task.producer.map(Into::into),
+            consumer: task.consumer.map(Into::into),
+            created_at: task.created_at,
+            delivered_at: task.delivered_at,
+            pushed_at: task.pushed_at,
+            ttl: task.ttl,
+            ancestry: task.ancestry,
+            task_type: task.task_type,
+            recordset,
+            recordset_checksum: task.recordset_checksum,
+            error: task.error.map(|error| pb::Error {
+                code: error.code,
+                reason: error.reason,
+            }),
+        })
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TaskIns {
+    pub task_id: String,
+    pub group_id: String,
+    pub run_id: i64,
+    pub task: Option<Task>,
+}
+
+impl From<pb::TaskIns> for TaskIns {
+    fn from(task_ins: pb::TaskIns) -> Self {
+        Self {
+            task_id: task_ins.task_id,
+            group_id: task_ins.group_id,
+            run_id: task_ins.run_id,
+            task: task_ins.task.map(Into::into),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TaskRes {
+    pub task_id: String,
+    pub group_id: String,
+    pub run_id: i64,
+    pub task: Option<Task>,
+}
+
+impl TryFrom<TaskRes> for pb::TaskRes {
+    type Error = Rejection;
+
+    fn try_from(task_res: TaskRes) -> Result<Self, Rejection> {
+        Ok(Self {
+            task_id: task_res.task_id,
+            group_id: task_res.group_id,
+            run_id: task_res.run_id,
+            task: task_res.task.map(TryInto::try_into).transpose()?,
+        })
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Reconnect {
+    pub reconnect: u64,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CreateNodeRequest {
+    pub ping_interval: f64,
+    pub properties: HashMap<String, String>,
+    pub task_types: Vec<String>,
+    pub prior_node_id: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreateNodeResponse {
+    pub node: Option<Node>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeleteNodeRequest {
+    pub node: Option<Node>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeleteNodeResponse {}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PingRequest {
+    pub node: Option<Node>,
+    pub ping_interval: f64,
+    pub task_types: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PingResponse {
+    pub success: bool,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PullTaskInsRequest {
+    pub node: Option<Node>,
+    pub task_ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PullTaskInsResponse {
+    pub reconnect: Option<Reconnect>,
+    pub task_ins_list: Vec<TaskIns>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PushTaskResRequest {
+    pub task_res_list: Vec<TaskRes>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PushTaskResResponse {
+    pub reconnect: Option<Reconnect>,
+    pub results: HashMap<String, u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GetServerInfoResponse {
+    pub api_version: u32,
+    pub min_api_version: u32,
+}
+
+// ---- endpoints ----
+
+async fn openapi() -> ([(header::HeaderName, &'static str); 1], &'static str) {
+    (
+        [(header::CONTENT_TYPE, "application/json")],
+        include_str!("rest/openapi.json"),
+    )
+}
+
+async fn create_node(
+    Extract(service): Extract<Arc<FleetService>>,
+    headers: HeaderMap,
+    Json(body): Json<CreateNodeRequest>,
+) -> RestResult<CreateNodeResponse> {
+    let request = pb::CreateNodeRequest {
+        ping_interval: body.ping_interval,
+        properties: body.properties,
+        task_types: body.task_types,
+        prior_node_id: body.prior_node_id,
+    };
+    let response = service
+        .create_node(grpc_request(&headers, request))
+        .await
+        .map_err(reject)?
+        .into_inner();
+    Ok(Json(CreateNodeResponse {
+        node: response.node.map(Into::into),
+    }))
+}
+
+async fn delete_node(
+    Extract(service): Extract<Arc<FleetService>>,
+    headers: HeaderMap,
+    Json(body): Json<DeleteNodeRequest>,
+) -> RestResult<DeleteNodeResponse> {
+    let request = pb::DeleteNodeRequest {
+        node: body.node.map(Into::into),
+    };
+    service
+        .delete_node(grpc_request(&headers, request))
+        .await
+        .map_err(reject)?;
+    Ok(Json(DeleteNodeResponse {}))
+}
+
+async fn ping(
+    Extract(service): Extract<Arc<FleetService>>,
+    headers: HeaderMap,
+    Json(body): Json<PingRequest>,
+) -> RestResult<PingResponse> {
+    let request = pb::PingRequest {
+        node: body.node.map(Into::into),
+        ping_interval: body.ping_interval,
+        task_types: body.task_types,
+    };
+    let response = service.ping(grpc_request(&headers, request)).await.map_err(reject)?;
+    Ok(Json(PingResponse {
+        success: response.into_inner().success,
+    }))
+}
+
+async fn pull_task_ins(
+    Extract(service): Extract<Arc<FleetService>>,
+    headers: HeaderMap,
+    Json(body): Json<PullTaskInsRequest>,
+) -> RestResult<PullTaskInsResponse> {
+    let request = pb::PullTaskInsRequest {
+        node: body.node.map(Into::into),
+        task_ids: body.task_ids,
+    };
+    let response = service
+        .pull_task_ins(grpc_request(&headers, request))
+        .await
+        .map_err(reject)?
+        .into_inner();
+    Ok(Json(PullTaskInsResponse {
+        reconnect: response.reconnect.map(|reconnect| Reconnect {
+            reconnect: reconnect.reconnect,
+        }),
+        task_ins_list: response.task_ins_list.into_iter().map(Into::into).collect(),
+    }))
+}
+
+async fn push_task_res(
+    Extract(service): Extract<Arc<FleetService>>,
+    headers: HeaderMap,
+    Json(body): Json<PushTaskResRequest>,
+) -> RestResult<PushTaskResResponse> {
+    let task_res_list = body
+        .task_res_list
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect::<Result<Vec<pb::TaskRes>, Rejection>>()?;
+    let request = pb::PushTaskResRequest { task_res_list };
+    let response = service
+        .push_task_res(grpc_request(&headers, request))
+        .await
+        .map_err(reject)?
+        .into_inner();
+    Ok(Json(PushTaskResResponse {
+        reconnect: response.reconnect.map(|reconnect| Reconnect {
+            reconnect: reconnect.reconnect,
+        }),
+        results: response.results,
+    }))
+}
+
+async fn server_info(
+    Extract(service): Extract<Arc<FleetService>>,
+    headers: HeaderMap,
+) -> RestResult<GetServerInfoResponse> {
+    let response = service
+        .get_server_info(grpc_request(&headers, pb::GetServerInfoRequest {}))
+        .await
+        .map_err(reject)?
+        .into_inner();
+    Ok(Json(GetServerInfoResponse {
+        api_version: response.api_version,
+        min_api_version: response.min_api_version,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn statuses_map_onto_the_conventional_http_codes() {
+        assert_eq!(http_status(tonic::Code::InvalidArgument), StatusCode::BAD_REQUEST);
+        assert_eq!(http_status(tonic::Code::Unauthenticated), StatusCode::UNAUTHORIZED);
+        assert_eq!(http_status(tonic::Code::ResourceExhausted), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(http_status(tonic::Code::Internal), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[test]
+    fn metadata_headers_are_carried_over() {
+        let mut headers = HeaderMap::new();
+        headers.insert(TENANT_METADATA_KEY, "alpha".parse().unwrap());
+        headers.insert(header::AUTHORIZATION, "Bearer secret".parse().unwrap());
+        headers.insert("x-unrelated", "dropped".parse().unwrap());
+        let request = grpc_request(&headers, ());
+        let metadata = request.metadata();
+        assert_eq!(metadata.get(TENANT_METADATA_KEY).unwrap(), "alpha");
+        assert_eq!(metadata.get("authorization").unwrap(), "Bearer secret");
+        assert!(metadata.get("x-unrelated").is_none());
+    }
+
+    #[test]
+    fn recordsets_round_trip_through_base64() {
+        let recordset = pb::RecordSet::default();
+        let pb_task = pb::Task {
+            task_type: "train".to_owned(),
+            recordset: Some(recordset.clone()),
+            ..Default::default()
+        };
+        let mirrored = Task::from(pb_task.clone());
+        assert_eq!(
+            mirrored.recordset.as_deref(),
+            Some(BASE64.encode(recordset.encode_to_vec()).as_str())
+        );
+        let back = pb::Task::try_from(mirrored).unwrap();
+        assert_eq!(back, pb_task);
+    }
+
+    #[test]
+    fn invalid_base64_is_rejected() {
+        let task = Task {
+            recordset: Some("not base64!".to_owned()),
+            ..Default::default()
+        };
+        let (status, Json(body)) = pb::Task::try_from(task).unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body.code, "InvalidArgument");
+    }
+}
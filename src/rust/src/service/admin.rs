@@ -0,0 +1,382 @@
+//! Admin API gRPC service for operators.
+
+use std::pin::Pin;
+use std::sync::Arc;
+
+use futures::Stream;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::StreamExt;
+use tonic::{Request, Response, Status};
+
+use crate::handler::AdminHandler;
+use crate::logging::LogFilterHandle;
+use crate::notifier::{Event, Notifier};
+use crate::model::handler::{secs_from_datetime, NodeId, RunId};
+use crate::pb::admin_server::Admin;
+use crate::pb::{
+    BanNodeRequest, BanNodeResponse, GetRunProgressRequest, GetRunProgressResponse,
+    ListAuditEventsRequest, ListAuditEventsResponse, ListClientVersionsRequest,
+    ListClientVersionsResponse, ListDeadLettersRequest, ListDeadLettersResponse,
+    ListNodesRequest, ListNodesResponse, ListTaskInsRequest, ListTaskInsResponse,
+    ListTaskResRequest, ListTaskResResponse, SetLogLevelRequest, SetLogLevelResponse,
+    StreamEventsRequest, UnbanNodeRequest, UnbanNodeResponse,
+};
+use crate::state::{TaskCursor, TaskFilter};
+
+use super::{state_err_into_grpc_err, tenant_from_request};
+
+pub struct AdminService {
+    handler: AdminHandler,
+    log_filter: LogFilterHandle,
+    notifier: Arc<Notifier>,
+}
+
+impl AdminService {
+    pub fn new(
+        handler: AdminHandler,
+        log_filter: LogFilterHandle,
+        notifier: Arc<Notifier>,
+    ) -> Self {
+        Self {
+            handler,
+            log_filter,
+            notifier,
+        }
+    }
+}
+
+/// Flatten a lifecycle event into the wire message.
+fn event_to_pb(event: Event) -> crate::pb::Event {
+    let mut message = crate::pb::Event::default();
+    match event {
+        Event::NodeJoined { node_id, .. } => {
+            message.event = "node_joined".to_owned();
+            message.node_id = node_id;
+        }
+        Event::NodeLeft { node_id, .. } => {
+            message.event = "node_left".to_owned();
+            message.node_id = node_id;
+        }
+        Event::RunCreated { run_id, .. } => {
+            message.event = "run_created".to_owned();
+            message.run_id = run_id;
+        }
+        Event::RunFinished { run_id, .. } => {
+            message.event = "run_finished".to_owned();
+            message.run_id = run_id;
+        }
+        Event::RoundFinished {
+            run_id,
+            group_id,
+            results,
+            ..
+        } => {
+            message.event = "round_finished".to_owned();
+            message.run_id = run_id;
+            message.group_id = group_id;
+            message.results = results;
+        }
+        Event::TaskDeadLettered {
+            run_id,
+            task_id,
+            reason,
+            ..
+        } => {
+            message.event = "task_dead_lettered".to_owned();
+            message.run_id = run_id;
+            message.task_id = task_id;
+            message.reason = reason;
+        }
+    }
+    message
+}
+
+/// Validate the raw filter triples of a list request, surfacing
+/// whitelist violations as INVALID_ARGUMENT.
+fn filters_from_pb(filters: &[crate::pb::TaskFilter]) -> Result<Vec<TaskFilter>, Status> {
+    filters
+        .iter()
+        .map(|filter| {
+            TaskFilter::parse(&filter.field, &filter.op, &filter.value)
+                .map_err(Status::invalid_argument)
+        })
+        .collect()
+}
+
+fn cursor_from_pb(cursor: Option<crate::pb::TaskCursor>) -> Option<TaskCursor> {
+    cursor.map(|cursor| TaskCursor {
+        created_at: cursor.created_at,
+        id: cursor.task_id,
+    })
+}
+
+fn next_cursor(created_at: Option<f64>, id: Option<&str>) -> Option<crate::pb::TaskCursor> {
+    Some(crate::pb::TaskCursor {
+        created_at: created_at?,
+        task_id: id?.to_owned(),
+    })
+}
+
+#[tonic::async_trait]
+impl Admin for AdminService {
+    type StreamEventsStream = Pin<Box<dyn Stream<Item = Result<crate::pb::Event, Status>> + Send>>;
+
+    async fn stream_events(
+        &self,
+        request: Request<StreamEventsRequest>,
+    ) -> Result<Response<Self::StreamEventsStream>, Status> {
+        let tenant = tenant_from_request(&request)?;
+        let events = BroadcastStream::new(self.notifier.subscribe()).filter_map(move |event| {
+            match event {
+                Ok(event) if event.tenant() == tenant => Some(Ok(event_to_pb(event))),
+                // Events of other tenants are not ours to stream, and
+                // a lagged subscriber simply misses events.
+                Ok(_) | Err(BroadcastStreamRecvError::Lagged(_)) => None,
+            }
+        });
+        Ok(Response::new(Box::pin(events)))
+    }
+
+    async fn list_task_ins(
+        &self,
+        request: Request<ListTaskInsRequest>,
+    ) -> Result<Response<ListTaskInsResponse>, Status> {
+        let tenant = tenant_from_request(&request)?;
+        let request = request.into_inner();
+        let after = cursor_from_pb(request.after);
+        let filters = filters_from_pb(&request.filters)?;
+        let page = self
+            .handler
+            .list_task_ins(
+                &tenant,
+                RunId(request.run_id),
+                &filters,
+                after.as_ref(),
+                request.page_size,
+            )
+            .await
+            .map_err(state_err_into_grpc_err)?;
+        let next = next_cursor(
+            page.last().map(|task_ins| secs_from_datetime(task_ins.task.created_at)),
+            page.last().map(|task_ins| task_ins.id.as_ref()),
+        );
+        let task_ins_list = page
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| {
+                tracing::error!(error = %err, "stored task failed to convert");
+                Status::internal("internal error")
+            })?;
+        Ok(Response::new(ListTaskInsResponse {
+            task_ins_list,
+            next,
+        }))
+    }
+
+    async fn list_task_res(
+        &self,
+        request: Request<ListTaskResRequest>,
+    ) -> Result<Response<ListTaskResResponse>, Status> {
+        let tenant = tenant_from_request(&request)?;
+        let request = request.into_inner();
+        let after = cursor_from_pb(request.after);
+        let filters = filters_from_pb(&request.filters)?;
+        let page = self
+            .handler
+            .list_task_res(
+                &tenant,
+                RunId(request.run_id),
+                &filters,
+                after.as_ref(),
+                request.page_size,
+            )
+            .await
+            .map_err(state_err_into_grpc_err)?;
+        let next = next_cursor(
+            page.last().map(|task_res| secs_from_datetime(task_res.task.created_at)),
+            page.last().map(|task_res| task_res.id.as_ref()),
+        );
+        let task_res_list = page
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| {
+                tracing::error!(error = %err, "stored task failed to convert");
+                Status::internal("internal error")
+            })?;
+        Ok(Response::new(ListTaskResResponse {
+            task_res_list,
+            next,
+        }))
+    }
+
+    async fn set_log_level(
+        &self,
+        request: Request<SetLogLevelRequest>,
+    ) -> Result<Response<SetLogLevelResponse>, Status> {
+        let request = request.into_inner();
+        self.log_filter
+            .set(&request.level)
+            .map_err(|err| Status::invalid_argument(format!("invalid log filter: {err}")))?;
+        tracing::info!(level = %request.level, "log filter updated");
+        Ok(Response::new(SetLogLevelResponse {}))
+    }
+
+    async fn get_run_progress(
+        &self,
+        request: Request<GetRunProgressRequest>,
+    ) -> Result<Response<GetRunProgressResponse>, Status> {
+        let tenant = tenant_from_request(&request)?;
+        let request = request.into_inner();
+        let progress = self
+            .handler
+            .run_progress(&tenant, RunId(request.run_id))
+            .await
+            .map_err(state_err_into_grpc_err)?;
+        let groups = progress
+            .into_iter()
+            .map(|(group_id, instructions, results)| {
+                crate::pb::get_run_progress_response::GroupProgress {
+                    group_id: group_id.0,
+                    instructions,
+                    results,
+                }
+            })
+            .collect();
+        Ok(Response::new(GetRunProgressResponse { groups }))
+    }
+
+    async fn list_nodes(
+        &self,
+        request: Request<ListNodesRequest>,
+    ) -> Result<Response<ListNodesResponse>, Status> {
+        let tenant = tenant_from_request(&request)?;
+        let nodes = self
+            .handler
+            .list_nodes(&tenant)
+            .await
+            .map_err(state_err_into_grpc_err)?;
+        Ok(Response::new(ListNodesResponse {
+            nodes: nodes
+                .into_iter()
+                .map(|node| crate::pb::list_nodes_response::NodeInfo {
+                    id: node.id.0,
+                    identity: node.identity,
+                    client_version: node.client_version,
+                    online_until: node.online_until,
+                    ping_interval: node.ping_interval,
+                    properties: node.properties,
+                })
+                .collect(),
+        }))
+    }
+
+    async fn list_client_versions(
+        &self,
+        request: Request<ListClientVersionsRequest>,
+    ) -> Result<Response<ListClientVersionsResponse>, Status> {
+        let tenant = tenant_from_request(&request)?;
+        let versions = self
+            .handler
+            .client_versions(&tenant)
+            .await
+            .map_err(state_err_into_grpc_err)?;
+        Ok(Response::new(ListClientVersionsResponse { versions }))
+    }
+
+    async fn ban_node(
+        &self,
+        request: Request<BanNodeRequest>,
+    ) -> Result<Response<BanNodeResponse>, Status> {
+        let tenant = tenant_from_request(&request)?;
+        let request = request.into_inner();
+        self.handler
+            .ban_node(&tenant, NodeId(request.node_id), &request.reason)
+            .await
+            .map_err(state_err_into_grpc_err)?;
+        tracing::info!(node_id = request.node_id, "node banned");
+        Ok(Response::new(BanNodeResponse {}))
+    }
+
+    async fn unban_node(
+        &self,
+        request: Request<UnbanNodeRequest>,
+    ) -> Result<Response<UnbanNodeResponse>, Status> {
+        let tenant = tenant_from_request(&request)?;
+        let request = request.into_inner();
+        self.handler
+            .unban_node(&tenant, NodeId(request.node_id))
+            .await
+            .map_err(state_err_into_grpc_err)?;
+        tracing::info!(node_id = request.node_id, "node ban lifted");
+        Ok(Response::new(UnbanNodeResponse {}))
+    }
+
+    async fn list_audit_events(
+        &self,
+        request: Request<ListAuditEventsRequest>,
+    ) -> Result<Response<ListAuditEventsResponse>, Status> {
+        let tenant = tenant_from_request(&request)?;
+        let request = request.into_inner();
+        let after = cursor_from_pb(request.after);
+        let page = self
+            .handler
+            .list_audit_events(&tenant, after.as_ref(), request.page_size)
+            .await
+            .map_err(state_err_into_grpc_err)?;
+        let next = next_cursor(
+            page.last().map(|event| event.created_at),
+            page.last().map(|event| event.id.as_str()),
+        );
+        let events = page
+            .into_iter()
+            .map(|event| crate::pb::AuditEvent {
+                id: event.id,
+                created_at: event.created_at,
+                action: event.action,
+                node_id: event.node_id.0,
+                run_id: event.run_id.0,
+                detail: event.detail,
+            })
+            .collect();
+        Ok(Response::new(ListAuditEventsResponse { events, next }))
+    }
+
+    async fn list_dead_letters(
+        &self,
+        request: Request<ListDeadLettersRequest>,
+    ) -> Result<Response<ListDeadLettersResponse>, Status> {
+        let tenant = tenant_from_request(&request)?;
+        let request = request.into_inner();
+        let after = cursor_from_pb(request.after);
+        let page = self
+            .handler
+            .list_dead_letters(&tenant, after.as_ref(), request.page_size)
+            .await
+            .map_err(state_err_into_grpc_err)?;
+        let next = next_cursor(
+            page.last().map(|dead| dead.dead_at),
+            page.last().map(|dead| dead.id.as_ref()),
+        );
+        let tasks = page
+            .into_iter()
+            .map(|dead| crate::pb::DeadLetter {
+                id: dead.id.0,
+                group_id: dead.group_id.0,
+                run_id: dead.run_id.0,
+                consumer: Some(crate::pb::Node {
+                    node_id: dead.consumer.id.0,
+                    anonymous: dead.consumer.anonymous,
+                }),
+                created_at: dead.created_at,
+                dead_at: dead.dead_at,
+                delivery_count: dead.delivery_count,
+                task_type: dead.task_type,
+                reason: dead.reason,
+            })
+            .collect();
+        Ok(Response::new(ListDeadLettersResponse { tasks, next }))
+    }
+}
@@ -0,0 +1,255 @@
+//! Chunked task transfer: assembles client-streamed recordset chunks
+//! and splits stored recordsets for server streaming.
+
+use prost::Message;
+use tonic::{Status, Streaming};
+
+use super::validate::ValidationConfig;
+use crate::pb::{
+    task_ins_chunk, task_res_chunk, RecordSet, TaskIns, TaskInsChunk, TaskRes, TaskResChunk,
+};
+
+/// Serialized recordset bytes sent per streamed chunk; comfortably
+/// below the default 4 MiB message size limit.
+pub(crate) const RECORDSET_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Assembled recordset bytes accepted when no recordset size limit is
+/// configured, so an endless chunk stream cannot exhaust server
+/// memory; `max_decoding_message_size` bounds only individual chunks.
+const MAX_ASSEMBLED_BYTES: usize = 256 * 1024 * 1024;
+
+/// Read a chunked task instruction: a header followed by the
+/// serialized recordset in chunks. Accumulation is capped at the
+/// configured recordset size limit for the header's task type.
+pub(crate) async fn assemble_task_ins(
+    stream: &mut Streaming<TaskInsChunk>,
+    validation: &ValidationConfig,
+) -> Result<TaskIns, Status> {
+    let mut header: Option<TaskIns> = None;
+    let mut cap: Option<usize> = None;
+    let mut buffer = Vec::new();
+    while let Some(chunk) = stream.message().await? {
+        match chunk.payload {
+            Some(task_ins_chunk::Payload::Header(task_ins)) => {
+                set_header(&mut header, task_ins, |task_ins| task_ins.task.as_ref())?;
+                let task = header.as_ref().and_then(|task_ins| task_ins.task.as_ref());
+                cap = Some(assembly_cap(validation, task));
+            }
+            Some(task_ins_chunk::Payload::RecordsetChunk(bytes)) => {
+                append_chunk(cap, &mut buffer, &bytes)?;
+            }
+            None => return Err(Status::invalid_argument("chunk payload must be set")),
+        }
+    }
+    let mut task_ins =
+        header.ok_or_else(|| Status::invalid_argument("stream ended before header"))?;
+    let recordset = decode_buffer(&buffer)?;
+    if let Some(task) = task_ins.task.as_mut() {
+        task.recordset = Some(recordset);
+    }
+    Ok(task_ins)
+}
+
+/// Read a chunked task result; see [`assemble_task_ins`].
+pub(crate) async fn assemble_task_res(
+    stream: &mut Streaming<TaskResChunk>,
+    validation: &ValidationConfig,
+) -> Result<TaskRes, Status> {
+    let mut header: Option<TaskRes> = None;
+    let mut cap: Option<usize> = None;
+    let mut buffer = Vec::new();
+    while let Some(chunk) = stream.message().await? {
+        match chunk.payload {
+            Some(task_res_chunk::Payload::Header(task_res)) => {
+                set_header(&mut header, task_res, |task_res| task_res.task.as_ref())?;
+                let task = header.as_ref().and_then(|task_res| task_res.task.as_ref());
+                cap = Some(assembly_cap(validation, task));
+            }
+            Some(task_res_chunk::Payload::RecordsetChunk(bytes)) => {
+                append_chunk(cap, &mut buffer, &bytes)?;
+            }
+            None => return Err(Status::invalid_argument("chunk payload must be set")),
+        }
+    }
+    let mut task_res =
+        header.ok_or_else(|| Status::invalid_argument("stream ended before header"))?;
+    let recordset = decode_buffer(&buffer)?;
+    if let Some(task) = task_res.task.as_mut() {
+        task.recordset = Some(recordset);
+    }
+    Ok(task_res)
+}
+
+fn set_header<T>(
+    header: &mut Option<T>,
+    incoming: T,
+    task: impl Fn(&T) -> Option<&crate::pb::Task>,
+) -> Result<(), Status> {
+    if header.is_some() {
+        return Err(Status::invalid_argument("header must be sent exactly once"));
+    }
+    if task(&incoming).is_some_and(|task| task.recordset.is_some()) {
+        return Err(Status::invalid_argument("header recordset must be unset"));
+    }
+    *header = Some(incoming);
+    Ok(())
+}
+
+/// The byte cap for one assembled recordset: the configured limit for
+/// the header's task type, or [`MAX_ASSEMBLED_BYTES`] when no limit is
+/// configured.
+fn assembly_cap(config: &ValidationConfig, task: Option<&crate::pb::Task>) -> usize {
+    let task_type = task.map_or("", |task| task.task_type.as_str());
+    let limit = config
+        .max_recordset_sizes
+        .get(task_type)
+        .copied()
+        .unwrap_or(config.max_recordset_size);
+    if limit > 0 {
+        limit
+    } else {
+        MAX_ASSEMBLED_BYTES
+    }
+}
+
+fn append_chunk(cap: Option<usize>, buffer: &mut Vec<u8>, bytes: &[u8]) -> Result<(), Status> {
+    let Some(cap) = cap else {
+        return Err(Status::invalid_argument("recordset chunk sent before header"));
+    };
+    if buffer.len() + bytes.len() > cap {
+        return Err(Status::resource_exhausted(format!(
+            "assembled recordset exceeds the {cap} byte limit"
+        )));
+    }
+    buffer.extend_from_slice(bytes);
+    Ok(())
+}
+
+fn decode_buffer(buffer: &[u8]) -> Result<RecordSet, Status> {
+    RecordSet::decode(buffer)
+        .map_err(|_| Status::invalid_argument("assembled recordset failed to decode"))
+}
+
+/// Split a task instruction into a header chunk followed by recordset
+/// chunks.
+pub(crate) fn chunk_task_ins(mut task_ins: TaskIns) -> Vec<TaskInsChunk> {
+    let encoded = task_ins
+        .task
+        .as_mut()
+        .and_then(|task| task.recordset.take())
+        .map(|recordset| recordset.encode_to_vec())
+        .unwrap_or_default();
+    let mut chunks = vec![TaskInsChunk {
+        payload: Some(task_ins_chunk::Payload::Header(task_ins)),
+    }];
+    for part in encoded.chunks(RECORDSET_CHUNK_SIZE) {
+        chunks.push(TaskInsChunk {
+            payload: Some(task_ins_chunk::Payload::RecordsetChunk(part.to_vec())),
+        });
+    }
+    chunks
+}
+
+/// Split a task result into a header chunk followed by recordset
+/// chunks.
+pub(crate) fn chunk_task_res(mut task_res: TaskRes) -> Vec<TaskResChunk> {
+    let encoded = task_res
+        .task
+        .as_mut()
+        .and_then(|task| task.recordset.take())
+        .map(|recordset| recordset.encode_to_vec())
+        .unwrap_or_default();
+    let mut chunks = vec![TaskResChunk {
+        payload: Some(task_res_chunk::Payload::Header(task_res)),
+    }];
+    for part in encoded.chunks(RECORDSET_CHUNK_SIZE) {
+        chunks.push(TaskResChunk {
+            payload: Some(task_res_chunk::Payload::RecordsetChunk(part.to_vec())),
+        });
+    }
+    chunks
+}
+
+// These tests build structured recordsets, which the raw-recordsets
+// feature replaces with opaque bytes.
+#[cfg(all(test, not(feature = "raw-recordsets")))]
+mod tests {
+    use super::*;
+
+    fn task_ins_with_recordset(data: Vec<u8>) -> TaskIns {
+        let parameters = crate::pb::ParametersRecord {
+            data_keys: vec!["weights".to_owned()],
+            data_values: vec![crate::pb::Array {
+                dtype: "float32".to_owned(),
+                shape: vec![data.len() as i32],
+                stype: "numpy.ndarray".to_owned(),
+                data,
+            }],
+        };
+        TaskIns {
+            task_id: "abc".to_owned(),
+            group_id: String::new(),
+            run_id: 1,
+            task: Some(crate::pb::Task {
+                recordset: Some(RecordSet {
+                    parameters: [("model".to_owned(), parameters)].into_iter().collect(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn assembly_cap_prefers_the_configured_limit() {
+        let task = crate::pb::Task {
+            task_type: "train".to_owned(),
+            ..Default::default()
+        };
+        let mut config = ValidationConfig::default();
+        assert_eq!(assembly_cap(&config, Some(&task)), MAX_ASSEMBLED_BYTES);
+        config.max_recordset_size = 64;
+        assert_eq!(assembly_cap(&config, Some(&task)), 64);
+        config.max_recordset_sizes.insert("train".to_owned(), 16);
+        assert_eq!(assembly_cap(&config, Some(&task)), 16);
+    }
+
+    #[test]
+    fn oversized_assemblies_are_rejected() {
+        let mut buffer = Vec::new();
+        append_chunk(Some(8), &mut buffer, &[0; 8]).unwrap();
+        let status = append_chunk(Some(8), &mut buffer, &[0; 1]).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+    }
+
+    #[test]
+    fn header_chunk_has_no_recordset() {
+        let chunks = chunk_task_ins(task_ins_with_recordset(vec![1, 2, 3]));
+        let Some(task_ins_chunk::Payload::Header(header)) = &chunks[0].payload else {
+            panic!("first chunk must be the header");
+        };
+        assert!(header.task.as_ref().unwrap().recordset.is_none());
+        assert!(chunks.len() > 1);
+    }
+
+    #[test]
+    fn chunks_reassemble_to_encoded_recordset() {
+        let task_ins = task_ins_with_recordset(vec![7; 64]);
+        let encoded = task_ins
+            .task
+            .as_ref()
+            .unwrap()
+            .recordset
+            .as_ref()
+            .unwrap()
+            .encode_to_vec();
+        let mut buffer = Vec::new();
+        for chunk in chunk_task_ins(task_ins).into_iter().skip(1) {
+            let Some(task_ins_chunk::Payload::RecordsetChunk(bytes)) = chunk.payload else {
+                panic!("expected a recordset chunk");
+            };
+            buffer.extend_from_slice(&bytes);
+        }
+        assert_eq!(buffer, encoded);
+    }
+}
@@ -0,0 +1,659 @@
+//! Conversions between the generated protobuf types and the domain
+//! model, validated through the rules in [`super::validate`].
+//!
+//! The rules mirror `flwr.server.utils.validator` on the Python side so
+//! both implementations accept the same wire traffic.
+
+use chrono::Utc;
+#[cfg(not(feature = "raw-recordsets"))]
+use prost::Message;
+use sha2::{Digest, Sha256};
+
+use crate::model::handler::{
+    datetime_from_secs, secs_from_datetime, GroupId, Node, NodeId, RunId, Task, TaskError, TaskId,
+    TaskIns, TaskRes,
+};
+use crate::pb;
+
+use super::validate::{
+    check_ancestry, check_client_timestamps, check_created_at, check_identifier, check_task_type,
+    check_ttl, validate_node, Kind, ValidationConfig, ValidationError,
+};
+
+/// Errors turning stored tasks back into protobuf messages.
+#[derive(Debug, thiserror::Error)]
+pub enum StoredTaskError {
+    #[error("stored recordset failed to decode: {0}")]
+    Decode(#[from] prost::DecodeError),
+    #[error("stored recordset failed checksum verification")]
+    ChecksumMismatch,
+}
+
+/// Lowercase hex SHA-256 of the serialized recordset.
+fn recordset_checksum(recordset: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(recordset))
+}
+
+/// The serialized bytes of a wire recordset. With the raw-recordsets
+/// feature the original wire bytes are moved out verbatim, so storage
+/// is guaranteed byte-identical; otherwise the parsed message is
+/// serialized once here.
+#[cfg(feature = "raw-recordsets")]
+fn recordset_into_bytes(recordset: pb::RecordSet) -> bytes::Bytes {
+    recordset.into_bytes()
+}
+
+#[cfg(not(feature = "raw-recordsets"))]
+fn recordset_into_bytes(recordset: pb::RecordSet) -> bytes::Bytes {
+    recordset.encode_to_vec().into()
+}
+
+/// A wire recordset wrapping stored bytes. With the raw-recordsets
+/// feature the bytes are adopted without a decode pass.
+#[cfg(feature = "raw-recordsets")]
+fn recordset_from_bytes(bytes: bytes::Bytes) -> Result<pb::RecordSet, prost::DecodeError> {
+    Ok(pb::RecordSet::from_bytes(bytes))
+}
+
+#[cfg(not(feature = "raw-recordsets"))]
+fn recordset_from_bytes(bytes: bytes::Bytes) -> Result<pb::RecordSet, prost::DecodeError> {
+    pb::RecordSet::decode(bytes)
+}
+
+impl From<pb::Node> for Node {
+    fn from(node: pb::Node) -> Self {
+        Self {
+            id: NodeId(node.node_id),
+            anonymous: node.anonymous,
+        }
+    }
+}
+
+impl From<Node> for pb::Node {
+    fn from(node: Node) -> Self {
+        Self {
+            node_id: node.id.0,
+            anonymous: node.anonymous,
+        }
+    }
+}
+
+fn try_task(
+    task: Option<pb::Task>,
+    kind: Kind,
+    config: &ValidationConfig,
+    err: &mut ValidationError,
+) -> Option<Task> {
+    let Some(task) = task else {
+        err.push("task", "must be set");
+        return None;
+    };
+    check_client_timestamps(&task.delivered_at, task.pushed_at, err);
+    check_created_at(task.created_at, config.message_expires_after, err);
+    check_task_type(&task.task_type, &config.allowed_task_types, err);
+    check_ttl(&task.ttl, err);
+    check_ancestry(&task.ancestry, kind, err);
+    if kind == Kind::Ins && task.error.is_some() {
+        err.push("task.error", "must be empty for TaskIns");
+    }
+    let producer = validate_node(task.producer, "task.producer", err);
+    let consumer = validate_node(task.consumer, "task.consumer", err);
+    let recordset = match task.recordset {
+        Some(recordset) => recordset_into_bytes(recordset),
+        None => {
+            err.push("task.recordset", "must be set");
+            bytes::Bytes::new()
+        }
+    };
+    let limit = config
+        .max_recordset_sizes
+        .get(&task.task_type)
+        .copied()
+        .unwrap_or(config.max_recordset_size);
+    if limit > 0 && recordset.len() > limit {
+        err.push(
+            "task.recordset",
+            &format!("serialized recordset exceeds the {limit} byte limit"),
+        );
+    }
+    let checksum = recordset_checksum(&recordset);
+    if !task.recordset_checksum.is_empty() && task.recordset_checksum != checksum {
+        err.push(
+            "task.recordset_checksum",
+            "does not match the serialized recordset",
+        );
+    }
+    Some(Task {
+        producer,
+        consumer,
+        created_at: datetime_from_secs(task.created_at),
+        delivered_at: None,
+        pushed_at: Utc::now(),
+        ttl: task.ttl,
+        ancestry: task.ancestry.into_iter().map(TaskId).collect(),
+        task_type: task.task_type,
+        recordset,
+        recordset_checksum: checksum,
+        error: task.error.map(|error| TaskError {
+            code: error.code,
+            reason: error.reason,
+        }),
+    })
+}
+
+impl TryFrom<(pb::TaskIns, &ValidationConfig)> for TaskIns {
+    type Error = ValidationError;
+
+    fn try_from((task_ins, config): (pb::TaskIns, &ValidationConfig)) -> Result<Self, Self::Error> {
+        let mut err = ValidationError::default();
+        if !task_ins.task_id.is_empty() {
+            err.push("task_id", "must not be set by the client");
+        }
+        check_identifier(&task_ins.group_id, "group_id", &mut err);
+        let task = try_task(task_ins.task, Kind::Ins, config, &mut err);
+        if !err.is_empty() {
+            return Err(err);
+        }
+        Ok(Self {
+            id: TaskId::default(),
+            group_id: GroupId(task_ins.group_id),
+            run_id: RunId(task_ins.run_id),
+            task: task.expect("validated"),
+        })
+    }
+}
+
+impl TryFrom<(pb::TaskRes, &ValidationConfig)> for TaskRes {
+    type Error = ValidationError;
+
+    fn try_from((task_res, config): (pb::TaskRes, &ValidationConfig)) -> Result<Self, Self::Error> {
+        let mut err = ValidationError::default();
+        if !task_res.task_id.is_empty() {
+            err.push("task_id", "must not be set by the client");
+        }
+        check_identifier(&task_res.group_id, "group_id", &mut err);
+        let task = try_task(task_res.task, Kind::Res, config, &mut err);
+        if let Some(task) = &task {
+            if task.producer.anonymous != task.consumer.anonymous && task.producer.anonymous {
+                // Anonymous producers may only answer anonymous instructions.
+                err.push("task.producer", "anonymous producer for registered consumer");
+            }
+        }
+        if !err.is_empty() {
+            return Err(err);
+        }
+        Ok(Self {
+            id: TaskId::default(),
+            group_id: GroupId(task_res.group_id),
+            run_id: RunId(task_res.run_id),
+            task: task.expect("validated"),
+        })
+    }
+}
+
+fn task_into_pb(task: Task) -> Result<pb::Task, StoredTaskError> {
+    if !task.recordset_checksum.is_empty()
+        && recordset_checksum(&task.recordset) != task.recordset_checksum
+    {
+        return Err(StoredTaskError::ChecksumMismatch);
+    }
+    let recordset = recordset_from_bytes(task.recordset)?;
+    Ok(pb::Task {
+        producer: Some(task.producer.into()),
+        consumer: Some(task.consumer.into()),
+        created_at: secs_from_datetime(task.created_at),
+        delivered_at: task
+            .delivered_at
+            .map(|at| at.to_rfc3339())
+            .unwrap_or_default(),
+        pushed_at: secs_from_datetime(task.pushed_at),
+        ttl: task.ttl,
+        ancestry: task.ancestry.into_iter().map(String::from).collect(),
+        task_type: task.task_type,
+        recordset: Some(recordset),
+        recordset_checksum: task.recordset_checksum,
+        error: task.error.map(|error| pb::Error {
+            code: error.code,
+            reason: error.reason,
+        }),
+    })
+}
+
+impl TryFrom<TaskIns> for pb::TaskIns {
+    type Error = StoredTaskError;
+
+    fn try_from(task_ins: TaskIns) -> Result<Self, Self::Error> {
+        Ok(Self {
+            task_id: task_ins.id.0,
+            group_id: task_ins.group_id.0,
+            run_id: task_ins.run_id.0,
+            task: Some(task_into_pb(task_ins.task)?),
+        })
+    }
+}
+
+impl TryFrom<TaskRes> for pb::TaskRes {
+    type Error = StoredTaskError;
+
+    fn try_from(task_res: TaskRes) -> Result<Self, Self::Error> {
+        Ok(Self {
+            task_id: task_res.id.0,
+            group_id: task_res.group_id.0,
+            run_id: task_res.run_id.0,
+            task: Some(task_into_pb(task_res.task)?),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use prost::Message;
+
+    use super::super::validate::now_secs;
+    use super::*;
+
+    fn pb_task_ins() -> pb::TaskIns {
+        pb::TaskIns {
+            task_id: String::new(),
+            group_id: "group".to_owned(),
+            run_id: 1,
+            task: Some(pb::Task {
+                producer: Some(pb::Node {
+                    node_id: 0,
+                    anonymous: true,
+                }),
+                consumer: Some(pb::Node {
+                    node_id: 7,
+                    anonymous: false,
+                }),
+                created_at: now_secs(),
+                delivered_at: String::new(),
+                pushed_at: 0.0,
+                ttl: String::new(),
+                ancestry: Vec::new(),
+                task_type: "train".to_owned(),
+                recordset: Some(pb::RecordSet::default()),
+                recordset_checksum: String::new(),
+                error: None,
+            }),
+        }
+    }
+
+    #[test]
+    fn valid_task_ins_converts() {
+        let config = ValidationConfig::default();
+        let task_ins = TaskIns::try_from((pb_task_ins(), &config)).unwrap();
+        assert_eq!(task_ins.run_id, RunId(1));
+        assert!(task_ins.task.pushed_at > chrono::DateTime::UNIX_EPOCH);
+    }
+
+    #[test]
+    fn task_ins_with_ancestry_is_rejected() {
+        let config = ValidationConfig::default();
+        let mut task_ins = pb_task_ins();
+        task_ins.task.as_mut().unwrap().ancestry = vec!["parent".to_owned()];
+        let err = TaskIns::try_from((task_ins, &config)).unwrap_err();
+        assert!(err
+            .violations()
+            .iter()
+            .any(|violation| violation.field == "task.ancestry"));
+    }
+
+    #[test]
+    fn oversized_or_unprintable_identifiers_are_rejected() {
+        let config = ValidationConfig::default();
+        let mut task_ins = pb_task_ins();
+        task_ins.group_id = "a".repeat(65);
+        let err = TaskIns::try_from((task_ins, &config)).unwrap_err();
+        assert!(err
+            .violations()
+            .iter()
+            .any(|violation| violation.field == "group_id"));
+        let mut task_ins = pb_task_ins();
+        task_ins.task.as_mut().unwrap().task_type = "train\nround".to_owned();
+        let err = TaskIns::try_from((task_ins, &config)).unwrap_err();
+        assert!(err
+            .violations()
+            .iter()
+            .any(|violation| violation.field == "task.task_type"));
+    }
+
+    #[test]
+    fn an_allowlist_rejects_unknown_task_types() {
+        let config = ValidationConfig {
+            allowed_task_types: vec!["train".to_owned()],
+            ..ValidationConfig::default()
+        };
+        assert!(TaskIns::try_from((pb_task_ins(), &config)).is_ok());
+        let mut task_ins = pb_task_ins();
+        task_ins.task.as_mut().unwrap().task_type = "exfiltrate".to_owned();
+        let err = TaskIns::try_from((task_ins, &config)).unwrap_err();
+        assert!(err
+            .violations()
+            .iter()
+            .any(|violation| violation.field == "task.task_type"));
+    }
+
+    #[test]
+    fn client_set_delivered_at_is_rejected() {
+        let config = ValidationConfig::default();
+        let mut task_ins = pb_task_ins();
+        task_ins.task.as_mut().unwrap().delivered_at = "2024-01-01T00:00:00Z".to_owned();
+        assert!(TaskIns::try_from((task_ins, &config)).is_err());
+    }
+
+    #[test]
+    fn client_set_pushed_at_is_rejected() {
+        let config = ValidationConfig::default();
+        let mut task_ins = pb_task_ins();
+        task_ins.task.as_mut().unwrap().pushed_at = now_secs();
+        let err = TaskIns::try_from((task_ins, &config)).unwrap_err();
+        assert!(err
+            .violations()
+            .iter()
+            .any(|violation| violation.field == "task.pushed_at"));
+    }
+
+    #[test]
+    fn anonymous_consumer_with_node_id_is_rejected() {
+        let config = ValidationConfig::default();
+        let mut task_ins = pb_task_ins();
+        task_ins.task.as_mut().unwrap().consumer = Some(pb::Node {
+            node_id: 7,
+            anonymous: true,
+        });
+        assert!(TaskIns::try_from((task_ins, &config)).is_err());
+    }
+
+    #[test]
+    fn wrong_checksum_is_rejected() {
+        let config = ValidationConfig::default();
+        let mut task_ins = pb_task_ins();
+        task_ins.task.as_mut().unwrap().recordset_checksum = "deadbeef".to_owned();
+        let err = TaskIns::try_from((task_ins, &config)).unwrap_err();
+        assert!(err
+            .violations()
+            .iter()
+            .any(|violation| violation.field == "task.recordset_checksum"));
+    }
+
+    #[test]
+    fn matching_checksum_is_accepted_and_stored() {
+        let config = ValidationConfig::default();
+        let mut task_ins = pb_task_ins();
+        let encoded = pb::RecordSet::default().encode_to_vec();
+        task_ins.task.as_mut().unwrap().recordset_checksum = recordset_checksum(&encoded);
+        let task_ins = TaskIns::try_from((task_ins, &config)).unwrap();
+        assert_eq!(task_ins.task.recordset_checksum, recordset_checksum(&encoded));
+    }
+
+    #[cfg(not(feature = "raw-recordsets"))]
+    #[test]
+    fn corrupted_stored_recordset_fails_conversion() {
+        let config = ValidationConfig::default();
+        let mut task_ins = TaskIns::try_from((pb_task_ins(), &config)).unwrap();
+        task_ins.task.recordset = pb::RecordSet {
+            metrics: [(
+                "loss".to_owned(),
+                pb::MetricsRecord {
+                    data: HashMap::new(),
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        }
+        .encode_to_vec();
+        assert!(matches!(
+            pb::TaskIns::try_from(task_ins),
+            Err(StoredTaskError::ChecksumMismatch)
+        ));
+    }
+
+    #[cfg(not(feature = "raw-recordsets"))]
+    #[test]
+    fn oversized_recordset_is_rejected() {
+        let config = ValidationConfig {
+            max_recordset_sizes: [("train".to_owned(), 4)].into_iter().collect(),
+            ..ValidationConfig::default()
+        };
+        let mut task_ins = pb_task_ins();
+        task_ins.task.as_mut().unwrap().recordset = Some(pb::RecordSet {
+            configs: [(
+                "settings".to_owned(),
+                pb::ConfigsRecord {
+                    data: HashMap::new(),
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        });
+        let err = TaskIns::try_from((task_ins, &config)).unwrap_err();
+        assert!(err
+            .violations()
+            .iter()
+            .any(|violation| violation.description.contains("byte limit")));
+    }
+
+    /// Wire bytes survive push and pull untouched when recordsets are
+    /// passed through raw.
+    #[cfg(feature = "raw-recordsets")]
+    #[test]
+    fn raw_recordset_bytes_are_stored_verbatim() {
+        let config = ValidationConfig::default();
+        let mut task_ins = pb_task_ins();
+        let bytes = bytes::Bytes::from_static(&[0x7a, 0x03, 1, 2, 3]);
+        task_ins.task.as_mut().unwrap().recordset =
+            Some(pb::RecordSet::from_bytes(bytes.clone()));
+        let stored = TaskIns::try_from((task_ins, &config)).unwrap();
+        assert_eq!(stored.task.recordset, bytes);
+        let pulled = pb::TaskIns::try_from(stored).unwrap();
+        assert_eq!(pulled.task.unwrap().recordset.unwrap().into_bytes(), bytes);
+    }
+
+    #[test]
+    fn task_res_requires_ancestry() {
+        let config = ValidationConfig::default();
+        let task_res = pb::TaskRes {
+            task_id: String::new(),
+            group_id: "group".to_owned(),
+            run_id: 1,
+            task: pb_task_ins().task,
+        };
+        let err = TaskRes::try_from((task_res, &config)).unwrap_err();
+        assert!(err
+            .violations()
+            .iter()
+            .any(|violation| violation.field == "task.ancestry"));
+    }
+}
+
+// The strategies build structured recordsets, which the
+// raw-recordsets feature replaces with opaque bytes.
+#[cfg(all(test, not(feature = "raw-recordsets")))]
+mod prop_tests {
+    use std::collections::HashMap;
+
+    use proptest::prelude::*;
+
+    use super::super::validate::now_secs;
+    use super::*;
+
+    fn node() -> impl Strategy<Value = pb::Node> {
+        prop_oneof![
+            Just(pb::Node {
+                node_id: 0,
+                anonymous: true,
+            }),
+            (1..i64::MAX).prop_map(|node_id| pb::Node {
+                node_id,
+                anonymous: false,
+            }),
+        ]
+    }
+
+    fn recordset() -> impl Strategy<Value = pb::RecordSet> {
+        let bytes = proptest::collection::vec(any::<u8>(), 0..64);
+        let finite = any::<f64>().prop_filter("finite", |value| value.is_finite());
+        (
+            proptest::collection::hash_map("[a-z]{1,8}", bytes, 0..3),
+            proptest::collection::hash_map("[a-z]{1,8}", finite, 0..3),
+        )
+            .prop_map(|(parameters, metrics)| pb::RecordSet {
+                parameters: parameters
+                    .into_iter()
+                    .map(|(key, data)| {
+                        (
+                            key,
+                            pb::ParametersRecord {
+                                data_keys: vec!["weights".to_owned()],
+                                data_values: vec![pb::Array {
+                                    dtype: "uint8".to_owned(),
+                                    shape: vec![data.len() as i32],
+                                    stype: "ndarray".to_owned(),
+                                    data,
+                                }],
+                            },
+                        )
+                    })
+                    .collect(),
+                metrics: metrics
+                    .into_iter()
+                    .map(|(key, value)| {
+                        (
+                            key,
+                            pb::MetricsRecord {
+                                data: [(
+                                    key_for(&key),
+                                    pb::MetricsRecordValue {
+                                        value: Some(pb::metrics_record_value::Value::Double(value)),
+                                    },
+                                )]
+                                .into_iter()
+                                .collect(),
+                            },
+                        )
+                    })
+                    .collect(),
+                configs: HashMap::new(),
+            })
+    }
+
+    fn key_for(key: &str) -> String {
+        format!("{key}.value")
+    }
+
+    fn task_error() -> impl Strategy<Value = Option<pb::Error>> {
+        proptest::option::of(
+            (any::<i64>(), "[a-z ]{0,32}").prop_map(|(code, reason)| pb::Error { code, reason }),
+        )
+    }
+
+    /// A pb::Task the Res-direction validator accepts.
+    fn valid_res_task() -> impl Strategy<Value = pb::Task> {
+        (
+            node(),
+            recordset(),
+            proptest::collection::vec(
+                any::<u128>().prop_map(|value| uuid::Uuid::from_u128(value).to_string()),
+                1..4,
+            ),
+            "[a-z]{1,8}",
+            "[0-9]{0,8}",
+            task_error(),
+        )
+            .prop_map(|(producer, recordset, ancestry, task_type, ttl, error)| {
+                // Anonymous producers may only answer anonymous consumers.
+                let consumer = pb::Node {
+                    node_id: 0,
+                    anonymous: true,
+                };
+                pb::Task {
+                    producer: Some(producer),
+                    consumer: Some(consumer),
+                    created_at: now_secs(),
+                    delivered_at: String::new(),
+                    pushed_at: 0.0,
+                    ttl,
+                    ancestry,
+                    task_type,
+                    recordset: Some(recordset),
+                    recordset_checksum: String::new(),
+                    error,
+                }
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn node_conversion_roundtrips(node in node()) {
+            let model = Node::from(node.clone());
+            prop_assert_eq!(pb::Node::from(model), node);
+        }
+
+        #[test]
+        fn recordset_bytes_roundtrip(recordset in recordset()) {
+            let encoded = recordset.encode_to_vec();
+            let decoded = pb::RecordSet::decode(encoded.as_slice()).unwrap();
+            prop_assert_eq!(&decoded, &recordset);
+            // Checksums of the re-encoded message stay stable.
+            prop_assert_eq!(
+                recordset_checksum(&encoded),
+                recordset_checksum(&decoded.encode_to_vec())
+            );
+        }
+
+        /// Whatever the push path accepts, the pull path can hand back
+        /// without loss: no validation asymmetry between directions.
+        #[test]
+        fn accepted_task_res_survives_the_pull_path(
+            task in valid_res_task(),
+            run_id in 0..i64::MAX,
+        ) {
+            let config = ValidationConfig::default();
+            let pushed = pb::TaskRes {
+                task_id: String::new(),
+                group_id: "group".to_owned(),
+                run_id,
+                task: Some(task.clone()),
+            };
+            let stored = TaskRes::try_from((pushed, &config)).unwrap();
+            let pulled = pb::TaskRes::try_from(stored).unwrap();
+            let pulled_task = pulled.task.unwrap();
+            prop_assert_eq!(pulled.run_id, run_id);
+            prop_assert_eq!(pulled_task.producer, task.producer);
+            prop_assert_eq!(pulled_task.consumer, task.consumer);
+            prop_assert_eq!(pulled_task.created_at, task.created_at);
+            prop_assert_eq!(pulled_task.ancestry, task.ancestry);
+            prop_assert_eq!(pulled_task.task_type, task.task_type);
+            prop_assert_eq!(pulled_task.ttl, task.ttl);
+            prop_assert_eq!(pulled_task.recordset, task.recordset);
+            prop_assert_eq!(pulled_task.error, task.error);
+            // The server stamps pushed_at; the client value was zero.
+            prop_assert!(pulled_task.pushed_at > 0.0);
+        }
+
+        #[test]
+        fn client_set_pushed_at_is_always_rejected(
+            task in valid_res_task(),
+            pushed_at in 0.1f64..1e12,
+        ) {
+            let config = ValidationConfig::default();
+            let mut task = task;
+            task.pushed_at = pushed_at;
+            let result = TaskRes::try_from((
+                pb::TaskRes {
+                    task_id: String::new(),
+                    group_id: String::new(),
+                    run_id: 1,
+                    task: Some(task),
+                },
+                &config,
+            ));
+            prop_assert!(result.is_err());
+        }
+    }
+}
@@ -0,0 +1,285 @@
+//! Driver API gRPC service.
+
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::handler::driver::PushLimits;
+use crate::handler::DriverHandler;
+use crate::model::handler::{RunId, TaskId, TaskIns};
+use crate::pb::driver_server::Driver;
+use crate::pb::{
+    AcknowledgeTaskResRequest, AcknowledgeTaskResResponse, BroadcastTaskInsRequest,
+    BroadcastTaskInsResponse, CreateRunRequest, CreateRunResponse, DeleteRunRequest,
+    DeleteRunResponse, GetNodesRequest, GetNodesResponse, PullTaskResRequest, PullTaskResResponse,
+    PushTaskInsRequest, PushTaskInsResponse, SampleNodesRequest, SampleNodesResponse, TaskInsChunk,
+    TaskResChunk,
+};
+
+use tokio::sync::watch;
+
+use crate::config::DynamicConfig;
+
+use super::validate::{validation_err_into_grpc_err, ValidationConfig};
+use super::{chunk, federation_from_request, state_err_into_grpc_err};
+
+pub struct DriverService {
+    handler: DriverHandler,
+    dynamic: watch::Receiver<DynamicConfig>,
+}
+
+impl DriverService {
+    pub fn new(handler: DriverHandler, dynamic: watch::Receiver<DynamicConfig>) -> Self {
+        Self { handler, dynamic }
+    }
+
+    /// The validation limits for `tenant`: the global config, with the
+    /// federation's task-type allowlist applied when it sets one.
+    fn validation(&self, tenant: &str) -> ValidationConfig {
+        let dynamic = self.dynamic.borrow();
+        let mut validation = dynamic.validation.clone();
+        if let Some(federation) = dynamic.federations.get(tenant) {
+            if !federation.allowed_task_types.is_empty() {
+                validation.allowed_task_types = federation.allowed_task_types.clone();
+            }
+        }
+        validation
+    }
+
+    /// The federation (tenant) of the request, with its policy
+    /// enforced.
+    fn tenant<T>(&self, request: &Request<T>) -> Result<String, Status> {
+        federation_from_request(request, &self.dynamic.borrow().federations)
+    }
+
+    fn push_limits(&self, tenant: &str) -> PushLimits {
+        let dynamic = self.dynamic.borrow();
+        let mut limits = PushLimits {
+            max_pending_per_node: dynamic.max_pending_per_node,
+            max_pending_per_run: dynamic.max_pending_per_run,
+            max_run_tasks: dynamic.max_run_tasks,
+            max_run_recordset_bytes: dynamic.max_run_recordset_bytes,
+            max_run_consumers: dynamic.max_run_consumers,
+        };
+        if let Some(federation) = dynamic.federations.get(tenant) {
+            if let Some(value) = federation.max_pending_per_node {
+                limits.max_pending_per_node = value;
+            }
+            if let Some(value) = federation.max_pending_per_run {
+                limits.max_pending_per_run = value;
+            }
+            if let Some(value) = federation.max_run_tasks {
+                limits.max_run_tasks = value;
+            }
+            if let Some(value) = federation.max_run_recordset_bytes {
+                limits.max_run_recordset_bytes = value;
+            }
+            if let Some(value) = federation.max_run_consumers {
+                limits.max_run_consumers = value;
+            }
+        }
+        limits
+    }
+}
+
+#[tonic::async_trait]
+impl Driver for DriverService {
+    async fn create_run(
+        &self,
+        request: Request<CreateRunRequest>,
+    ) -> Result<Response<CreateRunResponse>, Status> {
+        let tenant = self.tenant(&request)?;
+        let run_id = self
+            .handler
+            .create_run(&tenant)
+            .await
+            .map_err(state_err_into_grpc_err)?;
+        Ok(Response::new(CreateRunResponse { run_id: run_id.0 }))
+    }
+
+    async fn delete_run(
+        &self,
+        request: Request<DeleteRunRequest>,
+    ) -> Result<Response<DeleteRunResponse>, Status> {
+        let tenant = self.tenant(&request)?;
+        let request = request.into_inner();
+        self.handler
+            .delete_run(&tenant, RunId(request.run_id))
+            .await
+            .map_err(state_err_into_grpc_err)?;
+        Ok(Response::new(DeleteRunResponse {}))
+    }
+
+    async fn get_nodes(
+        &self,
+        request: Request<GetNodesRequest>,
+    ) -> Result<Response<GetNodesResponse>, Status> {
+        let tenant = self.tenant(&request)?;
+        let request = request.into_inner();
+        let nodes = self
+            .handler
+            .nodes(&tenant, RunId(request.run_id), &request.selector)
+            .await
+            .map_err(state_err_into_grpc_err)?;
+        Ok(Response::new(GetNodesResponse {
+            nodes: nodes.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn sample_nodes(
+        &self,
+        request: Request<SampleNodesRequest>,
+    ) -> Result<Response<SampleNodesResponse>, Status> {
+        let tenant = self.tenant(&request)?;
+        let request = request.into_inner();
+        let seed = (request.seed != 0).then_some(request.seed);
+        let nodes = self
+            .handler
+            .sample_nodes(&tenant, RunId(request.run_id), request.count, seed, &request.selector)
+            .await
+            .map_err(state_err_into_grpc_err)?;
+        Ok(Response::new(SampleNodesResponse {
+            nodes: nodes.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn push_task_ins(
+        &self,
+        request: Request<PushTaskInsRequest>,
+    ) -> Result<Response<PushTaskInsResponse>, Status> {
+        let tenant = self.tenant(&request)?;
+        let request = request.into_inner();
+        if request.task_ins_list.is_empty() {
+            return Err(Status::invalid_argument("task_ins_list must not be empty"));
+        }
+        let instructions = request
+            .task_ins_list
+            .into_iter()
+            .map(|task_ins| TaskIns::try_from((task_ins, &self.validation(&tenant))))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| validation_err_into_grpc_err(err, self.handler.metrics()))?;
+        let task_ids = self
+            .handler
+            .push_task_instructions(&tenant, instructions, &self.push_limits(&tenant))
+            .await
+            .map_err(state_err_into_grpc_err)?;
+        Ok(Response::new(PushTaskInsResponse {
+            task_ids: task_ids.into_iter().map(String::from).collect(),
+        }))
+    }
+
+    async fn broadcast_task_ins(
+        &self,
+        request: Request<BroadcastTaskInsRequest>,
+    ) -> Result<Response<BroadcastTaskInsResponse>, Status> {
+        let tenant = self.tenant(&request)?;
+        let request = request.into_inner();
+        let task_ins = request
+            .task_ins
+            .ok_or_else(|| Status::invalid_argument("task_ins must be set"))?;
+        let template = TaskIns::try_from((task_ins, &self.validation(&tenant)))
+            .map_err(|err| validation_err_into_grpc_err(err, self.handler.metrics()))?;
+        let task_ids = self
+            .handler
+            .broadcast_task_instructions(
+                &tenant,
+                template,
+                &request.selector,
+                &self.push_limits(&tenant),
+            )
+            .await
+            .map_err(state_err_into_grpc_err)?;
+        Ok(Response::new(BroadcastTaskInsResponse {
+            task_ids: task_ids.into_iter().map(String::from).collect(),
+        }))
+    }
+
+    async fn push_task_ins_stream(
+        &self,
+        request: Request<Streaming<TaskInsChunk>>,
+    ) -> Result<Response<PushTaskInsResponse>, Status> {
+        let tenant = self.tenant(&request)?;
+        let mut stream = request.into_inner();
+        let validation = self.validation(&tenant);
+        let task_ins = chunk::assemble_task_ins(&mut stream, &validation).await?;
+        let task_ins = TaskIns::try_from((task_ins, &validation))
+            .map_err(|err| validation_err_into_grpc_err(err, self.handler.metrics()))?;
+        let task_ids = self
+            .handler
+            .push_task_instructions(&tenant, vec![task_ins], &self.push_limits(&tenant))
+            .await
+            .map_err(state_err_into_grpc_err)?;
+        Ok(Response::new(PushTaskInsResponse {
+            task_ids: task_ids.into_iter().map(String::from).collect(),
+        }))
+    }
+
+    type PullTaskResStreamStream =
+        tokio_stream::Iter<std::vec::IntoIter<Result<TaskResChunk, Status>>>;
+
+    async fn pull_task_res_stream(
+        &self,
+        request: Request<PullTaskResRequest>,
+    ) -> Result<Response<Self::PullTaskResStreamStream>, Status> {
+        let tenant = self.tenant(&request)?;
+        let request = request.into_inner();
+        super::validate_task_ids(&request.task_ids)?;
+        let task_ids: Vec<TaskId> = request.task_ids.into_iter().map(TaskId).collect();
+        let results = self
+            .handler
+            .pull_task_results(&tenant, &task_ids, request.keep)
+            .await
+            .map_err(state_err_into_grpc_err)?;
+        let task_res_list: Vec<crate::pb::TaskRes> = results
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| {
+                tracing::error!(error = %err, "stored task failed to convert");
+                Status::internal("internal error")
+            })?;
+        let chunks: Vec<Result<TaskResChunk, Status>> = task_res_list
+            .into_iter()
+            .flat_map(chunk::chunk_task_res)
+            .map(Ok)
+            .collect();
+        Ok(Response::new(tokio_stream::iter(chunks)))
+    }
+
+    async fn pull_task_res(
+        &self,
+        request: Request<PullTaskResRequest>,
+    ) -> Result<Response<PullTaskResResponse>, Status> {
+        let tenant = self.tenant(&request)?;
+        let request = request.into_inner();
+        super::validate_task_ids(&request.task_ids)?;
+        let task_ids: Vec<TaskId> = request.task_ids.into_iter().map(TaskId).collect();
+        let results = self
+            .handler
+            .pull_task_results(&tenant, &task_ids, request.keep)
+            .await
+            .map_err(state_err_into_grpc_err)?;
+        let task_res_list = results
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| {
+                tracing::error!(error = %err, "stored task failed to convert");
+                Status::internal("internal error")
+            })?;
+        Ok(Response::new(PullTaskResResponse { task_res_list }))
+    }
+
+    async fn acknowledge_task_res(
+        &self,
+        request: Request<AcknowledgeTaskResRequest>,
+    ) -> Result<Response<AcknowledgeTaskResResponse>, Status> {
+        let tenant = self.tenant(&request)?;
+        let request = request.into_inner();
+        super::validate_task_ids(&request.task_ids)?;
+        let task_ids: Vec<TaskId> = request.task_ids.into_iter().map(TaskId).collect();
+        self.handler
+            .acknowledge_task_results(&tenant, &task_ids)
+            .await
+            .map_err(state_err_into_grpc_err)?;
+        Ok(Response::new(AcknowledgeTaskResResponse {}))
+    }
+}
@@ -0,0 +1,349 @@
+//! Fleet API gRPC service.
+
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::handler::FleetHandler;
+use crate::model::handler::{NodeId, TaskId, TaskRes};
+use crate::pb::fleet_server::Fleet;
+use crate::pb::{
+    CreateNodeRequest, CreateNodeResponse, CreateNodesRequest, CreateNodesResponse,
+    DeleteNodeRequest, DeleteNodeResponse, DeleteNodesRequest, DeleteNodesResponse,
+    GetServerInfoRequest, GetServerInfoResponse, NackTaskInsRequest, NackTaskInsResponse,
+    PingBatchRequest, PingBatchResponse, PingRequest, PingResponse, PullTaskInsRequest,
+    PullTaskInsResponse, PushTaskResRequest, PushTaskResResponse, Reconnect, TaskInsChunk,
+    TaskResChunk,
+};
+
+use tokio::sync::watch;
+
+use crate::config::DynamicConfig;
+
+use super::validate::{validation_err_into_grpc_err, ValidationConfig};
+use super::{
+    check_api_version, chunk, client_version_from_request, federation_from_request,
+    node_identity_from_request, state_err_into_grpc_err, API_VERSION,
+};
+
+pub struct FleetService {
+    handler: FleetHandler,
+    dynamic: watch::Receiver<DynamicConfig>,
+}
+
+impl FleetService {
+    pub fn new(handler: FleetHandler, dynamic: watch::Receiver<DynamicConfig>) -> Self {
+        Self { handler, dynamic }
+    }
+
+    /// The validation limits for `tenant`: the global config, with the
+    /// federation's task-type allowlist applied when it sets one.
+    fn validation(&self, tenant: &str) -> ValidationConfig {
+        let dynamic = self.dynamic.borrow();
+        let mut validation = dynamic.validation.clone();
+        if let Some(federation) = dynamic.federations.get(tenant) {
+            if !federation.allowed_task_types.is_empty() {
+                validation.allowed_task_types = federation.allowed_task_types.clone();
+            }
+        }
+        validation
+    }
+
+    fn min_api_version(&self) -> u32 {
+        self.dynamic.borrow().min_api_version
+    }
+
+    /// The federation (tenant) of the request, with its policy
+    /// enforced.
+    fn tenant<T>(&self, request: &Request<T>) -> Result<String, Status> {
+        federation_from_request(request, &self.dynamic.borrow().federations)
+    }
+}
+
+#[tonic::async_trait]
+impl Fleet for FleetService {
+    async fn create_node(
+        &self,
+        request: Request<CreateNodeRequest>,
+    ) -> Result<Response<CreateNodeResponse>, Status> {
+        check_api_version(&request, self.min_api_version())?;
+        let tenant = self.tenant(&request)?;
+        let client_version = client_version_from_request(&request);
+        let identity = node_identity_from_request(&request);
+        let request = request.into_inner();
+        if request.prior_node_id != 0 {
+            let reconnected = self
+                .handler
+                .reconnect_node(
+                    &tenant,
+                    NodeId(request.prior_node_id),
+                    request.ping_interval,
+                    &request.properties,
+                    &request.task_types,
+                    &client_version,
+                    &identity,
+                )
+                .await
+                .map_err(state_err_into_grpc_err)?;
+            if let Some(node) = reconnected {
+                return Ok(Response::new(CreateNodeResponse {
+                    node: Some(node.into()),
+                }));
+            }
+        }
+        let node = self
+            .handler
+            .create_node(
+                &tenant,
+                request.ping_interval,
+                &request.properties,
+                &request.task_types,
+                &client_version,
+                &identity,
+            )
+            .await
+            .map_err(state_err_into_grpc_err)?;
+        Ok(Response::new(CreateNodeResponse {
+            node: Some(node.into()),
+        }))
+    }
+
+    async fn create_nodes(
+        &self,
+        request: Request<CreateNodesRequest>,
+    ) -> Result<Response<CreateNodesResponse>, Status> {
+        check_api_version(&request, self.min_api_version())?;
+        let tenant = self.tenant(&request)?;
+        let request = request.into_inner();
+        let nodes = self
+            .handler
+            .create_nodes(
+                &tenant,
+                request.count,
+                request.ping_interval,
+                &request.properties,
+                &request.task_types,
+            )
+            .await
+            .map_err(state_err_into_grpc_err)?;
+        Ok(Response::new(CreateNodesResponse {
+            nodes: nodes.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn delete_nodes(
+        &self,
+        request: Request<DeleteNodesRequest>,
+    ) -> Result<Response<DeleteNodesResponse>, Status> {
+        let tenant = self.tenant(&request)?;
+        let request = request.into_inner();
+        let node_ids: Vec<NodeId> = request.node_ids.iter().copied().map(NodeId).collect();
+        self.handler
+            .delete_nodes(&tenant, &node_ids)
+            .await
+            .map_err(state_err_into_grpc_err)?;
+        Ok(Response::new(DeleteNodesResponse {}))
+    }
+
+    async fn delete_node(
+        &self,
+        request: Request<DeleteNodeRequest>,
+    ) -> Result<Response<DeleteNodeResponse>, Status> {
+        let tenant = self.tenant(&request)?;
+        let identity = node_identity_from_request(&request);
+        let request = request.into_inner();
+        let node = request
+            .node
+            .ok_or_else(|| Status::invalid_argument("node must be set"))?;
+        self.handler
+            .delete_node(&tenant, &node.into(), &identity)
+            .await
+            .map_err(state_err_into_grpc_err)?;
+        Ok(Response::new(DeleteNodeResponse {}))
+    }
+
+    async fn ping(&self, request: Request<PingRequest>) -> Result<Response<PingResponse>, Status> {
+        check_api_version(&request, self.min_api_version())?;
+        let tenant = self.tenant(&request)?;
+        let client_version = client_version_from_request(&request);
+        let identity = node_identity_from_request(&request);
+        let request = request.into_inner();
+        let node = request
+            .node
+            .ok_or_else(|| Status::invalid_argument("node must be set"))?;
+        let success = self
+            .handler
+            .ping(
+                &tenant,
+                &node.into(),
+                request.ping_interval,
+                &request.task_types,
+                &client_version,
+                &identity,
+            )
+            .await
+            .map_err(state_err_into_grpc_err)?;
+        Ok(Response::new(PingResponse { success }))
+    }
+
+    async fn ping_batch(
+        &self,
+        request: Request<PingBatchRequest>,
+    ) -> Result<Response<PingBatchResponse>, Status> {
+        check_api_version(&request, self.min_api_version())?;
+        let tenant = self.tenant(&request)?;
+        let request = request.into_inner();
+        if request.pings.is_empty() {
+            return Err(Status::invalid_argument("pings must not be empty"));
+        }
+        let pings: Vec<(NodeId, f64)> = request
+            .pings
+            .iter()
+            .map(|ping| (NodeId(ping.node_id), ping.ping_interval))
+            .collect();
+        let refreshed = self
+            .handler
+            .ping_batch(&tenant, &pings)
+            .await
+            .map_err(state_err_into_grpc_err)?;
+        Ok(Response::new(PingBatchResponse { refreshed }))
+    }
+
+    async fn pull_task_ins(
+        &self,
+        request: Request<PullTaskInsRequest>,
+    ) -> Result<Response<PullTaskInsResponse>, Status> {
+        let tenant = self.tenant(&request)?;
+        let identity = node_identity_from_request(&request);
+        let request = request.into_inner();
+        let node = request
+            .node
+            .ok_or_else(|| Status::invalid_argument("node must be set"))?;
+        let limit = self.dynamic.borrow().pull_task_ins_limit;
+        let instructions = self
+            .handler
+            .pull_task_instructions(&tenant, &node.into(), Some(limit), &identity)
+            .await
+            .map_err(state_err_into_grpc_err)?;
+        let task_ins_list = instructions
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| {
+                tracing::error!(error = %err, "stored task failed to convert");
+                Status::internal("internal error")
+            })?;
+        Ok(Response::new(PullTaskInsResponse {
+            reconnect: Some(Reconnect { reconnect: 0 }),
+            task_ins_list,
+        }))
+    }
+
+    type PullTaskInsStreamStream =
+        tokio_stream::Iter<std::vec::IntoIter<Result<TaskInsChunk, Status>>>;
+
+    async fn pull_task_ins_stream(
+        &self,
+        request: Request<PullTaskInsRequest>,
+    ) -> Result<Response<Self::PullTaskInsStreamStream>, Status> {
+        let tenant = self.tenant(&request)?;
+        let identity = node_identity_from_request(&request);
+        let request = request.into_inner();
+        let node = request
+            .node
+            .ok_or_else(|| Status::invalid_argument("node must be set"))?;
+        let limit = self.dynamic.borrow().pull_task_ins_limit;
+        let instructions = self
+            .handler
+            .pull_task_instructions(&tenant, &node.into(), Some(limit), &identity)
+            .await
+            .map_err(state_err_into_grpc_err)?;
+        let task_ins_list: Vec<crate::pb::TaskIns> = instructions
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| {
+                tracing::error!(error = %err, "stored task failed to convert");
+                Status::internal("internal error")
+            })?;
+        let chunks: Vec<Result<TaskInsChunk, Status>> = task_ins_list
+            .into_iter()
+            .flat_map(chunk::chunk_task_ins)
+            .map(Ok)
+            .collect();
+        Ok(Response::new(tokio_stream::iter(chunks)))
+    }
+
+    async fn nack_task_ins(
+        &self,
+        request: Request<NackTaskInsRequest>,
+    ) -> Result<Response<NackTaskInsResponse>, Status> {
+        let tenant = self.tenant(&request)?;
+        let identity = node_identity_from_request(&request);
+        let request = request.into_inner();
+        let node = request
+            .node
+            .ok_or_else(|| Status::invalid_argument("node must be set"))?;
+        super::validate_task_ids(&request.task_ids)?;
+        let task_ids: Vec<TaskId> = request.task_ids.into_iter().map(TaskId).collect();
+        self.handler
+            .nack_task_instructions(&tenant, &node.into(), &task_ids, &identity)
+            .await
+            .map_err(state_err_into_grpc_err)?;
+        Ok(Response::new(NackTaskInsResponse {}))
+    }
+
+    async fn push_task_res_stream(
+        &self,
+        request: Request<Streaming<TaskResChunk>>,
+    ) -> Result<Response<PushTaskResResponse>, Status> {
+        let tenant = self.tenant(&request)?;
+        let identity = node_identity_from_request(&request);
+        let mut stream = request.into_inner();
+        let validation = self.validation(&tenant);
+        let task_res = chunk::assemble_task_res(&mut stream, &validation).await?;
+        let task_res = TaskRes::try_from((task_res, &validation))
+            .map_err(|err| validation_err_into_grpc_err(err, self.handler.metrics()))?;
+        let task_id = self
+            .handler
+            .push_task_result(&tenant, task_res, &identity)
+            .await
+            .map_err(state_err_into_grpc_err)?;
+        Ok(Response::new(PushTaskResResponse {
+            reconnect: Some(Reconnect { reconnect: 0 }),
+            results: [(task_id.0, 0)].into_iter().collect(),
+        }))
+    }
+
+    async fn push_task_res(
+        &self,
+        request: Request<PushTaskResRequest>,
+    ) -> Result<Response<PushTaskResResponse>, Status> {
+        let tenant = self.tenant(&request)?;
+        let identity = node_identity_from_request(&request);
+        let request = request.into_inner();
+        let [task_res]: [crate::pb::TaskRes; 1] = request
+            .task_res_list
+            .try_into()
+            .map_err(|_| Status::invalid_argument("exactly one task_res must be pushed"))?;
+        let task_res = TaskRes::try_from((task_res, &self.validation(&tenant)))
+            .map_err(|err| validation_err_into_grpc_err(err, self.handler.metrics()))?;
+        let task_id = self
+            .handler
+            .push_task_result(&tenant, task_res, &identity)
+            .await
+            .map_err(state_err_into_grpc_err)?;
+        Ok(Response::new(PushTaskResResponse {
+            reconnect: Some(Reconnect { reconnect: 0 }),
+            results: [(task_id.0, 0)].into_iter().collect(),
+        }))
+    }
+
+    async fn get_server_info(
+        &self,
+        _request: Request<GetServerInfoRequest>,
+    ) -> Result<Response<GetServerInfoResponse>, Status> {
+        Ok(Response::new(GetServerInfoResponse {
+            api_version: API_VERSION,
+            min_api_version: self.min_api_version(),
+        }))
+    }
+}
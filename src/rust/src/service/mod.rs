@@ -0,0 +1,311 @@
+//! gRPC service implementations wiring the generated server traits to
+//! the handlers.
+
+pub mod admin;
+pub(crate) mod chunk;
+pub mod convertion;
+pub mod driver;
+pub mod fleet;
+pub mod validate;
+
+pub use admin::AdminService;
+pub use driver::DriverService;
+pub use fleet::FleetService;
+
+use std::time::Duration;
+
+use rand::Rng;
+use tonic_types::{ErrorDetails, StatusExt};
+
+use crate::state;
+
+/// Metadata key carrying the optional tenant identifier. Deployments
+/// running a single federation can omit it.
+pub const TENANT_METADATA_KEY: &str = "x-flwr-tenant";
+
+/// Protocol version this server speaks, advertised via GetServerInfo.
+pub const API_VERSION: u32 = 1;
+
+/// Metadata key carrying the client's spoken protocol version.
+pub const API_VERSION_METADATA_KEY: &str = "x-flwr-api-version";
+
+/// Reject requests whose protocol version is below `minimum`; requests
+/// without the header count as version 0 (predating negotiation).
+pub(crate) fn check_api_version<T>(
+    request: &tonic::Request<T>,
+    minimum: u32,
+) -> Result<(), tonic::Status> {
+    if minimum == 0 {
+        return Ok(());
+    }
+    let version: u32 = match request.metadata().get(API_VERSION_METADATA_KEY) {
+        Some(value) => value
+            .to_str()
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| {
+                tonic::Status::invalid_argument("x-flwr-api-version must be an unsigned integer")
+            })?,
+        None => 0,
+    };
+    if version < minimum {
+        return Err(tonic::Status::failed_precondition(format!(
+            "protocol version {version} is no longer accepted (minimum {minimum}); \
+             upgrade your Flower client to one speaking version {minimum} or newer"
+        )));
+    }
+    Ok(())
+}
+
+/// Metadata key carrying the Flower client version; requests without
+/// it fall back to the standard gRPC user-agent.
+pub const CLIENT_VERSION_METADATA_KEY: &str = "x-flwr-client-version";
+
+/// The client version reported by the request, or empty when absent.
+pub(crate) fn client_version_from_request<T>(request: &tonic::Request<T>) -> String {
+    for key in [CLIENT_VERSION_METADATA_KEY, "user-agent"] {
+        if let Some(value) = request.metadata().get(key) {
+            if let Ok(value) = value.to_str() {
+                return value.to_owned();
+            }
+        }
+    }
+    String::new()
+}
+
+/// Metadata key carrying the node's auth identity (public key or
+/// certificate fingerprint); the scrubbing middleware redacts it from
+/// logs.
+pub const NODE_KEY_METADATA_KEY: &str = "x-flwr-node-key";
+
+/// The node identity presented by the request, or empty when absent.
+pub(crate) fn node_identity_from_request<T>(request: &tonic::Request<T>) -> String {
+    request
+        .metadata()
+        .get(NODE_KEY_METADATA_KEY)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned)
+        .unwrap_or_default()
+}
+
+/// Extract the tenant from request metadata; absent means the default
+/// (empty) tenant.
+pub(crate) fn tenant_from_request<T>(
+    request: &tonic::Request<T>,
+) -> Result<String, tonic::Status> {
+    match request.metadata().get(TENANT_METADATA_KEY) {
+        Some(value) => value
+            .to_str()
+            .map(str::to_owned)
+            .map_err(|_| tonic::Status::invalid_argument("tenant must be valid ASCII")),
+        None => Ok(String::new()),
+    }
+}
+
+/// Resolve the tenant and enforce its federation policy.
+///
+/// With no federations configured this is [`tenant_from_request`].
+/// Otherwise the tenant must name a configured federation, and a
+/// federation carrying an auth token requires a matching
+/// `authorization: Bearer <token>` metadata entry.
+pub(crate) fn federation_from_request<T>(
+    request: &tonic::Request<T>,
+    federations: &std::collections::HashMap<String, crate::config::Federation>,
+) -> Result<String, tonic::Status> {
+    let tenant = tenant_from_request(request)?;
+    if federations.is_empty() {
+        return Ok(tenant);
+    }
+    let Some(federation) = federations.get(&tenant) else {
+        return Err(tonic::Status::permission_denied(format!(
+            "unknown federation {tenant:?}"
+        )));
+    };
+    if !federation.auth_token.is_empty() {
+        let presented = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        if presented != Some(federation.auth_token.as_str()) {
+            return Err(tonic::Status::unauthenticated(
+                "missing or invalid federation credentials",
+            ));
+        }
+    }
+    Ok(tenant)
+}
+
+/// Reject task ids that are not UUIDs before they reach the state
+/// layer, which stores ids in native uuid columns.
+pub(crate) fn validate_task_ids(task_ids: &[String]) -> Result<(), tonic::Status> {
+    for task_id in task_ids {
+        if uuid::Uuid::parse_str(task_id).is_err() {
+            return Err(tonic::Status::invalid_argument(format!(
+                "task id {task_id:?} is not a valid UUID"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Base backoff suggested on UNAVAILABLE rejections; matches the
+/// circuit breaker's cool-down so a retry can hit the probe window.
+const UNAVAILABLE_RETRY: Duration = Duration::from_secs(5);
+
+/// Base backoff suggested on RESOURCE_EXHAUSTED rejections; pending
+/// caps only drain once the consumer pulls, so retries can be spaced
+/// further apart.
+const EXHAUSTED_RETRY: Duration = Duration::from_secs(15);
+
+/// A status carrying a `RetryInfo` detail, so well-behaved clients
+/// space out retries during overload. The delay is jittered between
+/// 50% and 150% of `base` to keep rejected clients from retrying in
+/// lockstep.
+fn retry_later(code: tonic::Code, message: String, base: Duration) -> tonic::Status {
+    let delay = base.mul_f64(rand::thread_rng().gen_range(0.5..1.5));
+    tonic::Status::with_error_details(code, message, ErrorDetails::with_retry_info(Some(delay)))
+}
+
+/// Map a state error onto a gRPC status.
+pub(crate) fn state_err_into_grpc_err(err: state::Error) -> tonic::Status {
+    match err {
+        state::Error::UnknownRun(run_id) => {
+            tonic::Status::invalid_argument(format!("run {run_id} does not exist"))
+        }
+        state::Error::Connection(_) => retry_later(
+            tonic::Code::Unavailable,
+            "database unavailable".to_owned(),
+            UNAVAILABLE_RETRY,
+        ),
+        state::Error::Blob(_) => {
+            tracing::error!(error = %err, "blob storage access failed");
+            retry_later(
+                tonic::Code::Unavailable,
+                "blob storage unavailable".to_owned(),
+                UNAVAILABLE_RETRY,
+            )
+        }
+        state::Error::NodeBanned(_) => tonic::Status::permission_denied(err.to_string()),
+        state::Error::NotNodeOwner(_) => tonic::Status::permission_denied(err.to_string()),
+        state::Error::AncestorMismatch { .. } => {
+            tonic::Status::invalid_argument(err.to_string())
+        }
+        state::Error::Timeout { .. } => tonic::Status::deadline_exceeded(err.to_string()),
+        state::Error::CircuitOpen => {
+            retry_later(tonic::Code::Unavailable, err.to_string(), UNAVAILABLE_RETRY)
+        }
+        state::Error::PendingTaskLimit { .. } => {
+            retry_later(tonic::Code::ResourceExhausted, err.to_string(), EXHAUSTED_RETRY)
+        }
+        state::Error::RunTaskLimit { .. } => {
+            retry_later(tonic::Code::ResourceExhausted, err.to_string(), EXHAUSTED_RETRY)
+        }
+        // Quotas count stored tasks, which do not drain on their own,
+        // so no RetryInfo: retrying does not help.
+        state::Error::RunQuota { .. } => tonic::Status::resource_exhausted(err.to_string()),
+        state::Error::Query(_) => {
+            tracing::error!(error = %err, "state query failed");
+            tonic::Status::internal("internal error")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::model::handler::{NodeId, RunId};
+
+    #[test]
+    fn overload_rejections_carry_retry_info() {
+        let status = state_err_into_grpc_err(state::Error::CircuitOpen);
+        assert_eq!(status.code(), tonic::Code::Unavailable);
+        let retry = status.get_details_retry_info().unwrap();
+        let delay = retry.retry_delay.unwrap();
+        assert!(delay >= UNAVAILABLE_RETRY.mul_f64(0.5));
+        assert!(delay <= UNAVAILABLE_RETRY.mul_f64(1.5));
+
+        let status = state_err_into_grpc_err(state::Error::PendingTaskLimit {
+            node_id: NodeId(7),
+            pending: 10,
+            limit: 10,
+        });
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        assert!(status.get_details_retry_info().is_some());
+    }
+
+    #[test]
+    fn old_protocol_versions_are_rejected() {
+        let mut request = tonic::Request::new(());
+        request
+            .metadata_mut()
+            .insert(API_VERSION_METADATA_KEY, "1".parse().unwrap());
+        assert!(check_api_version(&request, 1).is_ok());
+        assert!(check_api_version(&request, 2).is_err());
+        let status = check_api_version(&request, 2).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+        assert!(status.message().contains("upgrade"));
+
+        // No header counts as version 0 and passes only when no
+        // minimum is enforced.
+        let request = tonic::Request::new(());
+        assert!(check_api_version(&request, 0).is_ok());
+        assert!(check_api_version(&request, 1).is_err());
+    }
+
+    #[test]
+    fn federations_gate_tenants_and_credentials() {
+        let mut federations = std::collections::HashMap::new();
+        federations.insert(
+            "acme".to_owned(),
+            crate::config::Federation {
+                auth_token: "secret".to_owned(),
+                ..Default::default()
+            },
+        );
+
+        // Without federations any tenant passes through.
+        let request = tonic::Request::new(());
+        assert_eq!(
+            federation_from_request(&request, &HashMap::new()).unwrap(),
+            ""
+        );
+
+        // An unconfigured federation is rejected.
+        let status = federation_from_request(&request, &federations).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+
+        // The right federation still needs the right token.
+        let mut request = tonic::Request::new(());
+        request
+            .metadata_mut()
+            .insert(TENANT_METADATA_KEY, "acme".parse().unwrap());
+        let status = federation_from_request(&request, &federations).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+        request
+            .metadata_mut()
+            .insert("authorization", "Bearer secret".parse().unwrap());
+        assert_eq!(
+            federation_from_request(&request, &federations).unwrap(),
+            "acme"
+        );
+    }
+
+    #[test]
+    fn client_errors_carry_no_retry_info() {
+        let status = state_err_into_grpc_err(state::Error::UnknownRun(RunId(42)));
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert!(status.get_details_retry_info().is_none());
+
+        let status = state_err_into_grpc_err(state::Error::RunQuota {
+            run_id: RunId(42),
+            resource: "task",
+            used: 100,
+            limit: 100,
+        });
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        assert!(status.get_details_retry_info().is_none());
+    }
+}
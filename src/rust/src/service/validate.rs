@@ -0,0 +1,298 @@
+//! Field-level validation rules for client-pushed tasks.
+//!
+//! The conversion layer in [`super::convertion`] builds domain values
+//! out of wire messages and calls into these helpers; every rule about
+//! what a client may send lives here, shared by the Driver and Fleet
+//! push paths so the two cannot diverge.
+
+use std::collections::HashMap;
+
+use chrono::Utc;
+use tonic_types::{ErrorDetails, FieldViolation, StatusExt};
+
+use crate::middleware::metrics::TaskMetrics;
+use crate::model::handler::{Node, NodeId};
+use crate::pb;
+
+/// Limits applied while validating incoming tasks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationConfig {
+    /// Maximum number of seconds `created_at` may lie in the past.
+    pub message_expires_after: f64,
+    /// Maximum serialized recordset size in bytes; 0 disables the
+    /// limit.
+    pub max_recordset_size: usize,
+    /// Per-task-type overrides of `max_recordset_size`.
+    pub max_recordset_sizes: HashMap<String, usize>,
+    /// Task types accepted on push; empty accepts all.
+    pub allowed_task_types: Vec<String>,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        Self {
+            message_expires_after: 3600.0,
+            max_recordset_size: 0,
+            max_recordset_sizes: HashMap::new(),
+            allowed_task_types: Vec::new(),
+        }
+    }
+}
+
+/// Longest accepted client-supplied identifier (`group_id`,
+/// `task_type`) in bytes, so arbitrary strings cannot blow up indexes
+/// or logs.
+const MAX_IDENTIFIER_LEN: usize = 64;
+
+/// Most ancestor ids a single `TaskRes` may reference.
+const MAX_ANCESTRY_LEN: usize = 8;
+
+/// Accumulated field violations for one request.
+#[derive(Debug, Default)]
+pub struct ValidationError {
+    violations: Vec<FieldViolation>,
+}
+
+impl ValidationError {
+    pub fn push(&mut self, field: &str, description: &str) {
+        self.violations.push(FieldViolation::new(field, description));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    pub fn violations(&self) -> &[FieldViolation] {
+        &self.violations
+    }
+}
+
+/// Map accumulated violations onto an INVALID_ARGUMENT status carrying
+/// `BadRequest` error details, counting each violated field path so
+/// operators can spot misbehaving clients.
+pub fn validation_err_into_grpc_err(
+    err: ValidationError,
+    metrics: Option<&TaskMetrics>,
+) -> tonic::Status {
+    if let Some(metrics) = metrics {
+        for violation in &err.violations {
+            metrics.validation_failure(&violation.field);
+        }
+    }
+    tonic::Status::with_error_details(
+        tonic::Code::InvalidArgument,
+        "request validation failed",
+        ErrorDetails::with_bad_request(err.violations),
+    )
+}
+
+/// Whether a task arrives as an instruction or a result; some rules
+/// apply to only one direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Kind {
+    Ins,
+    Res,
+}
+
+pub(crate) fn now_secs() -> f64 {
+    Utc::now().timestamp_micros() as f64 / 1e6
+}
+
+/// Check a client-supplied identifier: printable ASCII without
+/// whitespace, at most `MAX_IDENTIFIER_LEN` bytes. Empty values are
+/// accepted; fields that require one check that separately.
+pub(crate) fn check_identifier(value: &str, field: &str, err: &mut ValidationError) {
+    if value.len() > MAX_IDENTIFIER_LEN {
+        err.push(field, &format!("must be at most {MAX_IDENTIFIER_LEN} bytes"));
+    }
+    if !value.chars().all(|c| c.is_ascii_graphic()) {
+        err.push(field, "must be printable ASCII without whitespace");
+    }
+}
+
+/// Reject server-stamped timestamp fields the client set itself.
+pub(crate) fn check_client_timestamps(
+    delivered_at: &str,
+    pushed_at: f64,
+    err: &mut ValidationError,
+) {
+    if !delivered_at.is_empty() {
+        err.push("task.delivered_at", "must not be set by the client");
+    }
+    if pushed_at != 0.0 {
+        err.push("task.pushed_at", "must not be set by the client");
+    }
+}
+
+/// Check `created_at`: required, and at most `expires_after` seconds in
+/// the past.
+pub(crate) fn check_created_at(created_at: f64, expires_after: f64, err: &mut ValidationError) {
+    if created_at <= 0.0 {
+        err.push("task.created_at", "must be set");
+    } else if now_secs() - created_at > expires_after {
+        err.push("task.created_at", "message expired");
+    }
+}
+
+/// Check the task type: required, a well-formed identifier, and on the
+/// allowlist when one is configured.
+pub(crate) fn check_task_type(task_type: &str, allowed: &[String], err: &mut ValidationError) {
+    check_identifier(task_type, "task.task_type", err);
+    if task_type.is_empty() {
+        err.push("task.task_type", "must be set");
+    } else if !allowed.is_empty() && !allowed.iter().any(|accepted| accepted == task_type) {
+        err.push("task.task_type", "is not an accepted task type");
+    }
+}
+
+/// Check the ttl: empty, or a finite non-negative number of seconds.
+pub(crate) fn check_ttl(ttl: &str, err: &mut ValidationError) {
+    if ttl.is_empty() {
+        return;
+    }
+    match ttl.parse::<f64>() {
+        Ok(value) if value.is_finite() && value >= 0.0 => {}
+        _ => err.push("task.ttl", "must be a non-negative number of seconds"),
+    }
+}
+
+/// Check the ancestry: empty for instructions; for results a bounded,
+/// non-empty list of well-formed task ids.
+pub(crate) fn check_ancestry(ancestry: &[String], kind: Kind, err: &mut ValidationError) {
+    match kind {
+        Kind::Ins => {
+            if !ancestry.is_empty() {
+                err.push("task.ancestry", "must be empty for TaskIns");
+            }
+        }
+        Kind::Res => {
+            if ancestry.is_empty() {
+                err.push("task.ancestry", "must be set for TaskRes");
+            } else if ancestry.len() > MAX_ANCESTRY_LEN {
+                err.push(
+                    "task.ancestry",
+                    &format!("must reference at most {MAX_ANCESTRY_LEN} ancestors"),
+                );
+            }
+            // Task ids are native uuids in storage; reject malformed
+            // ancestors before they silently match nothing.
+            if ancestry.iter().any(|id| uuid::Uuid::parse_str(id).is_err()) {
+                err.push("task.ancestry", "entries must be valid task id UUIDs");
+            }
+        }
+    }
+}
+
+/// Check a producer or consumer node: anonymous nodes carry no id,
+/// registered ones must; a missing node defaults to anonymous.
+pub(crate) fn validate_node(
+    node: Option<pb::Node>,
+    field: &str,
+    err: &mut ValidationError,
+) -> Node {
+    let node = node.map(Node::from).unwrap_or(Node {
+        id: NodeId(0),
+        anonymous: true,
+    });
+    if node.anonymous && node.id != NodeId(0) {
+        err.push(field, "anonymous nodes must not set node_id");
+    }
+    if !node.anonymous && node.id == NodeId(0) {
+        err.push(field, "registered nodes must set node_id");
+    }
+    node
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(err: &ValidationError) -> Vec<&str> {
+        err.violations()
+            .iter()
+            .map(|violation| violation.field.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn created_at_must_lie_within_the_window() {
+        let mut err = ValidationError::default();
+        check_created_at(now_secs(), 3600.0, &mut err);
+        assert!(err.is_empty());
+        check_created_at(0.0, 3600.0, &mut err);
+        check_created_at(now_secs() - 7200.0, 3600.0, &mut err);
+        assert_eq!(fields(&err), ["task.created_at", "task.created_at"]);
+    }
+
+    #[test]
+    fn ttl_must_be_a_non_negative_number() {
+        let mut err = ValidationError::default();
+        check_ttl("", &mut err);
+        check_ttl("3600", &mut err);
+        check_ttl("0.5", &mut err);
+        assert!(err.is_empty());
+        check_ttl("-1", &mut err);
+        check_ttl("inf", &mut err);
+        check_ttl("soon", &mut err);
+        assert_eq!(fields(&err), ["task.ttl", "task.ttl", "task.ttl"]);
+    }
+
+    #[test]
+    fn task_type_must_be_set_and_allowed() {
+        let mut err = ValidationError::default();
+        check_task_type("train", &[], &mut err);
+        check_task_type("train", &["train".to_owned()], &mut err);
+        assert!(err.is_empty());
+        check_task_type("", &[], &mut err);
+        check_task_type("evaluate", &["train".to_owned()], &mut err);
+        assert_eq!(fields(&err), ["task.task_type", "task.task_type"]);
+    }
+
+    #[test]
+    fn ancestry_rules_depend_on_the_direction() {
+        let parent = uuid::Uuid::nil().to_string();
+        let mut err = ValidationError::default();
+        check_ancestry(&[], Kind::Ins, &mut err);
+        check_ancestry(std::slice::from_ref(&parent), Kind::Res, &mut err);
+        assert!(err.is_empty());
+        check_ancestry(std::slice::from_ref(&parent), Kind::Ins, &mut err);
+        check_ancestry(&[], Kind::Res, &mut err);
+        check_ancestry(&["parent".to_owned()], Kind::Res, &mut err);
+        let crowded = vec![parent; 9];
+        check_ancestry(&crowded, Kind::Res, &mut err);
+        assert_eq!(fields(&err).len(), 4);
+    }
+
+    #[test]
+    fn node_id_must_match_the_anonymous_flag() {
+        let mut err = ValidationError::default();
+        let node = validate_node(None, "task.producer", &mut err);
+        assert!(node.anonymous);
+        validate_node(
+            Some(pb::Node {
+                node_id: 1,
+                anonymous: false,
+            }),
+            "task.producer",
+            &mut err,
+        );
+        assert!(err.is_empty());
+        validate_node(
+            Some(pb::Node {
+                node_id: 1,
+                anonymous: true,
+            }),
+            "task.producer",
+            &mut err,
+        );
+        validate_node(
+            Some(pb::Node {
+                node_id: 0,
+                anonymous: false,
+            }),
+            "task.producer",
+            &mut err,
+        );
+        assert_eq!(fields(&err), ["task.producer", "task.producer"]);
+    }
+}
@@ -0,0 +1,119 @@
+//! Virtual node pool for simulation mode.
+//!
+//! In simulation mode the SuperLink pre-provisions a pool of node
+//! records itself instead of waiting for real SuperNodes to register.
+//! An embedded simulation engine (or external actors) can then pull
+//! instructions and push results under those ids, while a single
+//! keepalive task refreshes every ping in one bulk update so the nodes
+//! never appear offline to `sample_nodes`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::model::handler::NodeId;
+use crate::state::{Result, State};
+
+/// A pool of pre-provisioned virtual nodes backed by `State`.
+pub struct VirtualPool {
+    state: Arc<dyn State>,
+    tenant: String,
+    node_ids: Vec<NodeId>,
+    ping_interval: f64,
+}
+
+impl VirtualPool {
+    /// Provision `count` virtual nodes for `tenant` in one call.
+    pub async fn provision(
+        state: Arc<dyn State>,
+        tenant: &str,
+        count: u32,
+        ping_interval: f64,
+        properties: &HashMap<String, String>,
+        task_types: &[String],
+    ) -> Result<Self> {
+        let node_ids = state
+            .create_nodes(tenant, count, ping_interval, properties, task_types)
+            .await?;
+        Ok(Self {
+            state,
+            tenant: tenant.to_owned(),
+            node_ids,
+            ping_interval,
+        })
+    }
+
+    /// Ids of the provisioned nodes, for handing out to actors.
+    pub fn node_ids(&self) -> &[NodeId] {
+        &self.node_ids
+    }
+
+    /// Refresh the ping of every node in the pool in one bulk update.
+    pub async fn refresh(&self) -> Result<u64> {
+        let pings: Vec<(NodeId, f64)> = self
+            .node_ids
+            .iter()
+            .map(|&node_id| (node_id, self.ping_interval))
+            .collect();
+        self.state.update_pings(&self.tenant, &pings).await
+    }
+
+    /// Spawn a background task that refreshes the pool at half the
+    /// ping interval, keeping the nodes online with margin to spare.
+    pub fn spawn_keepalive(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        let period = Duration::from_secs_f64((self.ping_interval / 2.0).max(1.0));
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                match self.refresh().await {
+                    Ok(refreshed) if refreshed < self.node_ids.len() as u64 => {
+                        tracing::warn!(
+                            refreshed,
+                            pool = self.node_ids.len(),
+                            "some virtual nodes are missing"
+                        );
+                    }
+                    Ok(_) => {}
+                    Err(err) => tracing::warn!(error = %err, "virtual pool keepalive failed"),
+                }
+            }
+        })
+    }
+
+    /// Delete the pool's nodes, dead-lettering their unanswered tasks.
+    pub async fn release(&self) -> Result<()> {
+        self.state.delete_nodes(&self.tenant, &self.node_ids).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::handler::RunId;
+    use crate::state::memory::Memory;
+
+    #[tokio::test]
+    async fn provisioned_nodes_stay_online_across_refreshes() {
+        let state: Arc<dyn State> = Arc::new(Memory::default());
+        let pool = VirtualPool::provision(state.clone(), "t", 3, 0.1, &HashMap::new(), &[])
+            .await
+            .unwrap();
+        assert_eq!(pool.node_ids().len(), 3);
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(state.nodes("t", RunId(0), &HashMap::new()).await.unwrap().is_empty());
+        assert_eq!(pool.refresh().await.unwrap(), 3);
+        assert_eq!(state.nodes("t", RunId(0), &HashMap::new()).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn released_pools_disappear() {
+        let state: Arc<dyn State> = Arc::new(Memory::default());
+        let pool = VirtualPool::provision(state.clone(), "t", 2, 30.0, &HashMap::new(), &[])
+            .await
+            .unwrap();
+        pool.release().await.unwrap();
+        assert!(state.nodes("t", RunId(0), &HashMap::new()).await.unwrap().is_empty());
+        assert_eq!(pool.refresh().await.unwrap(), 0);
+    }
+}
@@ -0,0 +1,87 @@
+//! Filesystem-backed blob store, one file per blob under a root
+//! directory. Writes go to a temporary file first and are renamed into
+//! place so readers never observe partial blobs.
+
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use super::{BlobStore, Error, Result};
+
+pub struct Filesystem {
+    root: PathBuf,
+}
+
+impl Filesystem {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    /// The file backing `key`; keys must not traverse directories.
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        if key.is_empty() || key.contains(['/', '\\']) || key.contains("..") {
+            return Err(Error::Storage(format!("invalid blob key: {key}")));
+        }
+        Ok(self.root.join(key))
+    }
+}
+
+#[async_trait]
+impl BlobStore for Filesystem {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        let path = self.path(key)?;
+        tokio::fs::create_dir_all(&self.root).await?;
+        let tmp = self.root.join(format!(".tmp-{}", Uuid::new_v4()));
+        tokio::fs::write(&tmp, data).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>> {
+        let path = self.path(key)?;
+        match tokio::fs::read(&path).await {
+            Ok(data) => Ok(data),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                Err(Error::NotFound(key.to_owned()))
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let path = self.path(key)?;
+        match tokio::fs::remove_file(&path).await {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> Filesystem {
+        Filesystem::new(std::env::temp_dir().join(format!("flwr-blob-{}", Uuid::new_v4())))
+    }
+
+    #[tokio::test]
+    async fn put_get_delete_roundtrip() {
+        let store = store();
+        store.put("key", b"payload").await.unwrap();
+        assert_eq!(store.get("key").await.unwrap(), b"payload");
+        store.delete("key").await.unwrap();
+        assert!(matches!(store.get("key").await, Err(Error::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn traversal_keys_are_rejected() {
+        let store = store();
+        assert!(matches!(
+            store.get("../etc/passwd").await,
+            Err(Error::Storage(_))
+        ));
+    }
+}
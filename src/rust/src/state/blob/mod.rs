@@ -0,0 +1,104 @@
+//! Optional blob storage for large recordset payloads.
+//!
+//! When a backend is configured, recordsets above a size threshold are
+//! written to the store and the `recordset` column only keeps a
+//! `blobref:<key>` marker, keeping Postgres small when models are
+//! large. A valid serialized `RecordSet` never starts with the marker
+//! bytes (its fields 1-3 encode to different leading bytes), so the
+//! two forms are unambiguous.
+
+use std::sync::Arc;
+
+use bytes::Bytes;
+
+use async_trait::async_trait;
+use uuid::Uuid;
+
+pub mod filesystem;
+#[cfg(feature = "s3")]
+pub mod s3;
+
+pub use filesystem::Filesystem;
+#[cfg(feature = "s3")]
+pub use s3::S3;
+
+/// Marker prefixing a recordset column value that references a blob.
+const REF_PREFIX: &[u8] = b"blobref:";
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("blob not found: {0}")]
+    NotFound(String),
+    #[error("storage error: {0}")]
+    Storage(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Content-addressable storage for recordset payloads.
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<()>;
+    async fn get(&self, key: &str) -> Result<Vec<u8>>;
+    async fn delete(&self, key: &str) -> Result<()>;
+}
+
+/// A configured store plus the size above which recordsets are
+/// offloaded.
+#[derive(Clone)]
+pub struct BlobBackend {
+    pub store: Arc<dyn BlobStore>,
+    pub inline_threshold: usize,
+}
+
+impl BlobBackend {
+    /// Replace `recordset` with a blob reference if it is large enough.
+    pub async fn offload(&self, recordset: &mut Bytes) -> Result<()> {
+        if recordset.len() < self.inline_threshold || parse_ref(recordset).is_some() {
+            return Ok(());
+        }
+        let key = Uuid::new_v4().to_string();
+        self.store.put(&key, recordset).await?;
+        *recordset = blob_ref(&key);
+        Ok(())
+    }
+
+    /// Replace a blob reference in `recordset` with the stored bytes.
+    pub async fn resolve(&self, recordset: &mut Bytes) -> Result<()> {
+        if let Some(key) = parse_ref(recordset) {
+            *recordset = self.store.get(&key).await?.into();
+        }
+        Ok(())
+    }
+}
+
+fn blob_ref(key: &str) -> Bytes {
+    let mut bytes = REF_PREFIX.to_vec();
+    bytes.extend_from_slice(key.as_bytes());
+    bytes.into()
+}
+
+/// The blob key if `recordset` is a reference rather than inline bytes.
+fn parse_ref(recordset: &[u8]) -> Option<String> {
+    let key = recordset.strip_prefix(REF_PREFIX)?;
+    String::from_utf8(key.to_vec()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refs_roundtrip() {
+        let key = "0b7c88c1-8e3f-4a59-9c2f-1b61b5bfb3d7";
+        assert_eq!(parse_ref(&blob_ref(key)).as_deref(), Some(key));
+    }
+
+    #[test]
+    fn inline_bytes_are_not_refs() {
+        assert!(parse_ref(&[0x0a, 0x02, 0x01, 0x02]).is_none());
+        assert!(parse_ref(b"").is_none());
+    }
+}
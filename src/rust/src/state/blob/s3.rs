@@ -0,0 +1,77 @@
+//! S3-backed blob store, one object per blob in a single bucket.
+//!
+//! Credentials and region come from the usual AWS environment
+//! (environment variables, shared config, IMDS).
+
+use async_trait::async_trait;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::Client;
+
+use super::{BlobStore, Error, Result};
+
+pub struct S3 {
+    client: Client,
+    bucket: String,
+}
+
+impl S3 {
+    pub async fn new(bucket: impl Into<String>) -> Self {
+        let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+        Self {
+            client: Client::new(&config),
+            bucket: bucket.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl BlobStore for S3 {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .body(ByteStream::from(data.to_vec()))
+            .send()
+            .await
+            .map_err(|err| Error::Storage(err.to_string()))?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>> {
+        let object = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|err| {
+                if err
+                    .as_service_error()
+                    .is_some_and(|err| err.is_no_such_key())
+                {
+                    Error::NotFound(key.to_owned())
+                } else {
+                    Error::Storage(err.to_string())
+                }
+            })?;
+        let data = object
+            .body
+            .collect()
+            .await
+            .map_err(|err| Error::Storage(err.to_string()))?;
+        Ok(data.into_bytes().to_vec())
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|err| Error::Storage(err.to_string()))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,498 @@
+//! Circuit breaker decorator wrapping any `State` backend.
+//!
+//! After a configurable number of consecutive infrastructure failures
+//! the circuit opens and calls fail fast with [`Error::CircuitOpen`]
+//! (mapped to UNAVAILABLE). Once the cool-down elapses a single probe
+//! is let through; its outcome decides whether the circuit closes
+//! again. Domain errors such as an unknown run never trip the breaker.
+
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use tokio::sync::watch;
+
+use crate::model::handler::{
+    AuditEvent, DeadLetter, GroupId, Node, NodeId, NodeInfo, RunId, TaskId, TaskIns, TaskRes,
+};
+
+use super::{Error, PingOutcome, Result, RunUsage, State, TaskCursor, TaskFilter};
+
+/// Thresholds applied by [`Breaker`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct BreakerConfig {
+    /// Consecutive infrastructure failures before the circuit opens; 0
+    /// disables the breaker.
+    pub failure_threshold: u32,
+    /// Cool-down before an open circuit lets a probe through.
+    pub reset_after: Duration,
+}
+
+/// The breaker state machine, factored out of the decorator so the
+/// transitions can be tested without a `State` backend.
+#[derive(Debug)]
+struct BreakerCore {
+    config: BreakerConfig,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    probing: bool,
+}
+
+impl BreakerCore {
+    fn new(config: BreakerConfig) -> Self {
+        Self {
+            config,
+            consecutive_failures: 0,
+            opened_at: None,
+            probing: false,
+        }
+    }
+
+    /// Whether a call may proceed at `now`; marks the probe slot taken
+    /// when the circuit is half-open.
+    fn check(&mut self, now: Instant) -> bool {
+        if self.config.failure_threshold == 0 {
+            return true;
+        }
+        match self.opened_at {
+            None => true,
+            Some(opened_at) => {
+                if self.probing || now.duration_since(opened_at) < self.config.reset_after {
+                    return false;
+                }
+                self.probing = true;
+                true
+            }
+        }
+    }
+
+    /// Record a call outcome; returns the new open/closed status when
+    /// it changed.
+    fn record(&mut self, infrastructure_failure: bool, now: Instant) -> Option<bool> {
+        if self.config.failure_threshold == 0 {
+            return None;
+        }
+        self.probing = false;
+        if !infrastructure_failure {
+            self.consecutive_failures = 0;
+            return self.opened_at.take().map(|_| false);
+        }
+        self.consecutive_failures += 1;
+        if self.opened_at.is_some() {
+            // A failed probe re-arms the cool-down.
+            self.opened_at = Some(now);
+            return None;
+        }
+        if self.consecutive_failures >= self.config.failure_threshold {
+            self.opened_at = Some(now);
+            return Some(true);
+        }
+        None
+    }
+}
+
+/// Whether an error indicates database trouble rather than a bad
+/// request.
+fn is_infrastructure_failure(err: &Error) -> bool {
+    matches!(
+        err,
+        Error::Connection(_) | Error::Query(_) | Error::Timeout { .. }
+    )
+}
+
+/// A `State` decorator that fails fast during database outages.
+pub struct Breaker<S> {
+    inner: S,
+    core: Mutex<BreakerCore>,
+    open_tx: watch::Sender<bool>,
+}
+
+impl<S> Breaker<S> {
+    pub fn new(inner: S, config: BreakerConfig) -> Self {
+        let (open_tx, _) = watch::channel(false);
+        Self {
+            inner,
+            core: Mutex::new(BreakerCore::new(config)),
+            open_tx,
+        }
+    }
+
+    /// Observe the open/closed status, e.g. to flip the gRPC health
+    /// service during an outage.
+    pub fn subscribe(&self) -> watch::Receiver<bool> {
+        self.open_tx.subscribe()
+    }
+
+    async fn guarded<T>(&self, fut: impl Future<Output = Result<T>> + Send) -> Result<T> {
+        if !self.core.lock().unwrap().check(Instant::now()) {
+            return Err(Error::CircuitOpen);
+        }
+        let result = fut.await;
+        let failed = result.as_ref().err().is_some_and(is_infrastructure_failure);
+        if let Some(open) = self.core.lock().unwrap().record(failed, Instant::now()) {
+            if open {
+                tracing::error!("circuit breaker opened, failing fast");
+            } else {
+                tracing::info!("circuit breaker closed");
+            }
+            let _ = self.open_tx.send(open);
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl<S: State> State for Breaker<S> {
+    async fn insert_task_instructions(
+        &self,
+        tenant: &str,
+        instructions: &[TaskIns],
+    ) -> Result<Vec<TaskId>> {
+        self.guarded(self.inner.insert_task_instructions(tenant, instructions))
+            .await
+    }
+
+    async fn task_instructions(
+        &self,
+        tenant: &str,
+        node: &Node,
+        limit: Option<u32>,
+    ) -> Result<Vec<TaskIns>> {
+        self.guarded(self.inner.task_instructions(tenant, node, limit))
+            .await
+    }
+
+    async fn claim_pool_task_instructions(
+        &self,
+        tenant: &str,
+        node: &Node,
+        limit: Option<u32>,
+    ) -> Result<Vec<TaskIns>> {
+        self.guarded(self.inner.claim_pool_task_instructions(tenant, node, limit))
+            .await
+    }
+
+    async fn insert_task_results(&self, tenant: &str, results: &[TaskRes]) -> Result<Vec<TaskId>> {
+        self.guarded(self.inner.insert_task_results(tenant, results))
+            .await
+    }
+
+    async fn task_results(
+        &self,
+        tenant: &str,
+        task_ids: &[TaskId],
+        limit: Option<u32>,
+        mark: bool,
+    ) -> Result<Vec<TaskRes>> {
+        self.guarded(self.inner.task_results(tenant, task_ids, limit, mark))
+            .await
+    }
+
+    async fn release_tasks(&self, tenant: &str, node: &Node, task_ids: &[TaskId]) -> Result<u64> {
+        self.guarded(self.inner.release_tasks(tenant, node, task_ids))
+            .await
+    }
+
+    async fn release_expired_tasks(
+        &self,
+        lease: Duration,
+        max_redeliveries: u32,
+    ) -> Result<(u64, Vec<(String, DeadLetter)>)> {
+        self.guarded(self.inner.release_expired_tasks(lease, max_redeliveries))
+            .await
+    }
+
+    async fn task_ins_consumers(
+        &self,
+        tenant: &str,
+        task_ids: &[TaskId],
+    ) -> Result<HashMap<TaskId, (RunId, Node)>> {
+        self.guarded(self.inner.task_ins_consumers(tenant, task_ids))
+            .await
+    }
+
+    async fn pending_task_ins(&self, tenant: &str, consumer: &Node) -> Result<u64> {
+        self.guarded(self.inner.pending_task_ins(tenant, consumer))
+            .await
+    }
+
+    async fn pending_run_task_ins(&self, tenant: &str, run_id: RunId) -> Result<u64> {
+        self.guarded(self.inner.pending_run_task_ins(tenant, run_id))
+            .await
+    }
+
+    async fn group_progress(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        group_id: &GroupId,
+    ) -> Result<(u64, u64)> {
+        self.guarded(self.inner.group_progress(tenant, run_id, group_id))
+            .await
+    }
+
+    async fn run_progress(&self, tenant: &str, run_id: RunId) -> Result<Vec<(GroupId, u64, u64)>> {
+        self.guarded(self.inner.run_progress(tenant, run_id)).await
+    }
+
+    async fn run_usage(&self, tenant: &str, run_id: RunId) -> Result<RunUsage> {
+        self.guarded(self.inner.run_usage(tenant, run_id)).await
+    }
+
+    async fn delete_tasks(&self, tenant: &str, task_ids: &[TaskId]) -> Result<()> {
+        self.guarded(self.inner.delete_tasks(tenant, task_ids)).await
+    }
+
+    async fn create_node(
+        &self,
+        tenant: &str,
+        ping_interval: f64,
+        properties: &HashMap<String, String>,
+        task_types: &[String],
+    ) -> Result<NodeId> {
+        self.guarded(
+            self.inner
+                .create_node(tenant, ping_interval, properties, task_types),
+        )
+        .await
+    }
+
+    async fn create_nodes(
+        &self,
+        tenant: &str,
+        count: u32,
+        ping_interval: f64,
+        properties: &HashMap<String, String>,
+        task_types: &[String],
+    ) -> Result<Vec<NodeId>> {
+        self.guarded(
+            self.inner
+                .create_nodes(tenant, count, ping_interval, properties, task_types),
+        )
+        .await
+    }
+
+    async fn reconnect_node(
+        &self,
+        tenant: &str,
+        node_id: NodeId,
+        ping_interval: f64,
+        properties: &HashMap<String, String>,
+        task_types: &[String],
+    ) -> Result<bool> {
+        self.guarded(
+            self.inner
+                .reconnect_node(tenant, node_id, ping_interval, properties, task_types),
+        )
+        .await
+    }
+
+    async fn delete_node(&self, tenant: &str, node_id: NodeId) -> Result<()> {
+        self.guarded(self.inner.delete_node(tenant, node_id)).await
+    }
+
+    async fn delete_nodes(&self, tenant: &str, node_ids: &[NodeId]) -> Result<()> {
+        self.guarded(self.inner.delete_nodes(tenant, node_ids)).await
+    }
+
+    async fn update_ping(
+        &self,
+        tenant: &str,
+        node: &Node,
+        ping_interval: f64,
+        task_types: &[String],
+    ) -> Result<PingOutcome> {
+        self.guarded(self.inner.update_ping(tenant, node, ping_interval, task_types))
+            .await
+    }
+
+    async fn update_pings(&self, tenant: &str, pings: &[(NodeId, f64)]) -> Result<u64> {
+        self.guarded(self.inner.update_pings(tenant, pings)).await
+    }
+
+    async fn record_client_version(
+        &self,
+        tenant: &str,
+        node_id: NodeId,
+        version: &str,
+    ) -> Result<()> {
+        self.guarded(self.inner.record_client_version(tenant, node_id, version))
+            .await
+    }
+
+    async fn client_versions(&self, tenant: &str) -> Result<HashMap<String, u64>> {
+        self.guarded(self.inner.client_versions(tenant)).await
+    }
+
+    async fn record_node_identity(
+        &self,
+        tenant: &str,
+        node_id: NodeId,
+        identity: &str,
+    ) -> Result<()> {
+        self.guarded(self.inner.record_node_identity(tenant, node_id, identity))
+            .await
+    }
+
+    async fn node_identity(&self, tenant: &str, node_id: NodeId) -> Result<Option<String>> {
+        self.guarded(self.inner.node_identity(tenant, node_id)).await
+    }
+
+    async fn list_nodes(&self, tenant: &str) -> Result<Vec<NodeInfo>> {
+        self.guarded(self.inner.list_nodes(tenant)).await
+    }
+
+    async fn ban_node(&self, tenant: &str, node_id: NodeId, reason: &str) -> Result<()> {
+        self.guarded(self.inner.ban_node(tenant, node_id, reason))
+            .await
+    }
+
+    async fn unban_node(&self, tenant: &str, node_id: NodeId) -> Result<()> {
+        self.guarded(self.inner.unban_node(tenant, node_id)).await
+    }
+
+    async fn is_node_banned(&self, tenant: &str, node_id: NodeId) -> Result<bool> {
+        self.guarded(self.inner.is_node_banned(tenant, node_id)).await
+    }
+
+    async fn nodes(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        selector: &HashMap<String, String>,
+    ) -> Result<HashSet<NodeId>> {
+        self.guarded(self.inner.nodes(tenant, run_id, selector)).await
+    }
+
+    async fn sample_nodes(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        count: u32,
+        seed: Option<u64>,
+        selector: &HashMap<String, String>,
+    ) -> Result<Vec<NodeId>> {
+        self.guarded(self.inner.sample_nodes(tenant, run_id, count, seed, selector))
+            .await
+    }
+
+    async fn online_nodes(&self, tenant: &str) -> Result<u64> {
+        self.guarded(self.inner.online_nodes(tenant)).await
+    }
+
+    async fn record_audit_event(&self, tenant: &str, event: &AuditEvent) -> Result<()> {
+        self.guarded(self.inner.record_audit_event(tenant, event))
+            .await
+    }
+
+    async fn list_audit_events(
+        &self,
+        tenant: &str,
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<AuditEvent>> {
+        self.guarded(self.inner.list_audit_events(tenant, after, page_size))
+            .await
+    }
+
+    async fn list_dead_letters(
+        &self,
+        tenant: &str,
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<DeadLetter>> {
+        self.guarded(self.inner.list_dead_letters(tenant, after, page_size))
+            .await
+    }
+
+    async fn create_run(&self, tenant: &str) -> Result<RunId> {
+        self.guarded(self.inner.create_run(tenant)).await
+    }
+
+    async fn delete_run(&self, tenant: &str, run_id: RunId) -> Result<()> {
+        self.guarded(self.inner.delete_run(tenant, run_id)).await
+    }
+
+    async fn runs(&self, tenant: &str) -> Result<Vec<RunId>> {
+        self.guarded(self.inner.runs(tenant)).await
+    }
+
+    async fn list_task_ins(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        filters: &[TaskFilter],
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<TaskIns>> {
+        self.guarded(self.inner.list_task_ins(tenant, run_id, filters, after, page_size))
+            .await
+    }
+
+    async fn list_task_res(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        filters: &[TaskFilter],
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<TaskRes>> {
+        self.guarded(self.inner.list_task_res(tenant, run_id, filters, after, page_size))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn core() -> BreakerCore {
+        BreakerCore::new(BreakerConfig {
+            failure_threshold: 2,
+            reset_after: Duration::from_secs(5),
+        })
+    }
+
+    #[test]
+    fn opens_after_consecutive_failures_and_probes_after_cooldown() {
+        let mut core = core();
+        let start = Instant::now();
+        assert!(core.check(start));
+        assert_eq!(core.record(true, start), None);
+        assert!(core.check(start));
+        assert_eq!(core.record(true, start), Some(true));
+        // Open: calls are rejected until the cool-down elapses.
+        assert!(!core.check(start));
+        let later = start + Duration::from_secs(6);
+        assert!(core.check(later));
+        // Only one probe is allowed at a time.
+        assert!(!core.check(later));
+        assert_eq!(core.record(false, later), Some(false));
+        assert!(core.check(later));
+    }
+
+    #[test]
+    fn failed_probe_rearms_the_cooldown() {
+        let mut core = core();
+        let start = Instant::now();
+        core.record(true, start);
+        core.record(true, start);
+        let later = start + Duration::from_secs(6);
+        assert!(core.check(later));
+        assert_eq!(core.record(true, later), None);
+        assert!(!core.check(later + Duration::from_secs(1)));
+        assert!(core.check(later + Duration::from_secs(6)));
+    }
+
+    #[test]
+    fn domain_errors_reset_the_failure_streak() {
+        let mut core = core();
+        let now = Instant::now();
+        assert_eq!(core.record(true, now), None);
+        assert_eq!(core.record(false, now), None);
+        assert_eq!(core.record(true, now), None);
+        assert_eq!(core.record(true, now), Some(true));
+    }
+}
@@ -0,0 +1,421 @@
+//! Read cache decorator wrapping any `State` backend.
+//!
+//! Drivers tend to poll the node list every round, turning `nodes`
+//! into the hottest query on the backend. [`Cache`] keeps each node
+//! list for a short TTL and drops a tenant's cached lists whenever one
+//! of its nodes registers, pings or is deleted, so the answer is never
+//! staler than the TTL even across replicas.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+
+use crate::model::handler::{
+    AuditEvent, DeadLetter, GroupId, Node, NodeId, NodeInfo, RunId, TaskId, TaskIns, TaskRes,
+};
+
+use super::{PingOutcome, Result, RunUsage, State, TaskCursor, TaskFilter};
+
+/// Lifetimes applied by [`Cache`]; a TTL of 0 disables the cache.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CacheConfig {
+    /// How long a cached node list stays valid, in milliseconds.
+    pub node_ttl_ms: u64,
+}
+
+/// A cached `nodes` query: tenant, run id and sorted selector pairs.
+type NodeKey = (String, RunId, Vec<(String, String)>);
+
+/// A `State` decorator caching node list queries.
+pub struct Cache<S> {
+    inner: S,
+    config: CacheConfig,
+    nodes: Mutex<HashMap<NodeKey, (Instant, HashSet<NodeId>)>>,
+}
+
+impl<S> Cache<S> {
+    pub fn new(inner: S, config: CacheConfig) -> Self {
+        Self {
+            inner,
+            config,
+            nodes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn node_ttl(&self) -> Option<Duration> {
+        (self.config.node_ttl_ms > 0).then(|| Duration::from_millis(self.config.node_ttl_ms))
+    }
+
+    fn cached_nodes(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        selector: &HashMap<String, String>,
+    ) -> Option<HashSet<NodeId>> {
+        let ttl = self.node_ttl()?;
+        let cache = self.nodes.lock().unwrap();
+        let (stored_at, nodes) = cache.get(&node_key(tenant, run_id, selector))?;
+        (stored_at.elapsed() < ttl).then(|| nodes.clone())
+    }
+
+    fn store_nodes(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        selector: &HashMap<String, String>,
+        nodes: &HashSet<NodeId>,
+    ) {
+        let Some(ttl) = self.node_ttl() else { return };
+        let mut cache = self.nodes.lock().unwrap();
+        // Expired entries are only ever read again here, so pruning on
+        // insert keeps the map from growing with dead runs.
+        cache.retain(|_, (stored_at, _)| stored_at.elapsed() < ttl);
+        cache.insert(node_key(tenant, run_id, selector), (Instant::now(), nodes.clone()));
+    }
+
+    /// Drop every node list cached for `tenant`; called on any change
+    /// to its node membership or liveness.
+    fn invalidate_nodes(&self, tenant: &str) {
+        if self.config.node_ttl_ms == 0 {
+            return;
+        }
+        self.nodes.lock().unwrap().retain(|(cached, _, _), _| cached != tenant);
+    }
+}
+
+fn node_key(tenant: &str, run_id: RunId, selector: &HashMap<String, String>) -> NodeKey {
+    let mut selector: Vec<_> = selector
+        .iter()
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    selector.sort();
+    (tenant.to_owned(), run_id, selector)
+}
+
+#[async_trait]
+impl<S: State> State for Cache<S> {
+    async fn insert_task_instructions(
+        &self,
+        tenant: &str,
+        instructions: &[TaskIns],
+    ) -> Result<Vec<TaskId>> {
+        self.inner.insert_task_instructions(tenant, instructions).await
+    }
+
+    async fn task_instructions(
+        &self,
+        tenant: &str,
+        node: &Node,
+        limit: Option<u32>,
+    ) -> Result<Vec<TaskIns>> {
+        self.inner.task_instructions(tenant, node, limit).await
+    }
+
+    async fn claim_pool_task_instructions(
+        &self,
+        tenant: &str,
+        node: &Node,
+        limit: Option<u32>,
+    ) -> Result<Vec<TaskIns>> {
+        self.inner.claim_pool_task_instructions(tenant, node, limit).await
+    }
+
+    async fn insert_task_results(&self, tenant: &str, results: &[TaskRes]) -> Result<Vec<TaskId>> {
+        self.inner.insert_task_results(tenant, results).await
+    }
+
+    async fn task_results(
+        &self,
+        tenant: &str,
+        task_ids: &[TaskId],
+        limit: Option<u32>,
+        mark: bool,
+    ) -> Result<Vec<TaskRes>> {
+        self.inner.task_results(tenant, task_ids, limit, mark).await
+    }
+
+    async fn task_ins_consumers(
+        &self,
+        tenant: &str,
+        task_ids: &[TaskId],
+    ) -> Result<HashMap<TaskId, (RunId, Node)>> {
+        self.inner.task_ins_consumers(tenant, task_ids).await
+    }
+
+    async fn pending_task_ins(&self, tenant: &str, consumer: &Node) -> Result<u64> {
+        self.inner.pending_task_ins(tenant, consumer).await
+    }
+
+    async fn pending_run_task_ins(&self, tenant: &str, run_id: RunId) -> Result<u64> {
+        self.inner.pending_run_task_ins(tenant, run_id).await
+    }
+
+    async fn group_progress(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        group_id: &GroupId,
+    ) -> Result<(u64, u64)> {
+        self.inner.group_progress(tenant, run_id, group_id).await
+    }
+
+    async fn run_progress(&self, tenant: &str, run_id: RunId) -> Result<Vec<(GroupId, u64, u64)>> {
+        self.inner.run_progress(tenant, run_id).await
+    }
+
+    async fn run_usage(&self, tenant: &str, run_id: RunId) -> Result<RunUsage> {
+        self.inner.run_usage(tenant, run_id).await
+    }
+
+    async fn delete_tasks(&self, tenant: &str, task_ids: &[TaskId]) -> Result<()> {
+        self.inner.delete_tasks(tenant, task_ids).await
+    }
+
+    async fn release_tasks(&self, tenant: &str, node: &Node, task_ids: &[TaskId]) -> Result<u64> {
+        self.inner.release_tasks(tenant, node, task_ids).await
+    }
+
+    async fn release_expired_tasks(
+        &self,
+        lease: Duration,
+        max_redeliveries: u32,
+    ) -> Result<(u64, Vec<(String, DeadLetter)>)> {
+        self.inner.release_expired_tasks(lease, max_redeliveries).await
+    }
+
+    async fn list_dead_letters(
+        &self,
+        tenant: &str,
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<DeadLetter>> {
+        self.inner.list_dead_letters(tenant, after, page_size).await
+    }
+
+    async fn create_node(
+        &self,
+        tenant: &str,
+        ping_interval: f64,
+        properties: &HashMap<String, String>,
+        task_types: &[String],
+    ) -> Result<NodeId> {
+        self.invalidate_nodes(tenant);
+        self.inner.create_node(tenant, ping_interval, properties, task_types).await
+    }
+
+    async fn create_nodes(
+        &self,
+        tenant: &str,
+        count: u32,
+        ping_interval: f64,
+        properties: &HashMap<String, String>,
+        task_types: &[String],
+    ) -> Result<Vec<NodeId>> {
+        self.invalidate_nodes(tenant);
+        self.inner.create_nodes(tenant, count, ping_interval, properties, task_types).await
+    }
+
+    async fn reconnect_node(
+        &self,
+        tenant: &str,
+        node_id: NodeId,
+        ping_interval: f64,
+        properties: &HashMap<String, String>,
+        task_types: &[String],
+    ) -> Result<bool> {
+        self.invalidate_nodes(tenant);
+        self.inner
+            .reconnect_node(tenant, node_id, ping_interval, properties, task_types)
+            .await
+    }
+
+    async fn delete_node(&self, tenant: &str, node_id: NodeId) -> Result<()> {
+        self.invalidate_nodes(tenant);
+        self.inner.delete_node(tenant, node_id).await
+    }
+
+    async fn delete_nodes(&self, tenant: &str, node_ids: &[NodeId]) -> Result<()> {
+        self.invalidate_nodes(tenant);
+        self.inner.delete_nodes(tenant, node_ids).await
+    }
+
+    async fn update_ping(
+        &self,
+        tenant: &str,
+        node: &Node,
+        ping_interval: f64,
+        task_types: &[String],
+    ) -> Result<PingOutcome> {
+        self.invalidate_nodes(tenant);
+        self.inner.update_ping(tenant, node, ping_interval, task_types).await
+    }
+
+    async fn update_pings(&self, tenant: &str, pings: &[(NodeId, f64)]) -> Result<u64> {
+        self.invalidate_nodes(tenant);
+        self.inner.update_pings(tenant, pings).await
+    }
+
+    async fn record_client_version(
+        &self,
+        tenant: &str,
+        node_id: NodeId,
+        version: &str,
+    ) -> Result<()> {
+        self.inner.record_client_version(tenant, node_id, version).await
+    }
+
+    async fn client_versions(&self, tenant: &str) -> Result<HashMap<String, u64>> {
+        self.inner.client_versions(tenant).await
+    }
+
+    async fn record_node_identity(
+        &self,
+        tenant: &str,
+        node_id: NodeId,
+        identity: &str,
+    ) -> Result<()> {
+        self.inner.record_node_identity(tenant, node_id, identity).await
+    }
+
+    async fn node_identity(&self, tenant: &str, node_id: NodeId) -> Result<Option<String>> {
+        self.inner.node_identity(tenant, node_id).await
+    }
+
+    async fn list_nodes(&self, tenant: &str) -> Result<Vec<NodeInfo>> {
+        self.inner.list_nodes(tenant).await
+    }
+
+    async fn ban_node(&self, tenant: &str, node_id: NodeId, reason: &str) -> Result<()> {
+        self.inner.ban_node(tenant, node_id, reason).await
+    }
+
+    async fn unban_node(&self, tenant: &str, node_id: NodeId) -> Result<()> {
+        self.inner.unban_node(tenant, node_id).await
+    }
+
+    async fn is_node_banned(&self, tenant: &str, node_id: NodeId) -> Result<bool> {
+        self.inner.is_node_banned(tenant, node_id).await
+    }
+
+    async fn nodes(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        selector: &HashMap<String, String>,
+    ) -> Result<HashSet<NodeId>> {
+        if let Some(cached) = self.cached_nodes(tenant, run_id, selector) {
+            return Ok(cached);
+        }
+        let nodes = self.inner.nodes(tenant, run_id, selector).await?;
+        self.store_nodes(tenant, run_id, selector, &nodes);
+        Ok(nodes)
+    }
+
+    async fn sample_nodes(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        count: u32,
+        seed: Option<u64>,
+        selector: &HashMap<String, String>,
+    ) -> Result<Vec<NodeId>> {
+        self.inner.sample_nodes(tenant, run_id, count, seed, selector).await
+    }
+
+    async fn online_nodes(&self, tenant: &str) -> Result<u64> {
+        self.inner.online_nodes(tenant).await
+    }
+
+    async fn record_audit_event(&self, tenant: &str, event: &AuditEvent) -> Result<()> {
+        self.inner.record_audit_event(tenant, event).await
+    }
+
+    async fn list_audit_events(
+        &self,
+        tenant: &str,
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<AuditEvent>> {
+        self.inner.list_audit_events(tenant, after, page_size).await
+    }
+
+    async fn create_run(&self, tenant: &str) -> Result<RunId> {
+        self.inner.create_run(tenant).await
+    }
+
+    async fn delete_run(&self, tenant: &str, run_id: RunId) -> Result<()> {
+        self.inner.delete_run(tenant, run_id).await
+    }
+
+    async fn runs(&self, tenant: &str) -> Result<Vec<RunId>> {
+        self.inner.runs(tenant).await
+    }
+
+    async fn list_task_ins(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        filters: &[TaskFilter],
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<TaskIns>> {
+        self.inner.list_task_ins(tenant, run_id, filters, after, page_size).await
+    }
+
+    async fn list_task_res(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        filters: &[TaskFilter],
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<TaskRes>> {
+        self.inner.list_task_res(tenant, run_id, filters, after, page_size).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache(ttl_ms: u64) -> Cache<()> {
+        Cache::new((), CacheConfig { node_ttl_ms: ttl_ms })
+    }
+
+    #[test]
+    fn hits_within_ttl_and_misses_after_invalidation() {
+        let cache = cache(60_000);
+        let selector = HashMap::new();
+        let nodes: HashSet<NodeId> = [NodeId(1), NodeId(2)].into_iter().collect();
+        assert_eq!(cache.cached_nodes("t", RunId(1), &selector), None);
+        cache.store_nodes("t", RunId(1), &selector, &nodes);
+        assert_eq!(cache.cached_nodes("t", RunId(1), &selector), Some(nodes.clone()));
+        // Another tenant's invalidation leaves the entry alone.
+        cache.invalidate_nodes("other");
+        assert_eq!(cache.cached_nodes("t", RunId(1), &selector), Some(nodes));
+        cache.invalidate_nodes("t");
+        assert_eq!(cache.cached_nodes("t", RunId(1), &selector), None);
+    }
+
+    #[test]
+    fn selector_order_does_not_split_entries() {
+        let cache = cache(60_000);
+        let mut selector = HashMap::new();
+        selector.insert("gpu".to_owned(), "true".to_owned());
+        selector.insert("zone".to_owned(), "eu".to_owned());
+        let nodes: HashSet<NodeId> = [NodeId(7)].into_iter().collect();
+        cache.store_nodes("t", RunId(1), &selector, &nodes);
+        assert_eq!(node_key("t", RunId(1), &selector), node_key("t", RunId(1), &selector.clone()));
+        assert_eq!(cache.cached_nodes("t", RunId(1), &selector), Some(nodes));
+    }
+
+    #[test]
+    fn zero_ttl_disables_the_cache() {
+        let cache = cache(0);
+        let selector = HashMap::new();
+        cache.store_nodes("t", RunId(1), &selector, &HashSet::new());
+        assert_eq!(cache.cached_nodes("t", RunId(1), &selector), None);
+    }
+}
@@ -0,0 +1,1497 @@
+//! In-memory `State` implementation, used in tests and for running a
+//! SuperLink without external dependencies.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::model::handler::{
+    secs_from_datetime, AuditEvent, DeadLetter, GroupId, Node, NodeId, NodeInfo, RunId, Task,
+    TaskError, TaskId, TaskIns, TaskRes,
+};
+
+use super::{
+    matches_selector, Error, PingOutcome, Result, RunUsage, State, TaskCursor, TaskFilter,
+    DEAD_LETTER_CONSUMER_DELETED, DEAD_LETTER_REDELIVERY, ERROR_CODE_CONSUMER_DELETED,
+    ERROR_CODE_DEAD_LETTERED,
+};
+
+struct NodeEntry {
+    online_until: f64,
+    ping_interval: f64,
+    properties: HashMap<String, String>,
+    task_types: Vec<String>,
+    client_version: String,
+    identity: String,
+}
+
+#[derive(Default)]
+struct Shard {
+    task_ins: HashMap<TaskId, TaskIns>,
+    task_res: HashMap<TaskId, TaskRes>,
+    delivery_count: HashMap<TaskId, u32>,
+    nodes: HashMap<NodeId, NodeEntry>,
+    banned: HashMap<NodeId, String>,
+    audit: Vec<AuditEvent>,
+    dead: Vec<DeadLetter>,
+    runs: HashSet<RunId>,
+}
+
+impl Shard {
+    /// Move a stored `TaskIns` to the dead-letter queue and synthesize
+    /// an error TaskRes so result pulls learn about the failure.
+    fn dead_letter(&mut self, id: &TaskId, reason: &str, error_code: i64) -> Option<DeadLetter> {
+        let Some(task_ins) = self.task_ins.remove(id) else {
+            return None;
+        };
+        let delivery_count = self.delivery_count.remove(id).unwrap_or(0);
+        tracing::warn!(%id, reason, "task moved to the dead-letter queue");
+        let now = Utc::now();
+        let failure = TaskRes {
+            id: TaskId(uuid::Uuid::new_v4().to_string()),
+            group_id: task_ins.group_id.clone(),
+            run_id: task_ins.run_id,
+            task: Task {
+                producer: task_ins.task.consumer,
+                consumer: task_ins.task.producer,
+                created_at: now,
+                delivered_at: None,
+                pushed_at: now,
+                ttl: String::new(),
+                ancestry: vec![task_ins.id.clone()],
+                task_type: task_ins.task.task_type.clone(),
+                recordset: bytes::Bytes::new(),
+                recordset_checksum: String::new(),
+                error: Some(TaskError {
+                    code: error_code,
+                    reason: reason.to_owned(),
+                }),
+            },
+        };
+        self.task_res.insert(failure.id.clone(), failure);
+        let parked = DeadLetter {
+            id: task_ins.id,
+            group_id: task_ins.group_id,
+            run_id: task_ins.run_id,
+            consumer: task_ins.task.consumer,
+            created_at: secs_from_datetime(task_ins.task.created_at),
+            dead_at: secs_from_datetime(now),
+            delivery_count,
+            task_type: task_ins.task.task_type,
+            reason: reason.to_owned(),
+        };
+        self.dead.push(parked.clone());
+        Some(parked)
+    }
+}
+
+/// In-memory state backend; each tenant gets its own shard.
+#[derive(Default)]
+pub struct Memory {
+    tenants: Mutex<HashMap<String, Shard>>,
+    ping_grace: f64,
+}
+
+impl Memory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fraction of its own `ping_interval` a node may overshoot
+    /// `online_until` and still count as online, absorbing ping
+    /// jitter.
+    pub fn set_ping_grace(&mut self, ping_grace: f64) {
+        self.ping_grace = ping_grace;
+    }
+
+    /// Whether `entry` counts as online at `now`, allowing the
+    /// configured grace on top of its lease.
+    fn online(&self, entry: &NodeEntry, now: f64) -> bool {
+        entry.online_until + self.ping_grace * entry.ping_interval > now
+    }
+}
+
+fn now_secs() -> f64 {
+    Utc::now().timestamp_micros() as f64 / 1e6
+}
+
+fn after_cursor(after: Option<&TaskCursor>, created_at: f64, id: &str) -> bool {
+    match after {
+        None => true,
+        Some(cursor) => {
+            created_at > cursor.created_at
+                || (created_at == cursor.created_at && id > cursor.id.as_str())
+        }
+    }
+}
+
+#[async_trait]
+impl State for Memory {
+    async fn insert_task_instructions(
+        &self,
+        tenant: &str,
+        instructions: &[TaskIns],
+    ) -> Result<Vec<TaskId>> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        let mut stored = Vec::with_capacity(instructions.len());
+        for instruction in instructions {
+            if !inner.runs.contains(&instruction.run_id) {
+                return Err(Error::UnknownRun(instruction.run_id));
+            }
+            inner
+                .task_ins
+                .insert(instruction.id.clone(), instruction.clone());
+            stored.push(instruction.id.clone());
+        }
+        Ok(stored)
+    }
+
+    async fn task_instructions(
+        &self,
+        tenant: &str,
+        node: &Node,
+        limit: Option<u32>,
+    ) -> Result<Vec<TaskIns>> {
+        // Select-and-mark runs under the shard lock with no await
+        // points, so a cancelled caller observes all-or-nothing.
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        let limit = limit.map_or(usize::MAX, |limit| limit as usize);
+        let supported: Option<Vec<String>> = if node.anonymous {
+            None
+        } else {
+            inner
+                .nodes
+                .get(&node.id)
+                .map(|entry| entry.task_types.clone())
+                .filter(|task_types| !task_types.is_empty())
+        };
+        let mut ids: Vec<TaskId> = inner
+            .task_ins
+            .values()
+            .filter(|task_ins| {
+                task_ins.task.delivered_at.is_none()
+                    && if node.anonymous {
+                        task_ins.task.consumer.anonymous && task_ins.task.consumer.id == NodeId(0)
+                    } else {
+                        !task_ins.task.consumer.anonymous && task_ins.task.consumer.id == node.id
+                    }
+                    && supported
+                        .as_ref()
+                        .map_or(true, |supported| supported.contains(&task_ins.task.task_type))
+            })
+            .map(|task_ins| task_ins.id.clone())
+            .collect();
+        ids.sort_by_key(|id| inner.task_ins[id].task.created_at);
+        ids.truncate(limit);
+        let delivered_at = Utc::now();
+        let mut delivered = Vec::with_capacity(ids.len());
+        for id in ids {
+            let task_ins = inner.task_ins.get_mut(&id).unwrap();
+            task_ins.task.delivered_at = Some(delivered_at);
+            *inner.delivery_count.entry(id).or_default() += 1;
+            delivered.push(task_ins.clone());
+        }
+        Ok(delivered)
+    }
+
+    async fn claim_pool_task_instructions(
+        &self,
+        tenant: &str,
+        node: &Node,
+        limit: Option<u32>,
+    ) -> Result<Vec<TaskIns>> {
+        if node.anonymous {
+            return Ok(Vec::new());
+        }
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        let limit = limit.map_or(usize::MAX, |limit| limit as usize);
+        let supported: Option<Vec<String>> = inner
+            .nodes
+            .get(&node.id)
+            .map(|entry| entry.task_types.clone())
+            .filter(|task_types| !task_types.is_empty());
+        let mut ids: Vec<TaskId> = inner
+            .task_ins
+            .values()
+            .filter(|task_ins| {
+                task_ins.task.delivered_at.is_none()
+                    && task_ins.task.consumer.anonymous
+                    && task_ins.task.consumer.id == NodeId(0)
+                    && supported
+                        .as_ref()
+                        .map_or(true, |supported| supported.contains(&task_ins.task.task_type))
+            })
+            .map(|task_ins| task_ins.id.clone())
+            .collect();
+        ids.sort_by_key(|id| inner.task_ins[id].task.created_at);
+        ids.truncate(limit);
+        let delivered_at = Utc::now();
+        let mut claimed = Vec::with_capacity(ids.len());
+        for id in ids {
+            let task_ins = inner.task_ins.get_mut(&id).unwrap();
+            task_ins.task.delivered_at = Some(delivered_at);
+            *inner.delivery_count.entry(id).or_default() += 1;
+            claimed.push(task_ins.clone());
+        }
+        Ok(claimed)
+    }
+
+    async fn insert_task_results(
+        &self,
+        tenant: &str,
+        results: &[TaskRes],
+    ) -> Result<Vec<TaskId>> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        let mut stored = Vec::with_capacity(results.len());
+        for result in results {
+            if !inner.runs.contains(&result.run_id) {
+                return Err(Error::UnknownRun(result.run_id));
+            }
+            inner.task_res.insert(result.id.clone(), result.clone());
+            stored.push(result.id.clone());
+        }
+        Ok(stored)
+    }
+
+    async fn task_results(
+        &self,
+        tenant: &str,
+        task_ids: &[TaskId],
+        limit: Option<u32>,
+        mark: bool,
+    ) -> Result<Vec<TaskRes>> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        let limit = limit.map_or(usize::MAX, |limit| limit as usize);
+        let mut ids: Vec<TaskId> = inner
+            .task_res
+            .values()
+            .filter(|task_res| {
+                task_res.task.delivered_at.is_none()
+                    && task_res
+                        .task
+                        .ancestry
+                        .iter()
+                        .any(|ancestor| task_ids.contains(ancestor))
+            })
+            .map(|task_res| task_res.id.clone())
+            .collect();
+        ids.sort();
+        ids.truncate(limit);
+        let delivered_at = Utc::now();
+        let mut delivered = Vec::with_capacity(ids.len());
+        for id in ids {
+            let task_res = inner.task_res.get_mut(&id).unwrap();
+            if mark {
+                task_res.task.delivered_at = Some(delivered_at);
+            }
+            delivered.push(task_res.clone());
+        }
+        Ok(delivered)
+    }
+
+    async fn release_tasks(
+        &self,
+        tenant: &str,
+        node: &Node,
+        task_ids: &[TaskId],
+    ) -> Result<u64> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        let answered: HashSet<TaskId> = inner
+            .task_res
+            .values()
+            .flat_map(|task_res| task_res.task.ancestry.iter().cloned())
+            .collect();
+        let mut released = 0;
+        for id in task_ids {
+            let Some(task_ins) = inner.task_ins.get_mut(id) else {
+                continue;
+            };
+            let consumer = &task_ins.task.consumer;
+            let owned = if node.anonymous {
+                consumer.anonymous && consumer.id == NodeId(0)
+            } else {
+                !consumer.anonymous && consumer.id == node.id
+            };
+            if !owned || task_ins.task.delivered_at.is_none() || answered.contains(id) {
+                continue;
+            }
+            task_ins.task.delivered_at = None;
+            released += 1;
+        }
+        Ok(released)
+    }
+
+    async fn release_expired_tasks(
+        &self,
+        lease: Duration,
+        max_redeliveries: u32,
+    ) -> Result<(u64, Vec<(String, DeadLetter)>)> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let now = Utc::now();
+        let lease = chrono::Duration::milliseconds(
+            i64::try_from(lease.as_millis()).unwrap_or(i64::MAX),
+        );
+        let mut released = 0;
+        let mut dead = Vec::new();
+        for (tenant, shard) in tenants.iter_mut() {
+            let answered: HashSet<TaskId> = shard
+                .task_res
+                .values()
+                .flat_map(|task_res| task_res.task.ancestry.iter().cloned())
+                .collect();
+            let mut exhausted = Vec::new();
+            let delivery_count = &shard.delivery_count;
+            for task_ins in shard.task_ins.values_mut() {
+                let expired = task_ins
+                    .task
+                    .delivered_at
+                    .map_or(false, |delivered| now.signed_duration_since(delivered) >= lease);
+                if !expired || answered.contains(&task_ins.id) {
+                    continue;
+                }
+                let count = delivery_count.get(&task_ins.id).copied().unwrap_or(0);
+                if max_redeliveries > 0 && count >= max_redeliveries {
+                    exhausted.push(task_ins.id.clone());
+                    continue;
+                }
+                task_ins.task.delivered_at = None;
+                released += 1;
+            }
+            for id in exhausted {
+                if let Some(parked) =
+                    shard.dead_letter(&id, DEAD_LETTER_REDELIVERY, ERROR_CODE_DEAD_LETTERED)
+                {
+                    dead.push((tenant.clone(), parked));
+                }
+            }
+        }
+        Ok((released, dead))
+    }
+
+    async fn task_ins_consumers(
+        &self,
+        tenant: &str,
+        task_ids: &[TaskId],
+    ) -> Result<HashMap<TaskId, (RunId, Node)>> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        Ok(task_ids
+            .iter()
+            .filter_map(|id| {
+                let task_ins = inner.task_ins.get(id)?;
+                Some((id.clone(), (task_ins.run_id, task_ins.task.consumer)))
+            })
+            .collect())
+    }
+
+    async fn pending_task_ins(&self, tenant: &str, consumer: &Node) -> Result<u64> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        let pending = inner
+            .task_ins
+            .values()
+            .filter(|task_ins| {
+                task_ins.task.delivered_at.is_none()
+                    && task_ins.task.consumer.anonymous == consumer.anonymous
+                    && task_ins.task.consumer.id
+                        == if consumer.anonymous {
+                            NodeId(0)
+                        } else {
+                            consumer.id
+                        }
+            })
+            .count();
+        Ok(pending as u64)
+    }
+
+    async fn pending_run_task_ins(&self, tenant: &str, run_id: RunId) -> Result<u64> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        let pending = inner
+            .task_ins
+            .values()
+            .filter(|task_ins| {
+                task_ins.task.delivered_at.is_none() && task_ins.run_id == run_id
+            })
+            .count();
+        Ok(pending as u64)
+    }
+
+    async fn group_progress(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        group_id: &GroupId,
+    ) -> Result<(u64, u64)> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        let instructions = inner
+            .task_ins
+            .values()
+            .filter(|task_ins| task_ins.run_id == run_id && task_ins.group_id == *group_id)
+            .count() as u64;
+        let results = inner
+            .task_res
+            .values()
+            .filter(|task_res| task_res.run_id == run_id && task_res.group_id == *group_id)
+            .count() as u64;
+        Ok((instructions, results))
+    }
+
+    async fn run_progress(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+    ) -> Result<Vec<(GroupId, u64, u64)>> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        let mut groups: HashMap<GroupId, (u64, u64)> = HashMap::new();
+        for task_ins in inner.task_ins.values().filter(|task_ins| task_ins.run_id == run_id) {
+            groups.entry(task_ins.group_id.clone()).or_default().0 += 1;
+        }
+        for task_res in inner.task_res.values().filter(|task_res| task_res.run_id == run_id) {
+            groups.entry(task_res.group_id.clone()).or_default().1 += 1;
+        }
+        let mut progress: Vec<(GroupId, u64, u64)> = groups
+            .into_iter()
+            .map(|(group_id, (instructions, results))| (group_id, instructions, results))
+            .collect();
+        progress.sort();
+        Ok(progress)
+    }
+
+    async fn run_usage(&self, tenant: &str, run_id: RunId) -> Result<RunUsage> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        let mut usage = RunUsage::default();
+        for task_ins in inner.task_ins.values().filter(|task_ins| task_ins.run_id == run_id) {
+            usage.tasks += 1;
+            usage.recordset_bytes += task_ins.task.recordset.len() as u64;
+            if !task_ins.task.consumer.anonymous {
+                usage.consumers.insert(task_ins.task.consumer.id);
+            }
+        }
+        for task_res in inner.task_res.values().filter(|task_res| task_res.run_id == run_id) {
+            usage.recordset_bytes += task_res.task.recordset.len() as u64;
+        }
+        Ok(usage)
+    }
+
+    async fn delete_tasks(&self, tenant: &str, task_ids: &[TaskId]) -> Result<()> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        inner.task_ins.retain(|id, task_ins| {
+            !(task_ids.contains(id) && task_ins.task.delivered_at.is_some())
+        });
+        inner.task_res.retain(|_, task_res| {
+            !(task_res
+                .task
+                .ancestry
+                .iter()
+                .any(|ancestor| task_ids.contains(ancestor))
+                && task_res.task.delivered_at.is_some())
+        });
+        Ok(())
+    }
+
+    async fn create_node(
+        &self,
+        tenant: &str,
+        ping_interval: f64,
+        properties: &HashMap<String, String>,
+        task_types: &[String],
+    ) -> Result<NodeId> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        let node_id = NodeId(rand::thread_rng().gen());
+        inner.nodes.insert(
+            node_id,
+            NodeEntry {
+                online_until: now_secs() + ping_interval,
+                ping_interval,
+                properties: properties.clone(),
+                task_types: task_types.to_vec(),
+                client_version: String::new(),
+                identity: String::new(),
+            },
+        );
+        Ok(node_id)
+    }
+
+    async fn create_nodes(
+        &self,
+        tenant: &str,
+        count: u32,
+        ping_interval: f64,
+        properties: &HashMap<String, String>,
+        task_types: &[String],
+    ) -> Result<Vec<NodeId>> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        let online_until = now_secs() + ping_interval;
+        let mut node_ids = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let node_id = NodeId(rand::thread_rng().gen());
+            inner.nodes.insert(
+                node_id,
+                NodeEntry {
+                    online_until,
+                    ping_interval,
+                    properties: properties.clone(),
+                    task_types: task_types.to_vec(),
+                    client_version: String::new(),
+                    identity: String::new(),
+                },
+            );
+            node_ids.push(node_id);
+        }
+        Ok(node_ids)
+    }
+
+    async fn reconnect_node(
+        &self,
+        tenant: &str,
+        node_id: NodeId,
+        ping_interval: f64,
+        properties: &HashMap<String, String>,
+        task_types: &[String],
+    ) -> Result<bool> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        match inner.nodes.get_mut(&node_id) {
+            Some(entry) => {
+                entry.online_until = now_secs() + ping_interval;
+                entry.ping_interval = ping_interval;
+                entry.properties = properties.clone();
+                entry.task_types = task_types.to_vec();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn delete_node(&self, tenant: &str, node_id: NodeId) -> Result<()> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        inner.nodes.remove(&node_id);
+        let answered: HashSet<TaskId> = inner
+            .task_res
+            .values()
+            .flat_map(|task_res| task_res.task.ancestry.iter().cloned())
+            .collect();
+        let orphaned: Vec<TaskId> = inner
+            .task_ins
+            .values()
+            .filter(|task_ins| {
+                !task_ins.task.consumer.anonymous
+                    && task_ins.task.consumer.id == node_id
+                    && !answered.contains(&task_ins.id)
+            })
+            .map(|task_ins| task_ins.id.clone())
+            .collect();
+        for id in orphaned {
+            inner.dead_letter(&id, DEAD_LETTER_CONSUMER_DELETED, ERROR_CODE_CONSUMER_DELETED);
+        }
+        Ok(())
+    }
+
+    async fn delete_nodes(&self, tenant: &str, node_ids: &[NodeId]) -> Result<()> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        for node_id in node_ids {
+            inner.nodes.remove(node_id);
+        }
+        let answered: HashSet<TaskId> = inner
+            .task_res
+            .values()
+            .flat_map(|task_res| task_res.task.ancestry.iter().cloned())
+            .collect();
+        let orphaned: Vec<TaskId> = inner
+            .task_ins
+            .values()
+            .filter(|task_ins| {
+                !task_ins.task.consumer.anonymous
+                    && node_ids.contains(&task_ins.task.consumer.id)
+                    && !answered.contains(&task_ins.id)
+            })
+            .map(|task_ins| task_ins.id.clone())
+            .collect();
+        for id in orphaned {
+            inner.dead_letter(&id, DEAD_LETTER_CONSUMER_DELETED, ERROR_CODE_CONSUMER_DELETED);
+        }
+        Ok(())
+    }
+
+    async fn update_ping(
+        &self,
+        tenant: &str,
+        node: &Node,
+        ping_interval: f64,
+        task_types: &[String],
+    ) -> Result<PingOutcome> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        let now = now_secs();
+        match inner.nodes.get_mut(&node.id) {
+            Some(entry) => {
+                let lapsed = entry.online_until <= now;
+                entry.online_until = now + ping_interval;
+                entry.ping_interval = ping_interval;
+                if !task_types.is_empty() {
+                    entry.task_types = task_types.to_vec();
+                }
+                Ok(if lapsed {
+                    PingOutcome::Lapsed
+                } else {
+                    PingOutcome::Refreshed
+                })
+            }
+            None => Ok(PingOutcome::Unknown),
+        }
+    }
+
+    async fn update_pings(&self, tenant: &str, pings: &[(NodeId, f64)]) -> Result<u64> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        let now = now_secs();
+        let mut updated = 0;
+        for &(node_id, ping_interval) in pings {
+            if let Some(entry) = inner.nodes.get_mut(&node_id) {
+                entry.online_until = now + ping_interval;
+                entry.ping_interval = ping_interval;
+                updated += 1;
+            }
+        }
+        Ok(updated)
+    }
+
+    async fn record_client_version(
+        &self,
+        tenant: &str,
+        node_id: NodeId,
+        version: &str,
+    ) -> Result<()> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        if let Some(entry) = inner.nodes.get_mut(&node_id) {
+            entry.client_version = version.to_owned();
+        }
+        Ok(())
+    }
+
+    async fn client_versions(&self, tenant: &str) -> Result<HashMap<String, u64>> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        let mut versions = HashMap::new();
+        for entry in inner.nodes.values() {
+            *versions.entry(entry.client_version.clone()).or_default() += 1;
+        }
+        Ok(versions)
+    }
+
+    async fn record_node_identity(
+        &self,
+        tenant: &str,
+        node_id: NodeId,
+        identity: &str,
+    ) -> Result<()> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        if let Some(entry) = inner.nodes.get_mut(&node_id) {
+            entry.identity = identity.to_owned();
+        }
+        Ok(())
+    }
+
+    async fn node_identity(&self, tenant: &str, node_id: NodeId) -> Result<Option<String>> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        Ok(inner.nodes.get(&node_id).map(|entry| entry.identity.clone()))
+    }
+
+    async fn list_nodes(&self, tenant: &str) -> Result<Vec<NodeInfo>> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        let mut nodes: Vec<NodeInfo> = inner
+            .nodes
+            .iter()
+            .map(|(&id, entry)| NodeInfo {
+                id,
+                identity: entry.identity.clone(),
+                client_version: entry.client_version.clone(),
+                online_until: entry.online_until,
+                ping_interval: entry.ping_interval,
+                properties: entry.properties.clone(),
+            })
+            .collect();
+        nodes.sort_by_key(|node| node.id);
+        Ok(nodes)
+    }
+
+    async fn ban_node(&self, tenant: &str, node_id: NodeId, reason: &str) -> Result<()> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        inner.banned.entry(node_id).or_insert_with(|| reason.to_owned());
+        inner.nodes.remove(&node_id);
+        Ok(())
+    }
+
+    async fn unban_node(&self, tenant: &str, node_id: NodeId) -> Result<()> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        inner.banned.remove(&node_id);
+        Ok(())
+    }
+
+    async fn is_node_banned(&self, tenant: &str, node_id: NodeId) -> Result<bool> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        Ok(inner.banned.contains_key(&node_id))
+    }
+
+    async fn nodes(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        selector: &HashMap<String, String>,
+    ) -> Result<HashSet<NodeId>> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        if !inner.runs.contains(&run_id) {
+            return Ok(HashSet::new());
+        }
+        let now = now_secs();
+        Ok(inner
+            .nodes
+            .iter()
+            .filter(|(_, entry)| {
+                self.online(entry, now) && matches_selector(&entry.properties, selector)
+            })
+            .map(|(id, _)| *id)
+            .collect())
+    }
+
+    async fn sample_nodes(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        count: u32,
+        seed: Option<u64>,
+        selector: &HashMap<String, String>,
+    ) -> Result<Vec<NodeId>> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        if !inner.runs.contains(&run_id) {
+            return Ok(Vec::new());
+        }
+        let now = now_secs();
+        let mut ids: Vec<NodeId> = inner
+            .nodes
+            .iter()
+            .filter(|(_, entry)| {
+                self.online(entry, now) && matches_selector(&entry.properties, selector)
+            })
+            .map(|(id, _)| *id)
+            .collect();
+        ids.sort_unstable();
+        match seed {
+            Some(seed) => ids.shuffle(&mut rand::rngs::StdRng::seed_from_u64(seed)),
+            None => ids.shuffle(&mut rand::thread_rng()),
+        }
+        ids.truncate(count as usize);
+        Ok(ids)
+    }
+
+    async fn online_nodes(&self, tenant: &str) -> Result<u64> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        let now = now_secs();
+        let online = inner.nodes.values().filter(|entry| self.online(entry, now)).count();
+        Ok(online as u64)
+    }
+
+    async fn record_audit_event(&self, tenant: &str, event: &AuditEvent) -> Result<()> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        inner.audit.push(event.clone());
+        Ok(())
+    }
+
+    async fn list_audit_events(
+        &self,
+        tenant: &str,
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<AuditEvent>> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        let mut page: Vec<AuditEvent> = inner
+            .audit
+            .iter()
+            .filter(|event| after_cursor(after, event.created_at, &event.id))
+            .cloned()
+            .collect();
+        page.sort_by(|a, b| {
+            (a.created_at, a.id.as_str())
+                .partial_cmp(&(b.created_at, b.id.as_str()))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        page.truncate(page_size as usize);
+        Ok(page)
+    }
+
+    async fn list_dead_letters(
+        &self,
+        tenant: &str,
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<DeadLetter>> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        let mut page: Vec<DeadLetter> = inner
+            .dead
+            .iter()
+            .filter(|dead| after_cursor(after, dead.dead_at, dead.id.as_ref()))
+            .cloned()
+            .collect();
+        page.sort_by(|a, b| {
+            (a.dead_at, a.id.as_ref())
+                .partial_cmp(&(b.dead_at, b.id.as_ref()))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        page.truncate(page_size as usize);
+        Ok(page)
+    }
+
+    async fn create_run(&self, tenant: &str) -> Result<RunId> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        let run_id = RunId(rand::thread_rng().gen());
+        inner.runs.insert(run_id);
+        Ok(run_id)
+    }
+
+    async fn delete_run(&self, tenant: &str, run_id: RunId) -> Result<()> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        if !inner.runs.remove(&run_id) {
+            return Err(Error::UnknownRun(run_id));
+        }
+        inner.task_ins.retain(|_, task_ins| task_ins.run_id != run_id);
+        inner.task_res.retain(|_, task_res| task_res.run_id != run_id);
+        Ok(())
+    }
+
+    async fn runs(&self, tenant: &str) -> Result<Vec<RunId>> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        let mut runs: Vec<RunId> = inner.runs.iter().copied().collect();
+        runs.sort_unstable();
+        Ok(runs)
+    }
+
+    async fn list_task_ins(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        filters: &[TaskFilter],
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<TaskIns>> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        let mut page: Vec<TaskIns> = inner
+            .task_ins
+            .values()
+            .filter(|task_ins| {
+                let created_at = secs_from_datetime(task_ins.task.created_at);
+                task_ins.run_id == run_id
+                    && after_cursor(after, created_at, task_ins.id.as_ref())
+                    && filters.iter().all(|filter| {
+                        filter.matches(
+                            task_ins.group_id.as_ref(),
+                            &task_ins.task.task_type,
+                            task_ins.task.producer.id,
+                            task_ins.task.consumer.id,
+                            created_at,
+                        )
+                    })
+            })
+            .cloned()
+            .collect();
+        page.sort_by(|a, b| (a.task.created_at, &a.id).cmp(&(b.task.created_at, &b.id)));
+        page.truncate(page_size as usize);
+        Ok(page)
+    }
+
+    async fn list_task_res(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        filters: &[TaskFilter],
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<TaskRes>> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let inner = tenants.entry(tenant.to_owned()).or_default();
+        let mut page: Vec<TaskRes> = inner
+            .task_res
+            .values()
+            .filter(|task_res| {
+                let created_at = secs_from_datetime(task_res.task.created_at);
+                task_res.run_id == run_id
+                    && after_cursor(after, created_at, task_res.id.as_ref())
+                    && filters.iter().all(|filter| {
+                        filter.matches(
+                            task_res.group_id.as_ref(),
+                            &task_res.task.task_type,
+                            task_res.task.producer.id,
+                            task_res.task.consumer.id,
+                            created_at,
+                        )
+                    })
+            })
+            .cloned()
+            .collect();
+        page.sort_by(|a, b| (a.task.created_at, &a.id).cmp(&(b.task.created_at, &b.id)));
+        page.truncate(page_size as usize);
+        Ok(page)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn conformance() {
+        crate::state::testsuite::run(&Memory::new()).await;
+    }
+
+    fn task_ins(id: &str, run_id: RunId, consumer: Node) -> TaskIns {
+        TaskIns {
+            id: TaskId(id.to_owned()),
+            group_id: GroupId::default(),
+            run_id,
+            task: crate::model::handler::Task {
+                producer: Node {
+                    id: NodeId(0),
+                    anonymous: true,
+                },
+                consumer,
+                created_at: Utc::now(),
+                delivered_at: None,
+                pushed_at: Utc::now(),
+                ttl: String::new(),
+                ancestry: Vec::new(),
+                task_type: "train".to_owned(),
+                recordset: bytes::Bytes::new(),
+                recordset_checksum: String::new(),
+                error: None,
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn insert_rejects_unknown_run() {
+        let state = Memory::new();
+        let consumer = Node {
+            id: NodeId(7),
+            anonymous: false,
+        };
+        let result = state
+            .insert_task_instructions("", &[task_ins("a", RunId(42), consumer)])
+            .await;
+        assert!(matches!(result, Err(Error::UnknownRun(RunId(42)))));
+    }
+
+    #[tokio::test]
+    async fn task_instructions_marks_delivered() {
+        let state = Memory::new();
+        let run_id = state.create_run("").await.unwrap();
+        let consumer = Node {
+            id: NodeId(7),
+            anonymous: false,
+        };
+        state
+            .insert_task_instructions("", &[task_ins("a", run_id, consumer)])
+            .await
+            .unwrap();
+        let first = state.task_instructions("", &consumer, None).await.unwrap();
+        assert_eq!(first.len(), 1);
+        assert!(first[0].task.delivered_at.is_some());
+        let second = state.task_instructions("", &consumer, None).await.unwrap();
+        assert!(second.is_empty());
+    }
+
+    #[tokio::test]
+    async fn cancelled_pull_leaves_tasks_undelivered() {
+        let state = Memory::new();
+        let run_id = state.create_run("").await.unwrap();
+        let consumer = Node {
+            id: NodeId(7),
+            anonymous: false,
+        };
+        state
+            .insert_task_instructions("", &[task_ins("a", run_id, consumer)])
+            .await
+            .unwrap();
+        // Simulate a client disconnect: the pull future is dropped
+        // before it completes, so nothing may be marked delivered.
+        drop(state.task_instructions("", &consumer, None));
+        assert_eq!(state.pending_task_ins("", &consumer).await.unwrap(), 1);
+        let delivered = state.task_instructions("", &consumer, None).await.unwrap();
+        assert_eq!(delivered.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn run_queue_depth_counts_undelivered_only() {
+        let state = Memory::new();
+        let run_id = state.create_run("").await.unwrap();
+        let consumer = Node {
+            id: NodeId(7),
+            anonymous: false,
+        };
+        state
+            .insert_task_instructions(
+                "",
+                &[task_ins("a", run_id, consumer), task_ins("b", run_id, consumer)],
+            )
+            .await
+            .unwrap();
+        assert_eq!(state.pending_run_task_ins("", run_id).await.unwrap(), 2);
+        state.task_instructions("", &consumer, Some(1)).await.unwrap();
+        assert_eq!(state.pending_run_task_ins("", run_id).await.unwrap(), 1);
+        assert_eq!(
+            state.pending_run_task_ins("", RunId(run_id.0 + 1)).await.unwrap(),
+            0
+        );
+    }
+
+    fn task_res(id: &str, run_id: RunId, ancestor: &str) -> TaskRes {
+        TaskRes {
+            id: TaskId(id.to_owned()),
+            group_id: GroupId::default(),
+            run_id,
+            task: crate::model::handler::Task {
+                producer: Node {
+                    id: NodeId(7),
+                    anonymous: false,
+                },
+                consumer: Node {
+                    id: NodeId(0),
+                    anonymous: true,
+                },
+                created_at: Utc::now(),
+                delivered_at: None,
+                pushed_at: Utc::now(),
+                ttl: String::new(),
+                ancestry: vec![TaskId(ancestor.to_owned())],
+                task_type: "train".to_owned(),
+                recordset: bytes::Bytes::new(),
+                recordset_checksum: String::new(),
+                error: None,
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn nacked_tasks_become_pullable_again() {
+        let state = Memory::new();
+        let run_id = state.create_run("").await.unwrap();
+        let consumer = Node {
+            id: NodeId(7),
+            anonymous: false,
+        };
+        state
+            .insert_task_instructions("", &[task_ins("a", run_id, consumer)])
+            .await
+            .unwrap();
+        state.task_instructions("", &consumer, None).await.unwrap();
+        let task_ids = vec![TaskId("a".to_owned())];
+        // Another node must not be able to hand the task back.
+        let stranger = Node {
+            id: NodeId(8),
+            anonymous: false,
+        };
+        assert_eq!(state.release_tasks("", &stranger, &task_ids).await.unwrap(), 0);
+        assert_eq!(state.release_tasks("", &consumer, &task_ids).await.unwrap(), 1);
+        assert_eq!(state.task_instructions("", &consumer, None).await.unwrap().len(), 1);
+        // Once a result exists the task can no longer be nacked.
+        state
+            .insert_task_results("", &[task_res("r", run_id, "a")])
+            .await
+            .unwrap();
+        assert_eq!(state.release_tasks("", &consumer, &task_ids).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn expired_leases_release_tasks_until_the_redelivery_cap() {
+        let state = Memory::new();
+        let run_id = state.create_run("").await.unwrap();
+        let consumer = Node {
+            id: NodeId(7),
+            anonymous: false,
+        };
+        state
+            .insert_task_instructions("", &[task_ins("a", run_id, consumer)])
+            .await
+            .unwrap();
+        state.task_instructions("", &consumer, None).await.unwrap();
+        // A zero lease expires the delivery immediately.
+        assert_eq!(state.release_expired_tasks(Duration::ZERO, 2).await.unwrap().0, 1);
+        assert_eq!(state.task_instructions("", &consumer, None).await.unwrap().len(), 1);
+        // Two deliveries hit the cap: the lease is no longer released.
+        assert_eq!(state.release_expired_tasks(Duration::ZERO, 2).await.unwrap().0, 0);
+        assert!(state.task_instructions("", &consumer, None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn exhausted_tasks_move_to_the_dead_letter_queue() {
+        let state = Memory::new();
+        let run_id = state.create_run("").await.unwrap();
+        let consumer = Node {
+            id: NodeId(7),
+            anonymous: false,
+        };
+        state
+            .insert_task_instructions("", &[task_ins("a", run_id, consumer)])
+            .await
+            .unwrap();
+        state.task_instructions("", &consumer, None).await.unwrap();
+        // One delivery exhausts a cap of one: the task is parked, not
+        // redelivered.
+        assert_eq!(state.release_expired_tasks(Duration::ZERO, 1).await.unwrap().0, 0);
+        assert!(state.task_instructions("", &consumer, None).await.unwrap().is_empty());
+        let dead = state.list_dead_letters("", None, 10).await.unwrap();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].id, TaskId("a".to_owned()));
+        assert_eq!(dead[0].reason, DEAD_LETTER_REDELIVERY);
+        assert_eq!(dead[0].delivery_count, 1);
+    }
+
+    #[tokio::test]
+    async fn dead_lettered_tasks_surface_an_error_task_res() {
+        let state = Memory::new();
+        let run_id = state.create_run("").await.unwrap();
+        let consumer = Node {
+            id: NodeId(7),
+            anonymous: false,
+        };
+        state
+            .insert_task_instructions("", &[task_ins("a", run_id, consumer)])
+            .await
+            .unwrap();
+        state.task_instructions("", &consumer, None).await.unwrap();
+        state.release_expired_tasks(Duration::ZERO, 1).await.unwrap();
+        let results = state
+            .task_results("", &[TaskId("a".to_owned())], None, true)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        let error = results[0].task.error.as_ref().expect("synthesized error");
+        assert_eq!(error.code, ERROR_CODE_DEAD_LETTERED);
+        assert_eq!(error.reason, DEAD_LETTER_REDELIVERY);
+        assert_eq!(results[0].task.ancestry, vec![TaskId("a".to_owned())]);
+    }
+
+    #[tokio::test]
+    async fn deleting_a_node_dead_letters_its_pending_tasks() {
+        let state = Memory::new();
+        let run_id = state.create_run("").await.unwrap();
+        let node_id = state.create_node("", 30.0, &HashMap::new(), &[]).await.unwrap();
+        let consumer = Node {
+            id: node_id,
+            anonymous: false,
+        };
+        state
+            .insert_task_instructions("", &[task_ins("a", run_id, consumer)])
+            .await
+            .unwrap();
+        state.delete_node("", node_id).await.unwrap();
+        let dead = state.list_dead_letters("", None, 10).await.unwrap();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].reason, DEAD_LETTER_CONSUMER_DELETED);
+        assert_eq!(state.pending_task_ins("", &consumer).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn peeked_results_stay_pullable_until_acknowledged() {
+        let state = Memory::new();
+        let run_id = state.create_run("").await.unwrap();
+        state
+            .insert_task_results("", &[task_res("r", run_id, "a")])
+            .await
+            .unwrap();
+        let task_ids = vec![TaskId("a".to_owned())];
+        // Peeking returns the result without marking it delivered.
+        assert_eq!(state.task_results("", &task_ids, None, false).await.unwrap().len(), 1);
+        let peeked = state.task_results("", &task_ids, None, false).await.unwrap();
+        assert_eq!(peeked.len(), 1);
+        assert!(peeked[0].task.delivered_at.is_none());
+        // A marking pull consumes it.
+        assert_eq!(state.task_results("", &task_ids, None, true).await.unwrap().len(), 1);
+        assert!(state.task_results("", &task_ids, None, true).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn answered_tasks_keep_their_lease() {
+        let state = Memory::new();
+        let run_id = state.create_run("").await.unwrap();
+        let consumer = Node {
+            id: NodeId(7),
+            anonymous: false,
+        };
+        state
+            .insert_task_instructions("", &[task_ins("a", run_id, consumer)])
+            .await
+            .unwrap();
+        state.task_instructions("", &consumer, None).await.unwrap();
+        state
+            .insert_task_results("", &[task_res("r", run_id, "a")])
+            .await
+            .unwrap();
+        // A result arrived, so the expired lease must not be released.
+        assert_eq!(state.release_expired_tasks(Duration::ZERO, 0).await.unwrap().0, 0);
+    }
+
+    #[tokio::test]
+    async fn nodes_requires_existing_run() {
+        let state = Memory::new();
+        state.create_node("", 30.0, &HashMap::new(), &[]).await.unwrap();
+        assert!(state.nodes("", RunId(1), &HashMap::new()).await.unwrap().is_empty());
+        let run_id = state.create_run("").await.unwrap();
+        assert_eq!(state.nodes("", run_id, &HashMap::new()).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn bulk_node_creation_and_deletion() {
+        let state = Memory::new();
+        let run_id = state.create_run("").await.unwrap();
+        let node_ids = state
+            .create_nodes("", 100, 30.0, &HashMap::new(), &[])
+            .await
+            .unwrap();
+        assert_eq!(node_ids.len(), 100);
+        assert_eq!(state.nodes("", run_id, &HashMap::new()).await.unwrap().len(), 100);
+        state.delete_nodes("", &node_ids).await.unwrap();
+        assert!(state.nodes("", run_id, &HashMap::new()).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn sample_nodes_is_reproducible_with_seed() {
+        let state = Memory::new();
+        let run_id = state.create_run("").await.unwrap();
+        for _ in 0..10 {
+            state.create_node("", 30.0, &HashMap::new(), &[]).await.unwrap();
+        }
+        let first = state
+            .sample_nodes("", run_id, 3, Some(42), &HashMap::new())
+            .await
+            .unwrap();
+        let second = state
+            .sample_nodes("", run_id, 3, Some(42), &HashMap::new())
+            .await
+            .unwrap();
+        assert_eq!(first, second);
+        assert_eq!(first.len(), 3);
+        assert!(state
+            .sample_nodes("", RunId(999), 3, Some(42), &HashMap::new())
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn list_task_ins_paginates_by_cursor() {
+        let state = Memory::new();
+        let run_id = state.create_run("").await.unwrap();
+        let consumer = Node {
+            id: NodeId(7),
+            anonymous: false,
+        };
+        let mut instructions: Vec<TaskIns> = (0..5)
+            .map(|i| task_ins(&format!("task-{i}"), run_id, consumer))
+            .collect();
+        for (i, instruction) in instructions.iter_mut().enumerate() {
+            instruction.task.created_at = crate::model::handler::datetime_from_secs(i as f64);
+        }
+        state
+            .insert_task_instructions("", &instructions)
+            .await
+            .unwrap();
+        let first = state.list_task_ins("", run_id, &[], None, 2).await.unwrap();
+        assert_eq!(first.len(), 2);
+        let cursor = TaskCursor {
+            created_at: secs_from_datetime(first[1].task.created_at),
+            id: first[1].id.to_string(),
+        };
+        let second = state
+            .list_task_ins("", run_id, &[], Some(&cursor), 10)
+            .await
+            .unwrap();
+        assert_eq!(second.len(), 3);
+        assert!(second.iter().all(|task_ins| {
+            secs_from_datetime(task_ins.task.created_at) > cursor.created_at
+        }));
+    }
+
+    #[tokio::test]
+    async fn pending_task_ins_counts_undelivered() {
+        let state = Memory::new();
+        let run_id = state.create_run("").await.unwrap();
+        let consumer = Node {
+            id: NodeId(7),
+            anonymous: false,
+        };
+        state
+            .insert_task_instructions(
+                "",
+                &[task_ins("a", run_id, consumer), task_ins("b", run_id, consumer)],
+            )
+            .await
+            .unwrap();
+        assert_eq!(state.pending_task_ins("", &consumer).await.unwrap(), 2);
+        state.task_instructions("", &consumer, Some(1)).await.unwrap();
+        assert_eq!(state.pending_task_ins("", &consumer).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn task_instructions_respect_declared_task_types() {
+        let state = Memory::new();
+        let run_id = state.create_run("").await.unwrap();
+        let node_id = state
+            .create_node("", 30.0, &HashMap::new(), &["evaluate".to_owned()])
+            .await
+            .unwrap();
+        let consumer = Node {
+            id: node_id,
+            anonymous: false,
+        };
+        state
+            .insert_task_instructions("", &[task_ins("a", run_id, consumer)])
+            .await
+            .unwrap();
+        // The pending TaskIns is "train", which the node does not support.
+        assert!(state.task_instructions("", &consumer, None).await.unwrap().is_empty());
+        // Declaring "train" via ping makes it deliverable.
+        state
+            .update_ping("", &consumer, 30.0, &["train".to_owned()])
+            .await
+            .unwrap();
+        assert_eq!(state.task_instructions("", &consumer, None).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn a_ping_grace_keeps_jittery_nodes_online() {
+        let mut state = Memory::new();
+        state.set_ping_grace(0.5);
+        let run_id = state.create_run("").await.unwrap();
+        let node_id = state.create_node("", 60.0, &HashMap::new(), &[]).await.unwrap();
+        let rewind = |state: &Memory, lapsed: f64| {
+            let mut tenants = state.tenants.lock().unwrap();
+            let entry = tenants.get_mut("").unwrap().nodes.get_mut(&node_id).unwrap();
+            entry.online_until = now_secs() - lapsed;
+        };
+        // A lease 10s past its end is within the 30s grace (0.5 of
+        // the 60s interval), so the node still counts as online.
+        rewind(&state, 10.0);
+        let online = state.nodes("", run_id, &HashMap::new()).await.unwrap();
+        assert_eq!(online, [node_id].into_iter().collect());
+        assert_eq!(state.online_nodes("").await.unwrap(), 1);
+        // Past the allowance the node is offline.
+        rewind(&state, 40.0);
+        assert!(state.nodes("", run_id, &HashMap::new()).await.unwrap().is_empty());
+        assert_eq!(state.online_nodes("").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn ban_removes_node_until_unbanned() {
+        let state = Memory::new();
+        let run_id = state.create_run("").await.unwrap();
+        let node_id = state.create_node("", 30.0, &HashMap::new(), &[]).await.unwrap();
+        state.ban_node("", node_id, "abuse").await.unwrap();
+        assert!(state.is_node_banned("", node_id).await.unwrap());
+        assert!(state.nodes("", run_id, &HashMap::new()).await.unwrap().is_empty());
+        state.unban_node("", node_id).await.unwrap();
+        assert!(!state.is_node_banned("", node_id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn audit_events_paginate_in_order() {
+        let state = Memory::new();
+        for (index, action) in ["node.create", "node.ban", "node.unban"].iter().enumerate() {
+            let event = AuditEvent {
+                id: format!("event-{index}"),
+                created_at: index as f64,
+                action: (*action).to_owned(),
+                node_id: NodeId(7),
+                run_id: RunId(0),
+                detail: String::new(),
+            };
+            state.record_audit_event("", &event).await.unwrap();
+        }
+        let first = state.list_audit_events("", None, 2).await.unwrap();
+        assert_eq!(first.len(), 2);
+        assert_eq!(first[0].action, "node.create");
+        let cursor = TaskCursor {
+            created_at: first[1].created_at,
+            id: first[1].id.clone(),
+        };
+        let rest = state.list_audit_events("", Some(&cursor), 2).await.unwrap();
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].action, "node.unban");
+    }
+
+    #[tokio::test]
+    async fn nodes_filter_by_selector() {
+        let state = Memory::new();
+        let run_id = state.create_run("").await.unwrap();
+        let gpu: HashMap<String, String> =
+            [("device".to_owned(), "gpu".to_owned())].into_iter().collect();
+        let matching = state.create_node("", 30.0, &gpu, &[]).await.unwrap();
+        state.create_node("", 30.0, &HashMap::new(), &[]).await.unwrap();
+        let nodes = state.nodes("", run_id, &gpu).await.unwrap();
+        assert_eq!(nodes, [matching].into_iter().collect());
+    }
+
+    #[tokio::test]
+    async fn tenants_are_isolated() {
+        let state = Memory::new();
+        let run_id = state.create_run("alpha").await.unwrap();
+        let consumer = Node {
+            id: NodeId(7),
+            anonymous: false,
+        };
+        state
+            .insert_task_instructions("alpha", &[task_ins("a", run_id, consumer)])
+            .await
+            .unwrap();
+        let other = state
+            .task_instructions("beta", &consumer, None)
+            .await
+            .unwrap();
+        assert!(other.is_empty());
+        assert!(state
+            .nodes("beta", run_id, &HashMap::new())
+            .await
+            .unwrap()
+            .is_empty());
+        let own = state
+            .task_instructions("alpha", &consumer, None)
+            .await
+            .unwrap();
+        assert_eq!(own.len(), 1);
+    }
+}
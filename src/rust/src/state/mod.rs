@@ -0,0 +1,544 @@
+//! Persistent state behind the Fleet and Driver APIs.
+//!
+//! Cross-cutting storage concerns live in decorators that wrap any
+//! backend and compose by nesting: [`retry::Retry`] repeats calls that
+//! failed to acquire a connection, [`timeout::Timeout`] enforces
+//! per-operation deadlines, [`breaker::Breaker`] fails fast while the
+//! backend is down and [`cache::Cache`] absorbs hot read queries.
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::model::handler::{
+    AuditEvent, DeadLetter, GroupId, Node, NodeId, NodeInfo, RunId, TaskId, TaskIns, TaskRes,
+};
+
+pub mod blob;
+pub mod breaker;
+pub mod cache;
+pub mod memory;
+pub mod postgres;
+pub mod retry;
+#[cfg(any(test, feature = "testsuite"))]
+pub mod testsuite;
+pub mod timeout;
+
+/// Dead-letter reason used when the redelivery cap is exhausted.
+pub const DEAD_LETTER_REDELIVERY: &str = "redelivery limit exceeded";
+
+/// Dead-letter reason used when the consumer node is deleted.
+pub const DEAD_LETTER_CONSUMER_DELETED: &str = "consumer node deleted";
+
+/// Error code on the TaskRes synthesized for a dead-lettered task.
+pub const ERROR_CODE_DEAD_LETTERED: i64 = 1;
+
+/// Error code on the TaskRes synthesized when the consumer node was
+/// deleted before answering.
+pub const ERROR_CODE_CONSUMER_DELETED: i64 = 2;
+
+/// Errors surfaced by `State` implementations.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to acquire a database connection: {0}")]
+    Connection(String),
+    #[error("query failed")]
+    Query(#[from] diesel::result::Error),
+    #[error("run {0} does not exist")]
+    UnknownRun(RunId),
+    #[error("blob storage error: {0}")]
+    Blob(#[from] blob::Error),
+    #[error("node {0} is banned")]
+    NodeBanned(NodeId),
+    #[error("node {0} does not belong to the caller's identity")]
+    NotNodeOwner(NodeId),
+    #[error("result does not match ancestor task {task_id}: {reason}")]
+    AncestorMismatch {
+        task_id: TaskId,
+        reason: &'static str,
+    },
+    #[error("{operation} did not complete within {limit_ms}ms")]
+    Timeout {
+        operation: &'static str,
+        limit_ms: u64,
+    },
+    #[error("circuit breaker is open, database assumed down")]
+    CircuitOpen,
+    #[error("node {node_id} already has {pending} undelivered tasks (limit {limit})")]
+    PendingTaskLimit {
+        node_id: NodeId,
+        pending: u64,
+        limit: u32,
+    },
+    #[error("run {run_id} already has {pending} undelivered tasks (limit {limit})")]
+    RunTaskLimit {
+        run_id: RunId,
+        pending: u64,
+        limit: u32,
+    },
+    #[error("run {run_id} exceeds its {resource} quota ({used} used, limit {limit})")]
+    RunQuota {
+        run_id: RunId,
+        resource: &'static str,
+        used: u64,
+        limit: u64,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Whether `properties` contains every entry of `selector`.
+pub(crate) fn matches_selector(
+    properties: &HashMap<String, String>,
+    selector: &HashMap<String, String>,
+) -> bool {
+    selector
+        .iter()
+        .all(|(key, value)| properties.get(key) == Some(value))
+}
+
+/// Stored footprint of one run, for quota enforcement.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunUsage {
+    /// Stored TaskIns rows.
+    pub tasks: u64,
+    /// Bytes of stored TaskIns and TaskRes recordsets.
+    pub recordset_bytes: u64,
+    /// Distinct registered consumer nodes among the stored TaskIns.
+    pub consumers: HashSet<NodeId>,
+}
+
+/// Keyset cursor for paginated task listings, ordered by
+/// `(created_at, id)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskCursor {
+    pub created_at: f64,
+    pub id: String,
+}
+
+/// Outcome of a ping acknowledgement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PingOutcome {
+    /// The node was online and its lease was refreshed.
+    Refreshed,
+    /// The node was known but its lease had already lapsed; the ping
+    /// revived it.
+    Lapsed,
+    /// The node is unknown, never registered or since deleted.
+    Unknown,
+}
+
+/// Comparison operator of a [`TaskFilter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl FilterOp {
+    /// Whether `ordering` — the stored value relative to the filter
+    /// value — satisfies the operator.
+    fn accepts(self, ordering: Ordering) -> bool {
+        match self {
+            FilterOp::Eq => ordering.is_eq(),
+            FilterOp::Ne => ordering.is_ne(),
+            FilterOp::Lt => ordering.is_lt(),
+            FilterOp::Le => ordering.is_le(),
+            FilterOp::Gt => ordering.is_gt(),
+            FilterOp::Ge => ordering.is_ge(),
+        }
+    }
+}
+
+/// A filterable task field, carrying the typed value to compare
+/// against; the whitelist is what keeps operators away from raw SQL.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TaskField {
+    GroupId(String),
+    TaskType(String),
+    ProducerNodeId(NodeId),
+    ConsumerNodeId(NodeId),
+    /// Seconds since the Unix epoch.
+    CreatedAt(f64),
+}
+
+/// One validated server-side filter over the task listings; build it
+/// with [`TaskFilter::parse`] so only whitelisted fields and
+/// operators reach the backends.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskFilter {
+    pub field: TaskField,
+    pub op: FilterOp,
+}
+
+impl TaskFilter {
+    /// Validate a raw `field`/`op`/`value` triple against the
+    /// whitelist; the error names whatever was rejected.
+    pub fn parse(field: &str, op: &str, value: &str) -> std::result::Result<Self, String> {
+        let op = match op {
+            "eq" => FilterOp::Eq,
+            "ne" => FilterOp::Ne,
+            "lt" => FilterOp::Lt,
+            "le" => FilterOp::Le,
+            "gt" => FilterOp::Gt,
+            "ge" => FilterOp::Ge,
+            other => return Err(format!("unknown filter operator {other:?}")),
+        };
+        let int = |value: &str| {
+            value
+                .parse::<i64>()
+                .map_err(|_| format!("filter value for {field:?} must be an integer"))
+        };
+        let field = match field {
+            "group_id" => TaskField::GroupId(value.to_owned()),
+            "task_type" => TaskField::TaskType(value.to_owned()),
+            "producer_node_id" => TaskField::ProducerNodeId(NodeId(int(value)?)),
+            "consumer_node_id" => TaskField::ConsumerNodeId(NodeId(int(value)?)),
+            "created_at" => {
+                let seconds = value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|seconds| seconds.is_finite())
+                    .ok_or_else(|| format!("filter value for {field:?} must be a number"))?;
+                TaskField::CreatedAt(seconds)
+            }
+            other => return Err(format!("field {other:?} cannot be filtered on")),
+        };
+        Ok(Self { field, op })
+    }
+
+    /// Whether a task with the given field values passes the filter.
+    pub(crate) fn matches(
+        &self,
+        group_id: &str,
+        task_type: &str,
+        producer_node_id: NodeId,
+        consumer_node_id: NodeId,
+        created_at: f64,
+    ) -> bool {
+        let ordering = match &self.field {
+            TaskField::GroupId(value) => group_id.cmp(value.as_str()),
+            TaskField::TaskType(value) => task_type.cmp(value.as_str()),
+            TaskField::ProducerNodeId(value) => producer_node_id.cmp(value),
+            TaskField::ConsumerNodeId(value) => consumer_node_id.cmp(value),
+            TaskField::CreatedAt(value) => created_at.total_cmp(value),
+        };
+        self.op.accepts(ordering)
+    }
+}
+
+/// Abstract state, mirroring the semantics of the Python `State` ABC.
+///
+/// Every method takes a `tenant` identifier; tenants are fully isolated
+/// from each other and the empty string is the default tenant.
+#[async_trait]
+pub trait State: Send + Sync {
+    /// Store task instructions and return the ids of the stored rows.
+    ///
+    /// Instructions referencing an unknown run are rejected.
+    async fn insert_task_instructions(
+        &self,
+        tenant: &str,
+        instructions: &[TaskIns],
+    ) -> Result<Vec<TaskId>>;
+
+    /// Retrieve undelivered task instructions for `node`, oldest first,
+    /// marking them as delivered.
+    ///
+    /// An anonymous `node` pulls from the anonymous pool; a registered
+    /// node pulls instructions addressed to its id, restricted to the
+    /// task types it declared (none declared = all).
+    async fn task_instructions(
+        &self,
+        tenant: &str,
+        node: &Node,
+        limit: Option<u32>,
+    ) -> Result<Vec<TaskIns>>;
+
+    /// Claim undelivered instructions addressed to the anonymous
+    /// consumer on behalf of registered `node`, oldest first, marking
+    /// them as delivered.
+    ///
+    /// First-come-first-served: concurrent claimers each get disjoint
+    /// instructions. An anonymous `node` claims nothing here; it pulls
+    /// the same instructions through [`task_instructions`].
+    ///
+    /// [`task_instructions`]: State::task_instructions
+    async fn claim_pool_task_instructions(
+        &self,
+        tenant: &str,
+        node: &Node,
+        limit: Option<u32>,
+    ) -> Result<Vec<TaskIns>>;
+
+    /// Store task results and return the ids of the stored rows.
+    async fn insert_task_results(&self, tenant: &str, results: &[TaskRes])
+        -> Result<Vec<TaskId>>;
+
+    /// Retrieve undelivered task results whose ancestry matches one of
+    /// `task_ids`. With `mark` set they are marked as delivered; unset
+    /// they are only peeked and stay pullable.
+    async fn task_results(
+        &self,
+        tenant: &str,
+        task_ids: &[TaskId],
+        limit: Option<u32>,
+        mark: bool,
+    ) -> Result<Vec<TaskRes>>;
+
+    /// Run and consumer of each stored TaskIns in `task_ids`, keyed
+    /// by task id, for validating pushed results against their
+    /// ancestors; unknown ids are simply absent.
+    async fn task_ins_consumers(
+        &self,
+        tenant: &str,
+        task_ids: &[TaskId],
+    ) -> Result<HashMap<TaskId, (RunId, Node)>>;
+
+    /// Number of undelivered TaskIns addressed to `consumer`.
+    async fn pending_task_ins(&self, tenant: &str, consumer: &Node) -> Result<u64>;
+
+    /// Number of undelivered TaskIns queued for `run_id`.
+    async fn pending_run_task_ins(&self, tenant: &str, run_id: RunId) -> Result<u64>;
+
+    /// Number of stored TaskIns and TaskRes belonging to `group_id` of
+    /// `run_id`, for observing when a task group is complete.
+    async fn group_progress(&self, tenant: &str, run_id: RunId, group_id: &GroupId)
+        -> Result<(u64, u64)>;
+
+    /// TaskIns and TaskRes counts of every group of `run_id`, sorted
+    /// by group id, as `(group_id, instructions, results)`.
+    async fn run_progress(&self, tenant: &str, run_id: RunId)
+        -> Result<Vec<(GroupId, u64, u64)>>;
+
+    /// The stored footprint of `run_id`, for quota enforcement.
+    async fn run_usage(&self, tenant: &str, run_id: RunId) -> Result<RunUsage>;
+
+    /// Delete delivered TaskIns/TaskRes pairs for the given ids.
+    async fn delete_tasks(&self, tenant: &str, task_ids: &[TaskId]) -> Result<()>;
+
+    /// Clear `delivered_at` on the given TaskIns delivered to `node`
+    /// that have no result yet, making them immediately eligible for
+    /// redelivery. Returns how many tasks were released.
+    async fn release_tasks(&self, tenant: &str, node: &Node, task_ids: &[TaskId])
+        -> Result<u64>;
+
+    /// Clear `delivered_at` on TaskIns delivered longer than `lease`
+    /// ago without a matching TaskRes, making them eligible for
+    /// redelivery. Sweeps every tenant; tasks already delivered
+    /// `max_redeliveries` times (0 means no cap) are moved to the
+    /// dead-letter queue instead. Returns how many tasks were
+    /// released for redelivery and the tasks that were dead-lettered
+    /// instead, tagged with their tenant.
+    async fn release_expired_tasks(
+        &self,
+        lease: Duration,
+        max_redeliveries: u32,
+    ) -> Result<(u64, Vec<(String, DeadLetter)>)>;
+
+    /// One page of the dead-letter queue, ordered by `(dead_at, id)`;
+    /// the cursor's `created_at` field carries `dead_at`.
+    async fn list_dead_letters(
+        &self,
+        tenant: &str,
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<DeadLetter>>;
+
+    /// Register a new node with its key-value properties and supported
+    /// task types (empty means all) and return its id.
+    async fn create_node(
+        &self,
+        tenant: &str,
+        ping_interval: f64,
+        properties: &HashMap<String, String>,
+        task_types: &[String],
+    ) -> Result<NodeId>;
+
+    /// Register `count` nodes in one bulk insert, returning their ids;
+    /// all share the given ping interval, properties and task types.
+    async fn create_nodes(
+        &self,
+        tenant: &str,
+        count: u32,
+        ping_interval: f64,
+        properties: &HashMap<String, String>,
+        task_types: &[String],
+    ) -> Result<Vec<NodeId>>;
+
+    /// Refresh a previously registered node's record in place — its
+    /// lease, ping interval, properties and task types — keeping the
+    /// id. Returns `false` when the id is unknown (or was deleted) so
+    /// the caller can fall back to a fresh registration.
+    async fn reconnect_node(
+        &self,
+        tenant: &str,
+        node_id: NodeId,
+        ping_interval: f64,
+        properties: &HashMap<String, String>,
+        task_types: &[String],
+    ) -> Result<bool>;
+
+    /// Remove a node from the state; its unanswered TaskIns are moved
+    /// to the dead-letter queue.
+    async fn delete_node(&self, tenant: &str, node_id: NodeId) -> Result<()>;
+
+    /// Remove several nodes in one bulk delete; their unanswered
+    /// TaskIns are moved to the dead-letter queue.
+    async fn delete_nodes(&self, tenant: &str, node_ids: &[NodeId]) -> Result<()>;
+
+    /// Acknowledge a ping, refreshing `online_until`; a non-empty
+    /// `task_types` also replaces the node's declared task types.
+    /// Reports whether the lease was refreshed in time, had already
+    /// lapsed, or belongs to no known node.
+    async fn update_ping(
+        &self,
+        tenant: &str,
+        node: &Node,
+        ping_interval: f64,
+        task_types: &[String],
+    ) -> Result<PingOutcome>;
+
+    /// Refresh the pings of several nodes in one bulk update; each
+    /// entry pairs a node id with its ping interval. Returns how many
+    /// of the nodes were known and refreshed.
+    async fn update_pings(&self, tenant: &str, pings: &[(NodeId, f64)]) -> Result<u64>;
+
+    /// Record the client version string `node_id` reported, e.g. a
+    /// `flwr/x.y.z` header or the gRPC user-agent.
+    async fn record_client_version(&self, tenant: &str, node_id: NodeId, version: &str)
+        -> Result<()>;
+
+    /// Node counts per reported client version; nodes that never
+    /// reported one are counted under the empty string.
+    async fn client_versions(&self, tenant: &str) -> Result<HashMap<String, u64>>;
+
+    /// Record the auth identity (public key or certificate
+    /// fingerprint) `node_id` presented.
+    async fn record_node_identity(&self, tenant: &str, node_id: NodeId, identity: &str)
+        -> Result<()>;
+
+    /// The recorded identity of `node_id`: `None` for an unknown
+    /// node, an empty string for one that never presented an
+    /// identity.
+    async fn node_identity(&self, tenant: &str, node_id: NodeId) -> Result<Option<String>>;
+
+    /// Every registered node of the tenant with its identity and
+    /// liveness metadata, sorted by id, for the admin listing.
+    async fn list_nodes(&self, tenant: &str) -> Result<Vec<NodeInfo>>;
+
+    /// Ban a node: its row is removed and the id is rejected until the
+    /// ban is lifted.
+    async fn ban_node(&self, tenant: &str, node_id: NodeId, reason: &str) -> Result<()>;
+
+    /// Lift a ban issued via [`State::ban_node`].
+    async fn unban_node(&self, tenant: &str, node_id: NodeId) -> Result<()>;
+
+    /// Whether `node_id` is currently banned.
+    async fn is_node_banned(&self, tenant: &str, node_id: NodeId) -> Result<bool>;
+
+    /// All node ids currently online for `run_id` whose properties
+    /// contain every `selector` entry; empty when the run does not
+    /// exist.
+    async fn nodes(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        selector: &HashMap<String, String>,
+    ) -> Result<HashSet<NodeId>>;
+
+    /// A random sample of at most `count` online node ids for `run_id`;
+    /// the same `seed` yields the same sample. Empty when the run does
+    /// not exist.
+    async fn sample_nodes(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        count: u32,
+        seed: Option<u64>,
+        selector: &HashMap<String, String>,
+    ) -> Result<Vec<NodeId>>;
+
+    /// Number of nodes currently online, across every run of the
+    /// tenant.
+    async fn online_nodes(&self, tenant: &str) -> Result<u64>;
+
+    /// Append an event to the audit log.
+    async fn record_audit_event(&self, tenant: &str, event: &AuditEvent) -> Result<()>;
+
+    /// List audit events ordered by `(created_at, id)`, starting after
+    /// the cursor; at most `page_size` rows.
+    async fn list_audit_events(
+        &self,
+        tenant: &str,
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<AuditEvent>>;
+
+    /// Create a new run and return its id.
+    async fn create_run(&self, tenant: &str) -> Result<RunId>;
+
+    /// Delete `run_id` and every task stored for it.
+    async fn delete_run(&self, tenant: &str, run_id: RunId) -> Result<()>;
+
+    /// The run ids of a tenant, sorted ascending.
+    async fn runs(&self, tenant: &str) -> Result<Vec<RunId>>;
+
+    /// List task instructions for `run_id` matching every filter,
+    /// ordered by `(created_at, id)` and starting after the cursor; at
+    /// most `page_size` rows.
+    async fn list_task_ins(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        filters: &[TaskFilter],
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<TaskIns>>;
+
+    /// List task results for `run_id`, filtered and paginated like
+    /// [`State::list_task_ins`].
+    async fn list_task_res(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        filters: &[TaskFilter],
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<TaskRes>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_parse_against_a_whitelist() {
+        let filter = TaskFilter::parse("task_type", "eq", "train").unwrap();
+        assert!(filter.matches("", "train", NodeId(0), NodeId(0), 0.0));
+        assert!(!filter.matches("", "evaluate", NodeId(0), NodeId(0), 0.0));
+
+        let filter = TaskFilter::parse("created_at", "ge", "1.5").unwrap();
+        assert!(filter.matches("", "", NodeId(0), NodeId(0), 1.5));
+        assert!(!filter.matches("", "", NodeId(0), NodeId(0), 1.0));
+
+        assert!(TaskFilter::parse("recordset", "eq", "x")
+            .unwrap_err()
+            .contains("cannot be filtered on"));
+        assert!(TaskFilter::parse("task_type", "like", "x")
+            .unwrap_err()
+            .contains("unknown filter operator"));
+        assert!(TaskFilter::parse("consumer_node_id", "eq", "abc")
+            .unwrap_err()
+            .contains("must be an integer"));
+        assert!(TaskFilter::parse("created_at", "eq", "nan")
+            .unwrap_err()
+            .contains("must be a number"));
+    }
+}
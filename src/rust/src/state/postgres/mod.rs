@@ -0,0 +1,1640 @@
+//! Postgres-backed `State` implementation on top of diesel-async and a
+//! bb8 connection pool.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use diesel::debug_query;
+use diesel::dsl::{array, exists, not};
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_builder::QueryFragment;
+use diesel_async::pooled_connection::bb8::Pool;
+use diesel_async::pooled_connection::AsyncDieselConnectionManager;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use opentelemetry::metrics::{Histogram, Meter, Unit};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::config::LogSql;
+use crate::model::handler::{
+    datetime_from_secs, secs_from_datetime, AuditEvent, DeadLetter, GroupId, Node, NodeId,
+    NodeInfo, RunId, TaskId, TaskIns, TaskRes,
+};
+
+use super::{
+    matches_selector, Error, FilterOp, PingOutcome, Result, RunUsage, State, TaskCursor,
+    TaskField, TaskFilter, DEAD_LETTER_CONSUMER_DELETED,
+    DEAD_LETTER_REDELIVERY, ERROR_CODE_CONSUMER_DELETED, ERROR_CODE_DEAD_LETTERED,
+};
+
+pub mod models;
+pub mod partition;
+pub mod schema;
+
+use models::{
+    properties_from_json, properties_to_json, task_types_from_json, task_types_to_json,
+    parse_task_id, parse_task_ids, AuditEventRow, DeadLetterRow, NodeRow, TaskInsRow, TaskResRow,
+};
+use schema::{audit_log, banned_node, node, run, task_dead_letter, task_ins, task_res};
+
+/// Expand one whitelisted filter into the matching typed diesel
+/// predicate on a boxed list query; one arm per operator keeps every
+/// comparison a checked column expression rather than raw SQL.
+macro_rules! compare {
+    ($query:expr, $column:expr, $op:expr, $value:expr) => {
+        match $op {
+            FilterOp::Eq => $query.filter($column.eq($value)),
+            FilterOp::Ne => $query.filter($column.ne($value)),
+            FilterOp::Lt => $query.filter($column.lt($value)),
+            FilterOp::Le => $query.filter($column.le($value)),
+            FilterOp::Gt => $query.filter($column.gt($value)),
+            FilterOp::Ge => $query.filter($column.ge($value)),
+        }
+    };
+}
+
+/// Postgres state backend.
+#[derive(Clone)]
+pub struct Postgres {
+    pool: Pool<AsyncPgConnection>,
+    pool_wait_time: Option<Histogram<f64>>,
+    slow_query: Duration,
+    partition_by_run: bool,
+    log_sql: LogSql,
+    run_cache: Arc<Mutex<HashMap<(String, RunId), Instant>>>,
+    run_cache_ttl: Duration,
+    ping_grace: f64,
+}
+
+impl Postgres {
+    /// Connect a new pool against `database_uri`.
+    pub async fn new(database_uri: &str, pool_size: u32) -> Result<Self> {
+        let manager = AsyncDieselConnectionManager::<AsyncPgConnection>::new(database_uri);
+        let pool = Pool::builder()
+            .max_size(pool_size)
+            .build(manager)
+            .await
+            .map_err(|err| Error::Connection(err.to_string()))?;
+        Ok(Self {
+            pool,
+            pool_wait_time: None,
+            slow_query: Duration::ZERO,
+            partition_by_run: false,
+            log_sql: LogSql::Redacted,
+            run_cache: Arc::new(Mutex::new(HashMap::new())),
+            run_cache_ttl: Duration::ZERO,
+            ping_grace: 0.0,
+        })
+    }
+
+    /// Fraction of its own `ping_interval` a node may overshoot
+    /// `online_until` and still count as online, absorbing ping
+    /// jitter.
+    pub fn set_ping_grace(&mut self, ping_grace: f64) {
+        self.ping_grace = ping_grace;
+    }
+
+    /// Log state operations slower than `threshold` at WARN; zero
+    /// disables slow-query logging.
+    pub fn set_slow_query_threshold(&mut self, threshold: Duration) {
+        self.slow_query = threshold;
+    }
+
+    /// Manage one partition per run on the task tables; requires a
+    /// prior [`partition::convert`] of the schema.
+    pub fn set_partition_by_run(&mut self, enabled: bool) {
+        self.partition_by_run = enabled;
+    }
+
+    /// What the debug-level SQL statement logs may contain.
+    pub fn set_log_sql(&mut self, log_sql: LogSql) {
+        self.log_sql = log_sql;
+    }
+
+    /// Serve run existence checks from an in-process cache for `ttl`;
+    /// zero disables the cache.
+    pub fn set_run_cache_ttl(&mut self, ttl: Duration) {
+        self.run_cache_ttl = ttl;
+    }
+
+    /// Whether `(tenant, run_id)` exists, consulting the run cache
+    /// first. Only existence is cached — runs are created once and
+    /// deleted rarely — and `delete_run` evicts its entry; the TTL
+    /// bounds staleness across replicas.
+    async fn run_exists(
+        &self,
+        conn: &mut bb8::PooledConnection<'_, AsyncDieselConnectionManager<AsyncPgConnection>>,
+        tenant: &str,
+        run_id: RunId,
+    ) -> Result<bool> {
+        if !self.run_cache_ttl.is_zero() {
+            let cache = self.run_cache.lock().unwrap();
+            if let Some(stored_at) = cache.get(&(tenant.to_owned(), run_id)) {
+                if stored_at.elapsed() < self.run_cache_ttl {
+                    return Ok(true);
+                }
+            }
+        }
+        let count: i64 = run::table
+            .filter(run::id.eq(run_id.0))
+            .filter(run::tenant.eq(tenant))
+            .count()
+            .get_result_traced(conn)
+            .await?;
+        if count > 0 && !self.run_cache_ttl.is_zero() {
+            let mut cache = self.run_cache.lock().unwrap();
+            let ttl = self.run_cache_ttl;
+            cache.retain(|_, stored_at| stored_at.elapsed() < ttl);
+            cache.insert((tenant.to_owned(), run_id), Instant::now());
+        }
+        Ok(count > 0)
+    }
+
+    /// Log `query` at debug level, honouring the `log_sql` policy.
+    fn log_query<Q: QueryFragment<Pg>>(&self, query: &Q, message: &'static str) {
+        match self.log_sql {
+            LogSql::Off => {}
+            LogSql::Redacted => tracing::debug!(query = %redacted_statement(query), "{message}"),
+            LogSql::Full => tracing::debug!(query = %debug_query::<Pg, _>(query), "{message}"),
+        }
+    }
+
+    fn slow_query_guard(&self, operation: &'static str) -> SlowQueryGuard {
+        SlowQueryGuard {
+            operation,
+            threshold: self.slow_query,
+            started: Instant::now(),
+            rows: None,
+        }
+    }
+
+    /// Move the given TaskIns rows to the dead-letter queue and
+    /// synthesize an error TaskRes for each, in one transaction so a
+    /// crash cannot drop or duplicate them.
+    async fn dead_letter(
+        &self,
+        conn: &mut bb8::PooledConnection<'_, AsyncDieselConnectionManager<AsyncPgConnection>>,
+        rows: &[TaskInsRow],
+        reason: &str,
+        error_code: i64,
+    ) -> Result<Vec<(String, DeadLetter)>> {
+        let now = Utc::now();
+        let dead_at = secs_from_datetime(now);
+        let parked: Vec<DeadLetterRow> = rows
+            .iter()
+            .map(|row| DeadLetterRow::from_task(row, dead_at, reason))
+            .collect();
+        let failures: Vec<TaskResRow> = rows
+            .iter()
+            .map(|row| error_task_res(row, now, reason, error_code))
+            .collect();
+        let ids: Vec<Uuid> = rows.iter().map(|row| row.id).collect();
+        conn.transaction(|conn| {
+            async move {
+                diesel::insert_into(task_dead_letter::table)
+                    .values(&parked)
+                    .on_conflict_do_nothing()
+                    .execute_traced(conn)
+                    .await?;
+                diesel::insert_into(task_res::table)
+                    .values(&failures)
+                    .execute_traced(conn)
+                    .await?;
+                diesel::delete(task_ins::table.filter(task_ins::id.eq_any(&ids)))
+                    .execute_traced(conn)
+                    .await?;
+                Ok::<_, diesel::result::Error>(())
+            }
+            .scope_boxed()
+        })
+        .await?;
+        tracing::warn!(tasks = rows.len(), reason, "tasks moved to the dead-letter queue");
+        Ok(parked
+            .into_iter()
+            .map(|row| (row.tenant.clone(), DeadLetter::from(row)))
+            .collect())
+    }
+
+    /// Register connection pool gauges and the acquisition wait-time
+    /// histogram on `meter`, making database saturation visible before
+    /// requests start failing.
+    pub fn register_metrics(&mut self, meter: &Meter) {
+        let pool = self.pool.clone();
+        meter
+            .u64_observable_gauge("db.pool.connections")
+            .with_description("Open connections in the bb8 pool")
+            .with_callback(move |observer| {
+                observer.observe(u64::from(pool.state().connections), &[]);
+            })
+            .init();
+        let pool = self.pool.clone();
+        meter
+            .u64_observable_gauge("db.pool.idle_connections")
+            .with_description("Idle connections in the bb8 pool")
+            .with_callback(move |observer| {
+                observer.observe(u64::from(pool.state().idle_connections), &[]);
+            })
+            .init();
+        self.pool_wait_time = Some(
+            meter
+                .f64_histogram("db.pool.wait_time")
+                .with_unit(Unit::new("s"))
+                .with_description("Time spent waiting for a pooled connection")
+                .init(),
+        );
+    }
+
+    async fn conn(
+        &self,
+    ) -> Result<bb8::PooledConnection<'_, AsyncDieselConnectionManager<AsyncPgConnection>>> {
+        let started = Instant::now();
+        let conn = self.pool.get().await;
+        if let Some(wait_time) = &self.pool_wait_time {
+            wait_time.record(started.elapsed().as_secs_f64(), &[]);
+        }
+        conn.map_err(|err| Error::Connection(err.to_string()))
+    }
+}
+
+/// Logs a WARN when a state operation outlives the slow-query
+/// threshold; dropped at the end of each `State` method, so failed
+/// operations are covered too.
+struct SlowQueryGuard {
+    operation: &'static str,
+    threshold: Duration,
+    started: Instant,
+    rows: Option<usize>,
+}
+
+impl SlowQueryGuard {
+    fn rows(&mut self, rows: usize) {
+        self.rows = Some(rows);
+    }
+}
+
+impl Drop for SlowQueryGuard {
+    fn drop(&mut self) {
+        if self.threshold.is_zero() {
+            return;
+        }
+        let elapsed = self.started.elapsed();
+        if elapsed < self.threshold {
+            return;
+        }
+        let elapsed_ms = elapsed.as_millis() as u64;
+        match self.rows {
+            Some(rows) => tracing::warn!(
+                operation = self.operation,
+                elapsed_ms,
+                rows,
+                "slow state operation"
+            ),
+            None => tracing::warn!(operation = self.operation, elapsed_ms, "slow state operation"),
+        }
+    }
+}
+
+
+/// Child span for one statement, following the OpenTelemetry database
+/// conventions. The statement text comes from `debug_query` with the
+/// bind values stripped, so recordset bytes and other payloads never
+/// reach the trace backend.
+/// `debug_query` output with the bind values stripped.
+fn redacted_statement<Q: QueryFragment<Pg>>(query: &Q) -> String {
+    let sql = debug_query::<Pg, _>(query).to_string();
+    sql.split(" -- binds:").next().unwrap_or(sql.as_str()).to_owned()
+}
+
+fn query_span<Q: QueryFragment<Pg>>(query: &Q) -> tracing::Span {
+    let span = tracing::debug_span!(
+        "db.query",
+        db.system = "postgresql",
+        db.statement = tracing::field::Empty,
+    );
+    if !span.is_disabled() {
+        span.record("db.statement", redacted_statement(query).as_str());
+    }
+    span
+}
+
+/// Runs diesel queries inside a [`query_span`] child span, so traces
+/// show where time is spent inside a single RPC. Drop-in replacements
+/// for the `RunQueryDsl` methods of the same name.
+trait TracedDsl<C>: QueryFragment<Pg> + Sized
+where
+    C: AsyncConnection<Backend = Pg> + Send,
+{
+    async fn execute_traced(self, conn: &mut C) -> diesel::QueryResult<usize>
+    where
+        Self: diesel_async::methods::ExecuteDsl<C>,
+    {
+        let span = query_span(&self);
+        self.execute(conn).instrument(span).await
+    }
+
+    async fn load_traced<'query, U>(self, conn: &mut C) -> diesel::QueryResult<Vec<U>>
+    where
+        U: Send,
+        Self: diesel_async::methods::LoadQuery<'query, C, U> + 'query,
+    {
+        let span = query_span(&self);
+        self.load(conn).instrument(span).await
+    }
+
+    async fn get_result_traced<'query, U>(self, conn: &mut C) -> diesel::QueryResult<U>
+    where
+        U: Send,
+        Self: diesel_async::methods::LoadQuery<'query, C, U> + 'query,
+    {
+        let span = query_span(&self);
+        self.get_result(conn).instrument(span).await
+    }
+
+    async fn get_results_traced<'query, U>(self, conn: &mut C) -> diesel::QueryResult<Vec<U>>
+    where
+        U: Send,
+        Self: diesel_async::methods::LoadQuery<'query, C, U> + 'query,
+    {
+        self.load_traced(conn).await
+    }
+
+    async fn first_traced<'query, U>(self, conn: &mut C) -> diesel::QueryResult<U>
+    where
+        U: Send,
+        Self: diesel::query_dsl::methods::LimitDsl,
+        diesel::dsl::Limit<Self>:
+            diesel_async::methods::LoadQuery<'query, C, U> + QueryFragment<Pg> + Send + 'query,
+    {
+        let query = diesel::query_dsl::methods::LimitDsl::limit(self, 1);
+        let span = query_span(&query);
+        query.get_result(conn).instrument(span).await
+    }
+}
+
+impl<C, T> TracedDsl<C> for T
+where
+    C: AsyncConnection<Backend = Pg> + Send,
+    T: QueryFragment<Pg> + Sized,
+{
+}
+
+fn now_secs() -> f64 {
+    Utc::now().timestamp_micros() as f64 / 1e6
+}
+
+/// The TaskRes synthesized when `row` fails permanently, so drivers
+/// pulling results learn about the failure instead of waiting forever.
+fn error_task_res(
+    row: &TaskInsRow,
+    dead_at: DateTime<Utc>,
+    reason: &str,
+    error_code: i64,
+) -> TaskResRow {
+    TaskResRow {
+        id: Uuid::new_v4(),
+        group_id: row.group_id.clone(),
+        run_id: row.run_id,
+        producer_anonymous: row.consumer_anonymous,
+        producer_node_id: row.consumer_node_id,
+        consumer_anonymous: row.producer_anonymous,
+        consumer_node_id: row.producer_node_id,
+        created_at: dead_at,
+        delivered_at: None,
+        pushed_at: dead_at,
+        ttl: String::new(),
+        ancestry: vec![row.id],
+        task_type: row.task_type.clone(),
+        recordset: Vec::new(),
+        tenant: row.tenant.clone(),
+        recordset_checksum: String::new(),
+        error_code,
+        error_reason: reason.to_owned(),
+    }
+}
+
+#[async_trait]
+impl State for Postgres {
+    async fn insert_task_instructions(
+        &self,
+        tenant: &str,
+        instructions: &[TaskIns],
+    ) -> Result<Vec<TaskId>> {
+        let mut guard = self.slow_query_guard("insert_task_instructions");
+        let mut conn = self.conn().await?;
+        let mut stored = Vec::with_capacity(instructions.len());
+        for instruction in instructions {
+            if !self.run_exists(&mut conn, tenant, instruction.run_id).await? {
+                return Err(Error::UnknownRun(instruction.run_id));
+            }
+            let mut row = TaskInsRow::from(instruction);
+            row.tenant = tenant.to_owned();
+            let query = diesel::insert_into(task_ins::table).values(&row);
+            self.log_query(&query, "insert task_ins");
+            query.execute_traced(&mut conn).await?;
+            stored.push(instruction.id.clone());
+        }
+        guard.rows(stored.len());
+        Ok(stored)
+    }
+
+    async fn task_instructions(
+        &self,
+        tenant: &str,
+        node: &Node,
+        limit: Option<u32>,
+    ) -> Result<Vec<TaskIns>> {
+        let mut guard = self.slow_query_guard("task_instructions");
+        let mut conn = self.conn().await?;
+        let supported: Option<Vec<String>> = if node.anonymous {
+            None
+        } else {
+            node::table
+                .filter(node::tenant.eq(tenant))
+                .filter(node::id.eq(node.id.0))
+                .select(node::task_types)
+                .first_traced::<String>(&mut conn)
+                .await
+                .optional()?
+                .map(|json| task_types_from_json(&json))
+                .filter(|task_types| !task_types.is_empty())
+        };
+        // A single UPDATE ... RETURNING keeps select-and-mark atomic:
+        // if the client disconnects mid-request there is no open
+        // transaction to leak, the statement either applies in full or
+        // not at all.
+        let mut candidates = task_ins::table
+            .filter(task_ins::tenant.eq(tenant))
+            .filter(task_ins::delivered_at.is_null())
+            .order(task_ins::created_at.asc())
+            .into_boxed();
+        candidates = if node.anonymous {
+            candidates
+                .filter(task_ins::consumer_anonymous.eq(true))
+                .filter(task_ins::consumer_node_id.eq(0))
+        } else {
+            candidates
+                .filter(task_ins::consumer_anonymous.eq(false))
+                .filter(task_ins::consumer_node_id.eq(node.id.0))
+        };
+        if let Some(supported) = &supported {
+            candidates = candidates.filter(task_ins::task_type.eq_any(supported));
+        }
+        if let Some(limit) = limit {
+            candidates = candidates.limit(i64::from(limit));
+        }
+        let marked = task_ins::table.filter(task_ins::id.eq_any(candidates.select(task_ins::id)));
+        let mut rows: Vec<TaskInsRow> = diesel::update(marked)
+            .set((
+                task_ins::delivered_at.eq(Utc::now()),
+                task_ins::delivery_count.eq(task_ins::delivery_count + 1),
+            ))
+            .get_results_traced(&mut conn)
+            .await?;
+        // RETURNING does not guarantee an order; restore delivery order.
+        rows.sort_by_key(|row| row.created_at);
+        guard.rows(rows.len());
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    async fn claim_pool_task_instructions(
+        &self,
+        tenant: &str,
+        node: &Node,
+        limit: Option<u32>,
+    ) -> Result<Vec<TaskIns>> {
+        let mut guard = self.slow_query_guard("claim_pool_task_instructions");
+        if node.anonymous {
+            return Ok(Vec::new());
+        }
+        let mut conn = self.conn().await?;
+        let supported: Option<Vec<String>> = node::table
+            .filter(node::tenant.eq(tenant))
+            .filter(node::id.eq(node.id.0))
+            .select(node::task_types)
+            .first_traced::<String>(&mut conn)
+            .await
+            .optional()?
+            .map(|json| task_types_from_json(&json))
+            .filter(|task_types| !task_types.is_empty());
+        let limit = limit.map_or(i64::MAX, i64::from);
+        let tenant = tenant.to_owned();
+        // SELECT ... FOR UPDATE SKIP LOCKED lets concurrent claimers
+        // each take disjoint rows without blocking on one another.
+        let mut rows: Vec<TaskInsRow> = conn
+            .transaction(|conn| {
+                async move {
+                    let ids: Vec<Uuid> = if let Some(supported) = &supported {
+                        task_ins::table
+                            .filter(task_ins::tenant.eq(&tenant))
+                            .filter(task_ins::delivered_at.is_null())
+                            .filter(task_ins::consumer_anonymous.eq(true))
+                            .filter(task_ins::consumer_node_id.eq(0))
+                            .filter(task_ins::task_type.eq_any(supported))
+                            .order(task_ins::created_at.asc())
+                            .limit(limit)
+                            .select(task_ins::id)
+                            .for_update()
+                            .skip_locked()
+                            .load_traced(conn)
+                            .await?
+                    } else {
+                        task_ins::table
+                            .filter(task_ins::tenant.eq(&tenant))
+                            .filter(task_ins::delivered_at.is_null())
+                            .filter(task_ins::consumer_anonymous.eq(true))
+                            .filter(task_ins::consumer_node_id.eq(0))
+                            .order(task_ins::created_at.asc())
+                            .limit(limit)
+                            .select(task_ins::id)
+                            .for_update()
+                            .skip_locked()
+                            .load_traced(conn)
+                            .await?
+                    };
+                    diesel::update(task_ins::table.filter(task_ins::id.eq_any(&ids)))
+                        .set((
+                            task_ins::delivered_at.eq(Utc::now()),
+                            task_ins::delivery_count.eq(task_ins::delivery_count + 1),
+                        ))
+                        .get_results_traced(conn)
+                        .await
+                }
+                .scope_boxed()
+            })
+            .await?;
+        rows.sort_by_key(|row| row.created_at);
+        guard.rows(rows.len());
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    async fn insert_task_results(
+        &self,
+        tenant: &str,
+        results: &[TaskRes],
+    ) -> Result<Vec<TaskId>> {
+        let mut guard = self.slow_query_guard("insert_task_results");
+        let mut conn = self.conn().await?;
+        let mut stored = Vec::with_capacity(results.len());
+        for result in results {
+            if !self.run_exists(&mut conn, tenant, result.run_id).await? {
+                return Err(Error::UnknownRun(result.run_id));
+            }
+            let mut row = TaskResRow::from(result);
+            row.tenant = tenant.to_owned();
+            let query = diesel::insert_into(task_res::table).values(&row);
+            self.log_query(&query, "insert task_res");
+            query.execute_traced(&mut conn).await?;
+            stored.push(result.id.clone());
+        }
+        guard.rows(stored.len());
+        Ok(stored)
+    }
+
+    async fn task_results(
+        &self,
+        tenant: &str,
+        task_ids: &[TaskId],
+        limit: Option<u32>,
+        mark: bool,
+    ) -> Result<Vec<TaskRes>> {
+        let mut guard = self.slow_query_guard("task_results");
+        let mut conn = self.conn().await?;
+        let task_ids = parse_task_ids(task_ids);
+        let mut candidates = task_res::table
+            .filter(task_res::tenant.eq(tenant))
+            .filter(task_res::delivered_at.is_null())
+            .filter(task_res::ancestry.overlaps_with(&task_ids))
+            .order(task_res::created_at.asc())
+            .into_boxed();
+        if let Some(limit) = limit {
+            candidates = candidates.limit(i64::from(limit));
+        }
+        if !mark {
+            let rows: Vec<TaskResRow> = candidates.load_traced(&mut conn).await?;
+            guard.rows(rows.len());
+            return Ok(rows.into_iter().map(Into::into).collect());
+        }
+        let marked = task_res::table.filter(task_res::id.eq_any(candidates.select(task_res::id)));
+        let mut rows: Vec<TaskResRow> = diesel::update(marked)
+            .set(task_res::delivered_at.eq(Utc::now()))
+            .get_results_traced(&mut conn)
+            .await?;
+        // RETURNING does not guarantee an order; restore delivery order.
+        rows.sort_by_key(|row| row.created_at);
+        guard.rows(rows.len());
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    async fn release_tasks(&self, tenant: &str, node: &Node, task_ids: &[TaskId]) -> Result<u64> {
+        let mut guard = self.slow_query_guard("release_tasks");
+        let mut conn = self.conn().await?;
+        let task_ids = parse_task_ids(task_ids);
+        let mut target = task_ins::table
+            .filter(task_ins::tenant.eq(tenant))
+            .filter(task_ins::id.eq_any(&task_ids))
+            .filter(task_ins::delivered_at.is_not_null())
+            .filter(not(exists(
+                task_res::table.filter(task_res::ancestry.contains(array((task_ins::id,)))),
+            )))
+            .into_boxed();
+        target = if node.anonymous {
+            target
+                .filter(task_ins::consumer_anonymous.eq(true))
+                .filter(task_ins::consumer_node_id.eq(0))
+        } else {
+            target
+                .filter(task_ins::consumer_anonymous.eq(false))
+                .filter(task_ins::consumer_node_id.eq(node.id.0))
+        };
+        let marked = task_ins::table.filter(task_ins::id.eq_any(target.select(task_ins::id)));
+        let released = diesel::update(marked)
+            .set(task_ins::delivered_at.eq(None::<DateTime<Utc>>))
+            .execute_traced(&mut conn)
+            .await?;
+        guard.rows(released);
+        Ok(released as u64)
+    }
+
+    async fn release_expired_tasks(
+        &self,
+        lease: Duration,
+        max_redeliveries: u32,
+    ) -> Result<(u64, Vec<(String, DeadLetter)>)> {
+        let mut guard = self.slow_query_guard("release_expired_tasks");
+        let mut conn = self.conn().await?;
+        let cutoff = Utc::now()
+            - chrono::Duration::milliseconds(i64::try_from(lease.as_millis()).unwrap_or(i64::MAX));
+        let unanswered = task_ins::table
+            .filter(task_ins::delivered_at.lt(cutoff))
+            .filter(not(exists(
+                task_res::table.filter(task_res::ancestry.contains(array((task_ins::id,)))),
+            )));
+        let mut dead = Vec::new();
+        let released = if max_redeliveries > 0 {
+            diesel::update(
+                unanswered.filter(task_ins::delivery_count.lt(max_redeliveries as i32)),
+            )
+            .set(task_ins::delivered_at.eq(None::<DateTime<Utc>>))
+            .execute_traced(&mut conn)
+            .await?
+        } else {
+            diesel::update(unanswered)
+                .set(task_ins::delivered_at.eq(None::<DateTime<Utc>>))
+                .execute_traced(&mut conn)
+                .await?
+        };
+        if max_redeliveries > 0 {
+            let exhausted: Vec<TaskInsRow> = task_ins::table
+                .filter(task_ins::delivered_at.lt(cutoff))
+                .filter(not(exists(
+                task_res::table.filter(task_res::ancestry.contains(array((task_ins::id,)))),
+            )))
+                .filter(task_ins::delivery_count.ge(max_redeliveries as i32))
+                .load_traced(&mut conn)
+                .await?;
+            if !exhausted.is_empty() {
+                dead = self
+                    .dead_letter(
+                        &mut conn,
+                        &exhausted,
+                        DEAD_LETTER_REDELIVERY,
+                        ERROR_CODE_DEAD_LETTERED,
+                    )
+                    .await?;
+            }
+        }
+        guard.rows(released);
+        Ok((released as u64, dead))
+    }
+
+    async fn task_ins_consumers(
+        &self,
+        tenant: &str,
+        task_ids: &[TaskId],
+    ) -> Result<HashMap<TaskId, (RunId, Node)>> {
+        let mut guard = self.slow_query_guard("task_ins_consumers");
+        if task_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let mut conn = self.conn().await?;
+        let task_ids = parse_task_ids(task_ids);
+        let rows: Vec<(Uuid, i64, i64, bool)> = task_ins::table
+            .filter(task_ins::tenant.eq(tenant))
+            .filter(task_ins::id.eq_any(&task_ids))
+            .select((
+                task_ins::id,
+                task_ins::run_id,
+                task_ins::consumer_node_id,
+                task_ins::consumer_anonymous,
+            ))
+            .load_traced(&mut conn)
+            .await?;
+        guard.rows(rows.len());
+        Ok(rows
+            .into_iter()
+            .map(|(id, run_id, consumer_node_id, consumer_anonymous)| {
+                (
+                    TaskId(id.to_string()),
+                    (
+                        RunId(run_id),
+                        Node {
+                            id: NodeId(consumer_node_id),
+                            anonymous: consumer_anonymous,
+                        },
+                    ),
+                )
+            })
+            .collect())
+    }
+
+    async fn pending_task_ins(&self, tenant: &str, consumer: &Node) -> Result<u64> {
+        let _guard = self.slow_query_guard("pending_task_ins");
+        let mut conn = self.conn().await?;
+        let mut query = task_ins::table
+            .filter(task_ins::tenant.eq(tenant))
+            .filter(task_ins::delivered_at.is_null())
+            .into_boxed();
+        query = if consumer.anonymous {
+            query
+                .filter(task_ins::consumer_anonymous.eq(true))
+                .filter(task_ins::consumer_node_id.eq(0))
+        } else {
+            query
+                .filter(task_ins::consumer_anonymous.eq(false))
+                .filter(task_ins::consumer_node_id.eq(consumer.id.0))
+        };
+        let pending: i64 = query.count().get_result_traced(&mut conn).await?;
+        Ok(pending as u64)
+    }
+
+    async fn pending_run_task_ins(&self, tenant: &str, run_id: RunId) -> Result<u64> {
+        let _guard = self.slow_query_guard("pending_run_task_ins");
+        let mut conn = self.conn().await?;
+        let pending: i64 = task_ins::table
+            .filter(task_ins::tenant.eq(tenant))
+            .filter(task_ins::run_id.eq(run_id.0))
+            .filter(task_ins::delivered_at.is_null())
+            .count()
+            .get_result_traced(&mut conn)
+            .await?;
+        Ok(pending as u64)
+    }
+
+    async fn group_progress(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        group_id: &GroupId,
+    ) -> Result<(u64, u64)> {
+        let _guard = self.slow_query_guard("group_progress");
+        let mut conn = self.conn().await?;
+        let instructions: i64 = task_ins::table
+            .filter(task_ins::tenant.eq(tenant))
+            .filter(task_ins::run_id.eq(run_id.0))
+            .filter(task_ins::group_id.eq(group_id.as_ref()))
+            .count()
+            .get_result_traced(&mut conn)
+            .await?;
+        let results: i64 = task_res::table
+            .filter(task_res::tenant.eq(tenant))
+            .filter(task_res::run_id.eq(run_id.0))
+            .filter(task_res::group_id.eq(group_id.as_ref()))
+            .count()
+            .get_result_traced(&mut conn)
+            .await?;
+        Ok((instructions as u64, results as u64))
+    }
+
+    async fn run_progress(&self, tenant: &str, run_id: RunId) -> Result<Vec<(GroupId, u64, u64)>> {
+        let mut guard = self.slow_query_guard("run_progress");
+        let mut conn = self.conn().await?;
+        let instructions: Vec<(String, i64)> = task_ins::table
+            .filter(task_ins::tenant.eq(tenant))
+            .filter(task_ins::run_id.eq(run_id.0))
+            .group_by(task_ins::group_id)
+            .select((task_ins::group_id, diesel::dsl::count_star()))
+            .load_traced(&mut conn)
+            .await?;
+        let results: Vec<(String, i64)> = task_res::table
+            .filter(task_res::tenant.eq(tenant))
+            .filter(task_res::run_id.eq(run_id.0))
+            .group_by(task_res::group_id)
+            .select((task_res::group_id, diesel::dsl::count_star()))
+            .load_traced(&mut conn)
+            .await?;
+        let mut groups: HashMap<GroupId, (u64, u64)> = HashMap::new();
+        for (group_id, count) in instructions {
+            groups.entry(GroupId(group_id)).or_default().0 = count as u64;
+        }
+        for (group_id, count) in results {
+            groups.entry(GroupId(group_id)).or_default().1 = count as u64;
+        }
+        let mut progress: Vec<(GroupId, u64, u64)> = groups
+            .into_iter()
+            .map(|(group_id, (instructions, results))| (group_id, instructions, results))
+            .collect();
+        progress.sort();
+        guard.rows(progress.len());
+        Ok(progress)
+    }
+
+    async fn run_usage(&self, tenant: &str, run_id: RunId) -> Result<RunUsage> {
+        let _guard = self.slow_query_guard("run_usage");
+        let mut conn = self.conn().await?;
+        let sum_bytes = || {
+            diesel::dsl::sql::<diesel::sql_types::BigInt>(
+                "COALESCE(SUM(OCTET_LENGTH(recordset)), 0)",
+            )
+        };
+        let (tasks, ins_bytes): (i64, i64) = task_ins::table
+            .filter(task_ins::tenant.eq(tenant))
+            .filter(task_ins::run_id.eq(run_id.0))
+            .select((diesel::dsl::count_star(), sum_bytes()))
+            .get_result_traced(&mut conn)
+            .await?;
+        let res_bytes: i64 = task_res::table
+            .filter(task_res::tenant.eq(tenant))
+            .filter(task_res::run_id.eq(run_id.0))
+            .select(sum_bytes())
+            .get_result_traced(&mut conn)
+            .await?;
+        let consumers: Vec<i64> = task_ins::table
+            .filter(task_ins::tenant.eq(tenant))
+            .filter(task_ins::run_id.eq(run_id.0))
+            .filter(task_ins::consumer_anonymous.eq(false))
+            .select(task_ins::consumer_node_id)
+            .distinct()
+            .load_traced(&mut conn)
+            .await?;
+        Ok(RunUsage {
+            tasks: tasks as u64,
+            recordset_bytes: (ins_bytes + res_bytes) as u64,
+            consumers: consumers.into_iter().map(NodeId).collect(),
+        })
+    }
+
+    async fn delete_tasks(&self, tenant: &str, task_ids: &[TaskId]) -> Result<()> {
+        let _guard = self.slow_query_guard("delete_tasks");
+        if task_ids.is_empty() {
+            return Ok(());
+        }
+        let mut conn = self.conn().await?;
+        let task_ids = parse_task_ids(task_ids);
+        let tenant = tenant.to_owned();
+        conn.transaction(|conn| {
+            async move {
+                diesel::delete(
+                    task_ins::table
+                        .filter(task_ins::tenant.eq(&tenant))
+                        .filter(task_ins::id.eq_any(&task_ids))
+                        .filter(task_ins::delivered_at.is_not_null()),
+                )
+                .execute_traced(conn)
+                .await?;
+                diesel::delete(
+                    task_res::table
+                        .filter(task_res::tenant.eq(&tenant))
+                        .filter(task_res::ancestry.overlaps_with(&task_ids))
+                        .filter(task_res::delivered_at.is_not_null()),
+                )
+                .execute_traced(conn)
+                .await?;
+                Ok::<_, diesel::result::Error>(())
+            }
+            .scope_boxed()
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn create_node(
+        &self,
+        tenant: &str,
+        ping_interval: f64,
+        properties: &HashMap<String, String>,
+        task_types: &[String],
+    ) -> Result<NodeId> {
+        let _guard = self.slow_query_guard("create_node");
+        let mut conn = self.conn().await?;
+        let node_id: i64 = rand::thread_rng().gen();
+        let row = NodeRow {
+            id: node_id,
+            online_until: now_secs() + ping_interval,
+            ping_interval,
+            tenant: tenant.to_owned(),
+            properties: properties_to_json(properties),
+            task_types: task_types_to_json(task_types),
+            client_version: String::new(),
+            identity: String::new(),
+        };
+        diesel::insert_into(node::table)
+            .values(&row)
+            .execute_traced(&mut conn)
+            .await?;
+        Ok(NodeId(node_id))
+    }
+
+    async fn create_nodes(
+        &self,
+        tenant: &str,
+        count: u32,
+        ping_interval: f64,
+        properties: &HashMap<String, String>,
+        task_types: &[String],
+    ) -> Result<Vec<NodeId>> {
+        let mut guard = self.slow_query_guard("create_nodes");
+        let mut conn = self.conn().await?;
+        let online_until = now_secs() + ping_interval;
+        let rows: Vec<NodeRow> = (0..count)
+            .map(|_| NodeRow {
+                id: rand::thread_rng().gen(),
+                online_until,
+                ping_interval,
+                tenant: tenant.to_owned(),
+                properties: properties_to_json(properties),
+                task_types: task_types_to_json(task_types),
+                client_version: String::new(),
+                identity: String::new(),
+            })
+            .collect();
+        diesel::insert_into(node::table)
+            .values(&rows)
+            .execute_traced(&mut conn)
+            .await?;
+        guard.rows(rows.len());
+        Ok(rows.into_iter().map(|row| NodeId(row.id)).collect())
+    }
+
+    async fn reconnect_node(
+        &self,
+        tenant: &str,
+        node_id: NodeId,
+        ping_interval: f64,
+        properties: &HashMap<String, String>,
+        task_types: &[String],
+    ) -> Result<bool> {
+        let _guard = self.slow_query_guard("reconnect_node");
+        let mut conn = self.conn().await?;
+        let updated = diesel::update(
+            node::table
+                .filter(node::tenant.eq(tenant))
+                .filter(node::id.eq(node_id.0)),
+        )
+        .set((
+            node::online_until.eq(now_secs() + ping_interval),
+            node::ping_interval.eq(ping_interval),
+            node::properties.eq(properties_to_json(properties)),
+            node::task_types.eq(task_types_to_json(task_types)),
+        ))
+        .execute_traced(&mut conn)
+        .await?;
+        Ok(updated > 0)
+    }
+
+    async fn delete_node(&self, tenant: &str, node_id: NodeId) -> Result<()> {
+        let _guard = self.slow_query_guard("delete_node");
+        let mut conn = self.conn().await?;
+        let orphaned: Vec<TaskInsRow> = task_ins::table
+            .filter(task_ins::tenant.eq(tenant))
+            .filter(task_ins::consumer_anonymous.eq(false))
+            .filter(task_ins::consumer_node_id.eq(node_id.0))
+            .filter(not(exists(
+                task_res::table.filter(task_res::ancestry.contains(array((task_ins::id,)))),
+            )))
+            .load_traced(&mut conn)
+            .await?;
+        if !orphaned.is_empty() {
+            self.dead_letter(
+                &mut conn,
+                &orphaned,
+                DEAD_LETTER_CONSUMER_DELETED,
+                ERROR_CODE_CONSUMER_DELETED,
+            )
+            .await?;
+        }
+        diesel::delete(
+            node::table
+                .filter(node::tenant.eq(tenant))
+                .filter(node::id.eq(node_id.0)),
+        )
+        .execute_traced(&mut conn)
+        .await?;
+        Ok(())
+    }
+
+    async fn delete_nodes(&self, tenant: &str, node_ids: &[NodeId]) -> Result<()> {
+        let mut guard = self.slow_query_guard("delete_nodes");
+        let mut conn = self.conn().await?;
+        let node_ids: Vec<i64> = node_ids.iter().map(|node_id| node_id.0).collect();
+        let orphaned: Vec<TaskInsRow> = task_ins::table
+            .filter(task_ins::tenant.eq(tenant))
+            .filter(task_ins::consumer_anonymous.eq(false))
+            .filter(task_ins::consumer_node_id.eq_any(&node_ids))
+            .filter(not(exists(
+                task_res::table.filter(task_res::ancestry.contains(array((task_ins::id,)))),
+            )))
+            .load_traced(&mut conn)
+            .await?;
+        if !orphaned.is_empty() {
+            self.dead_letter(
+                &mut conn,
+                &orphaned,
+                DEAD_LETTER_CONSUMER_DELETED,
+                ERROR_CODE_CONSUMER_DELETED,
+            )
+            .await?;
+        }
+        let deleted = diesel::delete(
+            node::table
+                .filter(node::tenant.eq(tenant))
+                .filter(node::id.eq_any(&node_ids)),
+        )
+        .execute_traced(&mut conn)
+        .await?;
+        guard.rows(deleted);
+        Ok(())
+    }
+
+    async fn update_ping(
+        &self,
+        tenant: &str,
+        node: &Node,
+        ping_interval: f64,
+        task_types: &[String],
+    ) -> Result<PingOutcome> {
+        let _guard = self.slow_query_guard("update_ping");
+        let mut conn = self.conn().await?;
+        let now = now_secs();
+        // Read the lease being replaced in the same transaction, so a
+        // ping arriving after the lease lapsed can be told apart.
+        let tenant = tenant.to_owned();
+        let node = *node;
+        let task_types = task_types.to_vec();
+        let previous: Option<f64> = conn
+            .transaction(|conn| {
+                async move {
+                    let previous: Option<f64> = node::table
+                        .filter(node::tenant.eq(&tenant))
+                        .filter(node::id.eq(node.id.0))
+                        .select(node::online_until)
+                        .first_traced(conn)
+                        .await
+                        .optional()?;
+                    if previous.is_none() {
+                        return Ok(None);
+                    }
+                    diesel::update(
+                        node::table
+                            .filter(node::tenant.eq(&tenant))
+                            .filter(node::id.eq(node.id.0)),
+                    )
+                    .set((
+                        node::online_until.eq(now + ping_interval),
+                        node::ping_interval.eq(ping_interval),
+                    ))
+                    .execute_traced(conn)
+                    .await?;
+                    if !task_types.is_empty() && !node.anonymous {
+                        diesel::update(
+                            node::table
+                                .filter(node::tenant.eq(&tenant))
+                                .filter(node::id.eq(node.id.0)),
+                        )
+                        .set(node::task_types.eq(task_types_to_json(&task_types)))
+                        .execute_traced(conn)
+                        .await?;
+                    }
+                    Ok::<_, diesel::result::Error>(previous)
+                }
+                .scope_boxed()
+            })
+            .await?;
+        Ok(match previous {
+            None => PingOutcome::Unknown,
+            Some(previous) if previous <= now => PingOutcome::Lapsed,
+            Some(_) => PingOutcome::Refreshed,
+        })
+    }
+
+    async fn update_pings(&self, tenant: &str, pings: &[(NodeId, f64)]) -> Result<u64> {
+        let mut guard = self.slow_query_guard("update_pings");
+        let mut conn = self.conn().await?;
+        let (ids, intervals): (Vec<i64>, Vec<f64>) =
+            pings.iter().map(|&(node_id, interval)| (node_id.0, interval)).unzip();
+        // One UPDATE ... FROM over the unnested pairs instead of a
+        // statement per node; intervals may differ per device.
+        let updated = diesel::sql_query(
+            "UPDATE node SET online_until = $3 + v.ping_interval, \
+             ping_interval = v.ping_interval \
+             FROM UNNEST($1::bigint[], $2::float8[]) AS v(id, ping_interval) \
+             WHERE node.tenant = $4 AND node.id = v.id",
+        )
+        .bind::<diesel::sql_types::Array<diesel::sql_types::BigInt>, _>(&ids)
+        .bind::<diesel::sql_types::Array<diesel::sql_types::Double>, _>(&intervals)
+        .bind::<diesel::sql_types::Double, _>(now_secs())
+        .bind::<diesel::sql_types::Text, _>(tenant)
+        .execute_traced(&mut conn)
+        .await?;
+        guard.rows(updated);
+        Ok(updated as u64)
+    }
+
+    async fn record_client_version(
+        &self,
+        tenant: &str,
+        node_id: NodeId,
+        version: &str,
+    ) -> Result<()> {
+        let _guard = self.slow_query_guard("record_client_version");
+        let mut conn = self.conn().await?;
+        // The filter on the current value skips the write on the common
+        // path where the version has not changed since the last ping.
+        diesel::update(
+            node::table
+                .filter(node::tenant.eq(tenant))
+                .filter(node::id.eq(node_id.0))
+                .filter(node::client_version.ne(version)),
+        )
+        .set(node::client_version.eq(version))
+        .execute_traced(&mut conn)
+        .await?;
+        Ok(())
+    }
+
+    async fn client_versions(&self, tenant: &str) -> Result<HashMap<String, u64>> {
+        let mut guard = self.slow_query_guard("client_versions");
+        let mut conn = self.conn().await?;
+        let rows: Vec<(String, i64)> = node::table
+            .filter(node::tenant.eq(tenant))
+            .group_by(node::client_version)
+            .select((node::client_version, diesel::dsl::count_star()))
+            .load_traced(&mut conn)
+            .await?;
+        guard.rows(rows.len());
+        Ok(rows
+            .into_iter()
+            .map(|(version, count)| (version, count as u64))
+            .collect())
+    }
+
+    async fn record_node_identity(
+        &self,
+        tenant: &str,
+        node_id: NodeId,
+        identity: &str,
+    ) -> Result<()> {
+        let _guard = self.slow_query_guard("record_node_identity");
+        let mut conn = self.conn().await?;
+        diesel::update(
+            node::table
+                .filter(node::tenant.eq(tenant))
+                .filter(node::id.eq(node_id.0)),
+        )
+        .set(node::identity.eq(identity))
+        .execute_traced(&mut conn)
+        .await?;
+        Ok(())
+    }
+
+    async fn node_identity(&self, tenant: &str, node_id: NodeId) -> Result<Option<String>> {
+        let _guard = self.slow_query_guard("node_identity");
+        let mut conn = self.conn().await?;
+        let identity = node::table
+            .filter(node::tenant.eq(tenant))
+            .filter(node::id.eq(node_id.0))
+            .select(node::identity)
+            .first_traced(&mut conn)
+            .await
+            .optional()?;
+        Ok(identity)
+    }
+
+    async fn list_nodes(&self, tenant: &str) -> Result<Vec<NodeInfo>> {
+        let mut guard = self.slow_query_guard("list_nodes");
+        let mut conn = self.conn().await?;
+        let rows: Vec<NodeRow> = node::table
+            .filter(node::tenant.eq(tenant))
+            .order(node::id.asc())
+            .load_traced(&mut conn)
+            .await?;
+        guard.rows(rows.len());
+        Ok(rows
+            .into_iter()
+            .map(|row| NodeInfo {
+                id: NodeId(row.id),
+                identity: row.identity,
+                client_version: row.client_version,
+                online_until: row.online_until,
+                ping_interval: row.ping_interval,
+                properties: properties_from_json(&row.properties),
+            })
+            .collect())
+    }
+
+    async fn ban_node(&self, tenant: &str, node_id: NodeId, reason: &str) -> Result<()> {
+        let _guard = self.slow_query_guard("ban_node");
+        let mut conn = self.conn().await?;
+        let tenant = tenant.to_owned();
+        let reason = reason.to_owned();
+        conn.transaction(|conn| {
+            async move {
+                diesel::insert_into(banned_node::table)
+                    .values((
+                        banned_node::id.eq(node_id.0),
+                        banned_node::tenant.eq(&tenant),
+                        banned_node::reason.eq(&reason),
+                    ))
+                    .on_conflict((banned_node::id, banned_node::tenant))
+                    .do_nothing()
+                    .execute_traced(conn)
+                    .await?;
+                diesel::delete(
+                    node::table
+                        .filter(node::tenant.eq(&tenant))
+                        .filter(node::id.eq(node_id.0)),
+                )
+                .execute_traced(conn)
+                .await?;
+                Ok::<_, diesel::result::Error>(())
+            }
+            .scope_boxed()
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn unban_node(&self, tenant: &str, node_id: NodeId) -> Result<()> {
+        let _guard = self.slow_query_guard("unban_node");
+        let mut conn = self.conn().await?;
+        diesel::delete(
+            banned_node::table
+                .filter(banned_node::tenant.eq(tenant))
+                .filter(banned_node::id.eq(node_id.0)),
+        )
+        .execute_traced(&mut conn)
+        .await?;
+        Ok(())
+    }
+
+    async fn is_node_banned(&self, tenant: &str, node_id: NodeId) -> Result<bool> {
+        let _guard = self.slow_query_guard("is_node_banned");
+        let mut conn = self.conn().await?;
+        let banned: i64 = banned_node::table
+            .filter(banned_node::tenant.eq(tenant))
+            .filter(banned_node::id.eq(node_id.0))
+            .count()
+            .get_result_traced(&mut conn)
+            .await?;
+        Ok(banned > 0)
+    }
+
+    async fn nodes(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        selector: &HashMap<String, String>,
+    ) -> Result<HashSet<NodeId>> {
+        if run_id.0 == 0 {
+            // The protobuf default for an unset run id; answer without
+            // touching the pool.
+            return Ok(HashSet::new());
+        }
+        let mut guard = self.slow_query_guard("nodes");
+        let mut conn = self.conn().await?;
+        // One round trip: the run check rides along as an EXISTS
+        // subquery, and an unknown run yields an empty set just like
+        // one without online nodes.
+        let rows: Vec<(i64, String)> = node::table
+            .filter(node::tenant.eq(tenant))
+            .filter((node::online_until + node::ping_interval * self.ping_grace).gt(now_secs()))
+            .filter(exists(
+                run::table.filter(run::id.eq(run_id.0)).filter(run::tenant.eq(tenant)),
+            ))
+            .select((node::id, node::properties))
+            .load_traced(&mut conn)
+            .await?;
+        let ids: HashSet<NodeId> = rows
+            .into_iter()
+            .filter(|(_, properties)| matches_selector(&properties_from_json(properties), selector))
+            .map(|(id, _)| NodeId(id))
+            .collect();
+        guard.rows(ids.len());
+        Ok(ids)
+    }
+
+    async fn sample_nodes(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        count: u32,
+        seed: Option<u64>,
+        selector: &HashMap<String, String>,
+    ) -> Result<Vec<NodeId>> {
+        let mut guard = self.slow_query_guard("sample_nodes");
+        let mut conn = self.conn().await?;
+        if !self.run_exists(&mut conn, tenant, run_id).await? {
+            return Ok(Vec::new());
+        }
+        if !selector.is_empty() {
+            // The candidate set is only known after filtering in Rust,
+            // so sample with a seeded shuffle instead of `random()`.
+            let rows: Vec<(i64, String)> = node::table
+                .filter(node::tenant.eq(tenant))
+                .filter((node::online_until + node::ping_interval * self.ping_grace).gt(now_secs()))
+                .select((node::id, node::properties))
+                .load_traced(&mut conn)
+                .await?;
+            let mut ids: Vec<NodeId> = rows
+                .into_iter()
+                .filter(|(_, properties)| {
+                    matches_selector(&properties_from_json(properties), selector)
+                })
+                .map(|(id, _)| NodeId(id))
+                .collect();
+            ids.sort_unstable();
+            let mut rng = match seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            };
+            ids.shuffle(&mut rng);
+            ids.truncate(count as usize);
+            guard.rows(ids.len());
+            return Ok(ids);
+        }
+        if let Some(seed) = seed {
+            // setseed expects a value in [-1, 1] and applies to the
+            // random() calls issued on the same connection.
+            let seed = (seed as f64 / u64::MAX as f64) * 2.0 - 1.0;
+            diesel::sql_query("SELECT setseed($1)")
+                .bind::<diesel::sql_types::Double, _>(seed)
+                .execute_traced(&mut conn)
+                .await?;
+        }
+        let ids: Vec<i64> = node::table
+            .filter(node::tenant.eq(tenant))
+            .filter((node::online_until + node::ping_interval * self.ping_grace).gt(now_secs()))
+            .order(diesel::dsl::sql::<diesel::sql_types::Double>("random()"))
+            .limit(i64::from(count))
+            .select(node::id)
+            .load_traced(&mut conn)
+            .await?;
+        guard.rows(ids.len());
+        Ok(ids.into_iter().map(NodeId).collect())
+    }
+
+    async fn online_nodes(&self, tenant: &str) -> Result<u64> {
+        let _guard = self.slow_query_guard("online_nodes");
+        let mut conn = self.conn().await?;
+        let online: i64 = node::table
+            .filter(node::tenant.eq(tenant))
+            .filter((node::online_until + node::ping_interval * self.ping_grace).gt(now_secs()))
+            .count()
+            .get_result_traced(&mut conn)
+            .await?;
+        Ok(online as u64)
+    }
+
+    async fn record_audit_event(&self, tenant: &str, event: &AuditEvent) -> Result<()> {
+        let _guard = self.slow_query_guard("record_audit_event");
+        let mut conn = self.conn().await?;
+        let mut row = AuditEventRow::from(event);
+        row.tenant = tenant.to_owned();
+        diesel::insert_into(audit_log::table)
+            .values(&row)
+            .execute_traced(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn list_audit_events(
+        &self,
+        tenant: &str,
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<AuditEvent>> {
+        let mut guard = self.slow_query_guard("list_audit_events");
+        let mut conn = self.conn().await?;
+        let mut query = audit_log::table
+            .filter(audit_log::tenant.eq(tenant))
+            .order((audit_log::created_at.asc(), audit_log::id.asc()))
+            .limit(i64::from(page_size))
+            .into_boxed();
+        if let Some(after) = after {
+            query = query.filter(
+                audit_log::created_at.gt(after.created_at).or(audit_log::created_at
+                    .eq(after.created_at)
+                    .and(audit_log::id.gt(after.id.clone()))),
+            );
+        }
+        let rows: Vec<AuditEventRow> = query.load_traced(&mut conn).await?;
+        guard.rows(rows.len());
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    async fn list_dead_letters(
+        &self,
+        tenant: &str,
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<DeadLetter>> {
+        let mut guard = self.slow_query_guard("list_dead_letters");
+        let mut conn = self.conn().await?;
+        let mut query = task_dead_letter::table
+            .filter(task_dead_letter::tenant.eq(tenant))
+            .order((task_dead_letter::dead_at.asc(), task_dead_letter::id.asc()))
+            .limit(i64::from(page_size))
+            .into_boxed();
+        if let Some(after) = after {
+            query = query.filter(
+                task_dead_letter::dead_at.gt(after.created_at).or(task_dead_letter::dead_at
+                    .eq(after.created_at)
+                    .and(task_dead_letter::id.gt(parse_task_id(&after.id)))),
+            );
+        }
+        let rows: Vec<DeadLetterRow> = query.load_traced(&mut conn).await?;
+        guard.rows(rows.len());
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    async fn create_run(&self, tenant: &str) -> Result<RunId> {
+        let _guard = self.slow_query_guard("create_run");
+        let mut conn = self.conn().await?;
+        let run_id: i64 = rand::thread_rng().gen();
+        diesel::insert_into(run::table)
+            .values((run::id.eq(run_id), run::tenant.eq(tenant)))
+            .execute_traced(&mut conn)
+            .await?;
+        if self.partition_by_run {
+            for table in ["task_ins", "task_res"] {
+                let name = partition::partition_name(table, run_id);
+                diesel::sql_query(format!(
+                    "CREATE TABLE IF NOT EXISTS {name} PARTITION OF {table} \
+                     FOR VALUES IN ({run_id})"
+                ))
+                .execute_traced(&mut conn)
+                .await?;
+            }
+        }
+        Ok(RunId(run_id))
+    }
+
+    async fn delete_run(&self, tenant: &str, run_id: RunId) -> Result<()> {
+        let _guard = self.slow_query_guard("delete_run");
+        let mut conn = self.conn().await?;
+        let deleted = diesel::delete(
+            run::table.filter(run::id.eq(run_id.0)).filter(run::tenant.eq(tenant)),
+        )
+        .execute_traced(&mut conn)
+        .await?;
+        if deleted == 0 {
+            return Err(Error::UnknownRun(run_id));
+        }
+        self.run_cache.lock().unwrap().remove(&(tenant.to_owned(), run_id));
+        if self.partition_by_run {
+            // A partition holds only this run's rows, so dropping it is
+            // O(1) regardless of the run's size.
+            for table in ["task_ins", "task_res"] {
+                let name = partition::partition_name(table, run_id.0);
+                diesel::sql_query(format!("DROP TABLE IF EXISTS {name}"))
+                    .execute_traced(&mut conn)
+                    .await?;
+            }
+        }
+        // Rows written before partitioning was enabled (or ever, when
+        // it is off) are deleted the regular way.
+        diesel::delete(
+            task_ins::table
+                .filter(task_ins::tenant.eq(tenant))
+                .filter(task_ins::run_id.eq(run_id.0)),
+        )
+        .execute_traced(&mut conn)
+        .await?;
+        diesel::delete(
+            task_res::table
+                .filter(task_res::tenant.eq(tenant))
+                .filter(task_res::run_id.eq(run_id.0)),
+        )
+        .execute_traced(&mut conn)
+        .await?;
+        Ok(())
+    }
+
+    async fn runs(&self, tenant: &str) -> Result<Vec<RunId>> {
+        let mut guard = self.slow_query_guard("runs");
+        let mut conn = self.conn().await?;
+        let runs: Vec<i64> = run::table
+            .filter(run::tenant.eq(tenant))
+            .order(run::id.asc())
+            .select(run::id)
+            .load_traced(&mut conn)
+            .await?;
+        guard.rows(runs.len());
+        Ok(runs.into_iter().map(RunId).collect())
+    }
+
+    async fn list_task_ins(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        filters: &[TaskFilter],
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<TaskIns>> {
+        let mut guard = self.slow_query_guard("list_task_ins");
+        let mut conn = self.conn().await?;
+        let mut query = task_ins::table
+            .filter(task_ins::tenant.eq(tenant))
+            .filter(task_ins::run_id.eq(run_id.0))
+            .order((task_ins::created_at.asc(), task_ins::id.asc()))
+            .limit(i64::from(page_size))
+            .into_boxed();
+        if let Some(after) = after {
+            query = query.filter(
+                task_ins::created_at
+                    .gt(datetime_from_secs(after.created_at))
+                    .or(task_ins::created_at
+                        .eq(datetime_from_secs(after.created_at))
+                        .and(task_ins::id.gt(parse_task_id(&after.id)))),
+            );
+        }
+        for filter in filters {
+            query = match &filter.field {
+                TaskField::GroupId(value) => {
+                    compare!(query, task_ins::group_id, filter.op, value.clone())
+                }
+                TaskField::TaskType(value) => {
+                    compare!(query, task_ins::task_type, filter.op, value.clone())
+                }
+                TaskField::ProducerNodeId(value) => {
+                    compare!(query, task_ins::producer_node_id, filter.op, value.0)
+                }
+                TaskField::ConsumerNodeId(value) => {
+                    compare!(query, task_ins::consumer_node_id, filter.op, value.0)
+                }
+                TaskField::CreatedAt(value) => {
+                    compare!(query, task_ins::created_at, filter.op, datetime_from_secs(*value))
+                }
+            };
+        }
+        let rows: Vec<TaskInsRow> = query.load_traced(&mut conn).await?;
+        guard.rows(rows.len());
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    async fn list_task_res(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        filters: &[TaskFilter],
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<TaskRes>> {
+        let mut guard = self.slow_query_guard("list_task_res");
+        let mut conn = self.conn().await?;
+        let mut query = task_res::table
+            .filter(task_res::tenant.eq(tenant))
+            .filter(task_res::run_id.eq(run_id.0))
+            .order((task_res::created_at.asc(), task_res::id.asc()))
+            .limit(i64::from(page_size))
+            .into_boxed();
+        if let Some(after) = after {
+            query = query.filter(
+                task_res::created_at
+                    .gt(datetime_from_secs(after.created_at))
+                    .or(task_res::created_at
+                        .eq(datetime_from_secs(after.created_at))
+                        .and(task_res::id.gt(parse_task_id(&after.id)))),
+            );
+        }
+        for filter in filters {
+            query = match &filter.field {
+                TaskField::GroupId(value) => {
+                    compare!(query, task_res::group_id, filter.op, value.clone())
+                }
+                TaskField::TaskType(value) => {
+                    compare!(query, task_res::task_type, filter.op, value.clone())
+                }
+                TaskField::ProducerNodeId(value) => {
+                    compare!(query, task_res::producer_node_id, filter.op, value.0)
+                }
+                TaskField::ConsumerNodeId(value) => {
+                    compare!(query, task_res::consumer_node_id, filter.op, value.0)
+                }
+                TaskField::CreatedAt(value) => {
+                    compare!(query, task_res::created_at, filter.op, datetime_from_secs(*value))
+                }
+            };
+        }
+        let rows: Vec<TaskResRow> = query.load_traced(&mut conn).await?;
+        guard.rows(rows.len());
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+}
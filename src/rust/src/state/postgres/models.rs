@@ -0,0 +1,343 @@
+//! Row types mapping the domain model onto the diesel schema.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::model::handler::{
+    secs_from_datetime, AuditEvent, DeadLetter, GroupId, Node, NodeId, RunId, Task, TaskError,
+    TaskId, TaskIns, TaskRes,
+};
+
+use super::schema::{audit_log, node, task_dead_letter, task_ins, task_res};
+
+#[derive(Debug, Insertable, Queryable, Selectable)]
+#[diesel(table_name = node)]
+pub struct NodeRow {
+    pub id: i64,
+    pub online_until: f64,
+    pub ping_interval: f64,
+    pub tenant: String,
+    pub properties: String,
+    pub task_types: String,
+    pub client_version: String,
+    pub identity: String,
+}
+
+/// Serialize node properties into the text column.
+pub(crate) fn properties_to_json(properties: &HashMap<String, String>) -> String {
+    serde_json::to_string(properties).expect("string map serializes")
+}
+
+/// Deserialize node properties from the text column; rows written
+/// before the column existed hold `'{}'`.
+pub(crate) fn properties_from_json(json: &str) -> HashMap<String, String> {
+    serde_json::from_str(json).unwrap_or_default()
+}
+
+/// Serialize the supported task types into the text column.
+pub(crate) fn task_types_to_json(task_types: &[String]) -> String {
+    serde_json::to_string(task_types).expect("string list serializes")
+}
+
+/// Deserialize the supported task types from the text column.
+pub(crate) fn task_types_from_json(json: &str) -> Vec<String> {
+    serde_json::from_str(json).unwrap_or_default()
+}
+
+#[derive(Debug, Insertable, Queryable, Selectable)]
+#[diesel(table_name = audit_log)]
+pub struct AuditEventRow {
+    pub id: String,
+    pub tenant: String,
+    pub created_at: f64,
+    pub action: String,
+    pub node_id: i64,
+    pub run_id: i64,
+    pub detail: String,
+}
+
+impl From<&AuditEvent> for AuditEventRow {
+    fn from(event: &AuditEvent) -> Self {
+        Self {
+            id: event.id.clone(),
+            tenant: String::new(),
+            created_at: event.created_at,
+            action: event.action.clone(),
+            node_id: event.node_id.0,
+            run_id: event.run_id.0,
+            detail: event.detail.clone(),
+        }
+    }
+}
+
+impl From<AuditEventRow> for AuditEvent {
+    fn from(row: AuditEventRow) -> Self {
+        Self {
+            id: row.id,
+            created_at: row.created_at,
+            action: row.action,
+            node_id: NodeId(row.node_id),
+            run_id: RunId(row.run_id),
+            detail: row.detail,
+        }
+    }
+}
+
+#[derive(Debug, Insertable, Queryable, Selectable)]
+#[diesel(table_name = task_dead_letter)]
+pub struct DeadLetterRow {
+    pub id: Uuid,
+    pub tenant: String,
+    pub group_id: String,
+    pub run_id: i64,
+    pub consumer_anonymous: bool,
+    pub consumer_node_id: i64,
+    pub created_at: f64,
+    pub dead_at: f64,
+    pub delivery_count: i32,
+    pub task_type: String,
+    pub reason: String,
+}
+
+impl DeadLetterRow {
+    /// Park a stored `TaskIns` with the given failure reason.
+    pub fn from_task(row: &TaskInsRow, dead_at: f64, reason: &str) -> Self {
+        Self {
+            id: row.id,
+            tenant: row.tenant.clone(),
+            group_id: row.group_id.clone(),
+            run_id: row.run_id,
+            consumer_anonymous: row.consumer_anonymous,
+            consumer_node_id: row.consumer_node_id,
+            created_at: secs_from_datetime(row.created_at),
+            dead_at,
+            delivery_count: row.delivery_count,
+            task_type: row.task_type.clone(),
+            reason: reason.to_owned(),
+        }
+    }
+}
+
+impl From<DeadLetterRow> for DeadLetter {
+    fn from(row: DeadLetterRow) -> Self {
+        Self {
+            id: TaskId(row.id.to_string()),
+            group_id: GroupId(row.group_id),
+            run_id: RunId(row.run_id),
+            consumer: Node {
+                id: NodeId(row.consumer_node_id),
+                anonymous: row.consumer_anonymous,
+            },
+            created_at: row.created_at,
+            dead_at: row.dead_at,
+            delivery_count: row.delivery_count.max(0) as u32,
+            task_type: row.task_type,
+            reason: row.reason,
+        }
+    }
+}
+
+#[derive(Debug, Insertable, Queryable, Selectable)]
+#[diesel(table_name = task_ins)]
+pub struct TaskInsRow {
+    pub id: Uuid,
+    pub group_id: String,
+    pub run_id: i64,
+    pub producer_anonymous: bool,
+    pub producer_node_id: i64,
+    pub consumer_anonymous: bool,
+    pub consumer_node_id: i64,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub pushed_at: DateTime<Utc>,
+    pub ttl: String,
+    pub ancestry: Vec<Uuid>,
+    pub task_type: String,
+    pub recordset: Vec<u8>,
+    pub tenant: String,
+    pub recordset_checksum: String,
+    pub delivery_count: i32,
+}
+
+#[derive(Debug, Insertable, Queryable, Selectable)]
+#[diesel(table_name = task_res)]
+pub struct TaskResRow {
+    pub id: Uuid,
+    pub group_id: String,
+    pub run_id: i64,
+    pub producer_anonymous: bool,
+    pub producer_node_id: i64,
+    pub consumer_anonymous: bool,
+    pub consumer_node_id: i64,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub pushed_at: DateTime<Utc>,
+    pub ttl: String,
+    pub ancestry: Vec<Uuid>,
+    pub task_type: String,
+    pub recordset: Vec<u8>,
+    pub tenant: String,
+    pub recordset_checksum: String,
+    pub error_code: i64,
+    pub error_reason: String,
+}
+
+/// Parse a task id minted by the handlers or validated at the service
+/// boundary; a malformed id cannot reach the state layer, so `nil` is
+/// only a defensive fallback.
+pub(crate) fn parse_task_id(id: &str) -> Uuid {
+    Uuid::parse_str(id).unwrap_or(Uuid::nil())
+}
+
+/// Parse every well-formed id in `ids`; malformed ones cannot match a
+/// stored row and are dropped.
+pub(crate) fn parse_task_ids(ids: &[TaskId]) -> Vec<Uuid> {
+    ids.iter().filter_map(|id| Uuid::parse_str(id.as_ref()).ok()).collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn task_from_row(
+    producer_node_id: i64,
+    producer_anonymous: bool,
+    consumer_node_id: i64,
+    consumer_anonymous: bool,
+    created_at: DateTime<Utc>,
+    delivered_at: Option<DateTime<Utc>>,
+    pushed_at: DateTime<Utc>,
+    ttl: String,
+    ancestry: Vec<TaskId>,
+    task_type: String,
+    recordset: Vec<u8>,
+    recordset_checksum: String,
+    error: Option<TaskError>,
+) -> Task {
+    let recordset = recordset.into();
+    Task {
+        producer: Node {
+            id: NodeId(producer_node_id),
+            anonymous: producer_anonymous,
+        },
+        consumer: Node {
+            id: NodeId(consumer_node_id),
+            anonymous: consumer_anonymous,
+        },
+        created_at,
+        delivered_at,
+        pushed_at,
+        ttl,
+        ancestry,
+        task_type,
+        recordset,
+        recordset_checksum,
+        error,
+    }
+}
+
+impl From<&TaskIns> for TaskInsRow {
+    fn from(task_ins: &TaskIns) -> Self {
+        Self {
+            id: parse_task_id(task_ins.id.as_ref()),
+            group_id: task_ins.group_id.0.clone(),
+            run_id: task_ins.run_id.0,
+            producer_anonymous: task_ins.task.producer.anonymous,
+            producer_node_id: task_ins.task.producer.id.0,
+            consumer_anonymous: task_ins.task.consumer.anonymous,
+            consumer_node_id: task_ins.task.consumer.id.0,
+            created_at: task_ins.task.created_at,
+            delivered_at: task_ins.task.delivered_at,
+            pushed_at: task_ins.task.pushed_at,
+            ttl: task_ins.task.ttl.clone(),
+            ancestry: parse_task_ids(&task_ins.task.ancestry),
+            task_type: task_ins.task.task_type.clone(),
+            recordset: task_ins.task.recordset.to_vec(),
+            tenant: String::new(),
+            recordset_checksum: task_ins.task.recordset_checksum.clone(),
+            delivery_count: 0,
+        }
+    }
+}
+
+impl From<TaskInsRow> for TaskIns {
+    fn from(row: TaskInsRow) -> Self {
+        Self {
+            id: TaskId(row.id.to_string()),
+            group_id: GroupId(row.group_id),
+            run_id: RunId(row.run_id),
+            task: task_from_row(
+                row.producer_node_id,
+                row.producer_anonymous,
+                row.consumer_node_id,
+                row.consumer_anonymous,
+                row.created_at,
+                row.delivered_at,
+                row.pushed_at,
+                row.ttl,
+                row.ancestry.iter().map(|id| TaskId(id.to_string())).collect(),
+                row.task_type,
+                row.recordset,
+                row.recordset_checksum,
+                None,
+            ),
+        }
+    }
+}
+
+impl From<&TaskRes> for TaskResRow {
+    fn from(task_res: &TaskRes) -> Self {
+        Self {
+            id: parse_task_id(task_res.id.as_ref()),
+            group_id: task_res.group_id.0.clone(),
+            run_id: task_res.run_id.0,
+            producer_anonymous: task_res.task.producer.anonymous,
+            producer_node_id: task_res.task.producer.id.0,
+            consumer_anonymous: task_res.task.consumer.anonymous,
+            consumer_node_id: task_res.task.consumer.id.0,
+            created_at: task_res.task.created_at,
+            delivered_at: task_res.task.delivered_at,
+            pushed_at: task_res.task.pushed_at,
+            ttl: task_res.task.ttl.clone(),
+            ancestry: parse_task_ids(&task_res.task.ancestry),
+            task_type: task_res.task.task_type.clone(),
+            recordset: task_res.task.recordset.to_vec(),
+            tenant: String::new(),
+            recordset_checksum: task_res.task.recordset_checksum.clone(),
+            error_code: task_res.task.error.as_ref().map_or(0, |error| error.code),
+            error_reason: task_res
+                .task
+                .error
+                .as_ref()
+                .map_or_else(String::new, |error| error.reason.clone()),
+        }
+    }
+}
+
+impl From<TaskResRow> for TaskRes {
+    fn from(row: TaskResRow) -> Self {
+        Self {
+            id: TaskId(row.id.to_string()),
+            group_id: GroupId(row.group_id),
+            run_id: RunId(row.run_id),
+            task: task_from_row(
+                row.producer_node_id,
+                row.producer_anonymous,
+                row.consumer_node_id,
+                row.consumer_anonymous,
+                row.created_at,
+                row.delivered_at,
+                row.pushed_at,
+                row.ttl,
+                row.ancestry.iter().map(|id| TaskId(id.to_string())).collect(),
+                row.task_type,
+                row.recordset,
+                row.recordset_checksum,
+                (row.error_code != 0 || !row.error_reason.is_empty()).then(|| TaskError {
+                    code: row.error_code,
+                    reason: row.error_reason,
+                }),
+            ),
+        }
+    }
+}
@@ -0,0 +1,97 @@
+//! Optional declarative partitioning of the task tables by run id.
+//!
+//! Long-lived deployments accumulate millions of task rows; with
+//! `database.partition_tasks_by_run` enabled, `task_ins` and
+//! `task_res` become LIST-partitioned parents with one partition per
+//! run, so deleting a finished run is a partition drop instead of a
+//! huge DELETE. Rows written before a run had its own partition land
+//! in the DEFAULT partition and are removed the slow way.
+
+/// Advisory lock key serializing conversions across replicas.
+const ADVISORY_LOCK_KEY: i64 = 0x666c_7770; // "flwp"
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("database connection failed")]
+    Postgres(#[from] tokio_postgres::Error),
+}
+
+/// Convert `task_ins` and `task_res` into partitioned tables, copying
+/// any existing rows into the DEFAULT partition. Idempotent: tables
+/// that are already partitioned are left alone.
+pub async fn convert(database_uri: &str) -> Result<(), Error> {
+    let (client, connection) = tokio_postgres::connect(database_uri, tokio_postgres::NoTls).await?;
+    let handle = tokio::spawn(connection);
+
+    client
+        .query("SELECT pg_advisory_lock($1)", &[&ADVISORY_LOCK_KEY])
+        .await?;
+    let mut result = Ok(());
+    for table in ["task_ins", "task_res"] {
+        result = convert_table(&client, table).await;
+        if result.is_err() {
+            break;
+        }
+    }
+    client
+        .query("SELECT pg_advisory_unlock($1)", &[&ADVISORY_LOCK_KEY])
+        .await?;
+    result?;
+
+    drop(client);
+    handle.abort();
+    Ok(())
+}
+
+/// Whether `table` is already a partitioned parent.
+async fn is_partitioned(client: &tokio_postgres::Client, table: &str) -> Result<bool, Error> {
+    let row = client
+        .query_one(
+            "SELECT EXISTS (
+                 SELECT 1 FROM pg_partitioned_table pt
+                 JOIN pg_class c ON c.oid = pt.partrelid
+                 WHERE c.relname = $1
+             )",
+            &[&table],
+        )
+        .await?;
+    Ok(row.get(0))
+}
+
+async fn convert_table(client: &tokio_postgres::Client, table: &str) -> Result<(), Error> {
+    if is_partitioned(client, table).await? {
+        return Ok(());
+    }
+    tracing::info!(table, "converting to a partitioned table");
+    // The partition key must be part of the primary key, and the
+    // hot-query indexes are recreated on the parent so every partition
+    // inherits them.
+    let index = match table {
+        "task_ins" => {
+            "CREATE INDEX task_ins_consumer_idx
+                 ON task_ins (consumer_node_id, consumer_anonymous, delivered_at);"
+        }
+        _ => "CREATE INDEX task_res_ancestry_idx ON task_res USING GIN (ancestry);",
+    };
+    client
+        .batch_execute(&format!(
+            "BEGIN;
+             ALTER TABLE {table} RENAME TO {table}_unpartitioned;
+             CREATE TABLE {table} (LIKE {table}_unpartitioned INCLUDING DEFAULTS)
+                 PARTITION BY LIST (run_id);
+             CREATE TABLE {table}_default PARTITION OF {table} DEFAULT;
+             INSERT INTO {table} SELECT * FROM {table}_unpartitioned;
+             DROP TABLE {table}_unpartitioned;
+             ALTER TABLE {table} ADD PRIMARY KEY (run_id, id);
+             {index}
+             COMMIT;"
+        ))
+        .await?;
+    Ok(())
+}
+
+/// Name of the per-run partition of `table`; run ids can be negative,
+/// so the unsigned bit pattern keeps the identifier sign-free.
+pub(crate) fn partition_name(table: &str, run_id: i64) -> String {
+    format!("{table}_run_{}", run_id as u64)
+}
@@ -0,0 +1,103 @@
+//! Diesel schema for the SuperLink tables, kept in sync with the
+//! refinery migrations in `migrations/`.
+
+diesel::table! {
+    node (id) {
+        id -> BigInt,
+        online_until -> Double,
+        ping_interval -> Double,
+        tenant -> Text,
+        properties -> Text,
+        task_types -> Text,
+        client_version -> Text,
+        identity -> Text,
+    }
+}
+
+diesel::table! {
+    audit_log (id) {
+        id -> Text,
+        tenant -> Text,
+        created_at -> Double,
+        action -> Text,
+        node_id -> BigInt,
+        run_id -> BigInt,
+        detail -> Text,
+    }
+}
+
+diesel::table! {
+    banned_node (id, tenant) {
+        id -> BigInt,
+        tenant -> Text,
+        reason -> Text,
+    }
+}
+
+diesel::table! {
+    task_dead_letter (id) {
+        id -> Uuid,
+        tenant -> Text,
+        group_id -> Text,
+        run_id -> BigInt,
+        consumer_anonymous -> Bool,
+        consumer_node_id -> BigInt,
+        created_at -> Double,
+        dead_at -> Double,
+        delivery_count -> Integer,
+        task_type -> Text,
+        reason -> Text,
+    }
+}
+
+diesel::table! {
+    run (id) {
+        id -> BigInt,
+        tenant -> Text,
+    }
+}
+
+diesel::table! {
+    task_ins (id) {
+        id -> Uuid,
+        group_id -> Text,
+        run_id -> BigInt,
+        producer_anonymous -> Bool,
+        producer_node_id -> BigInt,
+        consumer_anonymous -> Bool,
+        consumer_node_id -> BigInt,
+        created_at -> Timestamptz,
+        delivered_at -> Nullable<Timestamptz>,
+        pushed_at -> Timestamptz,
+        ttl -> Text,
+        ancestry -> Array<Uuid>,
+        task_type -> Text,
+        recordset -> Bytea,
+        tenant -> Text,
+        recordset_checksum -> Text,
+        delivery_count -> Integer,
+    }
+}
+
+diesel::table! {
+    task_res (id) {
+        id -> Uuid,
+        group_id -> Text,
+        run_id -> BigInt,
+        producer_anonymous -> Bool,
+        producer_node_id -> BigInt,
+        consumer_anonymous -> Bool,
+        consumer_node_id -> BigInt,
+        created_at -> Timestamptz,
+        delivered_at -> Nullable<Timestamptz>,
+        pushed_at -> Timestamptz,
+        ttl -> Text,
+        ancestry -> Array<Uuid>,
+        task_type -> Text,
+        recordset -> Bytea,
+        tenant -> Text,
+        recordset_checksum -> Text,
+        error_code -> BigInt,
+        error_reason -> Text,
+    }
+}
@@ -0,0 +1,508 @@
+//! Retry decorator wrapping any `State` backend.
+//!
+//! Only [`Error::Connection`] failures are retried: they are raised
+//! while acquiring a pooled connection, before any statement runs, so
+//! repeating the call is safe even for writes. Query errors and
+//! domain errors pass through untouched, as do exhausted retries.
+
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::model::handler::{
+    AuditEvent, DeadLetter, GroupId, Node, NodeId, NodeInfo, RunId, TaskId, TaskIns, TaskRes,
+};
+
+use super::{Error, PingOutcome, Result, RunUsage, State, TaskCursor, TaskFilter};
+
+/// Retry policy applied by [`Retry`]; `attempts` of 0 disables
+/// retrying.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RetryConfig {
+    /// Additional attempts after the initial failure.
+    pub attempts: u32,
+    /// Delay before the first retry, doubling on each further one.
+    pub backoff_ms: u64,
+}
+
+impl RetryConfig {
+    /// The delay before retry number `attempt` (1-based).
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u64 << u64::from((attempt - 1).min(16));
+        Duration::from_millis(self.backoff_ms.saturating_mul(factor))
+    }
+}
+
+/// A `State` decorator retrying connection acquisition failures.
+pub struct Retry<S> {
+    inner: S,
+    config: RetryConfig,
+}
+
+impl<S> Retry<S> {
+    pub fn new(inner: S, config: RetryConfig) -> Self {
+        Self { inner, config }
+    }
+
+    async fn retrying<T, F, Fut>(&self, operation: &'static str, call: F) -> Result<T>
+    where
+        F: Fn() -> Fut + Send + Sync,
+        Fut: Future<Output = Result<T>> + Send,
+    {
+        let mut attempt = 0;
+        loop {
+            match call().await {
+                Err(Error::Connection(err)) if attempt < self.config.attempts => {
+                    attempt += 1;
+                    tracing::warn!(operation, attempt, error = %err, "retrying state call");
+                    tokio::time::sleep(self.config.backoff(attempt)).await;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<S: State> State for Retry<S> {
+    async fn insert_task_instructions(
+        &self,
+        tenant: &str,
+        instructions: &[TaskIns],
+    ) -> Result<Vec<TaskId>> {
+        self.retrying(
+            "insert_task_instructions",
+            move || self.inner.insert_task_instructions(tenant, instructions),
+        )
+        .await
+    }
+
+    async fn task_instructions(
+        &self,
+        tenant: &str,
+        node: &Node,
+        limit: Option<u32>,
+    ) -> Result<Vec<TaskIns>> {
+        self.retrying(
+            "task_instructions",
+            move || self.inner.task_instructions(tenant, node, limit),
+        )
+        .await
+    }
+
+    async fn claim_pool_task_instructions(
+        &self,
+        tenant: &str,
+        node: &Node,
+        limit: Option<u32>,
+    ) -> Result<Vec<TaskIns>> {
+        self.retrying(
+            "claim_pool_task_instructions",
+            move || self.inner.claim_pool_task_instructions(tenant, node, limit),
+        )
+        .await
+    }
+
+    async fn insert_task_results(&self, tenant: &str, results: &[TaskRes]) -> Result<Vec<TaskId>> {
+        self.retrying(
+            "insert_task_results",
+            move || self.inner.insert_task_results(tenant, results),
+        )
+        .await
+    }
+
+    async fn task_results(
+        &self,
+        tenant: &str,
+        task_ids: &[TaskId],
+        limit: Option<u32>,
+        mark: bool,
+    ) -> Result<Vec<TaskRes>> {
+        self.retrying(
+            "task_results",
+            move || self.inner.task_results(tenant, task_ids, limit, mark),
+        )
+        .await
+    }
+
+    async fn release_tasks(&self, tenant: &str, node: &Node, task_ids: &[TaskId]) -> Result<u64> {
+        self.retrying(
+            "release_tasks",
+            move || self.inner.release_tasks(tenant, node, task_ids),
+        )
+        .await
+    }
+
+    async fn release_expired_tasks(
+        &self,
+        lease: Duration,
+        max_redeliveries: u32,
+    ) -> Result<(u64, Vec<(String, DeadLetter)>)> {
+        self.retrying(
+            "release_expired_tasks",
+            move || self.inner.release_expired_tasks(lease, max_redeliveries),
+        )
+        .await
+    }
+
+    async fn task_ins_consumers(
+        &self,
+        tenant: &str,
+        task_ids: &[TaskId],
+    ) -> Result<HashMap<TaskId, (RunId, Node)>> {
+        self.retrying(
+            "task_ins_consumers",
+            move || self.inner.task_ins_consumers(tenant, task_ids),
+        )
+        .await
+    }
+
+    async fn pending_task_ins(&self, tenant: &str, consumer: &Node) -> Result<u64> {
+        self.retrying(
+            "pending_task_ins",
+            move || self.inner.pending_task_ins(tenant, consumer),
+        )
+        .await
+    }
+
+    async fn pending_run_task_ins(&self, tenant: &str, run_id: RunId) -> Result<u64> {
+        self.retrying(
+            "pending_run_task_ins",
+            move || self.inner.pending_run_task_ins(tenant, run_id),
+        )
+        .await
+    }
+
+    async fn group_progress(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        group_id: &GroupId,
+    ) -> Result<(u64, u64)> {
+        self.retrying(
+            "group_progress",
+            move || self.inner.group_progress(tenant, run_id, group_id),
+        )
+        .await
+    }
+
+    async fn run_progress(&self, tenant: &str, run_id: RunId) -> Result<Vec<(GroupId, u64, u64)>> {
+        self.retrying("run_progress", move || self.inner.run_progress(tenant, run_id))
+            .await
+    }
+
+    async fn run_usage(&self, tenant: &str, run_id: RunId) -> Result<RunUsage> {
+        self.retrying("run_usage", move || self.inner.run_usage(tenant, run_id))
+            .await
+    }
+
+    async fn delete_tasks(&self, tenant: &str, task_ids: &[TaskId]) -> Result<()> {
+        self.retrying("delete_tasks", move || self.inner.delete_tasks(tenant, task_ids))
+            .await
+    }
+
+    async fn create_node(
+        &self,
+        tenant: &str,
+        ping_interval: f64,
+        properties: &HashMap<String, String>,
+        task_types: &[String],
+    ) -> Result<NodeId> {
+        self.retrying(
+            "create_node",
+            move || self.inner
+                .create_node(tenant, ping_interval, properties, task_types),
+        )
+        .await
+    }
+
+    async fn create_nodes(
+        &self,
+        tenant: &str,
+        count: u32,
+        ping_interval: f64,
+        properties: &HashMap<String, String>,
+        task_types: &[String],
+    ) -> Result<Vec<NodeId>> {
+        self.retrying(
+            "create_nodes",
+            move || self.inner
+                .create_nodes(tenant, count, ping_interval, properties, task_types),
+        )
+        .await
+    }
+
+    async fn reconnect_node(
+        &self,
+        tenant: &str,
+        node_id: NodeId,
+        ping_interval: f64,
+        properties: &HashMap<String, String>,
+        task_types: &[String],
+    ) -> Result<bool> {
+        self.retrying("reconnect_node", move || {
+            self.inner
+                .reconnect_node(tenant, node_id, ping_interval, properties, task_types)
+        })
+        .await
+    }
+
+    async fn delete_node(&self, tenant: &str, node_id: NodeId) -> Result<()> {
+        self.retrying("delete_node", move || self.inner.delete_node(tenant, node_id))
+            .await
+    }
+
+    async fn delete_nodes(&self, tenant: &str, node_ids: &[NodeId]) -> Result<()> {
+        self.retrying("delete_nodes", move || self.inner.delete_nodes(tenant, node_ids))
+            .await
+    }
+
+    async fn update_ping(
+        &self,
+        tenant: &str,
+        node: &Node,
+        ping_interval: f64,
+        task_types: &[String],
+    ) -> Result<PingOutcome> {
+        self.retrying(
+            "update_ping",
+            move || self.inner.update_ping(tenant, node, ping_interval, task_types),
+        )
+        .await
+    }
+
+    async fn update_pings(&self, tenant: &str, pings: &[(NodeId, f64)]) -> Result<u64> {
+        self.retrying("update_pings", move || self.inner.update_pings(tenant, pings))
+            .await
+    }
+
+    async fn record_client_version(
+        &self,
+        tenant: &str,
+        node_id: NodeId,
+        version: &str,
+    ) -> Result<()> {
+        self.retrying(
+            "record_client_version",
+            move || self.inner.record_client_version(tenant, node_id, version),
+        )
+        .await
+    }
+
+    async fn client_versions(&self, tenant: &str) -> Result<HashMap<String, u64>> {
+        self.retrying("client_versions", move || self.inner.client_versions(tenant))
+            .await
+    }
+
+    async fn record_node_identity(
+        &self,
+        tenant: &str,
+        node_id: NodeId,
+        identity: &str,
+    ) -> Result<()> {
+        self.retrying("record_node_identity", move || {
+            self.inner.record_node_identity(tenant, node_id, identity)
+        })
+        .await
+    }
+
+    async fn node_identity(&self, tenant: &str, node_id: NodeId) -> Result<Option<String>> {
+        self.retrying("node_identity", move || self.inner.node_identity(tenant, node_id))
+            .await
+    }
+
+    async fn list_nodes(&self, tenant: &str) -> Result<Vec<NodeInfo>> {
+        self.retrying("list_nodes", move || self.inner.list_nodes(tenant))
+            .await
+    }
+
+    async fn ban_node(&self, tenant: &str, node_id: NodeId, reason: &str) -> Result<()> {
+        self.retrying("ban_node", move || self.inner.ban_node(tenant, node_id, reason))
+            .await
+    }
+
+    async fn unban_node(&self, tenant: &str, node_id: NodeId) -> Result<()> {
+        self.retrying("unban_node", move || self.inner.unban_node(tenant, node_id))
+            .await
+    }
+
+    async fn is_node_banned(&self, tenant: &str, node_id: NodeId) -> Result<bool> {
+        self.retrying(
+            "is_node_banned",
+            move || self.inner.is_node_banned(tenant, node_id),
+        )
+        .await
+    }
+
+    async fn nodes(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        selector: &HashMap<String, String>,
+    ) -> Result<HashSet<NodeId>> {
+        self.retrying("nodes", move || self.inner.nodes(tenant, run_id, selector))
+            .await
+    }
+
+    async fn sample_nodes(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        count: u32,
+        seed: Option<u64>,
+        selector: &HashMap<String, String>,
+    ) -> Result<Vec<NodeId>> {
+        self.retrying(
+            "sample_nodes",
+            move || self.inner.sample_nodes(tenant, run_id, count, seed, selector),
+        )
+        .await
+    }
+
+    async fn online_nodes(&self, tenant: &str) -> Result<u64> {
+        self.retrying("online_nodes", move || self.inner.online_nodes(tenant)).await
+    }
+
+    async fn record_audit_event(&self, tenant: &str, event: &AuditEvent) -> Result<()> {
+        self.retrying(
+            "record_audit_event",
+            move || self.inner.record_audit_event(tenant, event),
+        )
+        .await
+    }
+
+    async fn list_audit_events(
+        &self,
+        tenant: &str,
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<AuditEvent>> {
+        self.retrying(
+            "list_audit_events",
+            move || self.inner.list_audit_events(tenant, after, page_size),
+        )
+        .await
+    }
+
+    async fn list_dead_letters(
+        &self,
+        tenant: &str,
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<DeadLetter>> {
+        self.retrying(
+            "list_dead_letters",
+            move || self.inner.list_dead_letters(tenant, after, page_size),
+        )
+        .await
+    }
+
+    async fn create_run(&self, tenant: &str) -> Result<RunId> {
+        self.retrying("create_run", move || self.inner.create_run(tenant)).await
+    }
+
+    async fn delete_run(&self, tenant: &str, run_id: RunId) -> Result<()> {
+        self.retrying("delete_run", move || self.inner.delete_run(tenant, run_id)).await
+    }
+
+    async fn runs(&self, tenant: &str) -> Result<Vec<RunId>> {
+        self.retrying("runs", move || self.inner.runs(tenant)).await
+    }
+
+    async fn list_task_ins(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        filters: &[TaskFilter],
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<TaskIns>> {
+        self.retrying(
+            "list_task_ins",
+            move || self.inner.list_task_ins(tenant, run_id, filters, after, page_size),
+        )
+        .await
+    }
+
+    async fn list_task_res(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        filters: &[TaskFilter],
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<TaskRes>> {
+        self.retrying(
+            "list_task_res",
+            move || self.inner.list_task_res(tenant, run_id, filters, after, page_size),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_per_attempt() {
+        let config = RetryConfig {
+            attempts: 3,
+            backoff_ms: 100,
+        };
+        assert_eq!(config.backoff(1), Duration::from_millis(100));
+        assert_eq!(config.backoff(2), Duration::from_millis(200));
+        assert_eq!(config.backoff(3), Duration::from_millis(400));
+    }
+
+    #[tokio::test]
+    async fn connection_errors_are_retried_until_success() {
+        let retry = Retry::new(
+            (),
+            RetryConfig {
+                attempts: 3,
+                backoff_ms: 0,
+            },
+        );
+        let calls = AtomicU32::new(0);
+        let result = retry
+            .retrying("op", || async {
+                match calls.fetch_add(1, Ordering::SeqCst) {
+                    0 | 1 => Err(Error::Connection("pool exhausted".to_owned())),
+                    _ => Ok(42),
+                }
+            })
+            .await;
+        assert_eq!(result.unwrap(), 42);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn other_errors_and_exhausted_retries_pass_through() {
+        let retry = Retry::new(
+            (),
+            RetryConfig {
+                attempts: 1,
+                backoff_ms: 0,
+            },
+        );
+        let result: Result<()> = retry
+            .retrying("op", || async { Err(Error::UnknownRun(RunId(7))) })
+            .await;
+        assert!(matches!(result, Err(Error::UnknownRun(RunId(7)))));
+
+        let calls = AtomicU32::new(0);
+        let result: Result<()> = retry
+            .retrying("op", || async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err(Error::Connection("down".to_owned()))
+            })
+            .await;
+        assert!(matches!(result, Err(Error::Connection(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,606 @@
+//! Shared conformance suite for `State` implementations.
+//!
+//! Every backend must exhibit the same observable semantics — unknown
+//! runs rejected, delivered tasks not handed out twice, anonymous and
+//! registered consumers kept apart, peek mode non-destructive — so the
+//! cases live here once and each backend's tests call [`run`]. Each
+//! case uses a fresh random tenant, so the suite can run against a
+//! shared database without isolation tricks.
+
+use std::collections::HashMap;
+
+use uuid::Uuid;
+
+use crate::model::handler::{GroupId, Node, NodeId, RunId, Task, TaskId, TaskIns, TaskRes};
+
+use super::{Error, PingOutcome, State, TaskFilter};
+
+/// Run every conformance case against `state`.
+pub async fn run(state: &dyn State) {
+    insert_rejects_unknown_run(state).await;
+    pull_marks_delivered(state).await;
+    anonymous_and_registered_consumers_are_kept_apart(state).await;
+    registered_nodes_claim_the_anonymous_pool(state).await;
+    results_are_matched_by_ancestry(state).await;
+    multi_parent_ancestry_matches_any_parent(state).await;
+    peeked_results_stay_pullable(state).await;
+    nacked_tasks_become_pullable_again(state).await;
+    deleted_runs_drop_their_tasks(state).await;
+    bulk_created_nodes_are_online_and_deletable(state).await;
+    client_versions_are_tracked(state).await;
+    ping_refreshes_only_the_pinged_node(state).await;
+    group_progress_tracks_round_completion(state).await;
+    run_usage_reports_stored_footprint(state).await;
+    typed_filters_narrow_the_listings(state).await;
+    runs_and_online_nodes_back_the_overview(state).await;
+    reconnection_refreshes_the_existing_record(state).await;
+    identities_are_recorded_and_listed(state).await;
+    ancestor_consumers_are_reported(state).await;
+}
+
+fn tenant() -> String {
+    Uuid::new_v4().to_string()
+}
+
+fn task(producer: Node, consumer: Node, ancestry: Vec<TaskId>) -> Task {
+    let now = chrono::Utc::now();
+    Task {
+        producer,
+        consumer,
+        created_at: now,
+        delivered_at: None,
+        pushed_at: now,
+        ttl: String::new(),
+        ancestry,
+        task_type: "train".to_owned(),
+        recordset: bytes::Bytes::new(),
+        recordset_checksum: String::new(),
+        error: None,
+    }
+}
+
+fn task_ins(run_id: RunId, consumer: Node) -> TaskIns {
+    TaskIns {
+        id: TaskId(Uuid::new_v4().to_string()),
+        group_id: GroupId::default(),
+        run_id,
+        task: task(
+            Node {
+                id: NodeId(0),
+                anonymous: true,
+            },
+            consumer,
+            Vec::new(),
+        ),
+    }
+}
+
+fn task_res(run_id: RunId, producer: Node, ancestor: &TaskId) -> TaskRes {
+    TaskRes {
+        id: TaskId(Uuid::new_v4().to_string()),
+        group_id: GroupId::default(),
+        run_id,
+        task: task(
+            producer,
+            Node {
+                id: NodeId(0),
+                anonymous: true,
+            },
+            vec![ancestor.clone()],
+        ),
+    }
+}
+
+async fn register_node(state: &dyn State, tenant: &str) -> Node {
+    let node_id = state
+        .create_node(tenant, 3600.0, &HashMap::new(), &[])
+        .await
+        .unwrap();
+    Node {
+        id: node_id,
+        anonymous: false,
+    }
+}
+
+pub async fn insert_rejects_unknown_run(state: &dyn State) {
+    let tenant = tenant();
+    let consumer = register_node(state, &tenant).await;
+    let result = state
+        .insert_task_instructions(&tenant, &[task_ins(RunId(424242), consumer)])
+        .await;
+    assert!(matches!(result, Err(Error::UnknownRun(RunId(424242)))));
+}
+
+pub async fn pull_marks_delivered(state: &dyn State) {
+    let tenant = tenant();
+    let run_id = state.create_run(&tenant).await.unwrap();
+    let consumer = register_node(state, &tenant).await;
+    state
+        .insert_task_instructions(&tenant, &[task_ins(run_id, consumer)])
+        .await
+        .unwrap();
+    let first = state
+        .task_instructions(&tenant, &consumer, None)
+        .await
+        .unwrap();
+    assert_eq!(first.len(), 1);
+    assert!(first[0].task.delivered_at.is_some());
+    let second = state
+        .task_instructions(&tenant, &consumer, None)
+        .await
+        .unwrap();
+    assert!(second.is_empty());
+}
+
+pub async fn anonymous_and_registered_consumers_are_kept_apart(state: &dyn State) {
+    let tenant = tenant();
+    let run_id = state.create_run(&tenant).await.unwrap();
+    let consumer = register_node(state, &tenant).await;
+    let anonymous = Node {
+        id: NodeId(0),
+        anonymous: true,
+    };
+    state
+        .insert_task_instructions(
+            &tenant,
+            &[task_ins(run_id, consumer), task_ins(run_id, anonymous)],
+        )
+        .await
+        .unwrap();
+    let for_anonymous = state
+        .task_instructions(&tenant, &anonymous, None)
+        .await
+        .unwrap();
+    assert_eq!(for_anonymous.len(), 1);
+    assert!(for_anonymous[0].task.consumer.anonymous);
+    let for_registered = state
+        .task_instructions(&tenant, &consumer, None)
+        .await
+        .unwrap();
+    assert_eq!(for_registered.len(), 1);
+    assert_eq!(for_registered[0].task.consumer.id, consumer.id);
+}
+
+pub async fn registered_nodes_claim_the_anonymous_pool(state: &dyn State) {
+    let tenant = tenant();
+    let run_id = state.create_run(&tenant).await.unwrap();
+    let claimer = register_node(state, &tenant).await;
+    let anonymous = Node {
+        id: NodeId(0),
+        anonymous: true,
+    };
+    state
+        .insert_task_instructions(&tenant, &[task_ins(run_id, anonymous)])
+        .await
+        .unwrap();
+    let claimed = state
+        .claim_pool_task_instructions(&tenant, &claimer, None)
+        .await
+        .unwrap();
+    assert_eq!(claimed.len(), 1);
+    assert!(claimed[0].task.delivered_at.is_some());
+    let for_anonymous = state
+        .task_instructions(&tenant, &anonymous, None)
+        .await
+        .unwrap();
+    assert!(for_anonymous.is_empty());
+    let by_anonymous = state
+        .claim_pool_task_instructions(&tenant, &anonymous, None)
+        .await
+        .unwrap();
+    assert!(by_anonymous.is_empty());
+}
+
+pub async fn results_are_matched_by_ancestry(state: &dyn State) {
+    let tenant = tenant();
+    let run_id = state.create_run(&tenant).await.unwrap();
+    let consumer = register_node(state, &tenant).await;
+    let task_ids = state
+        .insert_task_instructions(&tenant, &[task_ins(run_id, consumer)])
+        .await
+        .unwrap();
+    state
+        .task_instructions(&tenant, &consumer, None)
+        .await
+        .unwrap();
+    state
+        .insert_task_results(&tenant, &[task_res(run_id, consumer, &task_ids[0])])
+        .await
+        .unwrap();
+    let results = state
+        .task_results(&tenant, &task_ids, None, true)
+        .await
+        .unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].task.ancestry, task_ids);
+    // Marked results are not handed out twice.
+    let again = state
+        .task_results(&tenant, &task_ids, None, true)
+        .await
+        .unwrap();
+    assert!(again.is_empty());
+}
+
+pub async fn multi_parent_ancestry_matches_any_parent(state: &dyn State) {
+    let tenant = tenant();
+    let run_id = state.create_run(&tenant).await.unwrap();
+    let consumer = register_node(state, &tenant).await;
+    let task_ids = state
+        .insert_task_instructions(
+            &tenant,
+            &[task_ins(run_id, consumer), task_ins(run_id, consumer)],
+        )
+        .await
+        .unwrap();
+    state
+        .task_instructions(&tenant, &consumer, None)
+        .await
+        .unwrap();
+    // One aggregated result answering both instructions.
+    let mut aggregated = task_res(run_id, consumer, &task_ids[0]);
+    aggregated.task.ancestry = task_ids.clone();
+    state
+        .insert_task_results(&tenant, &[aggregated])
+        .await
+        .unwrap();
+    let by_first = state
+        .task_results(&tenant, &task_ids[..1], None, false)
+        .await
+        .unwrap();
+    assert_eq!(by_first.len(), 1);
+    let by_second = state
+        .task_results(&tenant, &task_ids[1..], None, false)
+        .await
+        .unwrap();
+    assert_eq!(by_second.len(), 1);
+    assert_eq!(by_second[0].task.ancestry, task_ids);
+}
+
+pub async fn peeked_results_stay_pullable(state: &dyn State) {
+    let tenant = tenant();
+    let run_id = state.create_run(&tenant).await.unwrap();
+    let consumer = register_node(state, &tenant).await;
+    let task_ids = state
+        .insert_task_instructions(&tenant, &[task_ins(run_id, consumer)])
+        .await
+        .unwrap();
+    state
+        .task_instructions(&tenant, &consumer, None)
+        .await
+        .unwrap();
+    state
+        .insert_task_results(&tenant, &[task_res(run_id, consumer, &task_ids[0])])
+        .await
+        .unwrap();
+    let peeked = state
+        .task_results(&tenant, &task_ids, None, false)
+        .await
+        .unwrap();
+    assert_eq!(peeked.len(), 1);
+    let marked = state
+        .task_results(&tenant, &task_ids, None, true)
+        .await
+        .unwrap();
+    assert_eq!(marked.len(), 1);
+}
+
+pub async fn nacked_tasks_become_pullable_again(state: &dyn State) {
+    let tenant = tenant();
+    let run_id = state.create_run(&tenant).await.unwrap();
+    let consumer = register_node(state, &tenant).await;
+    state
+        .insert_task_instructions(&tenant, &[task_ins(run_id, consumer)])
+        .await
+        .unwrap();
+    let pulled = state
+        .task_instructions(&tenant, &consumer, None)
+        .await
+        .unwrap();
+    let task_ids: Vec<TaskId> = pulled.into_iter().map(|task_ins| task_ins.id).collect();
+    let released = state
+        .release_tasks(&tenant, &consumer, &task_ids)
+        .await
+        .unwrap();
+    assert_eq!(released, 1);
+    let again = state
+        .task_instructions(&tenant, &consumer, None)
+        .await
+        .unwrap();
+    assert_eq!(again.len(), 1);
+}
+
+pub async fn deleted_runs_drop_their_tasks(state: &dyn State) {
+    let tenant = tenant();
+    let run_id = state.create_run(&tenant).await.unwrap();
+    let consumer = register_node(state, &tenant).await;
+    state
+        .insert_task_instructions(&tenant, &[task_ins(run_id, consumer)])
+        .await
+        .unwrap();
+    state.delete_run(&tenant, run_id).await.unwrap();
+    let listed = state.list_task_ins(&tenant, run_id, &[], None, 10).await.unwrap();
+    assert!(listed.is_empty());
+    // The run itself is gone: deleting again reports an unknown run.
+    let again = state.delete_run(&tenant, run_id).await;
+    assert!(matches!(again, Err(Error::UnknownRun(id)) if id == run_id));
+}
+
+pub async fn bulk_created_nodes_are_online_and_deletable(state: &dyn State) {
+    let tenant = tenant();
+    let run_id = state.create_run(&tenant).await.unwrap();
+    let node_ids = state
+        .create_nodes(&tenant, 3, 3600.0, &HashMap::new(), &[])
+        .await
+        .unwrap();
+    assert_eq!(node_ids.len(), 3);
+    let online = state.nodes(&tenant, run_id, &HashMap::new()).await.unwrap();
+    assert_eq!(online.len(), 3);
+    state.delete_nodes(&tenant, &node_ids).await.unwrap();
+    let online = state.nodes(&tenant, run_id, &HashMap::new()).await.unwrap();
+    assert!(online.is_empty());
+}
+
+pub async fn group_progress_tracks_round_completion(state: &dyn State) {
+    let tenant = tenant();
+    let run_id = state.create_run(&tenant).await.unwrap();
+    let consumer = register_node(state, &tenant).await;
+    let round_one = GroupId("round-1".to_owned());
+    let mut first = task_ins(run_id, consumer);
+    first.group_id = round_one.clone();
+    let mut second = task_ins(run_id, consumer);
+    second.group_id = round_one.clone();
+    state
+        .insert_task_instructions(&tenant, &[first.clone(), second])
+        .await
+        .unwrap();
+    let progress = state.group_progress(&tenant, run_id, &round_one).await.unwrap();
+    assert_eq!(progress, (2, 0));
+    let mut result = task_res(run_id, consumer, &first.id);
+    result.group_id = round_one.clone();
+    state.insert_task_results(&tenant, &[result]).await.unwrap();
+    let progress = state.group_progress(&tenant, run_id, &round_one).await.unwrap();
+    assert_eq!(progress, (2, 1));
+    let round_two = GroupId("round-2".to_owned());
+    let progress = state.group_progress(&tenant, run_id, &round_two).await.unwrap();
+    assert_eq!(progress, (0, 0));
+}
+
+pub async fn run_usage_reports_stored_footprint(state: &dyn State) {
+    let tenant = tenant();
+    let run_id = state.create_run(&tenant).await.unwrap();
+    let first = register_node(state, &tenant).await;
+    let second = register_node(state, &tenant).await;
+    let mut instructions = vec![
+        task_ins(run_id, first),
+        task_ins(run_id, first),
+        task_ins(run_id, second),
+    ];
+    for instruction in &mut instructions {
+        instruction.task.recordset = vec![0; 10].into();
+    }
+    state
+        .insert_task_instructions(&tenant, &instructions)
+        .await
+        .unwrap();
+    let mut result = task_res(run_id, first, &instructions[0].id);
+    result.task.recordset = vec![0; 5].into();
+    state.insert_task_results(&tenant, &[result]).await.unwrap();
+    let usage = state.run_usage(&tenant, run_id).await.unwrap();
+    assert_eq!(usage.tasks, 3);
+    assert_eq!(usage.recordset_bytes, 35);
+    assert_eq!(usage.consumers, [first.id, second.id].into_iter().collect());
+    // Other runs do not bleed into the tally.
+    let other = state.create_run(&tenant).await.unwrap();
+    let usage = state.run_usage(&tenant, other).await.unwrap();
+    assert_eq!(usage, crate::state::RunUsage::default());
+}
+
+pub async fn typed_filters_narrow_the_listings(state: &dyn State) {
+    let tenant = tenant();
+    let run_id = state.create_run(&tenant).await.unwrap();
+    let first = register_node(state, &tenant).await;
+    let second = register_node(state, &tenant).await;
+    let mut instructions = vec![
+        task_ins(run_id, first),
+        task_ins(run_id, first),
+        task_ins(run_id, second),
+    ];
+    instructions[2].task.task_type = "evaluate".to_owned();
+    state
+        .insert_task_instructions(&tenant, &instructions)
+        .await
+        .unwrap();
+    state
+        .insert_task_results(&tenant, &[task_res(run_id, first, &instructions[0].id)])
+        .await
+        .unwrap();
+
+    let filters = |triples: &[(&str, &str, String)]| {
+        triples
+            .iter()
+            .map(|(field, op, value)| TaskFilter::parse(field, op, value).unwrap())
+            .collect::<Vec<_>>()
+    };
+    let evaluate = filters(&[("task_type", "eq", "evaluate".to_owned())]);
+    let listed = state
+        .list_task_ins(&tenant, run_id, &evaluate, None, 10)
+        .await
+        .unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].id, instructions[2].id);
+    let to_first = filters(&[("consumer_node_id", "eq", first.id.to_string())]);
+    let listed = state
+        .list_task_ins(&tenant, run_id, &to_first, None, 10)
+        .await
+        .unwrap();
+    assert_eq!(listed.len(), 2);
+    // Filters combine conjunctively: no evaluate task targets the
+    // first node.
+    let both = filters(&[
+        ("task_type", "eq", "evaluate".to_owned()),
+        ("consumer_node_id", "eq", first.id.to_string()),
+    ]);
+    let listed = state
+        .list_task_ins(&tenant, run_id, &both, None, 10)
+        .await
+        .unwrap();
+    assert!(listed.is_empty());
+    let recent = filters(&[("created_at", "gt", "0".to_owned())]);
+    let listed = state
+        .list_task_ins(&tenant, run_id, &recent, None, 10)
+        .await
+        .unwrap();
+    assert_eq!(listed.len(), 3);
+    // Results share the same filter path.
+    let by_first = filters(&[("producer_node_id", "eq", first.id.to_string())]);
+    let listed = state
+        .list_task_res(&tenant, run_id, &by_first, None, 10)
+        .await
+        .unwrap();
+    assert_eq!(listed.len(), 1);
+    let by_second = filters(&[("producer_node_id", "eq", second.id.to_string())]);
+    let listed = state
+        .list_task_res(&tenant, run_id, &by_second, None, 10)
+        .await
+        .unwrap();
+    assert!(listed.is_empty());
+}
+
+pub async fn ping_refreshes_only_the_pinged_node(state: &dyn State) {
+    let tenant = tenant();
+    let run_id = state.create_run(&tenant).await.unwrap();
+    let alive = register_node(state, &tenant).await;
+    // A node registered with a zero interval is offline right away.
+    let expired_id = state
+        .create_node(&tenant, 0.0, &HashMap::new(), &[])
+        .await
+        .unwrap();
+    let online = state.nodes(&tenant, run_id, &HashMap::new()).await.unwrap();
+    assert_eq!(online, [alive.id].into_iter().collect());
+    // Pinging one node must not refresh the other.
+    assert_eq!(
+        state.update_ping(&tenant, &alive, 3600.0, &[]).await.unwrap(),
+        PingOutcome::Refreshed
+    );
+    let online = state.nodes(&tenant, run_id, &HashMap::new()).await.unwrap();
+    assert_eq!(online, [alive.id].into_iter().collect());
+    // A ping from an unknown node reports it as such.
+    let unknown = Node {
+        id: NodeId(424_242),
+        anonymous: false,
+    };
+    assert_eq!(
+        state.update_ping(&tenant, &unknown, 3600.0, &[]).await.unwrap(),
+        PingOutcome::Unknown
+    );
+    // The expired node comes back online once it pings itself.
+    let expired = Node {
+        id: expired_id,
+        anonymous: false,
+    };
+    assert_eq!(
+        state.update_ping(&tenant, &expired, 3600.0, &[]).await.unwrap(),
+        PingOutcome::Lapsed
+    );
+    let online = state.nodes(&tenant, run_id, &HashMap::new()).await.unwrap();
+    assert_eq!(online, [alive.id, expired_id].into_iter().collect());
+}
+
+pub async fn client_versions_are_tracked(state: &dyn State) {
+    let tenant = tenant();
+    let node = register_node(state, &tenant).await;
+    let versions = state.client_versions(&tenant).await.unwrap();
+    assert_eq!(versions.get(""), Some(&1));
+    state
+        .record_client_version(&tenant, node.id, "flwr/1.7.0")
+        .await
+        .unwrap();
+    let versions = state.client_versions(&tenant).await.unwrap();
+    assert_eq!(versions.get("flwr/1.7.0"), Some(&1));
+    assert!(!versions.contains_key(""));
+}
+
+pub async fn runs_and_online_nodes_back_the_overview(state: &dyn State) {
+    let tenant = tenant();
+    assert!(state.runs(&tenant).await.unwrap().is_empty());
+    assert_eq!(state.online_nodes(&tenant).await.unwrap(), 0);
+    let first = state.create_run(&tenant).await.unwrap();
+    let second = state.create_run(&tenant).await.unwrap();
+    let mut expected = vec![first, second];
+    expected.sort_unstable();
+    assert_eq!(state.runs(&tenant).await.unwrap(), expected);
+    state
+        .create_nodes(&tenant, 2, 3600.0, &HashMap::new(), &[])
+        .await
+        .unwrap();
+    assert_eq!(state.online_nodes(&tenant).await.unwrap(), 2);
+    state.delete_run(&tenant, first).await.unwrap();
+    assert_eq!(state.runs(&tenant).await.unwrap(), vec![second]);
+}
+
+pub async fn reconnection_refreshes_the_existing_record(state: &dyn State) {
+    let tenant = tenant();
+    let run_id = state.create_run(&tenant).await.unwrap();
+    let selector: HashMap<String, String> = [("region".to_owned(), "eu".to_owned())].into();
+    let node_id = state
+        .create_node(&tenant, 3600.0, &HashMap::new(), &["train".to_owned()])
+        .await
+        .unwrap();
+    // Reconnecting keeps the id but replaces the record.
+    assert!(state
+        .reconnect_node(&tenant, node_id, 1800.0, &selector, &["evaluate".to_owned()])
+        .await
+        .unwrap());
+    let online = state.nodes(&tenant, run_id, &selector).await.unwrap();
+    assert_eq!(online, [node_id].into_iter().collect());
+    // An id that was never registered cannot reconnect.
+    assert!(!state
+        .reconnect_node(&tenant, NodeId(424_242), 1800.0, &HashMap::new(), &[])
+        .await
+        .unwrap());
+    // Neither can one that was deleted.
+    state.delete_node(&tenant, node_id).await.unwrap();
+    assert!(!state
+        .reconnect_node(&tenant, node_id, 1800.0, &HashMap::new(), &[])
+        .await
+        .unwrap());
+}
+
+pub async fn identities_are_recorded_and_listed(state: &dyn State) {
+    let tenant = tenant();
+    let node = register_node(state, &tenant).await;
+    // A freshly registered node has an empty identity, not a missing
+    // one.
+    assert_eq!(state.node_identity(&tenant, node.id).await.unwrap(), Some(String::new()));
+    state
+        .record_node_identity(&tenant, node.id, "spki:abc")
+        .await
+        .unwrap();
+    assert_eq!(
+        state.node_identity(&tenant, node.id).await.unwrap(),
+        Some("spki:abc".to_owned())
+    );
+    assert_eq!(state.node_identity(&tenant, NodeId(424_242)).await.unwrap(), None);
+    let nodes = state.list_nodes(&tenant).await.unwrap();
+    assert_eq!(nodes.len(), 1);
+    assert_eq!(nodes[0].id, node.id);
+    assert_eq!(nodes[0].identity, "spki:abc");
+    assert_eq!(nodes[0].ping_interval, 3600.0);
+}
+
+pub async fn ancestor_consumers_are_reported(state: &dyn State) {
+    let tenant = tenant();
+    let run_id = state.create_run(&tenant).await.unwrap();
+    let consumer = register_node(state, &tenant).await;
+    let instruction = task_ins(run_id, consumer);
+    state
+        .insert_task_instructions(&tenant, &[instruction.clone()])
+        .await
+        .unwrap();
+    let consumers = state
+        .task_ins_consumers(&tenant, &[instruction.id.clone(), TaskId(Uuid::new_v4().to_string())])
+        .await
+        .unwrap();
+    assert_eq!(consumers.len(), 1);
+    assert_eq!(consumers[&instruction.id], (run_id, consumer));
+}
@@ -0,0 +1,471 @@
+//! Deadline decorator wrapping any `State` backend.
+//!
+//! Every operation is raced against a configurable deadline via
+//! `tokio::time::timeout`, so a stuck database surfaces as a typed
+//! [`Error::Timeout`] (mapped to DEADLINE_EXCEEDED) instead of hanging
+//! the request stack.
+
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::model::handler::{
+    AuditEvent, DeadLetter, GroupId, Node, NodeId, NodeInfo, RunId, TaskId, TaskIns, TaskRes,
+};
+
+use super::{Error, PingOutcome, Result, RunUsage, State, TaskCursor, TaskFilter};
+
+/// Deadlines applied by [`Timeout`]; a value of 0 disables the
+/// deadline for the operation.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TimeoutConfig {
+    /// Deadline in milliseconds applied to every operation.
+    pub default_ms: u64,
+    /// Per-operation overrides, keyed by the `State` method name.
+    pub per_operation_ms: HashMap<String, u64>,
+}
+
+impl TimeoutConfig {
+    /// The deadline for `operation`, if one is configured.
+    fn limit_ms(&self, operation: &str) -> Option<u64> {
+        let limit = self
+            .per_operation_ms
+            .get(operation)
+            .copied()
+            .unwrap_or(self.default_ms);
+        (limit > 0).then_some(limit)
+    }
+}
+
+/// A `State` decorator enforcing per-operation deadlines.
+pub struct Timeout<S> {
+    inner: S,
+    config: TimeoutConfig,
+}
+
+impl<S> Timeout<S> {
+    pub fn new(inner: S, config: TimeoutConfig) -> Self {
+        Self { inner, config }
+    }
+
+    async fn deadline<T>(
+        &self,
+        operation: &'static str,
+        fut: impl Future<Output = Result<T>> + Send,
+    ) -> Result<T> {
+        match self.config.limit_ms(operation) {
+            None => fut.await,
+            Some(limit_ms) => tokio::time::timeout(Duration::from_millis(limit_ms), fut)
+                .await
+                .unwrap_or(Err(Error::Timeout {
+                    operation,
+                    limit_ms,
+                })),
+        }
+    }
+}
+
+#[async_trait]
+impl<S: State> State for Timeout<S> {
+    async fn insert_task_instructions(
+        &self,
+        tenant: &str,
+        instructions: &[TaskIns],
+    ) -> Result<Vec<TaskId>> {
+        self.deadline(
+            "insert_task_instructions",
+            self.inner.insert_task_instructions(tenant, instructions),
+        )
+        .await
+    }
+
+    async fn task_instructions(
+        &self,
+        tenant: &str,
+        node: &Node,
+        limit: Option<u32>,
+    ) -> Result<Vec<TaskIns>> {
+        self.deadline(
+            "task_instructions",
+            self.inner.task_instructions(tenant, node, limit),
+        )
+        .await
+    }
+
+    async fn claim_pool_task_instructions(
+        &self,
+        tenant: &str,
+        node: &Node,
+        limit: Option<u32>,
+    ) -> Result<Vec<TaskIns>> {
+        self.deadline(
+            "claim_pool_task_instructions",
+            self.inner.claim_pool_task_instructions(tenant, node, limit),
+        )
+        .await
+    }
+
+    async fn insert_task_results(&self, tenant: &str, results: &[TaskRes]) -> Result<Vec<TaskId>> {
+        self.deadline(
+            "insert_task_results",
+            self.inner.insert_task_results(tenant, results),
+        )
+        .await
+    }
+
+    async fn task_results(
+        &self,
+        tenant: &str,
+        task_ids: &[TaskId],
+        limit: Option<u32>,
+        mark: bool,
+    ) -> Result<Vec<TaskRes>> {
+        self.deadline(
+            "task_results",
+            self.inner.task_results(tenant, task_ids, limit, mark),
+        )
+        .await
+    }
+
+    async fn release_tasks(&self, tenant: &str, node: &Node, task_ids: &[TaskId]) -> Result<u64> {
+        self.deadline(
+            "release_tasks",
+            self.inner.release_tasks(tenant, node, task_ids),
+        )
+        .await
+    }
+
+    async fn release_expired_tasks(
+        &self,
+        lease: Duration,
+        max_redeliveries: u32,
+    ) -> Result<(u64, Vec<(String, DeadLetter)>)> {
+        self.deadline(
+            "release_expired_tasks",
+            self.inner.release_expired_tasks(lease, max_redeliveries),
+        )
+        .await
+    }
+
+    async fn task_ins_consumers(
+        &self,
+        tenant: &str,
+        task_ids: &[TaskId],
+    ) -> Result<HashMap<TaskId, (RunId, Node)>> {
+        self.deadline(
+            "task_ins_consumers",
+            self.inner.task_ins_consumers(tenant, task_ids),
+        )
+        .await
+    }
+
+    async fn pending_task_ins(&self, tenant: &str, consumer: &Node) -> Result<u64> {
+        self.deadline(
+            "pending_task_ins",
+            self.inner.pending_task_ins(tenant, consumer),
+        )
+        .await
+    }
+
+    async fn pending_run_task_ins(&self, tenant: &str, run_id: RunId) -> Result<u64> {
+        self.deadline(
+            "pending_run_task_ins",
+            self.inner.pending_run_task_ins(tenant, run_id),
+        )
+        .await
+    }
+
+    async fn group_progress(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        group_id: &GroupId,
+    ) -> Result<(u64, u64)> {
+        self.deadline(
+            "group_progress",
+            self.inner.group_progress(tenant, run_id, group_id),
+        )
+        .await
+    }
+
+    async fn run_progress(&self, tenant: &str, run_id: RunId) -> Result<Vec<(GroupId, u64, u64)>> {
+        self.deadline("run_progress", self.inner.run_progress(tenant, run_id))
+            .await
+    }
+
+    async fn run_usage(&self, tenant: &str, run_id: RunId) -> Result<RunUsage> {
+        self.deadline("run_usage", self.inner.run_usage(tenant, run_id))
+            .await
+    }
+
+    async fn delete_tasks(&self, tenant: &str, task_ids: &[TaskId]) -> Result<()> {
+        self.deadline("delete_tasks", self.inner.delete_tasks(tenant, task_ids))
+            .await
+    }
+
+    async fn create_node(
+        &self,
+        tenant: &str,
+        ping_interval: f64,
+        properties: &HashMap<String, String>,
+        task_types: &[String],
+    ) -> Result<NodeId> {
+        self.deadline(
+            "create_node",
+            self.inner
+                .create_node(tenant, ping_interval, properties, task_types),
+        )
+        .await
+    }
+
+    async fn create_nodes(
+        &self,
+        tenant: &str,
+        count: u32,
+        ping_interval: f64,
+        properties: &HashMap<String, String>,
+        task_types: &[String],
+    ) -> Result<Vec<NodeId>> {
+        self.deadline(
+            "create_nodes",
+            self.inner
+                .create_nodes(tenant, count, ping_interval, properties, task_types),
+        )
+        .await
+    }
+
+    async fn reconnect_node(
+        &self,
+        tenant: &str,
+        node_id: NodeId,
+        ping_interval: f64,
+        properties: &HashMap<String, String>,
+        task_types: &[String],
+    ) -> Result<bool> {
+        self.deadline(
+            "reconnect_node",
+            self.inner
+                .reconnect_node(tenant, node_id, ping_interval, properties, task_types),
+        )
+        .await
+    }
+
+    async fn delete_node(&self, tenant: &str, node_id: NodeId) -> Result<()> {
+        self.deadline("delete_node", self.inner.delete_node(tenant, node_id))
+            .await
+    }
+
+    async fn delete_nodes(&self, tenant: &str, node_ids: &[NodeId]) -> Result<()> {
+        self.deadline("delete_nodes", self.inner.delete_nodes(tenant, node_ids))
+            .await
+    }
+
+    async fn update_ping(
+        &self,
+        tenant: &str,
+        node: &Node,
+        ping_interval: f64,
+        task_types: &[String],
+    ) -> Result<PingOutcome> {
+        self.deadline(
+            "update_ping",
+            self.inner.update_ping(tenant, node, ping_interval, task_types),
+        )
+        .await
+    }
+
+    async fn update_pings(&self, tenant: &str, pings: &[(NodeId, f64)]) -> Result<u64> {
+        self.deadline("update_pings", self.inner.update_pings(tenant, pings))
+            .await
+    }
+
+    async fn record_client_version(
+        &self,
+        tenant: &str,
+        node_id: NodeId,
+        version: &str,
+    ) -> Result<()> {
+        self.deadline(
+            "record_client_version",
+            self.inner.record_client_version(tenant, node_id, version),
+        )
+        .await
+    }
+
+    async fn client_versions(&self, tenant: &str) -> Result<HashMap<String, u64>> {
+        self.deadline("client_versions", self.inner.client_versions(tenant))
+            .await
+    }
+
+    async fn record_node_identity(
+        &self,
+        tenant: &str,
+        node_id: NodeId,
+        identity: &str,
+    ) -> Result<()> {
+        self.deadline(
+            "record_node_identity",
+            self.inner.record_node_identity(tenant, node_id, identity),
+        )
+        .await
+    }
+
+    async fn node_identity(&self, tenant: &str, node_id: NodeId) -> Result<Option<String>> {
+        self.deadline("node_identity", self.inner.node_identity(tenant, node_id))
+            .await
+    }
+
+    async fn list_nodes(&self, tenant: &str) -> Result<Vec<NodeInfo>> {
+        self.deadline("list_nodes", self.inner.list_nodes(tenant)).await
+    }
+
+    async fn ban_node(&self, tenant: &str, node_id: NodeId, reason: &str) -> Result<()> {
+        self.deadline("ban_node", self.inner.ban_node(tenant, node_id, reason))
+            .await
+    }
+
+    async fn unban_node(&self, tenant: &str, node_id: NodeId) -> Result<()> {
+        self.deadline("unban_node", self.inner.unban_node(tenant, node_id))
+            .await
+    }
+
+    async fn is_node_banned(&self, tenant: &str, node_id: NodeId) -> Result<bool> {
+        self.deadline(
+            "is_node_banned",
+            self.inner.is_node_banned(tenant, node_id),
+        )
+        .await
+    }
+
+    async fn nodes(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        selector: &HashMap<String, String>,
+    ) -> Result<HashSet<NodeId>> {
+        self.deadline("nodes", self.inner.nodes(tenant, run_id, selector))
+            .await
+    }
+
+    async fn sample_nodes(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        count: u32,
+        seed: Option<u64>,
+        selector: &HashMap<String, String>,
+    ) -> Result<Vec<NodeId>> {
+        self.deadline(
+            "sample_nodes",
+            self.inner.sample_nodes(tenant, run_id, count, seed, selector),
+        )
+        .await
+    }
+
+    async fn online_nodes(&self, tenant: &str) -> Result<u64> {
+        self.deadline("online_nodes", self.inner.online_nodes(tenant)).await
+    }
+
+    async fn record_audit_event(&self, tenant: &str, event: &AuditEvent) -> Result<()> {
+        self.deadline(
+            "record_audit_event",
+            self.inner.record_audit_event(tenant, event),
+        )
+        .await
+    }
+
+    async fn list_audit_events(
+        &self,
+        tenant: &str,
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<AuditEvent>> {
+        self.deadline(
+            "list_audit_events",
+            self.inner.list_audit_events(tenant, after, page_size),
+        )
+        .await
+    }
+
+    async fn list_dead_letters(
+        &self,
+        tenant: &str,
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<DeadLetter>> {
+        self.deadline(
+            "list_dead_letters",
+            self.inner.list_dead_letters(tenant, after, page_size),
+        )
+        .await
+    }
+
+    async fn create_run(&self, tenant: &str) -> Result<RunId> {
+        self.deadline("create_run", self.inner.create_run(tenant)).await
+    }
+
+    async fn delete_run(&self, tenant: &str, run_id: RunId) -> Result<()> {
+        self.deadline("delete_run", self.inner.delete_run(tenant, run_id)).await
+    }
+
+    async fn runs(&self, tenant: &str) -> Result<Vec<RunId>> {
+        self.deadline("runs", self.inner.runs(tenant)).await
+    }
+
+    async fn list_task_ins(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        filters: &[TaskFilter],
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<TaskIns>> {
+        self.deadline(
+            "list_task_ins",
+            self.inner.list_task_ins(tenant, run_id, filters, after, page_size),
+        )
+        .await
+    }
+
+    async fn list_task_res(
+        &self,
+        tenant: &str,
+        run_id: RunId,
+        filters: &[TaskFilter],
+        after: Option<&TaskCursor>,
+        page_size: u32,
+    ) -> Result<Vec<TaskRes>> {
+        self.deadline(
+            "list_task_res",
+            self.inner.list_task_res(tenant, run_id, filters, after, page_size),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn per_operation_override_beats_default() {
+        let config = TimeoutConfig {
+            default_ms: 100,
+            per_operation_ms: [("task_instructions".to_owned(), 5000), ("nodes".to_owned(), 0)]
+                .into_iter()
+                .collect(),
+        };
+        assert_eq!(config.limit_ms("task_instructions"), Some(5000));
+        assert_eq!(config.limit_ms("create_node"), Some(100));
+        // An explicit 0 disables the deadline for that operation.
+        assert_eq!(config.limit_ms("nodes"), None);
+    }
+
+    #[test]
+    fn zero_default_disables_deadlines() {
+        let config = TimeoutConfig::default();
+        assert_eq!(config.limit_ms("task_instructions"), None);
+    }
+}
@@ -0,0 +1,240 @@
+//! OpenTelemetry setup: trace and metrics pipelines.
+//!
+//! Traces export via OTLP by default, with Jaeger and Zipkin
+//! pipelines behind the matching cargo features and a stdout exporter
+//! for local debugging. Metrics export via OTLP (or stdout), since
+//! Jaeger and Zipkin are trace-only protocols.
+
+use std::collections::HashMap;
+
+use opentelemetry::metrics::{Meter, MetricsError};
+use opentelemetry::trace::TraceError;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{runtime, Resource};
+
+/// Resource attributes identifying this process in the tracing
+/// backend.
+#[derive(Debug, Clone)]
+pub struct ResourceConfig {
+    /// `service.name`; defaults to the crate name.
+    pub service_name: String,
+    /// `deployment.environment`; empty omits it.
+    pub deployment_environment: String,
+    /// Further attributes, verbatim.
+    pub attributes: HashMap<String, String>,
+}
+
+fn resource(config: &ResourceConfig) -> Resource {
+    let mut attributes = vec![
+        KeyValue::new("service.name", config.service_name.clone()),
+        KeyValue::new("service.version", env!("CARGO_PKG_VERSION")),
+    ];
+    if !config.deployment_environment.is_empty() {
+        attributes.push(KeyValue::new(
+            "deployment.environment",
+            config.deployment_environment.clone(),
+        ));
+    }
+    for (key, value) in &config.attributes {
+        attributes.push(KeyValue::new(key.clone(), value.clone()));
+    }
+    Resource::new(attributes)
+}
+
+/// A reqwest client routing everything through `proxy_url`
+/// (`http://`, `https://` or `socks5://`). The tonic exporter cannot
+/// tunnel gRPC through a proxy, so proxied deployments export
+/// protobuf-over-HTTP instead; point `tracer.otlp_endpoint` at the
+/// collector's HTTP port (4318) when setting a proxy.
+fn proxied_client(proxy_url: &str) -> Result<reqwest::Client, String> {
+    let proxy =
+        reqwest::Proxy::all(proxy_url).map_err(|err| format!("invalid proxy URL: {err}"))?;
+    reqwest::Client::builder()
+        .proxy(proxy)
+        .build()
+        .map_err(|err| format!("failed to build proxied HTTP client: {err}"))
+}
+
+/// Install the global tracer provider for the configured exporter.
+pub fn install(
+    config: &crate::config::Tracer,
+    resource_config: &ResourceConfig,
+) -> Result<opentelemetry_sdk::trace::Tracer, TraceError> {
+    let trace_config =
+        opentelemetry_sdk::trace::config().with_resource(resource(resource_config));
+    match config.exporter {
+        crate::config::TraceExporter::Otlp => install_otlp(config, trace_config),
+        crate::config::TraceExporter::Jaeger => install_jaeger(config, trace_config),
+        crate::config::TraceExporter::Zipkin => install_zipkin(config, trace_config),
+        crate::config::TraceExporter::Stdout => Ok(install_stdout(trace_config)),
+    }
+}
+
+/// The OTLP trace pipeline, exporting through `tracer.proxy_url` when
+/// one is configured.
+fn install_otlp(
+    config: &crate::config::Tracer,
+    trace_config: opentelemetry_sdk::trace::Config,
+) -> Result<opentelemetry_sdk::trace::Tracer, TraceError> {
+    let endpoint = config.otlp_endpoint.as_str();
+    match config.proxy_url.as_deref() {
+        Some(proxy_url) => opentelemetry_otlp::new_pipeline()
+            .tracing()
+            .with_exporter(
+                opentelemetry_otlp::new_exporter()
+                    .http()
+                    .with_http_client(proxied_client(proxy_url).map_err(TraceError::from)?)
+                    .with_endpoint(endpoint),
+            )
+            .with_trace_config(trace_config)
+            .install_batch(runtime::Tokio),
+        None => opentelemetry_otlp::new_pipeline()
+            .tracing()
+            .with_exporter(
+                opentelemetry_otlp::new_exporter()
+                    .tonic()
+                    .with_endpoint(endpoint),
+            )
+            .with_trace_config(trace_config)
+            .install_batch(runtime::Tokio),
+    }
+}
+
+/// The Jaeger agent trace pipeline.
+#[cfg(feature = "jaeger")]
+fn install_jaeger(
+    config: &crate::config::Tracer,
+    trace_config: opentelemetry_sdk::trace::Config,
+) -> Result<opentelemetry_sdk::trace::Tracer, TraceError> {
+    opentelemetry_jaeger::new_agent_pipeline()
+        .with_endpoint(&config.jaeger_endpoint)
+        .with_trace_config(trace_config)
+        .install_batch(runtime::Tokio)
+}
+
+#[cfg(not(feature = "jaeger"))]
+fn install_jaeger(
+    _config: &crate::config::Tracer,
+    _trace_config: opentelemetry_sdk::trace::Config,
+) -> Result<opentelemetry_sdk::trace::Tracer, TraceError> {
+    Err(TraceError::from(
+        "this build does not include the Jaeger exporter; rebuild with --features jaeger",
+    ))
+}
+
+/// The Zipkin collector trace pipeline.
+#[cfg(feature = "zipkin")]
+fn install_zipkin(
+    config: &crate::config::Tracer,
+    trace_config: opentelemetry_sdk::trace::Config,
+) -> Result<opentelemetry_sdk::trace::Tracer, TraceError> {
+    opentelemetry_zipkin::new_pipeline()
+        .with_collector_endpoint(&config.zipkin_endpoint)
+        .with_trace_config(trace_config)
+        .install_batch(runtime::Tokio)
+}
+
+#[cfg(not(feature = "zipkin"))]
+fn install_zipkin(
+    _config: &crate::config::Tracer,
+    _trace_config: opentelemetry_sdk::trace::Config,
+) -> Result<opentelemetry_sdk::trace::Tracer, TraceError> {
+    Err(TraceError::from(
+        "this build does not include the Zipkin exporter; rebuild with --features zipkin",
+    ))
+}
+
+/// Spans printed to stdout, for local debugging without a collector.
+fn install_stdout(
+    trace_config: opentelemetry_sdk::trace::Config,
+) -> opentelemetry_sdk::trace::Tracer {
+    use opentelemetry::trace::TracerProvider as _;
+
+    let provider = opentelemetry_sdk::trace::TracerProvider::builder()
+        .with_batch_exporter(opentelemetry_stdout::SpanExporter::default(), runtime::Tokio)
+        .with_config(trace_config)
+        .build();
+    let tracer = provider.tracer("superlink");
+    opentelemetry::global::set_tracer_provider(provider);
+    tracer
+}
+
+/// Install the global meter provider and return a meter for the
+/// server middleware: stdout for the `stdout` exporter, OTLP
+/// otherwise (through `tracer.proxy_url` when one is configured).
+pub fn install_metrics(
+    config: &crate::config::Tracer,
+    resource_config: &ResourceConfig,
+) -> Result<Meter, MetricsError> {
+    if config.exporter == crate::config::TraceExporter::Stdout {
+        let reader = opentelemetry_sdk::metrics::PeriodicReader::builder(
+            opentelemetry_stdout::MetricsExporter::default(),
+            runtime::Tokio,
+        )
+        .build();
+        let provider = opentelemetry_sdk::metrics::SdkMeterProvider::builder()
+            .with_reader(reader)
+            .with_resource(resource(resource_config))
+            .build();
+        opentelemetry::global::set_meter_provider(provider);
+        return Ok(opentelemetry::global::meter("superlink"));
+    }
+    let endpoint = config.otlp_endpoint.as_str();
+    let provider = match config.proxy_url.as_deref() {
+        Some(proxy_url) => opentelemetry_otlp::new_pipeline()
+            .metrics(runtime::Tokio)
+            .with_exporter(
+                opentelemetry_otlp::new_exporter()
+                    .http()
+                    .with_http_client(proxied_client(proxy_url).map_err(MetricsError::Other)?)
+                    .with_endpoint(endpoint),
+            )
+            .with_resource(resource(resource_config))
+            .build()?,
+        None => opentelemetry_otlp::new_pipeline()
+            .metrics(runtime::Tokio)
+            .with_exporter(
+                opentelemetry_otlp::new_exporter()
+                    .tonic()
+                    .with_endpoint(endpoint),
+            )
+            .with_resource(resource(resource_config))
+            .build()?,
+    };
+    opentelemetry::global::set_meter_provider(provider);
+    Ok(opentelemetry::global::meter("superlink"))
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::{Key, Value};
+
+    use super::*;
+
+    #[test]
+    fn resource_carries_the_configured_attributes() {
+        let config = ResourceConfig {
+            service_name: "superlink-eu".to_owned(),
+            deployment_environment: "staging".to_owned(),
+            attributes: [("region".to_owned(), "eu-west-1".to_owned())].into_iter().collect(),
+        };
+        let resource = resource(&config);
+        assert_eq!(resource.get(Key::new("service.name")), Some(Value::from("superlink-eu")));
+        assert_eq!(
+            resource.get(Key::new("deployment.environment")),
+            Some(Value::from("staging"))
+        );
+        assert_eq!(resource.get(Key::new("region")), Some(Value::from("eu-west-1")));
+    }
+
+    #[test]
+    fn an_empty_environment_is_omitted() {
+        let config = ResourceConfig {
+            service_name: "superlink".to_owned(),
+            deployment_environment: String::new(),
+            attributes: HashMap::new(),
+        };
+        assert_eq!(resource(&config).get(Key::new("deployment.environment")), None);
+    }
+}